@@ -1,6 +1,10 @@
 #![allow(clippy::unit_arg)] // Required for black_box uses
 
-use criterion::{criterion_group, AxisScale, Criterion, PlotConfiguration};
+use criterion::{black_box, criterion_group, AxisScale, BenchmarkId, Criterion, PlotConfiguration};
+use hugr::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use hugr::ops::LeafOp;
+use hugr::types::{AbstractSignature, SimpleType};
+use hugr::Hugr;
 
 fn bench_it_works(c: &mut Criterion) {
     let mut group = c.benchmark_group("it_works");
@@ -9,9 +13,50 @@ fn bench_it_works(c: &mut Criterion) {
     group.finish();
 }
 
+/// A circuit of `layers` layers of Hadamard and CX gates on `width` qubits.
+fn circuit(width: usize, layers: usize) -> Hugr {
+    let qbs = vec![SimpleType::Qubit; width];
+    let mut dfg = DFGBuilder::new(AbstractSignature::new_df(qbs.clone(), qbs)).unwrap();
+    let mut wires: Vec<_> = dfg.input_wires().collect();
+    for _ in 0..layers {
+        for w in wires.iter_mut() {
+            *w = dfg.add_dataflow_op(LeafOp::H, [*w]).unwrap().out_wire(0);
+        }
+        for pair in wires.chunks_mut(2) {
+            if let [a, b] = pair {
+                [*a, *b] = dfg
+                    .add_dataflow_op(LeafOp::CX, [*a, *b])
+                    .unwrap()
+                    .outputs_arr();
+            }
+        }
+    }
+    dfg.finish_hugr_with_outputs(wires).unwrap()
+}
+
+fn bench_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+    group.plot_config(PlotConfiguration::default().summary_scale(AxisScale::Logarithmic));
+    for layers in [10, 100, 1000] {
+        let hugr = circuit(16, layers);
+        let json = serde_json::to_vec(&hugr).unwrap();
+        let mut binary = Vec::new();
+        hugr.write_binary(&mut binary).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("json", layers), &json, |b, json| {
+            b.iter(|| black_box(serde_json::from_slice::<Hugr>(json).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("binary", layers), &binary, |b, binary| {
+            b.iter(|| black_box(Hugr::read_binary(binary.as_slice()).unwrap()))
+        });
+    }
+    group.finish();
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default();
     targets =
         bench_it_works,
+        bench_load,
 }
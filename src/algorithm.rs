@@ -1,4 +1,17 @@
 //! Algorithms using the Hugr.
 
+pub mod canonical;
+pub mod cse;
+pub mod dataflow;
+pub mod diff;
+pub mod dominators;
 mod half_node;
+pub mod inline;
+pub mod interpret;
+pub mod lower_control_flow;
 pub mod nest_cfgs;
+pub mod simplify_cfg;
+pub mod simplify_conditionals;
+pub mod simulate;
+pub mod structurise;
+pub mod unitary;
@@ -0,0 +1,387 @@
+//! Structural equality and canonical hashing of HUGRs.
+//!
+//! Two HUGRs are structurally equal when there is a bijection between their
+//! nodes that preserves operations, port counts, input resources, the
+//! hierarchy and the edges between ports. Node indices are ignored, and so is
+//! the order of siblings except where it is meaningful: the first two
+//! children of dataflow parents and CFGs, and the cases of a [`Conditional`].
+//! Node metadata is ignored unless [`CanonicalConfig::include_metadata`] is
+//! set.
+//!
+//! Both the equality and the hash are computed from a canonical ordering of the
+//! nodes. Within each sibling graph, the ordering follows the edges in port
+//! order from the children with a fixed position, and starts each remaining
+//! connected component from its structurally smallest node. Structurally
+//! equal HUGRs therefore always have the same hash. Conversely, isomorphic
+//! HUGRs may exceptionally compare unequal when a sibling graph has several
+//! components whose nodes can only be told apart by distant parts of the
+//! graph.
+//!
+//! Regions can be compared and hashed through a [`RegionView`] or
+//! [`FlatRegionView`]. Edges leaving the region are then ignored.
+//!
+//! [`Conditional`]: crate::ops::Conditional
+//! [`RegionView`]: crate::hugr::region::RegionView
+//! [`FlatRegionView`]: crate::hugr::region::FlatRegionView
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use itertools::Itertools;
+
+use crate::hugr::HugrView;
+use crate::ops::{OpTag, OpType, ValidateOp};
+use crate::resource::ResourceId;
+use crate::{Direction, Node};
+
+/// The number of times node labels are refined with those of their
+/// neighbours, to break ties between siblings.
+const REFINEMENT_ROUNDS: usize = 3;
+
+/// Options for the structural comparison of HUGRs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CanonicalConfig {
+    /// Whether node metadata must be equal.
+    pub include_metadata: bool,
+}
+
+/// Returns whether the two HUGRs are structurally equal.
+///
+/// See the [module-level documentation](self) for what is compared.
+pub fn structurally_eq(a: &impl HugrView, b: &impl HugrView, config: &CanonicalConfig) -> bool {
+    a.node_count() == b.node_count()
+        && a.edge_count() == b.edge_count()
+        && CanonicalForm::new(a, config) == CanonicalForm::new(b, config)
+}
+
+/// Returns a hash of the HUGR that is invariant under node indices and
+/// sibling order.
+///
+/// Structurally equal HUGRs have the same hash. The hash is stable for a given
+/// build of the library, but may change between versions.
+pub fn canonical_hash(h: &impl HugrView, config: &CanonicalConfig) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    CanonicalForm::new(h, config).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns the nodes of the HUGR in canonical order.
+///
+/// The root comes first, and every node comes after its parent. Structurally
+/// equal HUGRs have their corresponding nodes at the same positions.
+pub fn canonical_order(h: &impl HugrView) -> Vec<Node> {
+    let encodings: HashMap<Node, NodeEncoding> = h
+        .nodes()
+        .map(|n| (n, NodeEncoding::new(h, n, &CanonicalConfig::default())))
+        .collect();
+    canonical_order_with(h, &encodings)
+}
+
+/// The data of a node compared in the canonical form, except for its parent.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct NodeEncoding {
+    /// The serialized operation.
+    op: Vec<u8>,
+    input_resources: Option<Vec<ResourceId>>,
+    num_inputs: usize,
+    num_outputs: usize,
+    /// The serialized metadata, if it is compared.
+    metadata: Option<String>,
+}
+
+impl NodeEncoding {
+    fn new(h: &impl HugrView, n: Node, config: &CanonicalConfig) -> Self {
+        let nodetype = h.get_nodetype(n);
+        let metadata = config
+            .include_metadata
+            .then(|| h.get_metadata(n).to_string());
+        Self {
+            op: serde_json::to_vec(nodetype.op()).expect("Operations are serializable"),
+            input_resources: nodetype
+                .input_resources()
+                .map(|rs| rs.iter().cloned().sorted().collect()),
+            num_inputs: h.num_inputs(n),
+            num_outputs: h.num_outputs(n),
+            metadata,
+        }
+    }
+}
+
+/// A representation of a HUGR in which nodes are identified by their position
+/// in the canonical order.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CanonicalForm {
+    /// Each node with the position of its parent.
+    nodes: Vec<(Option<usize>, NodeEncoding)>,
+    /// The edges between ports, sorted.
+    edges: Vec<[(usize, usize); 2]>,
+}
+
+impl CanonicalForm {
+    fn new(h: &impl HugrView, config: &CanonicalConfig) -> Self {
+        let mut encodings: HashMap<Node, NodeEncoding> = h
+            .nodes()
+            .map(|n| (n, NodeEncoding::new(h, n, config)))
+            .collect();
+        let order = canonical_order_with(h, &encodings);
+        let index: HashMap<Node, usize> = order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+
+        let nodes = order
+            .iter()
+            .map(|n| {
+                let parent = h.get_parent(*n).and_then(|p| index.get(&p).copied());
+                (parent, encodings.remove(n).unwrap())
+            })
+            .collect();
+        let mut edges = vec![];
+        for (i, &n) in order.iter().enumerate() {
+            for port in h.node_outputs(n) {
+                for (m, m_port) in h.linked_ports(n, port) {
+                    if let Some(&j) = index.get(&m) {
+                        edges.push([(i, port.index()), (j, m_port.index())]);
+                    }
+                }
+            }
+        }
+        edges.sort_unstable();
+        Self { nodes, edges }
+    }
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The number of leading children of a node whose position is meaningful.
+pub(crate) fn ordered_children(op: &OpType) -> usize {
+    let flags = op.validity_flags();
+    if let OpType::Conditional(_) = op {
+        usize::MAX
+    } else if flags.allowed_second_child != OpTag::Any {
+        2
+    } else if flags.allowed_first_child != OpTag::Any {
+        1
+    } else {
+        0
+    }
+}
+
+/// Computes a label for each node that is invariant under node indices and
+/// sibling order, from its operation, its descendants and its neighbourhood.
+fn node_labels(h: &impl HugrView, encodings: &HashMap<Node, NodeEncoding>) -> HashMap<Node, u64> {
+    let mut preorder = vec![];
+    let mut stack = vec![h.root()];
+    while let Some(n) = stack.pop() {
+        preorder.push(n);
+        stack.extend(h.children(n));
+    }
+
+    // Hash the subtree of each node, children first.
+    let mut labels = HashMap::with_capacity(preorder.len());
+    for &n in preorder.iter().rev() {
+        let fixed = ordered_children(h.get_optype(n));
+        let children = h.children(n).map(|c| labels[&c]).collect_vec();
+        let (ordered, unordered) = children.split_at(fixed.min(children.len()));
+        let unordered = unordered.iter().sorted().collect_vec();
+        labels.insert(n, hash_of((&encodings[&n], ordered, unordered)));
+    }
+
+    for _ in 0..REFINEMENT_ROUNDS {
+        labels = preorder
+            .iter()
+            .map(|&n| {
+                let neighbourhood = h
+                    .all_node_ports(n)
+                    .map(|port| {
+                        h.linked_ports(n, port)
+                            .filter_map(|(m, m_port)| Some((*labels.get(&m)?, m_port.index())))
+                            .sorted()
+                            .collect_vec()
+                    })
+                    .collect_vec();
+                (n, hash_of((labels[&n], neighbourhood)))
+            })
+            .collect();
+    }
+    labels
+}
+
+fn canonical_order_with(h: &impl HugrView, encodings: &HashMap<Node, NodeEncoding>) -> Vec<Node> {
+    let labels = node_labels(h, encodings);
+    let mut order = vec![h.root()];
+    let mut placed = HashSet::from([h.root()]);
+    let mut i = 0;
+    while i < order.len() {
+        order_children(h, order[i], &labels, &mut order, &mut placed);
+        i += 1;
+    }
+    order
+}
+
+/// Appends the children of `parent` to `order` in canonical order.
+fn order_children(
+    h: &impl HugrView,
+    parent: Node,
+    labels: &HashMap<Node, u64>,
+    order: &mut Vec<Node>,
+    placed: &mut HashSet<Node>,
+) {
+    let children = h.children(parent).collect_vec();
+    let fixed = ordered_children(h.get_optype(parent)).min(children.len());
+    let mut queue: VecDeque<Node> = children[..fixed].iter().copied().collect();
+    order.extend(&queue);
+    placed.extend(&queue);
+    // Start points for the components not reachable from the fixed children.
+    let mut starts = children[fixed..]
+        .iter()
+        .copied()
+        .sorted_by_key(|c| labels[c]);
+
+    loop {
+        while let Some(n) = queue.pop_front() {
+            for dir in [Direction::Outgoing, Direction::Incoming] {
+                for port in h.node_ports(n, dir) {
+                    let neighbours = h
+                        .linked_ports(n, port)
+                        .filter(|(m, _)| h.get_parent(*m) == Some(parent))
+                        .sorted_by_key(|(m, m_port)| (labels[m], m_port.index()));
+                    for (m, _) in neighbours {
+                        if placed.insert(m) {
+                            order.push(m);
+                            queue.push_back(m);
+                        }
+                    }
+                }
+            }
+        }
+        match starts.find(|c| !placed.contains(c)) {
+            Some(c) => {
+                placed.insert(c);
+                order.push(c);
+                queue.push_back(c);
+            }
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer,
+        HugrBuilder, ModuleBuilder,
+    };
+    use crate::hugr::region::{Region, RegionView};
+    use crate::hugr::HugrMut;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::types::{AbstractSignature, SimpleType};
+    use crate::{type_row, Hugr};
+
+    const QB: SimpleType = SimpleType::Qubit;
+
+    /// A circuit on three qubits applying `ops`, each a gate and the indices of
+    /// the qubits it acts on.
+    fn circuit(ops: &[(LeafOp, &[usize])]) -> Result<Hugr, BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB, QB],
+            type_row![QB, QB, QB],
+        ))?;
+        let mut wires = b.input_wires().collect_vec();
+        for (op, qbs) in ops {
+            let inputs = qbs.iter().map(|&q| wires[q]).collect_vec();
+            let outputs = b
+                .add_dataflow_op(op.clone(), inputs)?
+                .outputs()
+                .collect_vec();
+            for (&q, w) in qbs.iter().zip(outputs) {
+                wires[q] = w;
+            }
+        }
+        b.finish_hugr_with_outputs(wires)
+    }
+
+    #[test]
+    fn independent_gate_order() -> Result<(), BuildError> {
+        let config = CanonicalConfig::default();
+        let h1 = circuit(&[(LeafOp::H, &[0]), (LeafOp::X, &[1]), (LeafOp::CX, &[0, 2])])?;
+        let h2 = circuit(&[(LeafOp::X, &[1]), (LeafOp::H, &[0]), (LeafOp::CX, &[0, 2])])?;
+        assert_ne!(
+            serde_json::to_string(&h1).unwrap(),
+            serde_json::to_string(&h2).unwrap()
+        );
+        assert!(structurally_eq(&h1, &h2, &config));
+        assert_eq!(canonical_hash(&h1, &config), canonical_hash(&h2, &config));
+
+        // Port order matters.
+        let h3 = circuit(&[(LeafOp::X, &[1]), (LeafOp::H, &[0]), (LeafOp::CX, &[2, 0])])?;
+        assert!(!structurally_eq(&h1, &h3, &config));
+        assert_ne!(canonical_hash(&h1, &config), canonical_hash(&h3, &config));
+        Ok(())
+    }
+
+    #[test]
+    fn metadata() -> Result<(), BuildError> {
+        let h1 = circuit(&[(LeafOp::H, &[0])])?;
+        let mut h2 = h1.clone();
+        *h2.get_metadata_mut(h2.root()) = serde_json::json!({"name": "circuit"});
+
+        let ignore = CanonicalConfig::default();
+        let include = CanonicalConfig {
+            include_metadata: true,
+        };
+        assert!(structurally_eq(&h1, &h2, &ignore));
+        assert_eq!(canonical_hash(&h1, &ignore), canonical_hash(&h2, &ignore));
+        assert!(!structurally_eq(&h1, &h2, &include));
+        assert_ne!(canonical_hash(&h1, &include), canonical_hash(&h2, &include));
+        Ok(())
+    }
+
+    #[test]
+    fn functions() -> Result<(), BuildError> {
+        let config = CanonicalConfig::default();
+        let module = |names: [&str; 2]| -> Result<_, BuildError> {
+            let mut module_builder = ModuleBuilder::new();
+            let mut funcs = vec![];
+            for name in names {
+                let sig = AbstractSignature::new_df(type_row![QB], type_row![QB]);
+                let mut f_build = module_builder.define_function(name, sig.pure())?;
+                let [q] = f_build.input_wires_arr();
+                let op = if name == "h" { LeafOp::H } else { LeafOp::X };
+                let q = f_build.add_dataflow_op(op, [q])?.out_wire(0);
+                funcs.push(f_build.finish_with_outputs([q])?.node());
+            }
+            Ok((module_builder.finish_hugr()?, funcs))
+        };
+        let (h1, funcs1) = module(["h", "x"])?;
+        let (h2, funcs2) = module(["x", "h"])?;
+        assert!(structurally_eq(&h1, &h2, &config));
+        assert_eq!(canonical_hash(&h1, &config), canonical_hash(&h2, &config));
+
+        // Compare the regions of the functions.
+        let region = |h, f| RegionView::new(h, f);
+        assert!(structurally_eq(
+            &region(&h1, funcs1[0]),
+            &region(&h2, funcs2[1]),
+            &config
+        ));
+        assert!(!structurally_eq(
+            &region(&h1, funcs1[0]),
+            &region(&h2, funcs2[0]),
+            &config
+        ));
+        assert_eq!(
+            canonical_hash(&region(&h1, funcs1[1]), &config),
+            canonical_hash(&region(&h2, funcs2[0]), &config)
+        );
+
+        let order = canonical_order(&h1);
+        assert_eq!(order.len(), h1.node_count());
+        assert_eq!(order[0], h1.root());
+        Ok(())
+    }
+}
@@ -0,0 +1,338 @@
+//! Common Subexpression Elimination for the classical fragment of a Hugr.
+//!
+//! Two nodes in the same dataflow sibling graph compute the same value if they
+//! have equal operations and read the same input wires. When every port of
+//! such nodes is non-linear we keep only the first of them and redirect the
+//! outgoing edges of the others to it.
+//!
+//! Only operations without side effects are considered: leaf operations,
+//! constant loads and the [`Const`] definitions themselves. Nodes with
+//! children are never merged, as their bodies may differ.
+//!
+//! [`Const`]: crate::ops::Const
+
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::{OpName, OpTag, OpTrait, OpType};
+use crate::types::EdgeKind;
+use crate::{Hugr, Node, Port};
+
+/// Runs common subexpression elimination on every sibling graph of the Hugr.
+///
+/// Returns the number of nodes removed.
+pub fn eliminate_common_subexpressions(h: &mut Hugr) -> usize {
+    let parents = h
+        .nodes()
+        .filter(|n| h.children(*n).next().is_some())
+        .collect_vec();
+    parents
+        .into_iter()
+        .map(|p| {
+            let consts = merge_duplicate_consts(h, p);
+            let values = if OpTag::DataflowParent.is_superset(h.get_optype(p).tag()) {
+                merge_dataflow_duplicates(h, p)
+            } else {
+                0
+            };
+            consts + values
+        })
+        .sum()
+}
+
+/// Runs common subexpression elimination on the children of a single dataflow
+/// parent. Nested sibling graphs are not visited.
+///
+/// Returns the number of nodes removed.
+pub fn eliminate_common_subexpressions_in(h: &mut Hugr, parent: Node) -> Result<usize, CseError> {
+    let optype = h.get_optype(parent);
+    if !OpTag::DataflowParent.is_superset(optype.tag()) {
+        return Err(CseError::NotDataflowParent(parent, optype.clone()));
+    }
+    Ok(merge_duplicate_consts(h, parent) + merge_dataflow_duplicates(h, parent))
+}
+
+/// Error from a common subexpression elimination pass.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum CseError {
+    /// The given node is not the parent of a dataflow sibling graph.
+    #[error("Node {0:?} is not a dataflow parent (found {1:?})")]
+    NotDataflowParent(Node, OpType),
+}
+
+/// The part of a node's identity that can be hashed. Candidates with the same
+/// key are then compared on their full [`OpType`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CseKey {
+    name: smol_str::SmolStr,
+    /// The source of each incoming edge, in port order. Includes the sources
+    /// of Order edges, so that nodes with different ordering constraints are
+    /// never merged.
+    inputs: Vec<Vec<(Node, Port)>>,
+}
+
+/// Merge [`Const`] children of `parent` holding the same value and type.
+///
+/// [`Const`]: crate::ops::Const
+fn merge_duplicate_consts(h: &mut Hugr, parent: Node) -> usize {
+    let consts = h
+        .children(parent)
+        .filter(|n| h.get_optype(*n).tag() == OpTag::Const)
+        .collect_vec();
+    merge_in_order(h, consts)
+}
+
+/// Merge duplicate side-effect free nodes in the dataflow sibling graph under
+/// `parent`, visiting them in topological order so that merges can cascade.
+fn merge_dataflow_duplicates(h: &mut Hugr, parent: Node) -> usize {
+    let region = FlatRegionView::new(&*h, parent);
+    let order = petgraph::algo::toposort(&region, None)
+        .expect("Dataflow sibling graphs are acyclic")
+        .into_iter()
+        .filter(|n| *n != parent && is_candidate(h, *n))
+        .collect_vec();
+    merge_in_order(h, order)
+}
+
+/// Merge every node in `nodes` into the first earlier node it is equivalent
+/// to. Nodes must be given in an order where the sources of a node's inputs
+/// come before the node itself.
+fn merge_in_order(h: &mut Hugr, nodes: Vec<Node>) -> usize {
+    let mut seen: HashMap<CseKey, Vec<Node>> = HashMap::new();
+    let mut removed = 0;
+    for n in nodes {
+        let key = cse_key(h, n);
+        let bucket = seen.entry(key).or_default();
+        match bucket
+            .iter()
+            .find(|m| h.get_nodetype(**m) == h.get_nodetype(n))
+        {
+            Some(&kept) => {
+                replace_uses(h, n, kept);
+                h.remove_node(n).unwrap();
+                removed += 1;
+            }
+            None => bucket.push(n),
+        }
+    }
+    removed
+}
+
+fn cse_key(h: &Hugr, n: Node) -> CseKey {
+    let inputs = h
+        .node_inputs(n)
+        .map(|p| h.linked_ports(n, p).sorted().collect_vec())
+        .collect();
+    CseKey {
+        name: h.get_optype(n).name(),
+        inputs,
+    }
+}
+
+/// Whether the node may be merged with an equivalent one.
+fn is_candidate(h: &Hugr, n: Node) -> bool {
+    let op = h.get_optype(n);
+    let pure = matches!(op.tag(), OpTag::Leaf | OpTag::LoadConst | OpTag::Const);
+    pure && h.children(n).next().is_none()
+        && h.all_node_ports(n)
+            .all(|p| op.port_kind(p).map_or(true, |kind| !kind.is_linear()))
+}
+
+/// Move every outgoing edge of `old` to the same port of `new`.
+fn replace_uses(h: &mut Hugr, old: Node, new: Node) {
+    for out_port in h.node_outputs(old).collect_vec() {
+        for (tgt, tgt_port) in h.linked_ports(old, out_port).collect_vec() {
+            // Incoming Order ports may have other links, which must be kept.
+            // The link from `old` goes away when it is removed.
+            if !matches!(
+                h.get_optype(tgt).port_kind(tgt_port),
+                Some(EdgeKind::StateOrder)
+            ) {
+                h.disconnect(tgt, tgt_port).unwrap();
+            } else if h.linked_ports(new, out_port).contains(&(tgt, tgt_port)) {
+                // `new` is already ordered before `tgt`.
+                continue;
+            }
+            h.connect(new, out_port.index(), tgt, tgt_port.index())
+                .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::hugr::HugrView;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::{Const, LeafOp};
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+    use crate::{type_row, Direction};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+    const QB: SimpleType = SimpleType::Qubit;
+
+    fn count_op(h: &Hugr, name: &str) -> usize {
+        h.nodes()
+            .filter(|n| h.get_optype(*n).name() == name)
+            .count()
+    }
+
+    #[test]
+    fn merge_xors() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT, BIT],
+        ))?;
+        let [i0, i1] = b.input_wires_arr();
+        let x1 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?.out_wire(0);
+        let x2 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?.out_wire(0);
+        // These two become duplicates only once x1 and x2 have been merged.
+        let y1 = b.add_dataflow_op(LeafOp::Xor, [x1, i1])?.out_wire(0);
+        let y2 = b.add_dataflow_op(LeafOp::Xor, [x2, i1])?.out_wire(0);
+        let mut h = b.finish_hugr_with_outputs([y1, y2])?;
+
+        assert_eq!(eliminate_common_subexpressions(&mut h), 2);
+        h.validate().unwrap();
+        assert_eq!(count_op(&h, "Xor"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn different_ports_not_merged() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT, BIT],
+        ))?;
+        let [i0, i1] = b.input_wires_arr();
+        let x1 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?.out_wire(0);
+        let x2 = b.add_dataflow_op(LeafOp::Xor, [i1, i0])?.out_wire(0);
+        let mut h = b.finish_hugr_with_outputs([x1, x2])?;
+
+        assert_eq!(eliminate_common_subexpressions(&mut h), 0);
+        assert_eq!(count_op(&h, "Xor"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_constants() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![],
+            type_row![NAT, NAT, NAT],
+        ))?;
+        let c1 = b.add_load_const(Const::i64(3).unwrap())?;
+        let c2 = b.add_load_const(Const::i64(3).unwrap())?;
+        let c3 = b.add_load_const(Const::i64(4).unwrap())?;
+        let mut h = b.finish_hugr_with_outputs([c1, c2, c3])?;
+
+        // One Const and one LoadConstant are removed.
+        assert_eq!(eliminate_common_subexpressions(&mut h), 2);
+        h.validate().unwrap();
+        assert_eq!(count_op(&h, "LoadConstant"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn order_edges_respected() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT, BIT],
+        ))?;
+        let [i0, i1] = b.input_wires_arr();
+        let x1 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?;
+        let x2 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?;
+        b.set_order(&x1, &x2)?;
+        let mut h = b.finish_hugr_with_outputs([x1.out_wire(0), x2.out_wire(0)])?;
+
+        assert_eq!(eliminate_common_subexpressions(&mut h), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn order_successors_kept() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT, BIT, BIT],
+        ))?;
+        let [i0, i1] = b.input_wires_arr();
+        let x1 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?;
+        let x2 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?;
+        let y = b.add_dataflow_op(LeafOp::Xor, [i1, i1])?;
+        let z = b.add_dataflow_op(LeafOp::Xor, [i0, i0])?;
+        b.set_order(&x2, &z)?;
+        b.set_order(&y, &z)?;
+        let mut h = b.finish_hugr_with_outputs([x1.out_wire(0), x2.out_wire(0), z.out_wire(0)])?;
+
+        assert_eq!(eliminate_common_subexpressions(&mut h), 1);
+        h.validate().unwrap();
+        let z_preds = h.input_neighbours(z.node()).collect_vec();
+        assert!(z_preds.contains(&x1.node()));
+        assert!(z_preds.contains(&y.node()));
+        Ok(())
+    }
+
+    #[test]
+    fn shared_order_successor() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT, BIT, BIT],
+        ))?;
+        let [i0, i1] = b.input_wires_arr();
+        let x1 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?;
+        let x2 = b.add_dataflow_op(LeafOp::Xor, [i0, i1])?;
+        let z = b.add_dataflow_op(LeafOp::Xor, [i0, i0])?;
+        b.set_order(&x1, &z)?;
+        b.set_order(&x2, &z)?;
+        let mut h = b.finish_hugr_with_outputs([x1.out_wire(0), x2.out_wire(0), z.out_wire(0)])?;
+
+        assert_eq!(eliminate_common_subexpressions(&mut h), 1);
+        h.validate().unwrap();
+        // A single Order link remains from the kept node.
+        let order_out = h
+            .get_optype(x1.node())
+            .other_port_index(Direction::Outgoing)
+            .unwrap();
+        assert_eq!(
+            h.linked_ports(x1.node(), order_out).collect_vec(),
+            [(
+                z.node(),
+                h.get_optype(z.node())
+                    .other_port_index(Direction::Incoming)
+                    .unwrap()
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn linear_ops_ignored() -> Result<(), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB, BIT, BIT],
+        ))?;
+        let [q0, q1] = b.input_wires_arr();
+        let [q0, m0] = b.add_dataflow_op(LeafOp::Measure, [q0])?.outputs_arr();
+        let [q1, m1] = b.add_dataflow_op(LeafOp::Measure, [q1])?.outputs_arr();
+        let mut h = b.finish_hugr_with_outputs([q0, q1, m0, m1])?;
+
+        assert_eq!(eliminate_common_subexpressions(&mut h), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn not_dataflow_parent() {
+        let mut h = Hugr::default();
+        let root = h.root();
+        assert_matches!(
+            eliminate_common_subexpressions_in(&mut h, root),
+            Err(CseError::NotDataflowParent(n, _)) => assert_eq!(n, root)
+        );
+    }
+}
@@ -0,0 +1,712 @@
+//! Generic dataflow analysis over the regions of a Hugr.
+//!
+//! An analysis assigns a value of some [`Lattice`] to every dataflow port it
+//! reaches, and is defined by a [`DataflowAnalysis`] giving the transfer
+//! function of leaf operations. The framework takes care of the container
+//! nodes: values flow through [`DFG`]s, into the reachable cases of
+//! [`Conditional`]s, around [`TailLoop`] bodies and between the blocks of
+//! [`CFG`]s, iterating loops until a fixpoint is reached. Loops that have not
+//! stabilised after [`DataflowAnalysis::WIDENING_DELAY`] iterations use
+//! [`Lattice::widen`] instead of [`Lattice::join`], so that lattices of
+//! infinite height still terminate.
+//!
+//! Analyses run either [forward](AnalysisDirection::Forward), from the inputs
+//! of the region to its outputs, or [backward](AnalysisDirection::Backward).
+//! Example analyses are provided in [`const_prop`], [`liveness`] and
+//! [`qubits`].
+//!
+//! [`DFG`]: crate::ops::DFG
+//! [`Conditional`]: crate::ops::Conditional
+//! [`TailLoop`]: crate::ops::TailLoop
+//! [`CFG`]: crate::ops::CFG
+
+pub mod const_prop;
+pub mod liveness;
+pub mod qubits;
+
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use petgraph::visit::{DfsPostOrder, Walker};
+use thiserror::Error;
+
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::HugrView;
+use crate::ops::controlflow::BasicBlock;
+use crate::ops::{OpTag, OpTrait, OpType};
+use crate::types::SimpleRow;
+use crate::{Node, Port};
+
+/// A join-semilattice of abstract values.
+pub trait Lattice: Clone + PartialEq {
+    /// The least element, for values not (yet) known to be computed.
+    fn bottom() -> Self;
+
+    /// The greatest element, for values about which nothing is known.
+    fn top() -> Self;
+
+    /// Joins `other` into `self`, returning whether `self` changed.
+    fn join(&mut self, other: &Self) -> bool;
+
+    /// Widens `self` by `other` at the head of a loop, returning whether
+    /// `self` changed. Repeated widening must stabilise.
+    ///
+    /// The default is a plain [`Lattice::join`], which is enough for
+    /// lattices of finite height.
+    fn widen(&mut self, other: &Self) -> bool {
+        self.join(other)
+    }
+}
+
+/// The direction values flow in an analysis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnalysisDirection {
+    /// From the inputs of each operation to its outputs.
+    Forward,
+    /// From the outputs of each operation to its inputs.
+    Backward,
+}
+
+/// A dataflow analysis, defined by its lattice and the transfer functions of
+/// the operations that do not contain other nodes.
+pub trait DataflowAnalysis {
+    /// The abstract values assigned to ports.
+    type Value: Lattice;
+
+    /// The direction of the analysis.
+    const DIRECTION: AnalysisDirection;
+
+    /// The number of iterations of a loop after which values at its head are
+    /// widened rather than joined.
+    const WIDENING_DELAY: usize = 3;
+
+    /// Computes the values of the outputs of a node from the values of its
+    /// inputs (for forward analyses), or the values of the inputs from those
+    /// of the outputs (for backward analyses).
+    ///
+    /// Only dataflow ports are given values; the result must have one value
+    /// per dataflow port in the other direction.
+    fn transfer<H: HugrView>(&self, h: &H, node: Node, values: &[Self::Value]) -> Vec<Self::Value>;
+
+    /// The values at the boundary of the analysed region: its inputs for
+    /// forward analyses, its outputs for backward ones.
+    ///
+    /// Defaults to [`Lattice::top`] for every port.
+    fn boundary(&self, types: &SimpleRow) -> Vec<Self::Value> {
+        vec![Self::Value::top(); types.len()]
+    }
+
+    /// Forward analyses only: the values of the fields of variant `tag` of a
+    /// predicate, or `None` if the predicate cannot have that tag.
+    ///
+    /// Defaults to unknown fields for any predicate other than
+    /// [`Lattice::bottom`].
+    fn unpack_predicate(
+        &self,
+        pred: &Self::Value,
+        _tag: usize,
+        fields: usize,
+    ) -> Option<Vec<Self::Value>> {
+        (*pred != Self::Value::bottom()).then(|| vec![Self::Value::top(); fields])
+    }
+
+    /// Backward analyses only: the value of a predicate, given the values of
+    /// the fields of its variant `tag` where it is used.
+    ///
+    /// Defaults to [`Lattice::top`].
+    fn pack_predicate(&self, _tag: usize, _fields: &[Self::Value]) -> Self::Value {
+        Self::Value::top()
+    }
+}
+
+/// The values computed by a dataflow analysis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataflowResults<V> {
+    values: HashMap<(Node, Port), V>,
+    boundary: Vec<V>,
+}
+
+impl<V> DataflowResults<V> {
+    /// The value at a dataflow port, or `None` if the analysis never reached
+    /// the port.
+    pub fn value(&self, node: Node, port: Port) -> Option<&V> {
+        self.values.get(&(node, port))
+    }
+
+    /// The value at the `index`th dataflow input of a node.
+    pub fn input_value(&self, node: Node, index: usize) -> Option<&V> {
+        self.value(node, Port::new_incoming(index))
+    }
+
+    /// The value at the `index`th dataflow output of a node.
+    pub fn output_value(&self, node: Node, index: usize) -> Option<&V> {
+        self.value(node, Port::new_outgoing(index))
+    }
+
+    /// The values computed at the far boundary of the analysed region: its
+    /// outputs for forward analyses, its inputs for backward ones.
+    pub fn boundary_values(&self) -> &[V] {
+        &self.boundary
+    }
+}
+
+/// Runs a dataflow analysis on a region of a Hugr.
+///
+/// The region is either a dataflow container (a [`DFG`], [`FuncDefn`],
+/// [`Case`], or basic block) or a [`Conditional`], [`TailLoop`] or [`CFG`]
+/// node.
+///
+/// Values on non-local edges are read from wherever their source is. Only the
+/// region is analysed, so such sources outside it are never given values and
+/// appear as [`Lattice::bottom`].
+///
+/// [`DFG`]: crate::ops::DFG
+/// [`FuncDefn`]: crate::ops::FuncDefn
+/// [`Case`]: crate::ops::Case
+/// [`Conditional`]: crate::ops::Conditional
+/// [`TailLoop`]: crate::ops::TailLoop
+/// [`CFG`]: crate::ops::CFG
+pub fn run_analysis<A: DataflowAnalysis, H: HugrView>(
+    analysis: &A,
+    h: &H,
+    region: Node,
+) -> Result<DataflowResults<A::Value>, DataflowError> {
+    let mut solver = Solver {
+        analysis,
+        h,
+        values: HashMap::new(),
+        orders: HashMap::new(),
+    };
+    let op = h.get_optype(region);
+    let boundary = match op {
+        OpType::Conditional(_) | OpType::TailLoop(_) | OpType::CFG(_) => {
+            let sig = op.signature();
+            match A::DIRECTION {
+                AnalysisDirection::Forward => {
+                    solver.container_forward(region, analysis.boundary(&sig.input))
+                }
+                AnalysisDirection::Backward => {
+                    solver.container_backward(region, analysis.boundary(&sig.output))
+                }
+            }
+        }
+        _ => {
+            let [input, output] =
+                io_nodes(h, region).ok_or(DataflowError::NotARegion(region, op.tag()))?;
+            let (OpType::Input(i), OpType::Output(o)) = (h.get_optype(input), h.get_optype(output))
+            else {
+                return Err(DataflowError::NotARegion(region, op.tag()));
+            };
+            match A::DIRECTION {
+                AnalysisDirection::Forward => {
+                    solver.dsg_forward(region, analysis.boundary(&i.types))
+                }
+                AnalysisDirection::Backward => {
+                    solver.dsg_backward(region, analysis.boundary(&o.types))
+                }
+            }
+        }
+    };
+    Ok(DataflowResults {
+        values: solver.values,
+        boundary,
+    })
+}
+
+/// The state of a running analysis.
+struct Solver<'a, A: DataflowAnalysis, H> {
+    analysis: &'a A,
+    h: &'a H,
+    values: HashMap<(Node, Port), A::Value>,
+    /// Topological order of the children of each dataflow container.
+    orders: HashMap<Node, Vec<Node>>,
+}
+
+impl<'a, A: DataflowAnalysis, H: HugrView> Solver<'a, A, H> {
+    fn set_value(&mut self, node: Node, port: Port, v: A::Value) {
+        self.values.insert((node, port), v);
+    }
+
+    fn value(&self, node: Node, port: Port) -> A::Value {
+        self.values
+            .get(&(node, port))
+            .cloned()
+            .unwrap_or_else(A::Value::bottom)
+    }
+
+    fn order(&mut self, parent: Node) -> Vec<Node> {
+        let h = self.h;
+        self.orders
+            .entry(parent)
+            .or_insert_with(|| dataflow_order(h, parent))
+            .clone()
+    }
+
+    /// Reads the values reaching the dataflow inputs of a node, recording
+    /// them at the input ports.
+    fn read_inputs(&mut self, node: Node) -> Vec<A::Value> {
+        let count = self.h.get_optype(node).signature().input.len();
+        (0..count)
+            .map(|i| {
+                let port = Port::new_incoming(i);
+                let v = match self.h.linked_ports(node, port).next() {
+                    Some((src, src_port)) => self.value(src, src_port),
+                    None => A::Value::bottom(),
+                };
+                self.set_value(node, port, v.clone());
+                v
+            })
+            .collect()
+    }
+
+    /// Joins the values required by the targets of the dataflow outputs of a
+    /// node, recording them at the output ports.
+    fn read_outputs(&mut self, node: Node) -> Vec<A::Value> {
+        let count = self.h.get_optype(node).signature().output.len();
+        (0..count)
+            .map(|i| {
+                let port = Port::new_outgoing(i);
+                let mut v = A::Value::bottom();
+                for (tgt, tgt_port) in self.h.linked_ports(node, port) {
+                    v.join(&self.value(tgt, tgt_port));
+                }
+                self.set_value(node, port, v.clone());
+                v
+            })
+            .collect()
+    }
+
+    fn set_values(&mut self, node: Node, port: fn(usize) -> Port, values: Vec<A::Value>) {
+        for (i, v) in values.into_iter().enumerate() {
+            self.set_value(node, port(i), v);
+        }
+    }
+
+    /// Analyses a dataflow sibling graph forward, returning the values
+    /// reaching its outputs.
+    fn dsg_forward(&mut self, parent: Node, inputs: Vec<A::Value>) -> Vec<A::Value> {
+        let [input, output] = io_nodes(self.h, parent).expect("Not a dataflow container");
+        self.set_values(input, Port::new_outgoing, inputs);
+        for n in self.order(parent) {
+            if n == input {
+                continue;
+            }
+            let ins = self.read_inputs(n);
+            if n != output {
+                let outs = self.node_forward(n, ins);
+                self.set_values(n, Port::new_outgoing, outs);
+            }
+        }
+        self.read_inputs(output)
+    }
+
+    /// Analyses a dataflow sibling graph backward, returning the values
+    /// required at its inputs.
+    fn dsg_backward(&mut self, parent: Node, outputs: Vec<A::Value>) -> Vec<A::Value> {
+        let [input, output] = io_nodes(self.h, parent).expect("Not a dataflow container");
+        self.set_values(output, Port::new_incoming, outputs);
+        for n in self.order(parent).into_iter().rev() {
+            if n == output {
+                continue;
+            }
+            let outs = self.read_outputs(n);
+            if n != input {
+                let ins = self.node_backward(n, outs);
+                self.set_values(n, Port::new_incoming, ins);
+            }
+        }
+        self.read_outputs(input)
+    }
+
+    fn node_forward(&mut self, node: Node, inputs: Vec<A::Value>) -> Vec<A::Value> {
+        match self.h.get_optype(node) {
+            OpType::DFG(_) => self.dsg_forward(node, inputs),
+            OpType::Conditional(_) | OpType::TailLoop(_) | OpType::CFG(_) => {
+                self.container_forward(node, inputs)
+            }
+            _ => self.analysis.transfer(self.h, node, &inputs),
+        }
+    }
+
+    fn node_backward(&mut self, node: Node, outputs: Vec<A::Value>) -> Vec<A::Value> {
+        match self.h.get_optype(node) {
+            OpType::DFG(_) => self.dsg_backward(node, outputs),
+            OpType::Conditional(_) | OpType::TailLoop(_) | OpType::CFG(_) => {
+                self.container_backward(node, outputs)
+            }
+            _ => self.analysis.transfer(self.h, node, &outputs),
+        }
+    }
+
+    fn container_forward(&mut self, node: Node, inputs: Vec<A::Value>) -> Vec<A::Value> {
+        let op = self.h.get_optype(node);
+        let mut outputs = bottoms(op.signature().output.len());
+        match op {
+            OpType::Conditional(cond) => {
+                let cases = self
+                    .h
+                    .children(node)
+                    .zip(&cond.predicate_inputs)
+                    .collect_vec();
+                for (tag, (case, fields)) in cases.into_iter().enumerate() {
+                    if let Some(case_inputs) = self.unpack(&inputs[0], tag, fields.len()) {
+                        let case_inputs =
+                            case_inputs.into_iter().chain(inputs[1..].iter().cloned());
+                        let case_outputs = self.dsg_forward(case, case_inputs.collect());
+                        join_all(&mut outputs, &case_outputs, false);
+                    }
+                }
+            }
+            OpType::TailLoop(tail_loop) => {
+                let (n_continue, n_break) =
+                    (tail_loop.just_inputs.len(), tail_loop.just_outputs.len());
+                let mut body_inputs = inputs;
+                for iteration in 0.. {
+                    let body_outputs = self.dsg_forward(node, body_inputs.clone());
+                    let (pred, rest) = body_outputs.split_first().unwrap();
+                    if let Some(fields) = self.unpack(pred, 1, n_break) {
+                        join_all(&mut outputs, &[fields, rest.to_vec()].concat(), false);
+                    }
+                    let Some(fields) = self.unpack(pred, 0, n_continue) else {
+                        break;
+                    };
+                    let widen = iteration >= A::WIDENING_DELAY;
+                    if !join_all(&mut body_inputs, &[fields, rest.to_vec()].concat(), widen) {
+                        break;
+                    }
+                }
+            }
+            OpType::CFG(_) => {
+                let (entry, exit) = cfg_entry_exit(self.h, node);
+                let blocks = self.block_order(node, exit);
+                let mut block_inputs = HashMap::from([(entry, inputs)]);
+                for round in 0.. {
+                    let mut changed = false;
+                    for &b in blocks.iter().rev() {
+                        let Some(ins) = block_inputs.get(&b).cloned() else {
+                            continue;
+                        };
+                        let outs = self.dsg_forward(b, ins);
+                        let (pred, others) = outs.split_first().unwrap();
+                        for (tag, len) in predicate_lengths(self.h, b).into_iter().enumerate() {
+                            let Some(fields) = self.unpack(pred, tag, len) else {
+                                continue;
+                            };
+                            let succ_inputs = [fields, others.to_vec()].concat();
+                            for (succ, _) in self.h.linked_ports(b, Port::new_outgoing(tag)) {
+                                let widen = round >= A::WIDENING_DELAY;
+                                changed |= match block_inputs.get_mut(&succ) {
+                                    Some(current) => join_all(current, &succ_inputs, widen),
+                                    None => {
+                                        block_inputs.insert(succ, succ_inputs.clone());
+                                        true
+                                    }
+                                };
+                            }
+                        }
+                    }
+                    if !changed {
+                        break;
+                    }
+                }
+                if let Some(exit_inputs) = block_inputs.remove(&exit) {
+                    outputs = exit_inputs;
+                }
+            }
+            _ => panic!("Not a control flow container"),
+        }
+        outputs
+    }
+
+    fn container_backward(&mut self, node: Node, outputs: Vec<A::Value>) -> Vec<A::Value> {
+        let op = self.h.get_optype(node);
+        let mut inputs = bottoms(op.signature().input.len());
+        match op {
+            OpType::Conditional(cond) => {
+                let cases = self
+                    .h
+                    .children(node)
+                    .zip(&cond.predicate_inputs)
+                    .collect_vec();
+                let mut pred = A::Value::bottom();
+                for (tag, (case, fields)) in cases.into_iter().enumerate() {
+                    let case_inputs = self.dsg_backward(case, outputs.clone());
+                    let (fields, others) = case_inputs.split_at(fields.len());
+                    pred.join(&self.analysis.pack_predicate(tag, fields));
+                    join_all(&mut inputs[1..], others, false);
+                }
+                inputs[0] = pred;
+            }
+            OpType::TailLoop(tail_loop) => {
+                let n_continue = tail_loop.just_inputs.len();
+                let (breaks, rest) = outputs.split_at(tail_loop.just_outputs.len());
+                let break_pred = self.analysis.pack_predicate(1, breaks);
+                for iteration in 0.. {
+                    let (continues, next_rest) = inputs.split_at(n_continue);
+                    let mut pred = break_pred.clone();
+                    pred.join(&self.analysis.pack_predicate(0, continues));
+                    let mut body_outputs = vec![pred];
+                    body_outputs.extend(rest.iter().cloned());
+                    join_all(&mut body_outputs[1..], next_rest, false);
+                    let body_inputs = self.dsg_backward(node, body_outputs);
+                    let widen = iteration >= A::WIDENING_DELAY;
+                    if !join_all(&mut inputs, &body_inputs, widen) {
+                        break;
+                    }
+                }
+            }
+            OpType::CFG(_) => {
+                let (entry, exit) = cfg_entry_exit(self.h, node);
+                let blocks = self.block_order(node, exit);
+                let mut block_inputs: HashMap<Node, Vec<A::Value>> = self
+                    .h
+                    .children(node)
+                    .map(|b| (b, bottoms(block_input_count(self.h, b))))
+                    .collect();
+                block_inputs.insert(exit, outputs);
+                for round in 0.. {
+                    let mut changed = false;
+                    for &b in &blocks {
+                        let lengths = predicate_lengths(self.h, b);
+                        let n_others = block_output_count(self.h, b) - 1;
+                        let mut pred = A::Value::bottom();
+                        let mut others = bottoms(n_others);
+                        for (tag, len) in lengths.into_iter().enumerate() {
+                            for (succ, _) in self.h.linked_ports(b, Port::new_outgoing(tag)) {
+                                let (fields, succ_others) = block_inputs[&succ].split_at(len);
+                                pred.join(&self.analysis.pack_predicate(tag, fields));
+                                join_all(&mut others, succ_others, false);
+                            }
+                        }
+                        let body_outputs = [vec![pred], others].concat();
+                        let ins = self.dsg_backward(b, body_outputs);
+                        let widen = round >= A::WIDENING_DELAY;
+                        changed |= join_all(block_inputs.get_mut(&b).unwrap(), &ins, widen);
+                    }
+                    if !changed {
+                        break;
+                    }
+                }
+                inputs = block_inputs.remove(&entry).unwrap();
+            }
+            _ => panic!("Not a control flow container"),
+        }
+        inputs
+    }
+
+    /// The blocks of a CFG reachable from its entry, other than the exit, in
+    /// postorder.
+    ///
+    /// Blocks come after all the blocks they dominate, so values on
+    /// dominator edges are computed before (forward analyses in reverse
+    /// order) or after (backward analyses) they are used.
+    fn block_order(&self, cfg: Node, exit: Node) -> Vec<Node> {
+        let region = FlatRegionView::new(self.h, cfg);
+        let entry = self.h.children(cfg).next().unwrap();
+        DfsPostOrder::new(&region, entry)
+            .iter(&region)
+            .filter(|b| *b != exit)
+            .collect()
+    }
+
+    fn unpack(&self, pred: &A::Value, tag: usize, fields: usize) -> Option<Vec<A::Value>> {
+        self.analysis.unpack_predicate(pred, tag, fields)
+    }
+}
+
+/// The children of a dataflow container in topological order.
+///
+/// Only the edges between children are considered, ignoring those of the
+/// parent (such as the self-loop of a basic block).
+pub(crate) fn dataflow_order(h: &impl HugrView, parent: Node) -> Vec<Node> {
+    let local = |n: &Node| h.get_parent(*n) == Some(parent);
+    let mut n_preds: HashMap<Node, usize> = h
+        .children(parent)
+        .map(|n| (n, h.input_neighbours(n).filter(local).count()))
+        .collect();
+    let mut queue = h.children(parent).filter(|n| n_preds[n] == 0).collect_vec();
+    let mut order = Vec::with_capacity(n_preds.len());
+    while let Some(n) = queue.pop() {
+        order.push(n);
+        for succ in h.output_neighbours(n).filter(local) {
+            let count = n_preds.get_mut(&succ).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                queue.push(succ);
+            }
+        }
+    }
+    debug_assert_eq!(order.len(), n_preds.len(), "Cyclic dataflow graph");
+    order
+}
+
+fn bottoms<V: Lattice>(n: usize) -> Vec<V> {
+    vec![V::bottom(); n]
+}
+
+/// Joins (or widens) each of `new` into the corresponding element of
+/// `values`, returning whether any changed.
+fn join_all<V: Lattice>(values: &mut [V], new: &[V], widen: bool) -> bool {
+    let mut changed = false;
+    for (v, n) in values.iter_mut().zip(new) {
+        changed |= if widen { v.widen(n) } else { v.join(n) };
+    }
+    changed
+}
+
+fn io_nodes(h: &impl HugrView, parent: Node) -> Option<[Node; 2]> {
+    h.children(parent).take(2).collect_vec().try_into().ok()
+}
+
+fn cfg_entry_exit(h: &impl HugrView, cfg: Node) -> (Node, Node) {
+    h.children(cfg)
+        .take(2)
+        .collect_tuple()
+        .expect("CFG without entry or exit")
+}
+
+fn predicate_lengths(h: &impl HugrView, block: Node) -> Vec<usize> {
+    match h.get_optype(block) {
+        OpType::BasicBlock(BasicBlock::DFB {
+            predicate_variants, ..
+        }) => predicate_variants.iter().map(|r| r.len()).collect(),
+        _ => vec![],
+    }
+}
+
+fn block_input_count(h: &impl HugrView, block: Node) -> usize {
+    match h.get_optype(block) {
+        OpType::BasicBlock(b) => b.dataflow_input().len(),
+        _ => 0,
+    }
+}
+
+fn block_output_count(h: &impl HugrView, block: Node) -> usize {
+    let [_, output] = io_nodes(h, block).expect("Block without input and output");
+    h.get_optype(output).signature().input.len()
+}
+
+/// Errors that can occur when running a dataflow analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DataflowError {
+    /// The node is not a dataflow region.
+    #[error("Node {0:?} of type {1:?} is not a dataflow region")]
+    NotARegion(Node, OpTag),
+}
+
+#[cfg(test)]
+mod test {
+    use super::liveness::{Liveness, LivenessAnalysis};
+    use super::*;
+    use crate::algorithm::lower_control_flow::lower_control_flow;
+    use crate::algorithm::nest_cfgs::test::build_conditional_in_loop_cfg;
+    use crate::builder::{DFGBuilder, Dataflow, DataflowHugr};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+    use crate::{type_row, Hugr};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    /// The number of Xors a value went through, an infinite-height lattice.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Depth {
+        Unreached,
+        Finite(usize),
+        Infinite,
+    }
+
+    impl Lattice for Depth {
+        fn bottom() -> Self {
+            Self::Unreached
+        }
+
+        fn top() -> Self {
+            Self::Infinite
+        }
+
+        fn join(&mut self, other: &Self) -> bool {
+            let changed = other > self;
+            *self = (*self).max(*other);
+            changed
+        }
+
+        fn widen(&mut self, other: &Self) -> bool {
+            let changed = self.join(other);
+            if changed {
+                *self = Self::Infinite;
+            }
+            changed
+        }
+    }
+
+    struct XorDepth;
+
+    impl DataflowAnalysis for XorDepth {
+        type Value = Depth;
+
+        const DIRECTION: AnalysisDirection = AnalysisDirection::Forward;
+
+        fn transfer<H: HugrView>(&self, h: &H, node: Node, values: &[Depth]) -> Vec<Depth> {
+            let op = h.get_optype(node);
+            let depth = match (op, values.iter().max()) {
+                (OpType::LeafOp(LeafOp::Xor), Some(Depth::Finite(d))) => Depth::Finite(d + 1),
+                (_, Some(d)) => *d,
+                (_, None) => Depth::Finite(0),
+            };
+            vec![depth; op.signature().output.len()]
+        }
+
+        fn boundary(&self, types: &SimpleRow) -> Vec<Depth> {
+            vec![Depth::Finite(0); types.len()]
+        }
+    }
+
+    /// A loop xoring a bit with itself until a predicate says to stop.
+    fn build_xor_loop() -> Result<Hugr, Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(type_row![BIT], type_row![BIT]))?;
+        let [x] = b.input_wires_arr();
+        let mut loop_b = b.tail_loop_builder([], [(BIT, x)], type_row![])?;
+        let [x] = loop_b.input_wires_arr();
+        let [x] = loop_b.add_dataflow_op(LeafOp::Xor, [x, x])?.outputs_arr();
+        let pred = loop_b.make_predicate(0, [type_row![], type_row![]], [])?;
+        let loop_id = loop_b.finish_with_outputs(pred, [x])?;
+        Ok(b.finish_hugr_with_outputs(loop_id.outputs())?)
+    }
+
+    #[test]
+    fn widen_tail_loop() -> Result<(), Box<dyn std::error::Error>> {
+        let h = build_xor_loop()?;
+        let results = run_analysis(&XorDepth, &h, h.root())?;
+        assert_eq!(results.boundary_values(), [Depth::Infinite]);
+        Ok(())
+    }
+
+    #[test]
+    fn widen_cfg() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = build_xor_loop()?;
+        lower_control_flow(&mut h);
+        let results = run_analysis(&XorDepth, &h, h.root())?;
+        assert_eq!(results.boundary_values(), [Depth::Infinite]);
+        Ok(())
+    }
+
+    #[test]
+    fn backward_cfg() -> Result<(), Box<dyn std::error::Error>> {
+        let (h, head, _) = build_conditional_in_loop_cfg(false)?;
+        let results = run_analysis(&LivenessAnalysis, &h, h.root())?;
+        assert_eq!(results.boundary_values(), [Liveness::Live]);
+        let [input, _] = io_nodes(&h, head.node()).unwrap();
+        assert_eq!(results.output_value(input, 0), Some(&Liveness::Live));
+        Ok(())
+    }
+
+    #[test]
+    fn not_a_region() {
+        let h = Hugr::default();
+        assert_eq!(
+            run_analysis(&LivenessAnalysis, &h, h.root()),
+            Err(DataflowError::NotARegion(h.root(), OpTag::ModuleRoot))
+        );
+    }
+}
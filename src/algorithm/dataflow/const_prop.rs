@@ -0,0 +1,227 @@
+//! Constant propagation.
+//!
+//! A forward analysis finding the wires that always carry the same
+//! [`ConstValue`], starting from [`LoadConstant`] nodes and folding the leaf
+//! operations that can be evaluated on constants. Cases of
+//! [`Conditional`]s and successors of basic blocks are only analysed if their
+//! predicate may select them.
+//!
+//! [`LoadConstant`]: crate::ops::LoadConstant
+//! [`Conditional`]: crate::ops::Conditional
+
+use crate::hugr::HugrView;
+use crate::ops::{ConstValue, LeafOp, OpTrait, OpType};
+use crate::values::HashableValue;
+use crate::{Node, Port};
+
+use super::{AnalysisDirection, DataflowAnalysis, Lattice};
+
+/// The constant-propagation lattice: a wire carries no value yet, a single
+/// constant, or any value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstLattice {
+    /// No value reaches the wire.
+    Bottom,
+    /// The wire always carries this value.
+    Const(ConstValue),
+    /// The wire may carry different values.
+    Top,
+}
+
+impl Lattice for ConstLattice {
+    fn bottom() -> Self {
+        Self::Bottom
+    }
+
+    fn top() -> Self {
+        Self::Top
+    }
+
+    fn join(&mut self, other: &Self) -> bool {
+        let joined = match (&*self, other) {
+            (_, Self::Bottom) | (Self::Top, _) => return false,
+            (Self::Const(a), Self::Const(b)) if a == b => return false,
+            (Self::Bottom, _) => other.clone(),
+            _ => Self::Top,
+        };
+        *self = joined;
+        true
+    }
+}
+
+impl ConstLattice {
+    /// The constant value, if the wire always carries the same one.
+    pub fn as_const(&self) -> Option<&ConstValue> {
+        match self {
+            Self::Const(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// Constant propagation, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConstantPropagation;
+
+impl DataflowAnalysis for ConstantPropagation {
+    type Value = ConstLattice;
+
+    const DIRECTION: AnalysisDirection = AnalysisDirection::Forward;
+
+    fn transfer<H: HugrView>(
+        &self,
+        h: &H,
+        node: Node,
+        values: &[ConstLattice],
+    ) -> Vec<ConstLattice> {
+        let op = h.get_optype(node);
+        let n_outputs = op.signature().output.len();
+        if values.contains(&ConstLattice::Bottom) {
+            return vec![ConstLattice::Bottom; n_outputs];
+        }
+        let consts: Option<Vec<&ConstValue>> = values.iter().map(ConstLattice::as_const).collect();
+        let folded = match (op, consts) {
+            (OpType::LoadConstant(_), _) => h
+                .linked_ports(node, Port::new_incoming(0))
+                .next()
+                .and_then(|(c, _)| match h.get_optype(c) {
+                    OpType::Const(c) => Some(vec![c.value().clone()]),
+                    _ => None,
+                }),
+            (OpType::LeafOp(leaf), Some(consts)) => fold_leaf(leaf, &consts),
+            _ => None,
+        };
+        match folded {
+            Some(outs) => outs.into_iter().map(ConstLattice::Const).collect(),
+            None => vec![ConstLattice::Top; n_outputs],
+        }
+    }
+
+    fn unpack_predicate(
+        &self,
+        pred: &ConstLattice,
+        tag: usize,
+        fields: usize,
+    ) -> Option<Vec<ConstLattice>> {
+        match pred {
+            ConstLattice::Bottom => None,
+            ConstLattice::Top => Some(vec![ConstLattice::Top; fields]),
+            ConstLattice::Const(c) => {
+                let (t, value) = c.as_sum()?;
+                if t != tag {
+                    return None;
+                }
+                Some(match value.as_sequence() {
+                    Some(vs) if vs.len() == fields => {
+                        vs.into_iter().map(ConstLattice::Const).collect()
+                    }
+                    _ => vec![ConstLattice::Top; fields],
+                })
+            }
+        }
+    }
+}
+
+/// Evaluates a leaf operation on constant inputs, if supported.
+fn fold_leaf(leaf: &LeafOp, inputs: &[&ConstValue]) -> Option<Vec<ConstValue>> {
+    match leaf {
+        LeafOp::Noop { .. } => Some(vec![inputs[0].clone()]),
+        LeafOp::Xor => match inputs {
+            [ConstValue::Hashable(HashableValue::Int(a)), ConstValue::Hashable(HashableValue::Int(b))] => {
+                Some(vec![ConstValue::Hashable(HashableValue::Int(a ^ b))])
+            }
+            _ => None,
+        },
+        LeafOp::MakeTuple { .. } => {
+            let values = inputs.iter().map(|v| (*v).clone()).collect::<Vec<_>>();
+            Some(vec![ConstValue::sequence(&values)])
+        }
+        LeafOp::UnpackTuple { tys } => inputs[0].as_sequence().filter(|vs| vs.len() == tys.len()),
+        LeafOp::Tag { tag, .. } => Some(vec![ConstValue::sum(*tag, inputs[0].clone())]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::dataflow::run_analysis;
+    use crate::builder::{DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::Const;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+    use crate::{type_row, Hugr};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    fn bit(v: u128) -> ConstValue {
+        ConstValue::Hashable(HashableValue::Int(v))
+    }
+
+    /// Xors the input with a constant 1, and a constant 1 with a constant 0,
+    /// then branches on a constant predicate whose second case flips the
+    /// second result.
+    fn build() -> Result<(Hugr, Node, Node), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT],
+            type_row![BIT, BIT, BIT],
+        ))?;
+        let [x] = b.input_wires_arr();
+        let one = b.add_load_const(Const::int::<1>(1)?)?;
+        let zero = b.add_load_const(Const::int::<1>(0)?)?;
+        let [unknown] = b.add_dataflow_op(LeafOp::Xor, [x, one])?.outputs_arr();
+        let xor = b.add_dataflow_op(LeafOp::Xor, [one, zero])?;
+        let [known] = xor.outputs_arr();
+        let pred = b.add_load_const(Const::true_val())?;
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(BIT, known)],
+            type_row![BIT],
+        )?;
+        let mut cases = vec![];
+        for i in 0..2 {
+            let mut case_b = cond_b.case_builder(i)?;
+            let [mut y] = case_b.input_wires_arr();
+            if i == 1 {
+                let one = case_b.add_load_const(Const::int::<1>(1)?)?;
+                [y] = case_b.add_dataflow_op(LeafOp::Xor, [y, one])?.outputs_arr();
+            }
+            cases.push(case_b.finish_with_outputs([y])?.node());
+        }
+        let [flipped] = cond_b.finish_sub_container()?.outputs_arr();
+        let h = b.finish_hugr_with_outputs([unknown, known, flipped])?;
+        Ok((h, xor.node(), cases[0]))
+    }
+
+    #[test]
+    fn propagate() -> Result<(), Box<dyn std::error::Error>> {
+        let (h, xor, case0) = build()?;
+        let results = run_analysis(&ConstantPropagation, &h, h.root())?;
+        assert_eq!(
+            results.boundary_values(),
+            [
+                ConstLattice::Top,
+                ConstLattice::Const(bit(1)),
+                ConstLattice::Const(bit(0))
+            ]
+        );
+        assert_eq!(
+            results.output_value(xor, 0),
+            Some(&ConstLattice::Const(bit(1)))
+        );
+        // The first case is never selected.
+        let [case0_input, _] = h.children(case0).collect::<Vec<_>>().try_into().unwrap();
+        assert_eq!(results.output_value(case0_input, 0), None);
+        Ok(())
+    }
+
+    #[test]
+    fn join() {
+        let mut v = ConstLattice::Bottom;
+        assert!(v.join(&ConstLattice::Const(bit(1))));
+        assert!(!v.join(&ConstLattice::Const(bit(1))));
+        assert!(!v.join(&ConstLattice::Bottom));
+        assert!(v.join(&ConstLattice::Const(bit(0))));
+        assert_eq!(v, ConstLattice::Top);
+    }
+}
@@ -0,0 +1,108 @@
+//! Liveness analysis.
+//!
+//! A backward analysis finding the wires whose values may be used to compute
+//! the outputs of the region. The inputs of a node are live if any of its
+//! outputs is, or if it has no outputs at all and so is only there for its
+//! effects.
+
+use crate::hugr::HugrView;
+use crate::ops::OpTrait;
+use crate::Node;
+
+use super::{AnalysisDirection, DataflowAnalysis, Lattice};
+
+/// Whether the value of a wire may be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Liveness {
+    /// The value is never used.
+    Dead,
+    /// The value may be used.
+    Live,
+}
+
+impl Lattice for Liveness {
+    fn bottom() -> Self {
+        Self::Dead
+    }
+
+    fn top() -> Self {
+        Self::Live
+    }
+
+    fn join(&mut self, other: &Self) -> bool {
+        let changed = other > self;
+        *self = (*self).max(*other);
+        changed
+    }
+}
+
+/// Liveness analysis, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LivenessAnalysis;
+
+impl DataflowAnalysis for LivenessAnalysis {
+    type Value = Liveness;
+
+    const DIRECTION: AnalysisDirection = AnalysisDirection::Backward;
+
+    fn transfer<H: HugrView>(&self, h: &H, node: Node, values: &[Liveness]) -> Vec<Liveness> {
+        let n_inputs = h.get_optype(node).signature().input.len();
+        let live = values.is_empty() || values.contains(&Liveness::Live);
+        vec![if live { Liveness::Live } else { Liveness::Dead }; n_inputs]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::algorithm::dataflow::run_analysis;
+    use crate::builder::{Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    #[test]
+    fn dead_code() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module = ModuleBuilder::new();
+        let mut f = module.define_function(
+            "main",
+            AbstractSignature::new_df(type_row![BIT, BIT], type_row![BIT]).pure(),
+        )?;
+        let [a, b] = f.input_wires_arr();
+        let unused = f.add_dataflow_op(LeafOp::Xor, [a, b])?;
+        let [a2] = f
+            .add_dataflow_op(LeafOp::Noop { ty: BIT }, [a])?
+            .outputs_arr();
+        // A loop passing `b` around unchanged, whose output is unused.
+        let mut loop_b = f.tail_loop_builder([], [(BIT, b)], type_row![])?;
+        let [b2] = loop_b.input_wires_arr();
+        let pred = loop_b.make_predicate(1, [type_row![], type_row![]], [])?;
+        let tail_loop = loop_b.finish_with_outputs(pred, [b2])?;
+        let func = f.finish_with_outputs([a2])?;
+        let h = module.finish_hugr()?;
+
+        let results = run_analysis(&LivenessAnalysis, &h, func.node())?;
+        // `b` is only used by the unused Xor and loop.
+        assert_eq!(results.boundary_values(), [Liveness::Live, Liveness::Dead]);
+        assert_eq!(
+            results.output_value(unused.node(), 0),
+            Some(&Liveness::Dead)
+        );
+        assert_eq!(
+            (0..2)
+                .map(|i| results.input_value(unused.node(), i))
+                .collect_vec(),
+            [Some(&Liveness::Dead); 2]
+        );
+        assert_eq!(
+            results.input_value(tail_loop.node(), 0),
+            Some(&Liveness::Dead)
+        );
+        Ok(())
+    }
+}
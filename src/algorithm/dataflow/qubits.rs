@@ -0,0 +1,171 @@
+//! Qubit tracking.
+//!
+//! A forward analysis finding which of the qubits entering the region each
+//! linear wire may hold. Operations with as many linear outputs as linear
+//! inputs are assumed to map them in order, as quantum gates do; otherwise
+//! every linear output may hold any of the qubits of the linear inputs.
+
+use std::collections::BTreeSet;
+
+use crate::hugr::HugrView;
+use crate::ops::OpTrait;
+use crate::types::{PrimType, SimpleRow, SimpleType};
+use crate::Node;
+
+use super::{AnalysisDirection, DataflowAnalysis, Lattice};
+
+/// The qubits a wire may hold, identified by the index of the region input
+/// they entered through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QubitOrigins {
+    /// The wire holds one of these qubits, if any.
+    Known(BTreeSet<usize>),
+    /// The wire may hold any qubit.
+    Unknown,
+}
+
+impl Lattice for QubitOrigins {
+    fn bottom() -> Self {
+        Self::Known(BTreeSet::new())
+    }
+
+    fn top() -> Self {
+        Self::Unknown
+    }
+
+    fn join(&mut self, other: &Self) -> bool {
+        match (&mut *self, other) {
+            (Self::Unknown, _) => false,
+            (_, Self::Unknown) => {
+                *self = Self::Unknown;
+                true
+            }
+            (Self::Known(a), Self::Known(b)) => {
+                let len = a.len();
+                a.extend(b);
+                a.len() != len
+            }
+        }
+    }
+}
+
+/// Qubit tracking, see the [module documentation](self).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QubitTracking;
+
+impl DataflowAnalysis for QubitTracking {
+    type Value = QubitOrigins;
+
+    const DIRECTION: AnalysisDirection = AnalysisDirection::Forward;
+
+    fn transfer<H: HugrView>(
+        &self,
+        h: &H,
+        node: Node,
+        values: &[QubitOrigins],
+    ) -> Vec<QubitOrigins> {
+        let sig = h.get_optype(node).signature();
+        let linear_inputs = sig
+            .input
+            .iter()
+            .zip(values)
+            .filter(|(t, _)| is_linear(t))
+            .map(|(_, v)| v)
+            .collect::<Vec<_>>();
+        let n_linear_outputs = sig.output.iter().filter(|t| is_linear(t)).count();
+        let mut all = QubitOrigins::bottom();
+        for v in &linear_inputs {
+            all.join(v);
+        }
+        let in_order = n_linear_outputs == linear_inputs.len();
+        let mut linear_inputs = linear_inputs.into_iter();
+        sig.output
+            .iter()
+            .map(|t| match is_linear(t) {
+                false => QubitOrigins::bottom(),
+                true if in_order => linear_inputs.next().unwrap().clone(),
+                true => all.clone(),
+            })
+            .collect()
+    }
+
+    fn boundary(&self, types: &SimpleRow) -> Vec<QubitOrigins> {
+        types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| match is_linear(t) {
+                true => QubitOrigins::Known(BTreeSet::from([i])),
+                false => QubitOrigins::bottom(),
+            })
+            .collect()
+    }
+
+    fn unpack_predicate(
+        &self,
+        _pred: &QubitOrigins,
+        _tag: usize,
+        fields: usize,
+    ) -> Option<Vec<QubitOrigins>> {
+        // Predicates are classical, so every case may be selected.
+        Some(vec![QubitOrigins::bottom(); fields])
+    }
+}
+
+fn is_linear(t: &SimpleType) -> bool {
+    !t.tag().is_classical()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::dataflow::run_analysis;
+    use crate::builder::{DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{AbstractSignature, ClassicType};
+
+    const QB: SimpleType = SimpleType::Qubit;
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    fn known(qbs: impl IntoIterator<Item = usize>) -> QubitOrigins {
+        QubitOrigins::Known(qbs.into_iter().collect())
+    }
+
+    #[test]
+    fn track_qubits() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB, BIT],
+        ))?;
+        let [q0, q1] = b.input_wires_arr();
+        let [q0] = b.add_dataflow_op(LeafOp::H, [q0])?.outputs_arr();
+        let [q0, q1] = b.add_dataflow_op(LeafOp::CX, [q0, q1])?.outputs_arr();
+        let [q1, m] = b.add_dataflow_op(LeafOp::Measure, [q1])?.outputs_arr();
+        let pred = b.make_predicate(0, [type_row![], type_row![]], [])?;
+        // Swaps the qubits in one of the cases.
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(QB, q0), (QB, q1)],
+            type_row![QB, QB],
+        )?;
+        for i in 0..2 {
+            let case_b = cond_b.case_builder(i)?;
+            let [a, b] = case_b.input_wires_arr();
+            let outs = if i == 0 { [a, b] } else { [b, a] };
+            case_b.finish_with_outputs(outs)?;
+        }
+        let cond = cond_b.finish_sub_container()?;
+        let [r0, r1] = cond.outputs_arr();
+        let h = b.finish_hugr_with_outputs([r0, r1, m])?;
+
+        let results = run_analysis(&QubitTracking, &h, h.root())?;
+        assert_eq!(results.input_value(cond.node(), 1), Some(&known([0])));
+        assert_eq!(results.input_value(cond.node(), 2), Some(&known([1])));
+        assert_eq!(
+            results.boundary_values(),
+            [known([0, 1]), known([0, 1]), known([])]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,527 @@
+//! Differences between two HUGRs.
+//!
+//! [`diff`] matches the nodes of an old and a new HUGR, and reports the nodes
+//! and edges that were added, removed or changed. Nodes are matched top-down
+//! through the hierarchy, starting from the roots:
+//!
+//! - Children with a meaningful position, such as the [`Input`] and [`Output`]
+//!   of a dataflow parent, are matched with the children at the same position.
+//! - Nodes linked through the same ports to matched nodes are matched if their
+//!   parents are matched and their operations have the same name.
+//! - The remaining children of matched nodes are matched with equal
+//!   operations first, and then with operations of the same name.
+//! - Finally, unmatched nodes linked through the same ports to matched nodes
+//!   are matched if their operations have the same [`OpTag`].
+//!
+//! Matched nodes whose operation, ports, input resources or metadata differ
+//! are reported as changed.
+//!
+//! [`Input`]: crate::ops::Input
+//! [`Output`]: crate::ops::Output
+//! [`OpTag`]: crate::ops::OpTag
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{self, Display, Write};
+
+use itertools::Itertools;
+
+use super::canonical::ordered_children;
+use crate::hugr::render::dot_string_highlighted;
+use crate::hugr::HugrView;
+use crate::ops::{OpName, OpTrait};
+use crate::{Direction, Node, Port};
+
+/// Colour of removed nodes and edges in the DOT renderings.
+const REMOVED_COLOR: &str = "red";
+/// Colour of added nodes and edges in the DOT renderings.
+const ADDED_COLOR: &str = "green";
+/// Colour of changed nodes in the DOT renderings.
+const CHANGED_COLOR: &str = "orange";
+
+/// The differences between two HUGRs, as computed by [`diff`].
+///
+/// Nodes and edges of the old HUGR are reported with their indices in it, and
+/// those of the new HUGR with their indices in the new one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HugrDiff {
+    /// The matched nodes, from the old HUGR to the new one.
+    pub node_map: HashMap<Node, Node>,
+    /// Nodes of the new HUGR with no match in the old one.
+    pub added_nodes: Vec<Node>,
+    /// Nodes of the old HUGR with no match in the new one.
+    pub removed_nodes: Vec<Node>,
+    /// Matched nodes that differ.
+    pub changed_nodes: Vec<NodeChange>,
+    /// Edges of the new HUGR that do not exist between the matched nodes of
+    /// the old one.
+    pub added_edges: Vec<DiffEdge>,
+    /// Edges of the old HUGR that do not exist between the matched nodes of
+    /// the new one.
+    pub removed_edges: Vec<DiffEdge>,
+}
+
+/// A pair of matched nodes that differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeChange {
+    /// The node in the old HUGR.
+    pub old: Node,
+    /// The node in the new HUGR.
+    pub new: Node,
+    /// What differs between the nodes.
+    pub changes: Vec<ChangeKind>,
+}
+
+/// A difference between two matched nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The operations are different.
+    Op,
+    /// The numbers of ports are different.
+    Ports,
+    /// The input resources are different.
+    InputResources,
+    /// The metadata is different.
+    Metadata,
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChangeKind::Op => "operation",
+            ChangeKind::Ports => "ports",
+            ChangeKind::InputResources => "input resources",
+            ChangeKind::Metadata => "metadata",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An edge between two ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DiffEdge {
+    /// The source node.
+    pub source: Node,
+    /// The outgoing port of the source node.
+    pub source_port: Port,
+    /// The target node.
+    pub target: Node,
+    /// The incoming port of the target node.
+    pub target_port: Port,
+}
+
+/// Computes the differences between two HUGRs.
+///
+/// See the [module-level documentation](self) for how nodes are matched.
+pub fn diff(old: &impl HugrView, new: &impl HugrView) -> HugrDiff {
+    let mut matcher = Matcher::new(old, new);
+    matcher.run();
+    let Matcher {
+        forward, backward, ..
+    } = matcher;
+
+    let removed_nodes = old.nodes().filter(|n| !forward.contains_key(n)).collect();
+    let added_nodes = new.nodes().filter(|n| !backward.contains_key(n)).collect();
+    let changed_nodes = old
+        .nodes()
+        .filter_map(|a| {
+            let b = *forward.get(&a)?;
+            let changes = node_changes(old, a, new, b);
+            (!changes.is_empty()).then_some(NodeChange {
+                old: a,
+                new: b,
+                changes,
+            })
+        })
+        .collect();
+
+    HugrDiff {
+        added_nodes,
+        removed_nodes,
+        changed_nodes,
+        added_edges: unmatched_edges(new, old, &backward),
+        removed_edges: unmatched_edges(old, new, &forward),
+        node_map: forward,
+    }
+}
+
+impl HugrDiff {
+    /// Returns `true` if the HUGRs have no differences.
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_nodes.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+
+    /// Renders the differences as text, one per line.
+    ///
+    /// Lines starting with `-` describe removed nodes and edges, `+` added
+    /// ones and `~` changed nodes. Nodes are shown with their index and the
+    /// name of their operation.
+    pub fn to_text(&self, old: &impl HugrView, new: &impl HugrView) -> String {
+        let node = |h: &dyn Fn(Node) -> String, n: Node| format!("({}) {}", n.index(), h(n));
+        let old_name = |n: Node| old.get_optype(n).name().to_string();
+        let new_name = |n: Node| new.get_optype(n).name().to_string();
+        let edge = |h: &dyn Fn(Node) -> String, e: &DiffEdge| {
+            format!(
+                "{}:{} -> {}:{}",
+                node(h, e.source),
+                e.source_port.index(),
+                node(h, e.target),
+                e.target_port.index()
+            )
+        };
+
+        let mut out = String::new();
+        for &n in &self.removed_nodes {
+            writeln!(out, "- {}", node(&old_name, n)).unwrap();
+        }
+        for &n in &self.added_nodes {
+            writeln!(out, "+ {}", node(&new_name, n)).unwrap();
+        }
+        for change in &self.changed_nodes {
+            writeln!(
+                out,
+                "~ {} => {}: {}",
+                node(&old_name, change.old),
+                node(&new_name, change.new),
+                change.changes.iter().join(", ")
+            )
+            .unwrap();
+        }
+        for e in &self.removed_edges {
+            writeln!(out, "- {}", edge(&old_name, e)).unwrap();
+        }
+        for e in &self.added_edges {
+            writeln!(out, "+ {}", edge(&new_name, e)).unwrap();
+        }
+        out
+    }
+
+    /// Renders the old HUGR with [`render::dot_string`], highlighting the
+    /// removed nodes and edges in red and the changed nodes in orange.
+    ///
+    /// [`render::dot_string`]: crate::hugr::render::dot_string
+    pub fn dot_string_old(&self, old: &impl HugrView) -> String {
+        highlighted(
+            old,
+            self.removed_nodes.iter().copied().collect(),
+            self.changed_nodes.iter().map(|c| c.old).collect(),
+            self.removed_edges.iter().copied().collect(),
+            REMOVED_COLOR,
+        )
+    }
+
+    /// Renders the new HUGR with [`render::dot_string`], highlighting the
+    /// added nodes and edges in green and the changed nodes in orange.
+    ///
+    /// [`render::dot_string`]: crate::hugr::render::dot_string
+    pub fn dot_string_new(&self, new: &impl HugrView) -> String {
+        highlighted(
+            new,
+            self.added_nodes.iter().copied().collect(),
+            self.changed_nodes.iter().map(|c| c.new).collect(),
+            self.added_edges.iter().copied().collect(),
+            ADDED_COLOR,
+        )
+    }
+}
+
+fn highlighted(
+    h: &impl HugrView,
+    nodes: HashSet<Node>,
+    changed: HashSet<Node>,
+    edges: HashSet<DiffEdge>,
+    color: &'static str,
+) -> String {
+    dot_string_highlighted(
+        h,
+        &Default::default(),
+        |n| {
+            if nodes.contains(&n) {
+                Some(color)
+            } else if changed.contains(&n) {
+                Some(CHANGED_COLOR)
+            } else {
+                None
+            }
+        },
+        |(source, source_port), (target, target_port)| {
+            let edge = DiffEdge {
+                source,
+                source_port,
+                target,
+                target_port,
+            };
+            edges.contains(&edge).then_some(color)
+        },
+    )
+}
+
+/// Lists the differences between two matched nodes.
+fn node_changes(old: &impl HugrView, a: Node, new: &impl HugrView, b: Node) -> Vec<ChangeKind> {
+    let (old_type, new_type) = (old.get_nodetype(a), new.get_nodetype(b));
+    let mut changes = vec![];
+    if old_type.op() != new_type.op() {
+        changes.push(ChangeKind::Op);
+    }
+    if (old.num_inputs(a), old.num_outputs(a)) != (new.num_inputs(b), new.num_outputs(b)) {
+        changes.push(ChangeKind::Ports);
+    }
+    if old_type.input_resources() != new_type.input_resources() {
+        changes.push(ChangeKind::InputResources);
+    }
+    if old.get_metadata(a) != new.get_metadata(b) {
+        changes.push(ChangeKind::Metadata);
+    }
+    changes
+}
+
+/// The edges of `h` that do not exist between the corresponding nodes of
+/// `other`, given the map from the nodes of `h` to those of `other`.
+fn unmatched_edges(
+    h: &impl HugrView,
+    other: &impl HugrView,
+    node_map: &HashMap<Node, Node>,
+) -> Vec<DiffEdge> {
+    let mut edges = vec![];
+    for source in h.nodes() {
+        for source_port in h.node_outputs(source) {
+            for (target, target_port) in h.linked_ports(source, source_port) {
+                let exists = match (node_map.get(&source), node_map.get(&target)) {
+                    (Some(&s), Some(&t)) => {
+                        source_port.index() < other.num_outputs(s)
+                            && other
+                                .linked_ports(s, source_port)
+                                .contains(&(t, target_port))
+                    }
+                    _ => false,
+                };
+                if !exists {
+                    edges.push(DiffEdge {
+                        source,
+                        source_port,
+                        target,
+                        target_port,
+                    });
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Matches the nodes of two HUGRs.
+struct Matcher<'a, A, B> {
+    old: &'a A,
+    new: &'a B,
+    /// Matched nodes, from the old HUGR to the new one.
+    forward: HashMap<Node, Node>,
+    /// Matched nodes, from the new HUGR to the old one.
+    backward: HashMap<Node, Node>,
+    /// Matched pairs whose children and neighbours have not been visited.
+    queue: VecDeque<(Node, Node)>,
+    /// Matched pairs whose remaining children have not been matched.
+    parents: VecDeque<(Node, Node)>,
+    /// All the matched pairs, in the order they were matched.
+    matched: Vec<(Node, Node)>,
+}
+
+impl<'a, A: HugrView, B: HugrView> Matcher<'a, A, B> {
+    fn new(old: &'a A, new: &'a B) -> Self {
+        Self {
+            old,
+            new,
+            forward: HashMap::new(),
+            backward: HashMap::new(),
+            queue: VecDeque::new(),
+            parents: VecDeque::new(),
+            matched: vec![],
+        }
+    }
+
+    fn add(&mut self, a: Node, b: Node) {
+        self.forward.insert(a, b);
+        self.backward.insert(b, a);
+        self.queue.push_back((a, b));
+        self.matched.push((a, b));
+    }
+
+    fn is_unmatched(&self, a: Node, b: Node) -> bool {
+        !self.forward.contains_key(&a) && !self.backward.contains_key(&b)
+    }
+
+    /// Whether two unmatched nodes may be matched because of their position.
+    ///
+    /// If `strict`, their operations must have the same name, otherwise the
+    /// same tag.
+    fn is_compatible(&self, a: Node, b: Node, strict: bool) -> bool {
+        let (op_a, op_b) = (self.old.get_optype(a), self.new.get_optype(b));
+        let similar = match strict {
+            true => op_a.name() == op_b.name(),
+            false => op_a.tag() == op_b.tag(),
+        };
+        self.is_unmatched(a, b)
+            && similar
+            && match (self.old.get_parent(a), self.new.get_parent(b)) {
+                (Some(pa), Some(pb)) => self.forward.get(&pa) == Some(&pb),
+                _ => false,
+            }
+    }
+
+    fn run(&mut self) {
+        self.add(self.old.root(), self.new.root());
+        self.propagate(true);
+        // Revisit every matched pair to match the nodes in corresponding
+        // positions that have different operations.
+        self.queue.extend(self.matched.clone());
+        self.propagate(false);
+    }
+
+    /// Matches nodes from the queued pairs until no more can be matched.
+    fn propagate(&mut self, strict: bool) {
+        loop {
+            while let Some((a, b)) = self.queue.pop_front() {
+                self.parents.push_back((a, b));
+                self.match_fixed_children(a, b);
+                self.match_neighbours(a, b, strict);
+            }
+            match self.parents.pop_front() {
+                Some((a, b)) => self.match_remaining_children(a, b),
+                None => break,
+            }
+        }
+    }
+
+    fn match_fixed_children(&mut self, a: Node, b: Node) {
+        let fixed =
+            ordered_children(self.old.get_optype(a)).min(ordered_children(self.new.get_optype(b)));
+        let children = self
+            .old
+            .children(a)
+            .zip(self.new.children(b))
+            .take(fixed)
+            .collect_vec();
+        for (x, y) in children {
+            if self.is_compatible(x, y, false) {
+                self.add(x, y);
+            }
+        }
+    }
+
+    fn match_neighbours(&mut self, a: Node, b: Node, strict: bool) {
+        for dir in [Direction::Incoming, Direction::Outgoing] {
+            let ports = self.old.node_ports(a, dir).zip(self.new.node_ports(b, dir));
+            for (pa, pb) in ports.collect_vec() {
+                let mut ys = self.new.linked_ports(b, pb).collect_vec();
+                for (x, xp) in self.old.linked_ports(a, pa).collect_vec() {
+                    if let Some(i) = ys
+                        .iter()
+                        .position(|&(y, yp)| xp == yp && self.is_compatible(x, y, strict))
+                    {
+                        let (y, _) = ys.remove(i);
+                        self.add(x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    fn match_remaining_children(&mut self, a: Node, b: Node) {
+        let (old, new) = (self.old, self.new);
+        let xs = old.children(a).collect_vec();
+        let ys = new.children(b).collect_vec();
+        let same_op = |x: Node, y: Node| old.get_optype(x) == new.get_optype(y);
+        let same_name = |x: Node, y: Node| old.get_optype(x).name() == new.get_optype(y).name();
+        for similar in [&same_op as &dyn Fn(Node, Node) -> bool, &same_name] {
+            for &x in &xs {
+                if let Some(&y) = ys
+                    .iter()
+                    .find(|&&y| self.is_unmatched(x, y) && similar(x, y))
+                {
+                    self.add(x, y);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::hugr::HugrMut;
+    use crate::ops::LeafOp;
+    use crate::types::{AbstractSignature, SimpleType};
+    use crate::{type_row, Hugr};
+
+    const QB: SimpleType = SimpleType::Qubit;
+
+    /// A circuit on one qubit applying `ops` in sequence.
+    fn circuit(ops: &[LeafOp]) -> Result<Hugr, BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(type_row![QB], type_row![QB]))?;
+        let [mut q] = b.input_wires_arr();
+        for op in ops {
+            q = b.add_dataflow_op(op.clone(), [q])?.out_wire(0);
+        }
+        b.finish_hugr_with_outputs([q])
+    }
+
+    #[test]
+    fn identical() -> Result<(), BuildError> {
+        let h = circuit(&[LeafOp::H, LeafOp::X])?;
+        let d = diff(&h, &h.clone());
+        assert!(d.is_empty());
+        assert_eq!(d.node_map.len(), h.node_count());
+        assert!(d.node_map.iter().all(|(a, b)| a == b));
+        assert_eq!(d.to_text(&h, &h), "");
+        Ok(())
+    }
+
+    #[test]
+    fn changed_op() -> Result<(), BuildError> {
+        let old = circuit(&[LeafOp::H, LeafOp::X])?;
+        let mut new = circuit(&[LeafOp::H, LeafOp::Z])?;
+        let h = new
+            .nodes()
+            .find(|n| new.get_optype(*n) == &LeafOp::H.into());
+        *new.get_metadata_mut(h.unwrap()) = serde_json::json!("hadamard");
+
+        let d = diff(&old, &new);
+        assert!(d.added_nodes.is_empty() && d.removed_nodes.is_empty());
+        assert!(d.added_edges.is_empty() && d.removed_edges.is_empty());
+        let changes = d.changed_nodes.iter().map(|c| &c.changes).collect_vec();
+        assert_eq!(changes, [&[ChangeKind::Metadata], &[ChangeKind::Op]]);
+        assert_eq!(
+            d.to_text(&old, &new),
+            "~ (3) H => (3) H: metadata\n~ (4) X => (4) Z: operation\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn inserted_op() -> Result<(), BuildError> {
+        let old = circuit(&[LeafOp::H])?;
+        let new = circuit(&[LeafOp::X, LeafOp::H])?;
+
+        let d = diff(&old, &new);
+        assert!(d.removed_nodes.is_empty() && d.changed_nodes.is_empty());
+        assert_eq!(d.added_nodes, [Node::from(portgraph::NodeIndex::new(3))]);
+        assert_eq!(d.removed_edges.len(), 1);
+        assert_eq!(d.added_edges.len(), 2);
+        assert_eq!(
+            d.to_text(&old, &new),
+            "+ (3) X\n\
+             - (1) Input:0 -> (3) H:0\n\
+             + (1) Input:0 -> (3) X:0\n\
+             + (3) X:0 -> (4) H:0\n"
+        );
+
+        assert!(d.dot_string_old(&old).contains("color=\"red\""));
+        let dot = d.dot_string_new(&new);
+        assert!(dot.contains("<font color=\"green\"><b>(3) X</b></font>"));
+        assert_eq!(dot.matches(" color=\"green\" penwidth=2]").count(), 2);
+        Ok(())
+    }
+}
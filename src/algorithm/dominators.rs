@@ -0,0 +1,619 @@
+//! Dominator analyses of CFG regions.
+//!
+//! [`CfgDominators`] collects, for a single [`CFG`] node, the dominator and
+//! post-dominator trees of its basic blocks, their dominance frontiers and the
+//! forest of natural loops. The analysis can be updated in place after an
+//! [`OutlineCfg`] rewrite instead of being recomputed, and a
+//! [`DominatorCache`] keeps the analyses of several CFGs around between
+//! queries.
+//!
+//! Only blocks reachable from the entry appear in the dominator tree, and
+//! only blocks from which the exit can be reached appear in the
+//! post-dominator tree.
+//!
+//! [`CFG`]: crate::ops::CFG
+//! [`OutlineCfg`]: crate::hugr::rewrite::outline_cfg::OutlineCfg
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use petgraph::algo::dominators::{self, Dominators};
+use petgraph::visit::Reversed;
+use thiserror::Error;
+
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::HugrView;
+use crate::ops::{OpTag, OpTrait};
+use crate::Node;
+
+/// A tree of immediate (post-)dominators over the blocks of a CFG.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DominatorTree {
+    /// The entry block for dominators, or the exit block for post-dominators.
+    root: Node,
+    /// The immediate dominator of every block in the tree, `None` for the root.
+    idom: HashMap<Node, Option<Node>>,
+}
+
+impl DominatorTree {
+    /// Computes the dominator tree of the blocks of a CFG node, rooted at its
+    /// entry block.
+    pub fn dominators(h: &impl HugrView, cfg: Node) -> Result<Self, DominatorError> {
+        check_cfg(h, cfg)?;
+        let region = FlatRegionView::new(h, cfg);
+        let entry = h.children(cfg).next().unwrap();
+        let doms = dominators::simple_fast(&region, entry);
+        Ok(Self::from_petgraph(h.children(cfg), &doms))
+    }
+
+    /// Computes the post-dominator tree of the blocks of a CFG node, rooted
+    /// at its exit block.
+    pub fn post_dominators(h: &impl HugrView, cfg: Node) -> Result<Self, DominatorError> {
+        check_cfg(h, cfg)?;
+        let region = FlatRegionView::new(h, cfg);
+        let exit = h.children(cfg).nth(1).unwrap();
+        let doms = dominators::simple_fast(Reversed(&region), exit);
+        Ok(Self::from_petgraph(h.children(cfg), &doms))
+    }
+
+    fn from_petgraph(blocks: impl Iterator<Item = Node>, doms: &Dominators<Node>) -> Self {
+        let idom = blocks
+            .filter(|n| doms.dominators(*n).is_some())
+            .map(|n| (n, doms.immediate_dominator(n)))
+            .collect();
+        Self {
+            root: doms.root(),
+            idom,
+        }
+    }
+
+    /// The root of the tree.
+    pub fn root(&self) -> Node {
+        self.root
+    }
+
+    /// Whether the block is part of the tree, i.e. is reachable from the
+    /// entry (for dominators) or reaches the exit (for post-dominators).
+    pub fn contains(&self, n: Node) -> bool {
+        self.idom.contains_key(&n)
+    }
+
+    /// The blocks in the tree, in arbitrary order.
+    pub fn nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.idom.keys().copied()
+    }
+
+    /// The immediate dominator of a block, or `None` for the root and for
+    /// blocks not in the tree.
+    pub fn immediate_dominator(&self, n: Node) -> Option<Node> {
+        self.idom.get(&n).copied().flatten()
+    }
+
+    /// The blocks immediately dominated by `n`, in arbitrary order.
+    pub fn children(&self, n: Node) -> impl Iterator<Item = Node> + '_ {
+        self.idom
+            .iter()
+            .filter(move |(_, d)| **d == Some(n))
+            .map(|(c, _)| *c)
+    }
+
+    /// The dominators of a block, starting with the block itself and ending
+    /// with the root. Empty if the block is not in the tree.
+    pub fn dominators_of(&self, n: Node) -> impl Iterator<Item = Node> + '_ {
+        let start = self.contains(n).then_some(n);
+        std::iter::successors(start, |n| self.immediate_dominator(*n))
+    }
+
+    /// Whether `a` dominates `b`. Every block dominates itself.
+    pub fn dominates(&self, a: Node, b: Node) -> bool {
+        self.dominators_of(b).contains(&a)
+    }
+
+    /// Whether `a` dominates `b` and is distinct from it.
+    pub fn strictly_dominates(&self, a: Node, b: Node) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Contracts the `outlined` blocks into the single block `new_block`,
+    /// which is added to the tree with immediate dominator `new_idom` if
+    /// `in_tree`.
+    fn contract(
+        &mut self,
+        outlined: &HashSet<Node>,
+        new_block: Node,
+        new_idom: Option<Node>,
+        in_tree: bool,
+    ) {
+        if outlined.contains(&self.root) {
+            self.root = new_block;
+        }
+        self.idom.retain(|n, _| !outlined.contains(n));
+        for d in self.idom.values_mut() {
+            if d.is_some_and(|d| outlined.contains(&d)) {
+                *d = Some(new_block);
+            }
+        }
+        if in_tree {
+            self.idom.insert(new_block, new_idom);
+        }
+    }
+}
+
+/// A natural loop of a CFG, identified by its header block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NaturalLoop {
+    header: Node,
+    latches: Vec<Node>,
+    blocks: HashSet<Node>,
+    parent: Option<usize>,
+}
+
+impl NaturalLoop {
+    /// The header of the loop, which dominates all its blocks.
+    pub fn header(&self) -> Node {
+        self.header
+    }
+
+    /// The sources of the back edges to the header.
+    pub fn latches(&self) -> &[Node] {
+        &self.latches
+    }
+
+    /// The blocks of the loop, including the header and those of any nested
+    /// loops.
+    pub fn blocks(&self) -> impl Iterator<Item = Node> + '_ {
+        self.blocks.iter().copied()
+    }
+
+    /// Whether the block is part of the loop.
+    pub fn contains(&self, n: Node) -> bool {
+        self.blocks.contains(&n)
+    }
+}
+
+/// The natural loops of a CFG, nested by containment.
+///
+/// Loops are found from back edges, i.e. edges whose target dominates their
+/// source. Cycles in irreducible control flow have no such edge and are not
+/// reported.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoopForest {
+    /// Loops ordered from smallest to largest, so that every loop comes
+    /// before its parent.
+    loops: Vec<NaturalLoop>,
+    /// Index of the innermost loop containing each block.
+    innermost: HashMap<Node, usize>,
+}
+
+impl LoopForest {
+    /// Computes the loops of a CFG from its dominator tree.
+    fn new(h: &impl HugrView, doms: &DominatorTree) -> Self {
+        let mut latches: HashMap<Node, Vec<Node>> = HashMap::new();
+        for n in doms.nodes().sorted() {
+            for s in h.output_neighbours(n).unique() {
+                if doms.dominates(s, n) {
+                    latches.entry(s).or_default().push(n);
+                }
+            }
+        }
+        let mut loops = latches
+            .into_iter()
+            .map(|(header, latches)| {
+                let mut blocks = HashSet::from([header]);
+                let mut queue = latches.clone();
+                while let Some(n) = queue.pop() {
+                    if blocks.insert(n) {
+                        queue.extend(h.input_neighbours(n).filter(|p| doms.contains(*p)));
+                    }
+                }
+                NaturalLoop {
+                    header,
+                    latches,
+                    blocks,
+                    parent: None,
+                }
+            })
+            .sorted_by_key(|l| (l.blocks.len(), l.header))
+            .collect_vec();
+
+        // Loops with distinct headers are either disjoint or nested, so the
+        // parent is the smallest larger loop containing the header.
+        for i in 0..loops.len() {
+            let header = loops[i].header;
+            loops[i].parent = (i + 1..loops.len()).find(|j| loops[*j].contains(header));
+        }
+        let mut innermost = HashMap::new();
+        for (i, l) in loops.iter().enumerate().rev() {
+            innermost.extend(l.blocks().map(|n| (n, i)));
+        }
+        Self { loops, innermost }
+    }
+
+    /// All the loops, with inner loops before the loops containing them.
+    pub fn loops(&self) -> impl Iterator<Item = &NaturalLoop> + '_ {
+        self.loops.iter()
+    }
+
+    /// The outermost loops.
+    pub fn roots(&self) -> impl Iterator<Item = &NaturalLoop> + '_ {
+        self.loops.iter().filter(|l| l.parent.is_none())
+    }
+
+    /// The loop immediately containing `l`, if any.
+    pub fn parent(&self, l: &NaturalLoop) -> Option<&NaturalLoop> {
+        l.parent.map(|i| &self.loops[i])
+    }
+
+    /// The innermost loop containing a block, if any.
+    pub fn innermost_loop(&self, n: Node) -> Option<&NaturalLoop> {
+        self.innermost.get(&n).map(|i| &self.loops[*i])
+    }
+
+    /// The loop headed by a block, if it is a loop header.
+    pub fn loop_with_header(&self, header: Node) -> Option<&NaturalLoop> {
+        self.innermost_loop(header).filter(|l| l.header == header)
+    }
+
+    /// The number of loops containing a block.
+    pub fn loop_depth(&self, n: Node) -> usize {
+        std::iter::successors(self.innermost_loop(n), |l| self.parent(l)).count()
+    }
+}
+
+/// Dominator-based analyses of a single CFG node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CfgDominators {
+    cfg: Node,
+    dominators: DominatorTree,
+    post_dominators: DominatorTree,
+    frontiers: HashMap<Node, HashSet<Node>>,
+    post_frontiers: HashMap<Node, HashSet<Node>>,
+    loops: LoopForest,
+}
+
+impl CfgDominators {
+    /// Runs all the analyses on the blocks of a CFG node.
+    pub fn new(h: &impl HugrView, cfg: Node) -> Result<Self, DominatorError> {
+        let dominators = DominatorTree::dominators(h, cfg)?;
+        let post_dominators = DominatorTree::post_dominators(h, cfg)?;
+        let mut doms = Self {
+            cfg,
+            dominators,
+            post_dominators,
+            frontiers: HashMap::new(),
+            post_frontiers: HashMap::new(),
+            loops: LoopForest::default(),
+        };
+        doms.recompute_derived(h);
+        Ok(doms)
+    }
+
+    /// Recomputes the frontiers and loops from the dominator trees.
+    fn recompute_derived(&mut self, h: &impl HugrView) {
+        self.frontiers = frontiers(&self.dominators, |n| h.input_neighbours(n));
+        self.post_frontiers = frontiers(&self.post_dominators, |n| h.output_neighbours(n));
+        self.loops = LoopForest::new(h, &self.dominators);
+    }
+
+    /// The CFG node analysed.
+    pub fn cfg(&self) -> Node {
+        self.cfg
+    }
+
+    /// The dominator tree, rooted at the entry block.
+    pub fn dominators(&self) -> &DominatorTree {
+        &self.dominators
+    }
+
+    /// The post-dominator tree, rooted at the exit block.
+    pub fn post_dominators(&self) -> &DominatorTree {
+        &self.post_dominators
+    }
+
+    /// The dominance frontier of a block: the blocks it does not strictly
+    /// dominate but that have a predecessor it dominates.
+    pub fn dominance_frontier(&self, n: Node) -> impl Iterator<Item = Node> + '_ {
+        self.frontiers.get(&n).into_iter().flatten().copied()
+    }
+
+    /// The post-dominance frontier of a block, i.e. the blocks on whose
+    /// branching its execution is control dependent.
+    pub fn post_dominance_frontier(&self, n: Node) -> impl Iterator<Item = Node> + '_ {
+        self.post_frontiers.get(&n).into_iter().flatten().copied()
+    }
+
+    /// The natural loops of the CFG.
+    pub fn loops(&self) -> &LoopForest {
+        &self.loops
+    }
+
+    /// Updates the analyses after an [`OutlineCfg`] rewrite moved blocks of
+    /// the CFG into a nested CFG inside `new_block`.
+    ///
+    /// The dominator trees are updated by contracting the outlined blocks,
+    /// which is valid as the outlined region has a single entry and a single
+    /// exit edge; the frontiers and loops are recomputed from the trees.
+    ///
+    /// [`OutlineCfg`]: crate::hugr::rewrite::outline_cfg::OutlineCfg
+    pub fn update_outlined(
+        &mut self,
+        h: &impl HugrView,
+        new_block: Node,
+    ) -> Result<(), DominatorError> {
+        let not_outlined = || DominatorError::NotOutlined(new_block, self.cfg);
+        if h.get_parent(new_block) != Some(self.cfg) {
+            return Err(not_outlined());
+        }
+        let inner_cfg = h
+            .children(new_block)
+            .filter(|n| h.get_optype(*n).tag() == OpTag::Cfg)
+            .exactly_one()
+            .map_err(|_| not_outlined())?;
+        let successor = h
+            .output_neighbours(new_block)
+            .exactly_one()
+            .map_err(|_| not_outlined())?;
+        let mut inner = h.children(inner_cfg);
+        let entry = inner.next().ok_or_else(not_outlined)?;
+        let outlined: HashSet<Node> = [entry]
+            .into_iter()
+            .chain(inner.skip(1)) // The inner exit block is new.
+            .collect();
+
+        // The outlined region is entered only through `entry`, so the new
+        // block takes its place in the dominator tree. It is left only
+        // through `successor`, which immediately post-dominates it.
+        let idom = self.dominators.immediate_dominator(entry);
+        let reachable = self.dominators.contains(entry);
+        self.dominators
+            .contract(&outlined, new_block, idom, reachable);
+        let reaches_exit = self.post_dominators.contains(successor);
+        self.post_dominators
+            .contract(&outlined, new_block, Some(successor), reaches_exit);
+        self.recompute_derived(h);
+        Ok(())
+    }
+}
+
+/// Cache of [`CfgDominators`] analyses, indexed by CFG node.
+///
+/// Entries must be invalidated (or updated, see
+/// [`DominatorCache::update_outlined`]) when the CFG they describe is
+/// rewritten.
+#[derive(Clone, Debug, Default)]
+pub struct DominatorCache {
+    cfgs: HashMap<Node, CfgDominators>,
+}
+
+impl DominatorCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the analyses of a CFG node, computing them if not cached.
+    pub fn get(&mut self, h: &impl HugrView, cfg: Node) -> Result<&CfgDominators, DominatorError> {
+        Ok(match self.cfgs.entry(cfg) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(CfgDominators::new(h, cfg)?),
+        })
+    }
+
+    /// Returns the cached analyses of a CFG node, if any.
+    pub fn get_cached(&self, cfg: Node) -> Option<&CfgDominators> {
+        self.cfgs.get(&cfg)
+    }
+
+    /// Forgets the analyses of a CFG node.
+    pub fn invalidate(&mut self, cfg: Node) {
+        self.cfgs.remove(&cfg);
+    }
+
+    /// Forgets all the cached analyses.
+    pub fn clear(&mut self) {
+        self.cfgs.clear();
+    }
+
+    /// Updates the cached analyses of the CFG containing `new_block` after an
+    /// [`OutlineCfg`] rewrite, see [`CfgDominators::update_outlined`].
+    ///
+    /// Does nothing if the analyses of that CFG were not cached.
+    ///
+    /// [`OutlineCfg`]: crate::hugr::rewrite::outline_cfg::OutlineCfg
+    pub fn update_outlined(
+        &mut self,
+        h: &impl HugrView,
+        new_block: Node,
+    ) -> Result<(), DominatorError> {
+        let Some(cfg) = h.get_parent(new_block) else {
+            return Ok(());
+        };
+        match self.cfgs.get_mut(&cfg) {
+            Some(doms) => doms.update_outlined(h, new_block),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Computes the dominance frontiers of all blocks of a tree, given the
+/// predecessors of blocks in the direction the tree was computed.
+fn frontiers<I: Iterator<Item = Node>>(
+    tree: &DominatorTree,
+    preds: impl Fn(Node) -> I,
+) -> HashMap<Node, HashSet<Node>> {
+    let mut frontiers: HashMap<Node, HashSet<Node>> = HashMap::new();
+    for n in tree.nodes() {
+        let preds = preds(n)
+            .filter(|p| tree.contains(*p))
+            .unique()
+            .collect_vec();
+        // Blocks with a single predecessor are dominated by it, unless they
+        // are the root in which case the back edge makes them their own
+        // frontier.
+        if preds.len() < 2 && n != tree.root() {
+            continue;
+        }
+        let idom = tree.immediate_dominator(n);
+        for p in preds {
+            let mut runner = Some(p);
+            while runner.is_some() && runner != idom {
+                let r = runner.unwrap();
+                frontiers.entry(r).or_default().insert(n);
+                runner = tree.immediate_dominator(r);
+            }
+        }
+    }
+    frontiers
+}
+
+fn check_cfg(h: &impl HugrView, cfg: Node) -> Result<(), DominatorError> {
+    let tag = h.get_optype(cfg).tag();
+    if tag != OpTag::Cfg {
+        return Err(DominatorError::NotACfg(cfg, tag));
+    }
+    Ok(())
+}
+
+/// Errors that can occur when computing dominator analyses.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DominatorError {
+    /// The node is not a CFG.
+    #[error("Node {0:?} is not a CFG but a {1:?}")]
+    NotACfg(Node, OpTag),
+    /// The node is not a block created by outlining part of the CFG.
+    #[error("Node {0:?} is not a block of {1:?} containing an outlined CFG")]
+    NotOutlined(Node, Node),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::nest_cfgs::test::build_conditional_in_loop_cfg;
+    use crate::hugr::rewrite::outline_cfg::OutlineCfg;
+    use crate::ops::handle::NodeHandle;
+    use crate::Hugr;
+    use cool_asserts::assert_matches;
+
+    /// The blocks of `build_conditional_in_loop_cfg(false)`: entry, split
+    /// (which is also the loop header), left, right, merge, tail, exit.
+    fn blocks(h: &Hugr, head: Node, tail: Node) -> [Node; 7] {
+        let mut children = h.children(h.root());
+        let entry = children.next().unwrap();
+        let exit = children.next().unwrap();
+        let [left, right] = h.output_neighbours(head).collect_vec().try_into().unwrap();
+        let merge = h.output_neighbours(left).exactly_one().unwrap();
+        [entry, head, left, right, merge, tail, exit]
+    }
+
+    #[test]
+    fn dominator_trees() -> Result<(), Box<dyn std::error::Error>> {
+        let (h, head, tail) = build_conditional_in_loop_cfg(false)?;
+        let [entry, split, left, right, merge, tail, exit] = blocks(&h, head.node(), tail.node());
+        let doms = CfgDominators::new(&h, h.root())?;
+
+        let dom = doms.dominators();
+        assert_eq!(dom.root(), entry);
+        assert_eq!(dom.immediate_dominator(entry), None);
+        assert_eq!(dom.immediate_dominator(left), Some(split));
+        assert_eq!(dom.immediate_dominator(merge), Some(split));
+        assert_eq!(dom.immediate_dominator(exit), Some(tail));
+        assert_eq!(
+            dom.children(split).sorted().collect_vec(),
+            [left, right, merge].into_iter().sorted().collect_vec()
+        );
+        assert!(dom.dominates(split, tail));
+        assert!(dom.dominates(tail, tail));
+        assert!(!dom.strictly_dominates(tail, tail));
+        assert!(!dom.dominates(left, merge));
+        assert_eq!(
+            dom.dominators_of(tail).collect_vec(),
+            [tail, merge, split, entry]
+        );
+
+        let pdom = doms.post_dominators();
+        assert_eq!(pdom.root(), exit);
+        assert_eq!(pdom.immediate_dominator(split), Some(merge));
+        assert_eq!(pdom.immediate_dominator(entry), Some(split));
+        assert!(pdom.dominates(tail, left));
+        Ok(())
+    }
+
+    #[test]
+    fn frontiers_and_loops() -> Result<(), Box<dyn std::error::Error>> {
+        let (h, head, tail) = build_conditional_in_loop_cfg(false)?;
+        let [entry, split, left, right, merge, tail, exit] = blocks(&h, head.node(), tail.node());
+        let doms = CfgDominators::new(&h, h.root())?;
+
+        let df = |n| doms.dominance_frontier(n).sorted().collect_vec();
+        assert_eq!(df(left), [merge]);
+        assert_eq!(df(right), [merge]);
+        assert_eq!(df(merge), [split]);
+        assert_eq!(df(split), [split]);
+        assert_eq!(df(entry), []);
+        // Both branches are control dependent on the split.
+        assert_eq!(doms.post_dominance_frontier(left).collect_vec(), [split]);
+        assert_eq!(doms.post_dominance_frontier(exit).count(), 0);
+
+        let loops = doms.loops();
+        let l = loops.loop_with_header(split).unwrap();
+        assert_eq!(l.latches(), [tail]);
+        assert_eq!(
+            l.blocks().sorted().collect_vec(),
+            [split, left, right, merge, tail]
+                .into_iter()
+                .sorted()
+                .collect_vec()
+        );
+        assert_eq!(loops.roots().count(), 1);
+        assert_eq!(loops.loop_depth(left), 1);
+        assert_eq!(loops.loop_depth(exit), 0);
+        assert!(loops.loop_with_header(left).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn update_after_outline() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, head, tail) = build_conditional_in_loop_cfg(false)?;
+        let [entry, split, left, right, merge, tail, _] = blocks(&h, head.node(), tail.node());
+        let mut cache = DominatorCache::new();
+        cache.get(&h, h.root())?;
+
+        h.apply_rewrite(OutlineCfg::new([split, left, right, merge]))?;
+        let new_block = h.input_neighbours(tail).exactly_one().unwrap();
+        cache.update_outlined(&h, new_block)?;
+
+        let updated = cache.get_cached(h.root()).unwrap();
+        assert_eq!(updated, &CfgDominators::new(&h, h.root())?);
+        assert_eq!(
+            updated.dominators().immediate_dominator(new_block),
+            Some(entry)
+        );
+        assert_eq!(
+            updated
+                .loops()
+                .loop_with_header(new_block)
+                .unwrap()
+                .latches(),
+            [tail]
+        );
+        assert!(!updated.dominators().contains(split));
+
+        assert_matches!(
+            cache.update_outlined(&h, tail),
+            Err(DominatorError::NotOutlined(..))
+        );
+        cache.invalidate(h.root());
+        assert!(cache.get_cached(h.root()).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn not_a_cfg() {
+        let h = Hugr::default();
+        assert_matches!(
+            CfgDominators::new(&h, h.root()),
+            Err(DominatorError::NotACfg(..))
+        );
+    }
+}
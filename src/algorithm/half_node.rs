@@ -15,21 +15,20 @@ use crate::{Direction, Node};
 /// the in-edge from that extra/empty BB, might be the endpoint of a useful SESE region,
 /// but we don't have a way to identify *which subset* to select. (Here we say *all preds* if >1 succ)
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-enum HalfNode {
+pub(crate) enum HalfNode {
     /// All predecessors of original BB; successors if this does not break rule, else the X
     N(Node),
     // Exists only for BBs with multiple preds _and_ succs; has a single pred (the N), plus original succs
     X(Node),
 }
 
-struct HalfNodeView<'a, H> {
+pub(crate) struct HalfNodeView<'a, H> {
     h: &'a H,
     entry: Node,
     exit: Node,
 }
 
 impl<'a, H: HugrView> HalfNodeView<'a, H> {
-    #[allow(unused)]
     pub(crate) fn new(h: &'a H) -> Self {
         let mut children = h.children(h.root());
         let entry = children.next().unwrap(); // Panic if malformed
@@ -38,7 +37,7 @@ impl<'a, H: HugrView> HalfNodeView<'a, H> {
         Self { h, entry, exit }
     }
 
-    fn is_multi_node(&self, n: Node) -> bool {
+    pub(crate) fn is_multi_node(&self, n: Node) -> bool {
         // TODO if <n> is the entry-node, should we pretend there's an extra predecessor? (The "outside")
         // We could also setify here before counting, but never
         self.bb_preds(n).take(2).count() + self.bb_succs(n).take(2).count() == 4
@@ -60,7 +59,10 @@ impl<'a, H: HugrView> HalfNodeView<'a, H> {
 }
 
 impl<H: HugrView> CfgView<HalfNode> for HalfNodeView<'_, H> {
-    type Iterator<'c> = <Vec<HalfNode> as IntoIterator>::IntoIter where Self: 'c;
+    type Iterator<'c>
+        = <Vec<HalfNode> as IntoIterator>::IntoIter
+    where
+        Self: 'c;
     fn entry_node(&self) -> HalfNode {
         HalfNode::N(self.entry)
     }
@@ -126,7 +128,14 @@ mod test {
             .map(|(s, _)| s)
             .exactly_one()
             .unwrap();
-        let [&left,&right] = edge_classes.keys().filter(|(s,_)| *s == split).map(|(_,t)|t).collect::<Vec<_>>()[..] else {panic!("Split node should have two successors");};
+        let [&left, &right] = edge_classes
+            .keys()
+            .filter(|(s, _)| *s == split)
+            .map(|(_, t)| t)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("Split node should have two successors");
+        };
         let classes = group_by(edge_classes);
         assert_eq!(
             classes,
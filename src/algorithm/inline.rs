@@ -0,0 +1,138 @@
+//! Inlining of function calls.
+
+use itertools::Itertools;
+
+use crate::hugr::rewrite::inline_call::{InlineCall, InlineCallError};
+use crate::hugr::HugrView;
+use crate::ops::{OpTag, OpTrait};
+use crate::{Hugr, Node};
+
+/// Inlines the calls in the Hugr for which `should_inline` returns true.
+///
+/// The callback is given the Hugr in its current state and a [`Call`] node
+/// to a function definition. Each call site is only considered once, so calls
+/// introduced by inlining a function body are not themselves inlined. This
+/// guarantees termination for recursive functions; the pass can be run again
+/// to inline further.
+///
+/// If `flatten` is true, the inlined bodies are placed directly in the sibling
+/// graph of the call, otherwise they are wrapped in a [`DFG`] node.
+///
+/// Returns the number of call sites that were inlined.
+///
+/// [`Call`]: crate::ops::Call
+/// [`DFG`]: crate::ops::DFG
+pub fn inline_calls(
+    h: &mut Hugr,
+    flatten: bool,
+    mut should_inline: impl FnMut(&Hugr, Node) -> bool,
+) -> Result<usize, InlineCallError> {
+    let calls = h
+        .nodes()
+        .filter(|n| h.get_optype(*n).tag() == OpTag::FnCall)
+        .filter(|n| InlineCall::new(*n).callee(h).is_ok())
+        .collect_vec();
+    let mut inlined = 0;
+    for call in calls {
+        if !should_inline(h, call) {
+            continue;
+        }
+        let rewrite = match flatten {
+            true => InlineCall::flattened(call),
+            false => InlineCall::new(call),
+        };
+        h.apply_rewrite(rewrite)?;
+        inlined += 1;
+    }
+    Ok(inlined)
+}
+
+/// A heuristic for [`inline_calls`] that accepts calls to functions with at
+/// most `max_nodes` descendant nodes.
+pub fn callee_size_below(max_nodes: usize) -> impl FnMut(&Hugr, Node) -> bool {
+    move |h, call| {
+        let Ok(callee) = InlineCall::new(call).callee(h) else {
+            return false;
+        };
+        let mut size = 0;
+        let mut stack = vec![callee];
+        while let Some(n) = stack.pop() {
+            for child in h.children(n) {
+                size += 1;
+                if size > max_nodes {
+                    return false;
+                }
+                stack.push(child);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    /// A module where `main` calls `small` and `big`, and `big` calls itself.
+    fn build_module() -> Result<Hugr, BuildError> {
+        let sig = AbstractSignature::new_df(type_row![BIT], type_row![BIT]).pure();
+        let mut module_builder = ModuleBuilder::new();
+        let small = module_builder.define_function("small", sig.clone())?;
+        let w = small.input_wires();
+        let small = small.finish_with_outputs(w)?;
+
+        let big_decl = module_builder.declare("big", sig.clone())?;
+        let mut big = module_builder.define_declaration(&big_decl)?;
+        let [mut w] = big.input_wires_arr();
+        for _ in 0..4 {
+            [w] = big.add_dataflow_op(LeafOp::Xor, [w, w])?.outputs_arr();
+        }
+        [w] = big.call(&big_decl, [w])?.outputs_arr();
+        let big = big.finish_with_outputs([w])?;
+
+        let mut main = module_builder.define_function("main", sig)?;
+        let [w] = main.input_wires_arr();
+        let [w] = main.call(small.handle(), [w])?.outputs_arr();
+        let [w] = main.call(big.handle(), [w])?.outputs_arr();
+        main.finish_with_outputs([w])?;
+        Ok(module_builder.finish_hugr()?)
+    }
+
+    fn num_calls(h: &Hugr) -> usize {
+        h.nodes()
+            .filter(|n| h.get_optype(*n).tag() == OpTag::FnCall)
+            .count()
+    }
+
+    #[test]
+    fn inline_small() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = build_module()?;
+        assert_eq!(num_calls(&h), 3);
+        assert_eq!(inline_calls(&mut h, true, callee_size_below(4))?, 1);
+        h.validate()?;
+        assert_eq!(num_calls(&h), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_recursive_once() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = build_module()?;
+        // The recursive call in `big` and the two calls in `main` are
+        // inlined; each copy of `big` brings a new recursive call.
+        assert_eq!(inline_calls(&mut h, false, |_, _| true)?, 3);
+        h.validate()?;
+        assert_eq!(num_calls(&h), 2);
+        assert_eq!(inline_calls(&mut h, false, |_, _| true)?, 2);
+        h.validate()?;
+        assert_eq!(num_calls(&h), 2);
+        Ok(())
+    }
+}
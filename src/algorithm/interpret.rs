@@ -0,0 +1,712 @@
+//! Interpreter for the classical fragment of a Hugr.
+//!
+//! Evaluates a function or dataflow region on concrete input values, without
+//! compiling it for any backend. This is intended for testing: every node is
+//! executed one at a time, in a topological order of its sibling graph.
+//!
+//! Leaf operations of the core set are evaluated where they are classical;
+//! operations from resources are evaluated by callbacks registered for their
+//! [`OpDef`] in an [`EvaluatorRegistry`]. Quantum operations can be handled
+//! by a [`QuantumBackend`], such as the statevector [`Simulator`].
+//!
+//! [`OpDef`]: crate::resource::OpDef
+//! [`Simulator`]: crate::algorithm::simulate::Simulator
+
+use std::collections::HashMap;
+use std::fmt;
+
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::algorithm::dataflow::dataflow_order;
+use crate::hugr::HugrView;
+use crate::ops::custom::ExternalOp;
+use crate::ops::{ConstValue, LeafOp, OpName, OpTag, OpTrait, OpType};
+use crate::resource::{OpDef, ResourceId};
+use crate::types::type_param::TypeArg;
+use crate::values::HashableValue;
+use crate::{Node, Port};
+
+/// A runtime value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A classical constant.
+    Const(ConstValue),
+    /// A function, given by its [`FuncDefn`] node, as taken by
+    /// [`CallIndirect`].
+    ///
+    /// [`FuncDefn`]: crate::ops::FuncDefn
+    /// [`CallIndirect`]: crate::ops::CallIndirect
+    Function(Node),
+    /// A qubit, given by its index in the state of a [`QuantumBackend`].
+    Qubit(usize),
+}
+
+impl Value {
+    /// Returns the constant value, if this is a classical constant.
+    pub fn as_const(&self) -> Option<&ConstValue> {
+        match self {
+            Value::Const(c) => Some(c),
+            Value::Function(_) | Value::Qubit(_) => None,
+        }
+    }
+}
+
+impl From<ConstValue> for Value {
+    fn from(c: ConstValue) -> Self {
+        Self::Const(c)
+    }
+}
+
+/// A callback evaluating a resource operation, given its type arguments and
+/// input values.
+pub type OpEvaluator =
+    dyn Fn(&[TypeArg], &[ConstValue]) -> Result<Vec<ConstValue>, String> + Send + Sync;
+
+/// Evaluators for resource operations, indexed by their qualified name.
+#[derive(Default)]
+pub struct EvaluatorRegistry {
+    evaluators: HashMap<SmolStr, Box<OpEvaluator>>,
+}
+
+impl EvaluatorRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the evaluator of an operation definition, replacing any
+    /// previous one.
+    pub fn register(
+        &mut self,
+        def: &OpDef,
+        evaluator: impl Fn(&[TypeArg], &[ConstValue]) -> Result<Vec<ConstValue>, String>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.register_op(def.resource(), def.name(), evaluator)
+    }
+
+    /// Registers the evaluator of an operation given by its resource and
+    /// name, for operations whose definition is not loaded.
+    pub fn register_op(
+        &mut self,
+        resource: &ResourceId,
+        op_name: &str,
+        evaluator: impl Fn(&[TypeArg], &[ConstValue]) -> Result<Vec<ConstValue>, String>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.evaluators
+            .insert(format!("{resource}.{op_name}").into(), Box::new(evaluator));
+    }
+
+    fn get(&self, op: &ExternalOp) -> Option<&OpEvaluator> {
+        self.evaluators.get(&op.name()).map(AsRef::as_ref)
+    }
+}
+
+impl fmt::Debug for EvaluatorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.evaluators.keys()).finish()
+    }
+}
+
+/// Evaluates the quantum operations of a Hugr, on behalf of an
+/// [`Interpreter`].
+pub trait QuantumBackend {
+    /// Evaluates a leaf operation, returning `None` if it is not handled by
+    /// the backend and should be evaluated classically.
+    fn apply(
+        &mut self,
+        node: Node,
+        op: &LeafOp,
+        inputs: &[Value],
+    ) -> Option<Result<Vec<Value>, InterpretError>>;
+}
+
+/// Evaluates a region of a Hugr on the given inputs, with no resource
+/// operation evaluators. See [`Interpreter::run`].
+pub fn interpret<H: HugrView>(
+    h: &H,
+    node: Node,
+    inputs: impl IntoIterator<Item = Value>,
+) -> Result<Vec<Value>, InterpretError> {
+    Interpreter::new(h).run(node, inputs)
+}
+
+/// An interpreter for the classical fragment of a Hugr.
+pub struct Interpreter<'a, H> {
+    h: &'a H,
+    registry: Option<&'a EvaluatorRegistry>,
+    backend: Option<&'a mut dyn QuantumBackend>,
+    max_steps: Option<usize>,
+    steps: usize,
+    /// Topological order of the children of each dataflow container.
+    orders: HashMap<Node, Vec<Node>>,
+}
+
+impl<'a, H: HugrView> Interpreter<'a, H> {
+    /// Creates an interpreter for a Hugr, with no resource operation
+    /// evaluators and no step limit.
+    pub fn new(h: &'a H) -> Self {
+        Self {
+            h,
+            registry: None,
+            backend: None,
+            max_steps: None,
+            steps: 0,
+            orders: HashMap::new(),
+        }
+    }
+
+    /// Sets the evaluators used for resource operations.
+    pub fn with_registry(mut self, registry: &'a EvaluatorRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Sets the backend evaluating quantum operations.
+    pub fn with_backend(mut self, backend: &'a mut dyn QuantumBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Limits the number of nodes evaluated by each call to
+    /// [`Interpreter::run`], to catch non-terminating loops.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Evaluates a region of the Hugr on the given inputs, returning its
+    /// outputs.
+    ///
+    /// The region is a dataflow container ([`FuncDefn`], [`DFG`], ...) or a
+    /// [`Conditional`], [`TailLoop`] or [`CFG`] node. Non-local edges into the
+    /// region are not supported.
+    ///
+    /// [`FuncDefn`]: crate::ops::FuncDefn
+    /// [`DFG`]: crate::ops::DFG
+    /// [`Conditional`]: crate::ops::Conditional
+    /// [`TailLoop`]: crate::ops::TailLoop
+    /// [`CFG`]: crate::ops::CFG
+    pub fn run(
+        &mut self,
+        node: Node,
+        inputs: impl IntoIterator<Item = Value>,
+    ) -> Result<Vec<Value>, InterpretError> {
+        self.steps = 0;
+        let inputs: Vec<Value> = inputs.into_iter().collect();
+        let op = self.h.get_optype(node);
+        let expected = match op {
+            OpType::Conditional(_) | OpType::TailLoop(_) | OpType::CFG(_) => {
+                op.signature().input.len()
+            }
+            _ => match self.h.children(node).next().map(|n| self.h.get_optype(n)) {
+                Some(OpType::Input(input)) => input.types.len(),
+                _ => return Err(InterpretError::NotARegion(node, op.tag())),
+            },
+        };
+        if inputs.len() != expected {
+            return Err(InterpretError::WrongInputCount {
+                node,
+                expected,
+                actual: inputs.len(),
+            });
+        }
+        let mut frame = Frame::default();
+        match op {
+            OpType::Conditional(_) | OpType::TailLoop(_) | OpType::CFG(_) => {
+                self.eval_node(&mut frame, node, inputs)
+            }
+            _ => self.eval_dsg(&mut frame, node, inputs),
+        }
+    }
+
+    /// Evaluates a dataflow sibling graph, returning the values reaching its
+    /// output node.
+    fn eval_dsg(
+        &mut self,
+        frame: &mut Frame,
+        parent: Node,
+        inputs: Vec<Value>,
+    ) -> Result<Vec<Value>, InterpretError> {
+        let mut children = self.h.children(parent);
+        let (input, output) = (children.next().unwrap(), children.next().unwrap());
+        frame.set_outputs(input, inputs);
+        let h = self.h;
+        let order = self
+            .orders
+            .entry(parent)
+            .or_insert_with(|| dataflow_order(h, parent))
+            .clone();
+        for n in order {
+            if n == input || n == output {
+                continue;
+            }
+            let op = self.h.get_optype(n);
+            if OpTag::ScopedDefn.is_superset(op.tag()) || op.tag() == OpTag::Const {
+                continue;
+            }
+            self.step(n)?;
+            let ins = frame.inputs(self.h, n)?;
+            let outs = self.eval_node(frame, n, ins)?;
+            frame.set_outputs(n, outs);
+        }
+        frame.inputs(self.h, output)
+    }
+
+    fn step(&mut self, node: Node) -> Result<(), InterpretError> {
+        self.steps += 1;
+        match self.max_steps {
+            Some(max) if self.steps > max => Err(InterpretError::StepLimit(node)),
+            _ => Ok(()),
+        }
+    }
+
+    fn eval_node(
+        &mut self,
+        frame: &mut Frame,
+        node: Node,
+        inputs: Vec<Value>,
+    ) -> Result<Vec<Value>, InterpretError> {
+        let h = self.h;
+        match h.get_optype(node) {
+            OpType::DFG(_) => self.eval_dsg(frame, node, inputs),
+            OpType::LoadConstant(_) => match static_source(h, node, 0) {
+                Some(OpType::Const(c)) => Ok(vec![Value::Const(c.value().clone())]),
+                _ => Err(InterpretError::MissingStaticInput(node)),
+            },
+            OpType::LeafOp(leaf) => {
+                if let Some(outs) = self
+                    .backend
+                    .as_mut()
+                    .and_then(|b| b.apply(node, leaf, &inputs))
+                {
+                    return outs;
+                }
+                if let LeafOp::Noop { .. } | LeafOp::Lift { .. } = leaf {
+                    return Ok(inputs);
+                }
+                let consts = inputs
+                    .iter()
+                    .map(|v| v.as_const().cloned().ok_or(InterpretError::BadValue(node)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let outs = self.eval_leaf(node, leaf, consts)?;
+                Ok(outs.into_iter().map(Value::Const).collect())
+            }
+            OpType::Call(call) => {
+                let func = h
+                    .linked_ports(node, Port::new_incoming(call.signature.input.len()))
+                    .next()
+                    .map(|(f, _)| f)
+                    .ok_or(InterpretError::MissingStaticInput(node))?;
+                self.call(node, func, inputs)
+            }
+            OpType::CallIndirect(_) => {
+                let (func, args) = inputs.split_first().unwrap();
+                let Value::Function(func) = func else {
+                    return Err(InterpretError::BadValue(node));
+                };
+                self.call(node, *func, args.to_vec())
+            }
+            OpType::Conditional(cond) => {
+                let (tag, fields) = unpack_predicate(node, &inputs[0])?;
+                let case = h
+                    .children(node)
+                    .nth(tag)
+                    .filter(|_| tag < cond.predicate_inputs.len())
+                    .ok_or(InterpretError::BadValue(node))?;
+                let case_inputs = fields.into_iter().chain(inputs[1..].iter().cloned());
+                self.eval_dsg(frame, case, case_inputs.collect())
+            }
+            OpType::TailLoop(_) => {
+                let mut body_inputs = inputs;
+                loop {
+                    self.step(node)?;
+                    let outs = self.eval_dsg(frame, node, body_inputs)?;
+                    let (tag, fields) = unpack_predicate(node, &outs[0])?;
+                    let next = fields
+                        .into_iter()
+                        .chain(outs[1..].iter().cloned())
+                        .collect();
+                    match tag {
+                        0 => body_inputs = next,
+                        _ => return Ok(next),
+                    }
+                }
+            }
+            OpType::CFG(_) => {
+                let mut children = h.children(node);
+                let (mut block, exit) = (children.next().unwrap(), children.next().unwrap());
+                let mut block_inputs = inputs;
+                while block != exit {
+                    self.step(block)?;
+                    let outs = self.eval_dsg(frame, block, block_inputs)?;
+                    let (tag, fields) = unpack_predicate(block, &outs[0])?;
+                    block = h
+                        .linked_ports(block, Port::new_outgoing(tag))
+                        .next()
+                        .map(|(succ, _)| succ)
+                        .ok_or(InterpretError::BadValue(block))?;
+                    block_inputs = fields
+                        .into_iter()
+                        .chain(outs[1..].iter().cloned())
+                        .collect();
+                }
+                Ok(block_inputs)
+            }
+            op => Err(InterpretError::UnsupportedOp(node, op.name())),
+        }
+    }
+
+    /// Calls a function with a fresh frame, as it cannot see the values of
+    /// the caller.
+    fn call(
+        &mut self,
+        node: Node,
+        func: Node,
+        inputs: Vec<Value>,
+    ) -> Result<Vec<Value>, InterpretError> {
+        match self.h.get_optype(func) {
+            OpType::FuncDefn(_) => self.eval_dsg(&mut Frame::default(), func, inputs),
+            _ => Err(InterpretError::UndefinedFunction(node, func)),
+        }
+    }
+
+    fn eval_leaf(
+        &self,
+        node: Node,
+        leaf: &LeafOp,
+        inputs: Vec<ConstValue>,
+    ) -> Result<Vec<ConstValue>, InterpretError> {
+        Ok(match leaf {
+            LeafOp::Xor => match inputs.as_slice() {
+                [ConstValue::Hashable(HashableValue::Int(a)), ConstValue::Hashable(HashableValue::Int(b))] =>
+                {
+                    vec![HashableValue::Int(a ^ b).into()]
+                }
+                _ => return Err(InterpretError::BadValue(node)),
+            },
+            LeafOp::MakeTuple { .. } => vec![ConstValue::sequence(&inputs)],
+            LeafOp::UnpackTuple { .. } => inputs[0]
+                .as_sequence()
+                .ok_or(InterpretError::BadValue(node))?,
+            LeafOp::Tag { tag, .. } => vec![ConstValue::sum(*tag, inputs[0].clone())],
+            LeafOp::CustomOp(op) => {
+                let evaluator = self
+                    .registry
+                    .and_then(|r| r.get(op))
+                    .ok_or_else(|| InterpretError::MissingEvaluator(node, op.name()))?;
+                evaluator(op.args(), &inputs)
+                    .map_err(|message| InterpretError::EvaluationFailed { node, message })?
+            }
+            _ => return Err(InterpretError::UnsupportedOp(node, leaf.name())),
+        })
+    }
+}
+
+/// The values computed so far in a function activation.
+#[derive(Default)]
+struct Frame {
+    values: HashMap<(Node, Port), Value>,
+}
+
+impl Frame {
+    fn set_outputs(&mut self, node: Node, values: Vec<Value>) {
+        for (i, v) in values.into_iter().enumerate() {
+            self.values.insert((node, Port::new_outgoing(i)), v);
+        }
+    }
+
+    fn inputs(&self, h: &impl HugrView, node: Node) -> Result<Vec<Value>, InterpretError> {
+        let count = h.get_optype(node).signature().input.len();
+        (0..count)
+            .map(|i| {
+                let port = Port::new_incoming(i);
+                h.linked_ports(node, port)
+                    .next()
+                    .and_then(|(src, src_port)| self.values.get(&(src, src_port)))
+                    .cloned()
+                    .ok_or(InterpretError::MissingInput(node, port))
+            })
+            .collect()
+    }
+}
+
+/// The operation of the node connected to a static input port.
+fn static_source(h: &impl HugrView, node: Node, index: usize) -> Option<&OpType> {
+    h.linked_ports(node, Port::new_incoming(index))
+        .next()
+        .map(|(src, _)| h.get_optype(src))
+}
+
+/// The tag of a predicate value and the values of the fields of the variant.
+fn unpack_predicate(node: Node, pred: &Value) -> Result<(usize, Vec<Value>), InterpretError> {
+    pred.as_const()
+        .and_then(ConstValue::as_sum)
+        .and_then(|(tag, v)| Some((tag, v.as_sequence()?)))
+        .map(|(tag, fields)| (tag, fields.into_iter().map(Value::Const).collect()))
+        .ok_or(InterpretError::BadValue(node))
+}
+
+/// Errors that can occur when interpreting a Hugr.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InterpretError {
+    /// The node is not a region that can be evaluated.
+    #[error("Node {0:?} of type {1:?} cannot be evaluated")]
+    NotARegion(Node, OpTag),
+    /// The number of inputs does not match the region.
+    #[error("Node {node:?} expects {expected} inputs, but {actual} were given")]
+    WrongInputCount {
+        /// The region evaluated.
+        node: Node,
+        /// The number of inputs of the region.
+        expected: usize,
+        /// The number of inputs given.
+        actual: usize,
+    },
+    /// The operation is not part of the classical fragment.
+    #[error("Operation {1} of node {0:?} cannot be evaluated")]
+    UnsupportedOp(Node, SmolStr),
+    /// No evaluator was registered for a resource operation.
+    #[error("No evaluator registered for operation {1} of node {0:?}")]
+    MissingEvaluator(Node, SmolStr),
+    /// The evaluator of a resource operation failed.
+    #[error("Evaluation of node {node:?} failed: {message}")]
+    EvaluationFailed {
+        /// The node evaluated.
+        node: Node,
+        /// The error reported by the evaluator.
+        message: String,
+    },
+    /// A node received a value of the wrong form.
+    #[error("Node {0:?} received an unexpected value")]
+    BadValue(Node),
+    /// A dataflow input has no value, because it is not connected or comes
+    /// from outside the evaluated region.
+    #[error("No value for input {1:?} of node {0:?}")]
+    MissingInput(Node, Port),
+    /// A static input (constant or called function) is not connected.
+    #[error("Static input of node {0:?} is not connected")]
+    MissingStaticInput(Node),
+    /// A called function has no definition.
+    #[error("Node {0:?} calls {1:?}, which is not a function definition")]
+    UndefinedFunction(Node, Node),
+    /// The step limit was reached while evaluating the node.
+    #[error("Step limit reached while evaluating node {0:?}")]
+    StepLimit(Node),
+    /// The region has too many qubits to be simulated.
+    #[error(
+        "Region {node:?} has {n_qubits} qubits, more than the maximum of {}",
+        super::simulate::MAX_QUBITS
+    )]
+    TooManyQubits {
+        /// The region.
+        node: Node,
+        /// Its number of qubits.
+        n_qubits: usize,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::algorithm::lower_control_flow::lower_control_flow;
+    use crate::builder::{
+        Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, HugrBuilder,
+        ModuleBuilder,
+    };
+    use crate::extensions::logic;
+    use crate::ops::custom::OpaqueOp;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::{self, Const};
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+    use crate::{type_row, Hugr};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    fn bit(v: u128) -> Value {
+        Value::Const(HashableValue::Int(v).into())
+    }
+
+    fn boolean(v: bool) -> Value {
+        Value::Const(ConstValue::simple_predicate(v as usize))
+    }
+
+    #[test]
+    fn leaf_ops() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT, BIT],
+        ))?;
+        let [x, y] = b.input_wires_arr();
+        let one = b.add_load_const(Const::int::<1>(1)?)?;
+        let [x] = b.add_dataflow_op(LeafOp::Xor, [x, one])?.outputs_arr();
+        let tys = type_row![BIT, BIT];
+        let [t] = b
+            .add_dataflow_op(LeafOp::MakeTuple { tys: tys.clone() }, [x, y])?
+            .outputs_arr();
+        let [a, b2] = b
+            .add_dataflow_op(LeafOp::UnpackTuple { tys }, [t])?
+            .outputs_arr();
+        let h = b.finish_hugr_with_outputs([b2, a])?;
+
+        let outputs = interpret(&h, h.root(), [bit(0), bit(1)])?;
+        assert_eq!(outputs, [bit(1), bit(1)]);
+        assert_matches!(
+            interpret(&h, h.root(), [bit(0)]),
+            Err(InterpretError::WrongInputCount {
+                expected: 2,
+                actual: 1,
+                ..
+            })
+        );
+        Ok(())
+    }
+
+    /// A loop negating a boolean, exiting when the previous value was true.
+    fn build_not_loop() -> Result<(Hugr, Node), Box<dyn std::error::Error>> {
+        let not_op = OpaqueOp::new(
+            "Test".into(),
+            "Not",
+            "logical 'not'".into(),
+            [],
+            Some(AbstractSignature::new_df(
+                vec![logic::bool_type()],
+                vec![logic::bool_type()],
+            )),
+        );
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![logic::bool_type()],
+            vec![logic::bool_type()],
+        ))?;
+        let [x] = b.input_wires_arr();
+        let mut loop_b = b.tail_loop_builder([], [(logic::bool_type(), x)], type_row![])?;
+        let [x] = loop_b.input_wires_arr();
+        let not = loop_b.add_dataflow_op(LeafOp::CustomOp(ExternalOp::Opaque(not_op)), [x])?;
+        let tail_loop = loop_b.finish_with_outputs(x, not.outputs())?;
+        let h = b.finish_hugr_with_outputs(tail_loop.outputs())?;
+        Ok((h, not.node()))
+    }
+
+    fn logic_registry() -> EvaluatorRegistry {
+        let mut registry = EvaluatorRegistry::new();
+        registry.register_op(&"Test".into(), "Not", |_, inputs| {
+            let (tag, _) = inputs[0].as_sum().ok_or("not a boolean")?;
+            Ok(vec![ConstValue::simple_predicate(1 - tag)])
+        });
+        registry
+    }
+
+    #[test]
+    fn tail_loop_and_cfg() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, not) = build_not_loop()?;
+        let registry = logic_registry();
+        let mut interpreter = Interpreter::new(&h).with_registry(&registry);
+        assert_eq!(
+            interpreter.run(h.root(), [boolean(false)])?,
+            [boolean(false)]
+        );
+        assert_eq!(
+            interpreter.run(h.root(), [boolean(true)])?,
+            [boolean(false)]
+        );
+
+        assert_eq!(
+            interpret(&h, h.root(), [boolean(true)]),
+            Err(InterpretError::MissingEvaluator(not, "Test.Not".into()))
+        );
+
+        lower_control_flow(&mut h);
+        let mut interpreter = Interpreter::new(&h).with_registry(&registry);
+        assert_eq!(
+            interpreter.run(h.root(), [boolean(false)])?,
+            [boolean(false)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn step_limit() -> Result<(), Box<dyn std::error::Error>> {
+        let (h, _) = build_not_loop()?;
+        let registry = logic_registry();
+        let mut interpreter = Interpreter::new(&h)
+            .with_registry(&registry)
+            .with_max_steps(3);
+        assert_matches!(
+            interpreter.run(h.root(), [boolean(false)]),
+            Err(InterpretError::StepLimit(_))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn calls() -> Result<(), Box<dyn std::error::Error>> {
+        let flip_sig = AbstractSignature::new_df(type_row![BIT], type_row![BIT]);
+        let mut module = ModuleBuilder::new();
+
+        let mut f = module.define_function("flip", flip_sig.clone().pure())?;
+        let [x] = f.input_wires_arr();
+        let one = f.add_load_const(Const::int::<1>(1)?)?;
+        let xor = f.add_dataflow_op(LeafOp::Xor, [x, one])?;
+        let flip = f.finish_with_outputs(xor.outputs())?;
+
+        let mut f = module.define_function("twice", flip_sig.clone().pure())?;
+        let [x] = f.input_wires_arr();
+        let call = f.call(flip.handle(), [x])?;
+        let call = f.call(flip.handle(), call.outputs())?;
+        let twice = f.finish_with_outputs(call.outputs())?;
+
+        let graph = ClassicType::graph_from_sig(flip_sig.clone());
+        let mut f = module.define_function(
+            "apply",
+            AbstractSignature::new_df(vec![graph.into(), BIT], type_row![BIT]).pure(),
+        )?;
+        let [g, x] = f.input_wires_arr();
+        let call = f.add_dataflow_op(
+            ops::CallIndirect {
+                signature: flip_sig,
+            },
+            [g, x],
+        )?;
+        let apply = f.finish_with_outputs(call.outputs())?;
+        let h = module.finish_hugr()?;
+
+        assert_eq!(interpret(&h, twice.node(), [bit(1)])?, [bit(1)]);
+        assert_eq!(
+            interpret(&h, apply.node(), [Value::Function(flip.node()), bit(1)])?,
+            [bit(0)]
+        );
+        assert_eq!(
+            interpret(&h, apply.node(), [bit(0), bit(1)]),
+            Err(InterpretError::BadValue(call.node()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![SimpleType::Qubit],
+            type_row![SimpleType::Qubit],
+        ))?;
+        let [q] = b.input_wires_arr();
+        let gate = b.add_dataflow_op(LeafOp::H, [q])?;
+        let h = b.finish_hugr_with_outputs(gate.outputs())?;
+        assert_eq!(
+            interpret(&h, h.root(), [bit(0)]),
+            Err(InterpretError::UnsupportedOp(gate.node(), "H".into()))
+        );
+        assert_matches!(
+            interpret(&Hugr::default(), Hugr::default().root(), []),
+            Err(InterpretError::NotARegion(..))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+//! Lowering of structured control flow to control flow graphs.
+//!
+//! Replaces [`Conditional`] and [`TailLoop`] nodes by equivalent [`CFG`]
+//! nodes, so that analyses written against [`CfgView`] handle all the control
+//! flow of a Hugr. This is the reverse of [structurisation].
+//!
+//! [`Conditional`]: crate::ops::Conditional
+//! [`TailLoop`]: crate::ops::TailLoop
+//! [`CFG`]: crate::ops::CFG
+//! [`CfgView`]: crate::algorithm::nest_cfgs::CfgView
+//! [structurisation]: crate::algorithm::structurise
+
+use itertools::Itertools;
+
+use crate::hugr::rewrite::lower_control_flow::{LowerConditional, LowerTailLoop};
+use crate::hugr::HugrView;
+use crate::ops::{OpTag, OpTrait};
+use crate::Hugr;
+
+/// Lowers every [`Conditional`] and [`TailLoop`] node in the Hugr to a
+/// [`CFG`] node, see [`LowerConditional`] and [`LowerTailLoop`].
+///
+/// Returns the number of nodes lowered.
+///
+/// [`Conditional`]: crate::ops::Conditional
+/// [`TailLoop`]: crate::ops::TailLoop
+/// [`CFG`]: crate::ops::CFG
+pub fn lower_control_flow(h: &mut Hugr) -> usize {
+    // The nodes are lowered in place, so lowering one does not affect the
+    // others.
+    let nodes = h
+        .nodes()
+        .filter(|n| matches!(h.get_optype(*n).tag(), OpTag::Conditional | OpTag::TailLoop))
+        .collect_vec();
+    for &n in &nodes {
+        match h.get_optype(n).tag() {
+            OpTag::Conditional => h.apply_rewrite(LowerConditional::new(n)),
+            _ => h.apply_rewrite(LowerTailLoop::new(n)),
+        }
+        .expect("Node was checked to be a Conditional or TailLoop");
+    }
+    nodes.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::nest_cfgs::{CfgView, SimpleCfgView};
+    use crate::algorithm::structurise::structurise_cfgs;
+    use crate::builder::{DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer};
+    use crate::hugr::region::{FlatRegionView, Region};
+    use crate::ops::LeafOp;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+    use crate::{classic_row, type_row};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes()
+            .filter(|n| tag.is_superset(h.get_optype(*n).tag()))
+            .count()
+    }
+
+    /// A loop flipping a bit until the predicate it computes says to stop,
+    /// with a conditional flipping it once more in one of the cases.
+    fn build_loop_with_conditional() -> Result<Hugr, Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(type_row![BIT], type_row![BIT]))?;
+        let [x] = b.input_wires_arr();
+        let mut loop_b = b.tail_loop_builder(
+            vec![(ClassicType::bit(), x)],
+            [],
+            classic_row![ClassicType::bit()],
+        )?;
+        let signature = loop_b.loop_signature()?.clone();
+        let [x] = loop_b.input_wires_arr();
+        let pred = loop_b.make_predicate(0, [type_row![], type_row![]], [])?;
+        let mut cond_b = loop_b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(BIT, x)],
+            type_row![BIT],
+        )?;
+        for i in 0..2 {
+            let mut case_b = cond_b.case_builder(i)?;
+            let [mut x] = case_b.input_wires_arr();
+            if i == 1 {
+                [x] = case_b.add_dataflow_op(LeafOp::Xor, [x, x])?.outputs_arr();
+            }
+            case_b.finish_with_outputs([x])?;
+        }
+        let [x] = cond_b.finish_sub_container()?.outputs_arr();
+        let break_wire = loop_b.make_break(signature, [x])?;
+        let loop_id = loop_b.finish_with_outputs(break_wire, [])?;
+        Ok(b.finish_hugr_with_outputs(loop_id.outputs())?)
+    }
+
+    #[test]
+    fn lower_nested() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = build_loop_with_conditional()?;
+        assert_eq!(lower_control_flow(&mut h), 2);
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 0);
+        assert_eq!(count_tag(&h, OpTag::TailLoop), 0);
+        assert_eq!(count_tag(&h, OpTag::Cfg), 2);
+
+        // Every CFG can be viewed as such; the lowered loop branches back to
+        // its entry.
+        let cfgs = h
+            .nodes()
+            .filter(|n| h.get_optype(*n).tag() == OpTag::Cfg)
+            .collect_vec();
+        let loops = cfgs
+            .iter()
+            .filter(|cfg| {
+                let region = FlatRegionView::new(&h, **cfg);
+                let view = SimpleCfgView::new(&region);
+                let entry = view.entry_node();
+                view.successors(entry).contains(&entry)
+            })
+            .count();
+        assert_eq!(loops, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn lower_then_structurise() -> Result<(), Box<dyn std::error::Error>> {
+        let mut h = build_loop_with_conditional()?;
+        lower_control_flow(&mut h);
+        assert_eq!(structurise_cfgs(&mut h)?, 2);
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Cfg), 0);
+        assert_eq!(count_tag(&h, OpTag::TailLoop), 1);
+        Ok(())
+    }
+}
@@ -37,20 +37,28 @@
 //! * Multiple edges in the same direction between the same BBs will "confuse" the algorithm in the paper.
 //! However it is straightforward for us to treat successors and predecessors as sets. (Two edges between
 //! the same BBs but in opposite directions must be distinct!)
+//!
+//! # Transformation
+//! Two consecutive edges of the same class bound a SESE region, found by [sese_regions].
+//! [nest_cfg] moves each such region into its own nested CFG node using the
+//! [OutlineCfg] rewrite, innermost regions first, so the result is maximally nested.
+//! [nest_cfg_with_half_nodes] does the same on a view where blocks with multiple predecessors
+//! and successors are split in two, splitting the actual blocks where that exposes more regions.
 
 use std::collections::{HashMap, HashSet, LinkedList, VecDeque};
 use std::hash::Hash;
 
 use itertools::Itertools;
+use thiserror::Error;
 
+use super::half_node::{HalfNode, HalfNodeView};
+use crate::builder::{BlockBuilder, Container, Dataflow};
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::rewrite::outline_cfg::{OutlineCfg, OutlineCfgError};
 use crate::hugr::view::HugrView;
-use crate::ops::OpTag;
-use crate::ops::OpTrait;
-use crate::{Direction, Node};
-
-// TODO: transform the CFG: each SESE region can be turned into its own Kappa-node
-// (in a BB with one predecessor and one successor, which may then be merged
-//     and contents parallelized with predecessor or successor).
+use crate::hugr::HugrMut;
+use crate::ops::{BasicBlock, Const, OpTag, OpTrait, OpType};
+use crate::{type_row, Direction, Hugr, Node};
 
 /// A view of a CFG. `T` is the type of basic block; one interpretation of `T` would be a BasicBlock
 /// (e.g. `Node`) in the Hugr, but this extra level of indirection allows "splitting" one HUGR BB
@@ -103,10 +111,13 @@ fn all_edges<'a, T: Copy + Clone + PartialEq + Eq + Hash + 'a>(
     } else {
         vec![]
     };
+    // Self-loops are never part of a cycle with any other edge, so cannot
+    // bound a SESE region.
     cfg.successors(n)
         .chain(extra.into_iter())
         .map(EdgeDest::Forward)
         .chain(cfg.predecessors(n).map(EdgeDest::Backward))
+        .filter(move |d| d.target() != n)
         .unique()
 }
 
@@ -149,7 +160,8 @@ impl<H: HugrView> CfgView<Node> for SimpleCfgView<'_, H> {
         self.exit
     }
 
-    type Iterator<'c> = H::Neighbours<'c>
+    type Iterator<'c>
+        = H::Neighbours<'c>
     where
         Self: 'c;
 
@@ -394,8 +406,185 @@ impl<T: Copy + Clone + PartialEq + Eq + Hash> EdgeClassifier<T> {
     }
 }
 
+/// Computes the SESE regions of a CFG, each as the set of its nodes. Each region is bounded by two
+/// consecutive edges of the same class, see [EdgeClassifier::get_edge_classes]. Regions with a single
+/// node are omitted.
+///
+/// Any two regions are either disjoint or nested. The result is ordered by increasing size, so
+/// nested regions come before the regions enclosing them.
+pub fn sese_regions<T: Copy + Clone + PartialEq + Eq + Hash>(
+    cfg: &impl CfgView<T>,
+) -> Vec<HashSet<T>> {
+    let edge_classes = EdgeClassifier::get_edge_classes(cfg);
+    let mut class_edges: HashMap<usize, HashSet<CfgEdge<T>>> = HashMap::new();
+    for (e, class) in edge_classes.iter() {
+        class_edges.entry(*class).or_default().insert(*e);
+    }
+    let mut regions = Vec::new();
+    for ((src, tgt), class) in edge_classes {
+        let boundary = &class_edges[&class];
+        if boundary.len() < 2 {
+            continue;
+        }
+        // The region is everything reachable from the edge, without
+        // traversing another edge of the same class.
+        let mut region = HashSet::new();
+        let mut pending = vec![tgt];
+        let mut bounded = true;
+        while let Some(n) = pending.pop() {
+            if n == cfg.exit_node() || n == src {
+                bounded = false;
+                break;
+            }
+            if region.insert(n) {
+                pending.extend(cfg.successors(n).filter(|s| !boundary.contains(&(n, *s))));
+            }
+        }
+        if bounded && region.len() > 1 {
+            regions.push(region);
+        }
+    }
+    regions.sort_by_key(HashSet::len);
+    regions
+}
+
+/// Moves every SESE region of a CFG node into its own nested CFG node, so that the result is
+/// maximally nested. Regions are identified on a [SimpleCfgView] of the CFG.
+///
+/// Returns the number of regions nested.
+pub fn nest_cfg(h: &mut Hugr, cfg: Node) -> Result<usize, NestCfgError> {
+    check_cfg(h, cfg)?;
+    let regions = sese_regions(&SimpleCfgView::new(&FlatRegionView::new(h, cfg)));
+    outline_regions(h, regions)
+}
+
+/// Moves every SESE region of a CFG node into its own nested CFG node, identifying regions on a
+/// view where basic blocks with multiple predecessors and multiple successors are split in two.
+///
+/// Where a region boundary falls between the two halves of a block, the block is split by adding
+/// an empty block in front of it, taking all its predecessors. This finds more regions than
+/// [nest_cfg], e.g. a conditional inside a loop sharing its header.
+///
+/// Returns the number of regions nested.
+pub fn nest_cfg_with_half_nodes(h: &mut Hugr, cfg: Node) -> Result<usize, NestCfgError> {
+    check_cfg(h, cfg)?;
+    let region_view = FlatRegionView::new(h, cfg);
+    let view = HalfNodeView::new(&region_view);
+    let regions = sese_regions(&view);
+    // Blocks whose halves are in different regions must be split.
+    let split = regions
+        .iter()
+        .flat_map(|region| {
+            region.iter().filter_map(|half| match half {
+                HalfNode::N(n) | HalfNode::X(n)
+                    if view.is_multi_node(*n)
+                        && region.contains(&HalfNode::N(*n))
+                            != region.contains(&HalfNode::X(*n)) =>
+                {
+                    Some(*n)
+                }
+                _ => None,
+            })
+        })
+        .unique()
+        .collect_vec();
+    let heads: HashMap<Node, Node> = split.into_iter().map(|n| (n, split_block(h, n))).collect();
+    let regions = regions
+        .into_iter()
+        .map(|region| {
+            region
+                .into_iter()
+                .map(|half| match half {
+                    HalfNode::N(n) => heads.get(&n).copied().unwrap_or(n),
+                    HalfNode::X(n) => n,
+                })
+                .collect()
+        })
+        .collect();
+    outline_regions(h, regions)
+}
+
+/// Error from nesting the SESE regions of a CFG.
+#[derive(Debug, Error)]
+pub enum NestCfgError {
+    /// The given node is not a CFG.
+    #[error("Node {0:?} is not a CFG (found {1:?})")]
+    NotACfg(Node, OpType),
+    /// A region could not be outlined.
+    #[error("Could not outline region: {0}")]
+    OutlineError(#[from] OutlineCfgError),
+}
+
+fn check_cfg(h: &Hugr, cfg: Node) -> Result<(), NestCfgError> {
+    match h.get_optype(cfg) {
+        OpType::CFG(_) => Ok(()),
+        op => Err(NestCfgError::NotACfg(cfg, op.clone())),
+    }
+}
+
+/// Outlines each region in turn, replacing its blocks with the new block in the
+/// regions enclosing it. Regions must be ordered by increasing size.
+fn outline_regions(h: &mut Hugr, mut regions: Vec<HashSet<Node>>) -> Result<usize, NestCfgError> {
+    let mut nested = 0;
+    while !regions.is_empty() {
+        let blocks = regions.remove(0);
+        // Regions may have been reduced to a single block by nesting the
+        // regions they contained.
+        if blocks.len() < 2 {
+            continue;
+        }
+        let some_block = *blocks.iter().next().unwrap();
+        h.apply_rewrite(OutlineCfg::new(blocks.iter().copied()))?;
+        nested += 1;
+        // The blocks are now in a CFG node, inside a new block.
+        let cfg_node = h.get_parent(some_block).unwrap();
+        let new_block = h.get_parent(cfg_node).unwrap();
+        for region in regions.iter_mut() {
+            if region.is_superset(&blocks) {
+                region.retain(|n| !blocks.contains(n));
+                region.insert(new_block);
+            }
+        }
+    }
+    Ok(nested)
+}
+
+/// Adds an empty block in front of `block`, taking all its predecessors.
+/// Returns the new block.
+fn split_block(h: &mut Hugr, block: Node) -> Node {
+    let OpType::BasicBlock(BasicBlock::DFB { inputs, .. }) = h.get_optype(block) else {
+        panic!("Not a dataflow block")
+    };
+    let inputs = inputs.clone();
+    let cfg = h.get_parent(block).unwrap();
+    let head = {
+        let mut head_bldr = BlockBuilder::new(inputs.clone(), vec![type_row![]], inputs).unwrap();
+        let predicate = head_bldr
+            .add_constant(Const::simple_unary_predicate())
+            .unwrap();
+        let pred_wire = head_bldr.load_const(&predicate).unwrap();
+        let wires = head_bldr.input_wires();
+        let head_hugr = head_bldr
+            .finish_hugr_with_outputs(pred_wire, wires)
+            .unwrap();
+        h.insert_hugr(cfg, head_hugr).unwrap()
+    };
+    let block_input = h.node_inputs(block).exactly_one().ok().unwrap();
+    for (pred, br) in h.linked_ports(block, block_input).collect_vec() {
+        h.disconnect(pred, br).unwrap();
+        h.connect(pred, br.index(), head, 0).unwrap();
+    }
+    if h.children(cfg).next() == Some(block) {
+        h.move_before_sibling(head, block).unwrap();
+    }
+    h.connect(head, 0, block, 0).unwrap();
+    head
+}
+
 #[cfg(test)]
 pub(crate) mod test {
+    use cool_asserts::assert_matches;
+
     use super::*;
     use crate::builder::{BuildError, CFGBuilder, Container, DataflowSubContainer, HugrBuilder};
     use crate::hugr::region::{FlatRegionView, Region};
@@ -449,7 +638,14 @@ pub(crate) mod test {
         // that we *can* (as we'll need to for "real" module Hugr's).
         let v = FlatRegionView::new(&h, h.root());
         let edge_classes = EdgeClassifier::get_edge_classes(&SimpleCfgView::new(&v));
-        let [&left,&right] = edge_classes.keys().filter(|(s,_)| *s == split).map(|(_,t)|t).collect::<Vec<_>>()[..] else {panic!("Split node should have two successors");};
+        let [&left, &right] = edge_classes
+            .keys()
+            .filter(|(s, _)| *s == split)
+            .map(|(_, t)| t)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("Split node should have two successors");
+        };
 
         let classes = group_by(edge_classes);
         assert_eq!(
@@ -482,7 +678,14 @@ pub(crate) mod test {
             .unwrap();
 
         let edge_classes = EdgeClassifier::get_edge_classes(&SimpleCfgView::new(&h));
-        let [&left,&right] = edge_classes.keys().filter(|(s,_)| *s == entry).map(|(_,t)|t).collect::<Vec<_>>()[..] else {panic!("Entry node should have two successors");};
+        let [&left, &right] = edge_classes
+            .keys()
+            .filter(|(s, _)| *s == entry)
+            .map(|(_, t)| t)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("Entry node should have two successors");
+        };
 
         let classes = group_by(edge_classes);
         assert_eq!(
@@ -515,7 +718,14 @@ pub(crate) mod test {
         let v = SimpleCfgView::new(&h);
         let edge_classes = EdgeClassifier::get_edge_classes(&v);
         let SimpleCfgView { h: _, entry, exit } = v;
-        let [&left,&right] = edge_classes.keys().filter(|(s,_)| *s == split).map(|(_,t)|t).collect::<Vec<_>>()[..] else {panic!("Split node should have two successors");};
+        let [&left, &right] = edge_classes
+            .keys()
+            .filter(|(s, _)| *s == split)
+            .map(|(_, t)| t)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("Split node should have two successors");
+        };
         let classes = group_by(edge_classes);
         assert_eq!(
             classes,
@@ -551,7 +761,14 @@ pub(crate) mod test {
             .map(|(s, _)| s)
             .exactly_one()
             .unwrap();
-        let [&left,&right] = edge_classes.keys().filter(|(s,_)| *s == head).map(|(_,t)|t).collect::<Vec<_>>()[..] else {panic!("Loop header should have two successors");};
+        let [&left, &right] = edge_classes
+            .keys()
+            .filter(|(s, _)| *s == head)
+            .map(|(_, t)| t)
+            .collect::<Vec<_>>()[..]
+        else {
+            panic!("Loop header should have two successors");
+        };
         let classes = group_by(edge_classes);
         assert_eq!(
             classes,
@@ -566,6 +783,60 @@ pub(crate) mod test {
         Ok(())
     }
 
+    fn depth(h: &Hugr, n: Node) -> usize {
+        std::iter::successors(h.get_parent(n), |p| h.get_parent(*p)).count()
+    }
+
+    #[test]
+    fn test_nest_cond_in_loop_separate_headers() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, head, tail) = build_conditional_in_loop_cfg(true)?;
+        let (head, tail) = (head.node(), tail.node());
+        let split = h.output_neighbours(head).exactly_one().unwrap();
+        let left = h.output_neighbours(split).next().unwrap();
+        let root = h.root();
+        assert_eq!(nest_cfg(&mut h, root)?, 2);
+        h.validate()?;
+        // The loop is nested in a block of the root CFG, and the conditional
+        // in a block of the loop's CFG.
+        assert_eq!(depth(&h, head), 3);
+        assert_eq!(depth(&h, tail), 3);
+        assert_eq!(depth(&h, split), 5);
+        assert_eq!(depth(&h, left), 5);
+        // Nothing more to nest.
+        assert_eq!(nest_cfg(&mut h, root)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nest_cond_in_loop_combined_headers() -> Result<(), Box<dyn std::error::Error>> {
+        let (h, head, _) = build_conditional_in_loop_cfg(false)?;
+        let head = head.node();
+        let left = h.output_neighbours(head).next().unwrap();
+        let root = h.root();
+
+        let mut simple = h.clone();
+        assert_eq!(nest_cfg(&mut simple, root)?, 1);
+        simple.validate()?;
+        assert_eq!(depth(&simple, head), 3);
+        assert_eq!(depth(&simple, left), 3);
+
+        // The half-node view allows splitting the loop header from the
+        // conditional, which is nested separately.
+        let mut half = h;
+        assert_eq!(nest_cfg_with_half_nodes(&mut half, root)?, 2);
+        half.validate()?;
+        assert_eq!(depth(&half, head), 5);
+        assert_eq!(depth(&half, left), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nest_not_cfg() {
+        let mut h = Hugr::default();
+        let root = h.root();
+        assert_matches!(nest_cfg(&mut h, root), Err(NestCfgError::NotACfg(..)));
+    }
+
     fn n_identity<T: DataflowSubContainer>(
         mut dataflow_builder: T,
         pred_const: &ConstID,
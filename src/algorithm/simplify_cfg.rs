@@ -0,0 +1,195 @@
+//! Simplification of control flow graphs.
+//!
+//! Building CFGs block by block often leaves chains of basic blocks with a
+//! single successor and a single predecessor, empty blocks just forwarding
+//! their inputs, and branches whose targets are all the same block. This pass
+//! cleans those up, along with blocks that cannot be reached from the entry.
+
+use std::collections::HashSet;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::algorithm::nest_cfgs::{CfgView, SimpleCfgView};
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::rewrite::simplify_cfg::{BypassBlock, MergeBasicBlocks, SimplifyBranch};
+use crate::hugr::rewrite::utils::remove_subtree;
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::HugrView;
+use crate::ops::{OpTag, OpTrait, OpType};
+use crate::{Hugr, Node};
+
+/// Simplifies every [`CFG`] node in the Hugr.
+///
+/// Returns the number of blocks removed or simplified.
+///
+/// [`CFG`]: crate::ops::CFG
+pub fn simplify_cfgs(h: &mut Hugr) -> usize {
+    let cfgs = h
+        .nodes()
+        .filter(|n| h.get_optype(*n).tag() == OpTag::Cfg)
+        .collect_vec();
+    cfgs.into_iter()
+        .map(|cfg| simplify_cfg(h, cfg).unwrap())
+        .sum()
+}
+
+/// Simplifies the control flow graph of a single [`CFG`] node, until no more
+/// simplification applies:
+///
+/// - Blocks unreachable from the entry are removed.
+/// - Branches whose successors are all the same block are made unconditional,
+///   see [`SimplifyBranch`].
+/// - Empty blocks forwarding their inputs are bypassed, see [`BypassBlock`].
+/// - Blocks are merged with their unique successor when they are its only
+///   predecessor, see [`MergeBasicBlocks`].
+///
+/// Nested CFGs are not visited. Returns the number of blocks removed or
+/// simplified.
+///
+/// [`CFG`]: crate::ops::CFG
+pub fn simplify_cfg(h: &mut Hugr, cfg: Node) -> Result<usize, SimplifyCfgError> {
+    let optype = h.get_optype(cfg);
+    if !matches!(optype, OpType::CFG(_)) {
+        return Err(SimplifyCfgError::NotACfg(cfg, optype.clone()));
+    }
+    let mut simplified = remove_unreachable(h, cfg);
+    while simplify_once(h, cfg) {
+        simplified += 1;
+    }
+    Ok(simplified)
+}
+
+/// Error from a CFG simplification pass.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SimplifyCfgError {
+    /// The given node is not a CFG.
+    #[error("Node {0:?} is not a CFG (found {1:?})")]
+    NotACfg(Node, OpType),
+}
+
+/// Removes the blocks that cannot be reached from the entry of the CFG,
+/// returning how many were removed.
+pub(crate) fn remove_unreachable(h: &mut Hugr, cfg: Node) -> usize {
+    let region = FlatRegionView::new(h, cfg);
+    let view = SimpleCfgView::new(&region);
+    let mut reachable = HashSet::new();
+    let mut stack = vec![view.entry_node()];
+    while let Some(n) = stack.pop() {
+        if reachable.insert(n) {
+            stack.extend(view.successors(n));
+        }
+    }
+    let unreachable = h
+        .children(cfg)
+        .filter(|n| h.get_optype(*n).tag() == OpTag::BasicBlock && !reachable.contains(n))
+        .collect_vec();
+    for &block in &unreachable {
+        remove_subtree(h, block);
+    }
+    unreachable.len()
+}
+
+/// Applies the first applicable simplification to a block of the CFG,
+/// returning whether any was found.
+fn simplify_once(h: &mut Hugr, cfg: Node) -> bool {
+    let blocks = h
+        .children(cfg)
+        .filter(|n| h.get_optype(*n).tag() == OpTag::BasicBlock)
+        .collect_vec();
+    blocks.into_iter().any(|block| {
+        apply_verified(h, SimplifyBranch::new(block))
+            || apply_verified(h, BypassBlock::new(block))
+            || apply_verified(h, MergeBasicBlocks::new(block))
+    })
+}
+
+/// Applies a rewrite if it can be verified, returning whether it was applied.
+fn apply_verified<R: Rewrite>(h: &mut Hugr, rewrite: R) -> bool
+where
+    R::Error: std::fmt::Debug,
+{
+    if rewrite.verify(h).is_err() {
+        return false;
+    }
+    h.apply_rewrite(rewrite).expect("Verified rewrite failed");
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algorithm::nest_cfgs::test::{
+        build_cond_then_loop_cfg, build_conditional_in_loop_cfg,
+    };
+    use crate::builder::{CFGBuilder, Container, Dataflow, HugrBuilder};
+    use crate::ops::Const;
+    use crate::type_row;
+    use crate::types::{ClassicType, SimpleType};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    fn count_blocks(h: &Hugr) -> usize {
+        h.nodes()
+            .filter(|n| OpTag::BasicBlock.is_superset(h.get_optype(*n).tag()))
+            .count()
+    }
+
+    #[test]
+    fn straight_line() -> Result<(), Box<dyn std::error::Error>> {
+        // entry -> a -> b -> exit, with an unreachable block c -> b.
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let unit_const = cfg_builder.add_constant(Const::simple_unary_predicate())?;
+        let mut blocks = Vec::new();
+        for i in 0..4 {
+            let mut block_b = match i {
+                0 => cfg_builder.simple_entry_builder(type_row![NAT], 1)?,
+                _ => cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], 1)?,
+            };
+            let [n] = block_b.input_wires_arr();
+            let unit = block_b.load_const(&unit_const)?;
+            blocks.push(block_b.finish_with_outputs(unit, [n])?);
+        }
+        let exit = cfg_builder.exit_block();
+        cfg_builder.branch(&blocks[0], 0, &blocks[1])?;
+        cfg_builder.branch(&blocks[1], 0, &blocks[2])?;
+        cfg_builder.branch(&blocks[3], 0, &blocks[2])?;
+        cfg_builder.branch(&blocks[2], 0, &exit)?;
+        let mut h = cfg_builder.finish_hugr()?;
+
+        let root = h.root();
+        assert_eq!(simplify_cfg(&mut h, root)?, 3);
+        h.validate()?;
+        assert_eq!(count_blocks(&h), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn loops_preserved() -> Result<(), Box<dyn std::error::Error>> {
+        for separate in [false, true] {
+            let (mut h, _, _) = build_conditional_in_loop_cfg(separate)?;
+            simplify_cfgs(&mut h);
+            h.validate()?;
+            // The two branches of the conditional are bypassed, making it
+            // unconditional, and the whole loop body is merged into a single
+            // block looping on itself.
+            assert_eq!(count_blocks(&h), 3);
+
+            let (mut h, _, _) = build_cond_then_loop_cfg(separate)?;
+            simplify_cfgs(&mut h);
+            h.validate()?;
+            assert_eq!(count_blocks(&h), 3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn not_a_cfg() {
+        let mut h = Hugr::default();
+        let root = h.root();
+        assert_eq!(
+            simplify_cfg(&mut h, root),
+            Err(SimplifyCfgError::NotACfg(root, h.get_optype(root).clone()))
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Simplification of conditional branching.
+
+use std::collections::{HashSet, VecDeque};
+
+use itertools::Itertools;
+
+use crate::hugr::rewrite::conditional::{HoistCommonCode, MergeConditionals, ResolveConditional};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::HugrView;
+use crate::ops::{LeafOp, OpTag, OpTrait, OpType};
+use crate::{Hugr, Node, Port};
+
+/// Simplifies the [`Conditional`] nodes in the Hugr, until no more
+/// simplification applies:
+///
+/// - Conditionals whose predicate is known are replaced by the selected case,
+///   see [`ResolveConditional`]. If `flatten` is true the nodes of the case are
+///   placed directly in the sibling graph of the conditional, otherwise they
+///   are wrapped in a [`DFG`] node.
+/// - Conditionals branching on the same predicate are merged, see
+///   [`MergeConditionals`].
+/// - Operations common to all the cases of a conditional are hoisted out of
+///   it, see [`HoistCommonCode`].
+///
+/// Conditionals are visited from a worklist. After a rewrite only the
+/// conditionals around the rewritten one are revisited: those sharing its
+/// predicate, those using its outputs as predicate, and those nested in its
+/// cases.
+///
+/// Returns the number of rewrites applied.
+///
+/// [`Conditional`]: crate::ops::Conditional
+/// [`DFG`]: crate::ops::DFG
+pub fn simplify_conditionals(h: &mut Hugr, flatten: bool) -> usize {
+    let mut worklist = Worklist::default();
+    worklist.extend(h.nodes().filter(|n| is_conditional(h, *n)));
+    let mut applied = 0;
+    while let Some(cond) = worklist.pop() {
+        // Nodes are removed by rewrites, and their indices may be reused.
+        if !is_conditional(h, cond) {
+            continue;
+        }
+        if let Some(affected) = simplify_once(h, cond, flatten) {
+            applied += 1;
+            worklist.extend(affected.into_iter().filter(|n| is_conditional(h, *n)));
+        }
+    }
+    applied
+}
+
+/// A queue of conditionals to visit, without duplicates.
+#[derive(Default)]
+struct Worklist {
+    queue: VecDeque<Node>,
+    queued: HashSet<Node>,
+}
+
+impl Worklist {
+    fn pop(&mut self) -> Option<Node> {
+        let n = self.queue.pop_front()?;
+        self.queued.remove(&n);
+        Some(n)
+    }
+
+    fn extend(&mut self, nodes: impl IntoIterator<Item = Node>) {
+        for n in nodes {
+            if self.queued.insert(n) {
+                self.queue.push_back(n);
+            }
+        }
+    }
+}
+
+/// Applies the first applicable simplification to `cond`. Returns the nodes
+/// that should be revisited if one was applied.
+fn simplify_once(h: &mut Hugr, cond: Node, flatten: bool) -> Option<Vec<Node>> {
+    let pred_src = predicate_source(h, cond);
+    let parent = h.get_parent(cond);
+    // The conditionals around `cond`, collected before it is rewritten.
+    let mut affected = nested_conditionals(h, cond);
+    affected.extend(dependent_conditionals(h, cond));
+
+    let rewrite = match flatten {
+        true => ResolveConditional::flattened(cond),
+        false => ResolveConditional::new(cond),
+    };
+    let mut applied = apply_verified(h, rewrite);
+
+    // Candidates for merging share the parent and predicate source.
+    let siblings = sharing_predicate(h, pred_src, parent);
+    if !applied {
+        applied = siblings.iter().any(|&other| {
+            other != cond
+                && (apply_verified(h, MergeConditionals::new(cond, other))
+                    || apply_verified(h, MergeConditionals::new(other, cond)))
+        });
+    }
+    if !applied {
+        applied = apply_verified(h, HoistCommonCode::new(cond));
+    }
+    if !applied {
+        return None;
+    }
+
+    // Merging and hoisting replace `cond` with a new conditional reading the
+    // same predicate.
+    for new in sharing_predicate(h, pred_src, parent) {
+        affected.extend(nested_conditionals(h, new));
+        affected.push(new);
+    }
+    affected.extend(siblings);
+    Some(affected)
+}
+
+fn is_conditional(h: &Hugr, n: Node) -> bool {
+    h.get_optype(n).tag() == OpTag::Conditional
+}
+
+/// The source of the predicate of a conditional.
+fn predicate_source(h: &Hugr, cond: Node) -> Option<(Node, Port)> {
+    h.linked_ports(cond, Port::new_incoming(0)).next()
+}
+
+/// The conditionals with parent `parent` whose predicate comes from `src`.
+fn sharing_predicate(h: &Hugr, src: Option<(Node, Port)>, parent: Option<Node>) -> Vec<Node> {
+    let Some((src, src_port)) = src else {
+        return vec![];
+    };
+    h.linked_ports(src, src_port)
+        .filter(|&(n, p)| p.index() == 0 && is_conditional(h, n) && h.get_parent(n) == parent)
+        .map(|(n, _)| n)
+        .unique()
+        .collect()
+}
+
+/// The conditionals directly inside the cases of `cond`.
+fn nested_conditionals(h: &Hugr, cond: Node) -> Vec<Node> {
+    h.children(cond)
+        .flat_map(|case| h.children(case))
+        .filter(|n| is_conditional(h, *n))
+        .collect()
+}
+
+/// The conditionals using an output of `n` as predicate, possibly through
+/// [`LeafOp::Noop`] operations.
+///
+/// [`LeafOp::Noop`]: crate::ops::LeafOp::Noop
+fn dependent_conditionals(h: &Hugr, n: Node) -> Vec<Node> {
+    let mut found = vec![];
+    let mut stack = vec![n];
+    while let Some(n) = stack.pop() {
+        for tgt in h.output_neighbours(n) {
+            match h.get_optype(tgt) {
+                OpType::LeafOp(LeafOp::Noop { .. }) => stack.push(tgt),
+                op if op.tag() == OpTag::Conditional => found.push(tgt),
+                _ => {}
+            }
+        }
+    }
+    found
+}
+
+/// Applies a rewrite if it can be verified, returning whether it was applied.
+fn apply_verified<R: Rewrite>(h: &mut Hugr, rewrite: R) -> bool
+where
+    R::Error: std::fmt::Debug,
+{
+    if rewrite.verify(h).is_err() {
+        return false;
+    }
+    h.apply_rewrite(rewrite).expect("Verified rewrite failed");
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer,
+    };
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+    use crate::Wire;
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes().filter(|n| h.get_optype(*n).tag() == tag).count()
+    }
+
+    /// Adds a conditional with two cases, both computing the XOR of the
+    /// inputs, with the second case negating the result with another XOR.
+    fn add_conditional(
+        b: &mut DFGBuilder<Hugr>,
+        pred: Wire,
+        [x, y]: [Wire; 2],
+    ) -> Result<Wire, BuildError> {
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(BIT, x), (BIT, y)],
+            type_row![BIT],
+        )?;
+        for i in 0..2 {
+            let mut case = cond_b.case_builder(i)?;
+            let [x, y] = case.input_wires_arr();
+            let [mut out] = case.add_dataflow_op(LeafOp::Xor, [x, y])?.outputs_arr();
+            if i == 1 {
+                [out] = case.add_dataflow_op(LeafOp::Xor, [out, y])?.outputs_arr();
+            }
+            case.finish_with_outputs([out])?;
+        }
+        let [out] = cond_b.finish_sub_container()?.outputs_arr();
+        Ok(out)
+    }
+
+    #[test]
+    fn simplify_known_predicate() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT],
+        ))?;
+        let [x, y] = b.input_wires_arr();
+        let pred = b.make_predicate(1, [type_row![], type_row![]], [])?;
+        let out = add_conditional(&mut b, pred, [x, y])?;
+        let mut h = b.finish_hugr_with_outputs([out])?;
+
+        assert!(simplify_conditionals(&mut h, true) > 0);
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 0);
+        assert_eq!(count_tag(&h, OpTag::Case), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn simplify_cascade() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT],
+        ))?;
+        let [x, y] = b.input_wires_arr();
+        // The first conditional selects the predicate of the second one.
+        let pred = b.make_predicate(1, [type_row![], type_row![]], [])?;
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [],
+            vec![SimpleType::new_simple_predicate(2)].into(),
+        )?;
+        for i in 0..2 {
+            let mut case = cond_b.case_builder(i)?;
+            let inner = case.make_predicate(i, [type_row![], type_row![]], [])?;
+            let inner = case
+                .add_dataflow_op(
+                    LeafOp::Noop {
+                        ty: SimpleType::new_simple_predicate(2),
+                    },
+                    [inner],
+                )?
+                .out_wire(0);
+            case.finish_with_outputs([inner])?;
+        }
+        let [inner] = cond_b.finish_sub_container()?.outputs_arr();
+        let out = add_conditional(&mut b, inner, [x, y])?;
+        let mut h = b.finish_hugr_with_outputs([out])?;
+
+        assert_eq!(simplify_conditionals(&mut h, true), 2);
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 0);
+        // The second case, negating the XOR, was selected.
+        let num_xor = h
+            .nodes()
+            .filter(|n| h.get_optype(*n) == &LeafOp::Xor.into())
+            .count();
+        assert_eq!(num_xor, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn simplify_shared_predicate() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![SimpleType::new_simple_predicate(2), BIT, BIT],
+            type_row![BIT, BIT],
+        ))?;
+        let [pred, x, y] = b.input_wires_arr();
+        let out1 = add_conditional(&mut b, pred, [x, y])?;
+        let out2 = add_conditional(&mut b, pred, [y, x])?;
+        let mut h = b.finish_hugr_with_outputs([out1, out2])?;
+
+        // The two conditionals are merged, and the XORs of the inputs hoisted.
+        assert_eq!(simplify_conditionals(&mut h, false), 3);
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 1);
+        let cond = h
+            .nodes()
+            .find(|n| h.get_optype(*n).tag() == OpTag::Conditional)
+            .unwrap();
+        let cases = h.children(cond).collect_vec();
+        // Input and Output, plus the two negations in the second case.
+        assert_eq!(h.children(cases[0]).count(), 2);
+        assert_eq!(h.children(cases[1]).count(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,680 @@
+//! Statevector simulation of small quantum Hugrs.
+//!
+//! The quantum leaf operations are applied to a dense vector of `2^n`
+//! amplitudes, so this is only practical for a few tens of qubits at most. It
+//! is intended for testing gate-level rewrites.
+//!
+//! The [`Simulator`] is a [`QuantumBackend`] for the classical
+//! [`Interpreter`], which evaluates the rest of the Hugr. In particular,
+//! control flow may depend on measurement results.
+
+use std::f64::consts::FRAC_1_SQRT_2;
+use std::f64::consts::FRAC_PI_4;
+
+use num_complex::Complex64;
+use rand::{Rng, RngCore};
+
+use crate::hugr::HugrView;
+use crate::ops::{ConstValue, LeafOp, OpTrait, OpType};
+use crate::types::{SimpleRow, SimpleType};
+use crate::values::HashableValue;
+use crate::Node;
+
+use super::interpret::{EvaluatorRegistry, InterpretError, Interpreter, QuantumBackend, Value};
+
+/// The largest number of qubits of a [`StateVector`].
+pub const MAX_QUBITS: usize = 24;
+
+/// The state of a register of qubits.
+///
+/// Qubit `q` corresponds to bit `q` of the index of each amplitude, so that
+/// qubit 0 is the least significant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateVector {
+    amplitudes: Vec<Complex64>,
+}
+
+impl StateVector {
+    /// Creates the all-zero state of `n_qubits` qubits.
+    ///
+    /// # Panics
+    ///
+    /// If `n_qubits` is larger than [`MAX_QUBITS`].
+    pub fn new(n_qubits: usize) -> Self {
+        Self::basis(n_qubits, 0)
+    }
+
+    /// Creates the computational basis state with the given index.
+    ///
+    /// # Panics
+    ///
+    /// If `n_qubits` is larger than [`MAX_QUBITS`], or the index is not
+    /// smaller than `2^n_qubits`.
+    pub fn basis(n_qubits: usize, index: usize) -> Self {
+        assert!(
+            n_qubits <= MAX_QUBITS,
+            "At most {MAX_QUBITS} qubits can be simulated"
+        );
+        let mut amplitudes = vec![Complex64::new(0.0, 0.0); 1 << n_qubits];
+        amplitudes[index] = Complex64::new(1.0, 0.0);
+        Self { amplitudes }
+    }
+
+    /// Creates a state from its amplitudes, which are assumed to be
+    /// normalised.
+    ///
+    /// # Panics
+    ///
+    /// If the number of amplitudes is not a power of two.
+    pub fn from_amplitudes(amplitudes: Vec<Complex64>) -> Self {
+        assert!(
+            amplitudes.len().is_power_of_two(),
+            "The number of amplitudes must be a power of two"
+        );
+        Self { amplitudes }
+    }
+
+    /// The number of qubits.
+    pub fn n_qubits(&self) -> usize {
+        self.amplitudes.len().trailing_zeros() as usize
+    }
+
+    /// The amplitudes of the computational basis states.
+    pub fn amplitudes(&self) -> &[Complex64] {
+        &self.amplitudes
+    }
+
+    /// The probabilities of measuring each computational basis state.
+    pub fn probabilities(&self) -> Vec<f64> {
+        self.amplitudes.iter().map(|a| a.norm_sqr()).collect()
+    }
+
+    /// Applies a single-qubit gate, given by its matrix in row-major order.
+    pub fn apply_1q(&mut self, q: usize, matrix: [[Complex64; 2]; 2]) {
+        let bit = 1 << q;
+        for i in (0..self.amplitudes.len()).filter(|i| i & bit == 0) {
+            let (a0, a1) = (self.amplitudes[i], self.amplitudes[i | bit]);
+            self.amplitudes[i] = matrix[0][0] * a0 + matrix[0][1] * a1;
+            self.amplitudes[i | bit] = matrix[1][0] * a0 + matrix[1][1] * a1;
+        }
+    }
+
+    /// Applies a two-qubit gate, given by its matrix in row-major order. The
+    /// row and column indices are `2 * b0 + b1`, where `b0` and `b1` are the
+    /// values of qubits `q0` and `q1`.
+    ///
+    /// # Panics
+    ///
+    /// If the two qubits are the same.
+    pub fn apply_2q(&mut self, q0: usize, q1: usize, matrix: [[Complex64; 4]; 4]) {
+        assert_ne!(q0, q1, "A two-qubit gate needs two distinct qubits");
+        let (bit0, bit1) = (1 << q0, 1 << q1);
+        let offsets = [0, bit1, bit0, bit0 | bit1];
+        for i in (0..self.amplitudes.len()).filter(|i| i & (bit0 | bit1) == 0) {
+            let old = offsets.map(|o| self.amplitudes[i | o]);
+            for (row, o) in matrix.iter().zip(offsets) {
+                self.amplitudes[i | o] = row.iter().zip(&old).map(|(m, a)| m * a).sum();
+            }
+        }
+    }
+
+    /// The probability of measuring a qubit as 1.
+    pub fn probability_one(&self, q: usize) -> f64 {
+        let bit = 1 << q;
+        self.amplitudes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i & bit != 0)
+            .map(|(_, a)| a.norm_sqr())
+            .sum()
+    }
+
+    /// Measures a qubit in the computational basis, collapsing the state.
+    pub fn measure(&mut self, q: usize, rng: &mut (impl RngCore + ?Sized)) -> bool {
+        let p1 = self.probability_one(q);
+        let outcome = rng.gen::<f64>() < p1;
+        let norm = if outcome { p1 } else { 1.0 - p1 }.sqrt();
+        let bit = 1 << q;
+        for (i, a) in self.amplitudes.iter_mut().enumerate() {
+            if (i & bit != 0) == outcome {
+                *a /= norm;
+            } else {
+                *a = Complex64::new(0.0, 0.0);
+            }
+        }
+        outcome
+    }
+
+    /// Resets a qubit to 0, by measuring it and flipping it if needed.
+    pub fn reset(&mut self, q: usize, rng: &mut (impl RngCore + ?Sized)) {
+        if self.measure(q, rng) {
+            self.apply_1q(q, X);
+        }
+    }
+
+    /// Reorders the qubits, so that qubit `i` of the result is qubit
+    /// `order[i]` of this state.
+    ///
+    /// # Panics
+    ///
+    /// If `order` is not a permutation of the qubits.
+    pub fn permute_qubits(&self, order: &[usize]) -> Self {
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        assert!(
+            sorted.into_iter().eq(0..self.n_qubits()),
+            "Not a permutation of the qubits"
+        );
+        let mut amplitudes = vec![Complex64::new(0.0, 0.0); self.amplitudes.len()];
+        for (i, a) in self.amplitudes.iter().enumerate() {
+            let j: usize = order
+                .iter()
+                .enumerate()
+                .map(|(new, &old)| ((i >> old) & 1) << new)
+                .sum();
+            amplitudes[j] = *a;
+        }
+        Self { amplitudes }
+    }
+
+    /// Whether two states are equal up to a global phase, within the given
+    /// tolerance on the fidelity.
+    pub fn equal_up_to_phase(&self, other: &Self, tolerance: f64) -> bool {
+        if self.amplitudes.len() != other.amplitudes.len() {
+            return false;
+        }
+        let overlap: Complex64 = self
+            .amplitudes
+            .iter()
+            .zip(&other.amplitudes)
+            .map(|(a, b)| a.conj() * b)
+            .sum();
+        (1.0 - overlap.norm_sqr()).abs() <= tolerance
+    }
+}
+
+const fn c(re: f64, im: f64) -> Complex64 {
+    Complex64::new(re, im)
+}
+
+const ZERO: Complex64 = c(0.0, 0.0);
+const ONE: Complex64 = c(1.0, 0.0);
+const I: Complex64 = c(0.0, 1.0);
+const X: [[Complex64; 2]; 2] = [[ZERO, ONE], [ONE, ZERO]];
+
+/// The matrix of a single-qubit gate without parameters.
+fn gate_1q(op: &LeafOp) -> Option<[[Complex64; 2]; 2]> {
+    let diag = |p: Complex64| [[ONE, ZERO], [ZERO, p]];
+    Some(match op {
+        LeafOp::H => {
+            let h = c(FRAC_1_SQRT_2, 0.0);
+            [[h, h], [h, -h]]
+        }
+        LeafOp::X => X,
+        LeafOp::Y => [[ZERO, -I], [I, ZERO]],
+        LeafOp::Z => diag(-ONE),
+        LeafOp::S => diag(I),
+        LeafOp::Sadj => diag(-I),
+        LeafOp::T => diag(Complex64::from_polar(1.0, FRAC_PI_4)),
+        LeafOp::Tadj => diag(Complex64::from_polar(1.0, -FRAC_PI_4)),
+        _ => return None,
+    })
+}
+
+/// The matrix of a two-qubit gate.
+fn gate_2q(op: &LeafOp) -> Option<[[Complex64; 4]; 4]> {
+    Some(match op {
+        LeafOp::CX => [
+            [ONE, ZERO, ZERO, ZERO],
+            [ZERO, ONE, ZERO, ZERO],
+            [ZERO, ZERO, ZERO, ONE],
+            [ZERO, ZERO, ONE, ZERO],
+        ],
+        LeafOp::ZZMax => {
+            // exp(-i pi/4 Z⊗Z)
+            let (m, p) = (
+                Complex64::from_polar(1.0, -FRAC_PI_4),
+                Complex64::from_polar(1.0, FRAC_PI_4),
+            );
+            [
+                [m, ZERO, ZERO, ZERO],
+                [ZERO, p, ZERO, ZERO],
+                [ZERO, ZERO, p, ZERO],
+                [ZERO, ZERO, ZERO, m],
+            ]
+        }
+        _ => return None,
+    })
+}
+
+/// A statevector simulator, evaluating the quantum operations of a Hugr
+/// for an [`Interpreter`].
+///
+/// Qubit inputs of the simulated region are given as [`Value::Qubit`]s
+/// indexing into the state. Measurements and resets need a random number
+/// generator.
+pub struct Simulator<'r> {
+    state: StateVector,
+    rng: Option<&'r mut dyn RngCore>,
+}
+
+impl<'r> Simulator<'r> {
+    /// Creates a simulator starting from the given state, without a random
+    /// number generator.
+    pub fn new(state: StateVector) -> Self {
+        Self { state, rng: None }
+    }
+
+    /// Sets the random number generator used for measurements.
+    pub fn with_rng(mut self, rng: &'r mut dyn RngCore) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// The current state.
+    pub fn state(&self) -> &StateVector {
+        &self.state
+    }
+
+    /// Returns the current state.
+    pub fn into_state(self) -> StateVector {
+        self.state
+    }
+
+    fn qubit(&self, node: Node, v: &Value) -> Result<usize, InterpretError> {
+        match v {
+            Value::Qubit(q) if *q < self.state.n_qubits() => Ok(*q),
+            _ => Err(InterpretError::BadValue(node)),
+        }
+    }
+
+    fn apply_op(
+        &mut self,
+        node: Node,
+        op: &LeafOp,
+        inputs: &[Value],
+    ) -> Result<Vec<Value>, InterpretError> {
+        let q = self.qubit(node, &inputs[0])?;
+        if let Some(matrix) = gate_1q(op) {
+            self.state.apply_1q(q, matrix);
+        } else if let Some(matrix) = gate_2q(op) {
+            let q1 = self.qubit(node, &inputs[1])?;
+            if q == q1 {
+                return Err(InterpretError::BadValue(node));
+            }
+            self.state.apply_2q(q, q1, matrix);
+        } else {
+            match op {
+                LeafOp::RzF64 => {
+                    let Some(ConstValue::F64(angle)) = inputs[1].as_const() else {
+                        return Err(InterpretError::BadValue(node));
+                    };
+                    let matrix = [
+                        [Complex64::from_polar(1.0, -angle / 2.0), ZERO],
+                        [ZERO, Complex64::from_polar(1.0, angle / 2.0)],
+                    ];
+                    self.state.apply_1q(q, matrix);
+                }
+                LeafOp::Measure => {
+                    let rng = self.rng.as_deref_mut().ok_or_else(|| no_rng(node))?;
+                    let outcome = self.state.measure(q, rng);
+                    let bit = ConstValue::Hashable(HashableValue::Int(outcome as u128));
+                    return Ok(vec![Value::Qubit(q), bit.into()]);
+                }
+                LeafOp::Reset => {
+                    let rng = self.rng.as_deref_mut().ok_or_else(|| no_rng(node))?;
+                    self.state.reset(q, rng);
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(inputs
+            .iter()
+            .filter(|v| matches!(v, Value::Qubit(_)))
+            .cloned()
+            .collect())
+    }
+}
+
+fn no_rng(node: Node) -> InterpretError {
+    InterpretError::EvaluationFailed {
+        node,
+        message: "Measurement needs a random number generator".into(),
+    }
+}
+
+impl QuantumBackend for Simulator<'_> {
+    fn apply(
+        &mut self,
+        node: Node,
+        op: &LeafOp,
+        inputs: &[Value],
+    ) -> Option<Result<Vec<Value>, InterpretError>> {
+        match op {
+            LeafOp::H
+            | LeafOp::T
+            | LeafOp::S
+            | LeafOp::X
+            | LeafOp::Y
+            | LeafOp::Z
+            | LeafOp::Tadj
+            | LeafOp::Sadj
+            | LeafOp::CX
+            | LeafOp::ZZMax
+            | LeafOp::Reset
+            | LeafOp::Measure
+            | LeafOp::RzF64 => Some(self.apply_op(node, op, inputs)),
+            _ => None,
+        }
+    }
+}
+
+/// The input and output types of a region that can be run by an
+/// [`Interpreter`].
+pub(crate) fn region_types(h: &impl HugrView, node: Node) -> Option<(SimpleRow, SimpleRow)> {
+    let op = h.get_optype(node);
+    match op {
+        OpType::Conditional(_) | OpType::TailLoop(_) | OpType::CFG(_) => {
+            let sig = op.signature();
+            Some((sig.input, sig.output))
+        }
+        _ => {
+            let mut children = h.children(node);
+            match (
+                children.next().map(|n| h.get_optype(n)),
+                children.next().map(|n| h.get_optype(n)),
+            ) {
+                (Some(OpType::Input(i)), Some(OpType::Output(o))) => {
+                    Some((i.types.clone(), o.types.clone()))
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Simulates a purely quantum region of a Hugr on the given state.
+///
+/// The inputs and outputs of the region must all be qubits, as many as in
+/// the state. Input `i` is qubit `i` of the state, and so is output `i` of
+/// the returned state. Measurements and resets are not supported.
+pub fn statevector<H: HugrView>(
+    h: &H,
+    node: Node,
+    state: StateVector,
+) -> Result<StateVector, InterpretError> {
+    let n = state.n_qubits();
+    let mut simulator = Simulator::new(state);
+    let outputs = Interpreter::new(h)
+        .with_backend(&mut simulator)
+        .run(node, (0..n).map(Value::Qubit))?;
+    let order = outputs
+        .iter()
+        .map(|v| match v {
+            Value::Qubit(q) => Ok(*q),
+            _ => Err(InterpretError::BadValue(node)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if order.len() != n {
+        return Err(InterpretError::BadValue(node));
+    }
+    Ok(simulator.into_state().permute_qubits(&order))
+}
+
+/// Runs a region of a Hugr repeatedly, returning its classical outputs for
+/// each shot.
+///
+/// Every qubit input of the region starts in the zero state, and the
+/// classical inputs are given in order by `inputs`. Resource operations are
+/// evaluated by the `registry`, if given.
+pub fn sample<H: HugrView>(
+    h: &H,
+    node: Node,
+    inputs: impl IntoIterator<Item = Value>,
+    shots: usize,
+    rng: &mut impl RngCore,
+    registry: Option<&EvaluatorRegistry>,
+) -> Result<Vec<Vec<Value>>, InterpretError> {
+    let (input_types, _) = region_types(h, node)
+        .ok_or_else(|| InterpretError::NotARegion(node, h.get_optype(node).tag()))?;
+    let classical: Vec<Value> = inputs.into_iter().collect();
+    let n_qubits = input_types
+        .iter()
+        .filter(|t| matches!(t, SimpleType::Qubit))
+        .count();
+    if classical.len() != input_types.len() - n_qubits {
+        return Err(InterpretError::WrongInputCount {
+            node,
+            expected: input_types.len() - n_qubits,
+            actual: classical.len(),
+        });
+    }
+    if n_qubits > MAX_QUBITS {
+        return Err(InterpretError::TooManyQubits { node, n_qubits });
+    }
+    let mut classical = classical.into_iter();
+    let mut qubits = 0..n_qubits;
+    let inputs: Vec<Value> = input_types
+        .iter()
+        .map(|t| match t {
+            SimpleType::Qubit => Value::Qubit(qubits.next().unwrap()),
+            _ => classical.next().unwrap(),
+        })
+        .collect();
+    (0..shots)
+        .map(|_| {
+            let mut simulator = Simulator::new(StateVector::new(n_qubits)).with_rng(&mut *rng);
+            let mut interpreter = Interpreter::new(h).with_backend(&mut simulator);
+            if let Some(registry) = registry {
+                interpreter = interpreter.with_registry(registry);
+            }
+            let outputs = interpreter.run(node, inputs.clone())?;
+            Ok(outputs
+                .into_iter()
+                .filter(|v| !matches!(v, Value::Qubit(_)))
+                .collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64::consts::PI;
+
+    use cool_asserts::assert_matches;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer,
+    };
+    use crate::ops::custom::{ExternalOp, OpaqueOp};
+    use crate::ops::Const;
+    use crate::types::{AbstractSignature, ClassicType};
+    use crate::{type_row, Hugr};
+
+    const QB: SimpleType = SimpleType::Qubit;
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+    const TOLERANCE: f64 = 1e-10;
+
+    fn bit(v: u128) -> Value {
+        Value::Const(HashableValue::Int(v).into())
+    }
+
+    /// A DFG applying a sequence of gates, each given with the indices of the
+    /// qubits it acts on.
+    fn circuit(n_qubits: usize, gates: &[(LeafOp, &[usize])]) -> Result<Hugr, BuildError> {
+        let row: SimpleRow = vec![QB; n_qubits].into();
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(row.clone(), row))?;
+        let mut wires = b.input_wires().collect::<Vec<_>>();
+        for (op, qbs) in gates {
+            let mut ins = qbs.iter().map(|&q| wires[q]).collect::<Vec<_>>();
+            if let LeafOp::RzF64 = op {
+                let angle = Const::new(ConstValue::F64(PI), ClassicType::F64).unwrap();
+                ins.push(b.add_load_const(angle)?);
+            }
+            let outs = b.add_dataflow_op(op.clone(), ins)?.outputs();
+            for (&q, w) in qbs.iter().zip(outs) {
+                wires[q] = w;
+            }
+        }
+        b.finish_hugr_with_outputs(wires)
+    }
+
+    #[test]
+    fn bell_state() -> Result<(), Box<dyn std::error::Error>> {
+        let h = circuit(2, &[(LeafOp::H, &[0]), (LeafOp::CX, &[0, 1])])?;
+        let state = statevector(&h, h.root(), StateVector::new(2))?;
+        let probs = state.probabilities();
+        for (p, expected) in probs.iter().zip([0.5, 0.0, 0.0, 0.5]) {
+            assert!((p - expected).abs() < TOLERANCE);
+        }
+        Ok(())
+    }
+
+    /// Whether two circuits on two qubits are equal up to a global phase on
+    /// the basis states and a superposition of them.
+    fn equivalent(a: &[(LeafOp, &[usize])], b: &[(LeafOp, &[usize])]) -> bool {
+        let (a, b) = (circuit(2, a).unwrap(), circuit(2, b).unwrap());
+        let superposition = StateVector::from_amplitudes(vec![
+            c(0.5, 0.0),
+            c(0.0, 0.5),
+            c(-0.5, 0.0),
+            Complex64::from_polar(0.5, PI / 3.0),
+        ]);
+        let inputs = (0..4).map(|i| StateVector::basis(2, i));
+        inputs.chain([superposition]).all(|state| {
+            let out_a = statevector(&a, a.root(), state.clone()).unwrap();
+            let out_b = statevector(&b, b.root(), state).unwrap();
+            out_a.equal_up_to_phase(&out_b, TOLERANCE)
+        })
+    }
+
+    #[test]
+    fn gate_identities() {
+        assert!(equivalent(
+            &[(LeafOp::T, &[0]), (LeafOp::T, &[0])],
+            &[(LeafOp::S, &[0])]
+        ));
+        assert!(equivalent(&[(LeafOp::S, &[1]), (LeafOp::Sadj, &[1])], &[]));
+        assert!(equivalent(
+            &[(LeafOp::X, &[0]), (LeafOp::Z, &[0])],
+            &[(LeafOp::Y, &[0])]
+        ));
+        assert!(equivalent(&[(LeafOp::RzF64, &[0])], &[(LeafOp::Z, &[0])]));
+        // Both are a CZ gate.
+        assert!(equivalent(
+            &[(LeafOp::H, &[1]), (LeafOp::CX, &[0, 1]), (LeafOp::H, &[1])],
+            &[
+                (LeafOp::ZZMax, &[0, 1]),
+                (LeafOp::Sadj, &[0]),
+                (LeafOp::Sadj, &[1])
+            ]
+        ));
+        assert!(!equivalent(
+            &[(LeafOp::CX, &[0, 1])],
+            &[(LeafOp::CX, &[1, 0])]
+        ));
+        assert!(!equivalent(&[(LeafOp::T, &[0])], &[(LeafOp::Tadj, &[0])]));
+    }
+
+    #[test]
+    fn permuted_outputs() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB],
+        ))?;
+        let [q0, q1] = b.input_wires_arr();
+        let [q0] = b.add_dataflow_op(LeafOp::X, [q0])?.outputs_arr();
+        let h = b.finish_hugr_with_outputs([q1, q0])?;
+        let state = statevector(&h, h.root(), StateVector::new(2))?;
+        assert_eq!(state, StateVector::basis(2, 0b10));
+        Ok(())
+    }
+
+    /// Measures and resets a qubit in the |+> state, and flips a second qubit
+    /// if the outcome is 1. Both qubits are then measured.
+    fn build_feedforward() -> Result<Hugr, BuildError> {
+        let bool_type = SimpleType::new_simple_predicate(2);
+        let to_bool = OpaqueOp::new(
+            "Test".into(),
+            "ToBool",
+            "bit to boolean".into(),
+            [],
+            Some(AbstractSignature::new_df(type_row![BIT], vec![bool_type])),
+        );
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB, BIT, BIT],
+        ))?;
+        let [q0, q1] = b.input_wires_arr();
+        let [q0] = b.add_dataflow_op(LeafOp::H, [q0])?.outputs_arr();
+        let [q0, m0] = b.add_dataflow_op(LeafOp::Measure, [q0])?.outputs_arr();
+        let [q0] = b.add_dataflow_op(LeafOp::Reset, [q0])?.outputs_arr();
+        let [pred] = b
+            .add_dataflow_op(LeafOp::CustomOp(ExternalOp::Opaque(to_bool)), [m0])?
+            .outputs_arr();
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(QB, q1)],
+            type_row![QB],
+        )?;
+        let case_b = cond_b.case_builder(0)?;
+        let [q] = case_b.input_wires_arr();
+        case_b.finish_with_outputs([q])?;
+        let mut case_b = cond_b.case_builder(1)?;
+        let [q] = case_b.input_wires_arr();
+        let [q] = case_b.add_dataflow_op(LeafOp::X, [q])?.outputs_arr();
+        case_b.finish_with_outputs([q])?;
+        let [q1] = cond_b.finish_sub_container()?.outputs_arr();
+        let [q1, m1] = b.add_dataflow_op(LeafOp::Measure, [q1])?.outputs_arr();
+        let [q0, m2] = b.add_dataflow_op(LeafOp::Measure, [q0])?.outputs_arr();
+        b.finish_hugr_with_outputs([q0, q1, m1, m2])
+    }
+
+    #[test]
+    fn feedforward() -> Result<(), Box<dyn std::error::Error>> {
+        let h = build_feedforward()?;
+        let mut registry = EvaluatorRegistry::new();
+        registry.register_op(&"Test".into(), "ToBool", |_, inputs| match &inputs[0] {
+            ConstValue::Hashable(HashableValue::Int(b)) => {
+                Ok(vec![ConstValue::simple_predicate(*b as usize)])
+            }
+            _ => Err("not a bit".into()),
+        });
+        let mut rng = StdRng::seed_from_u64(42);
+        let shots = sample(&h, h.root(), [], 100, &mut rng, Some(&registry))?;
+        assert_eq!(shots.len(), 100);
+        // The second qubit was flipped exactly when the first measured 1, and
+        // the first was reset.
+        let ones = shots.iter().filter(|s| s[0] == bit(1)).count();
+        assert!(ones > 20 && ones < 80);
+        assert!(shots.iter().all(|s| s[1] == bit(0)));
+
+        assert_matches!(
+            statevector(&h, h.root(), StateVector::new(2)),
+            Err(InterpretError::EvaluationFailed { .. })
+        );
+        assert_matches!(
+            sample(&h, h.root(), [bit(0)], 1, &mut rng, Some(&registry)),
+            Err(InterpretError::WrongInputCount {
+                expected: 0,
+                actual: 1,
+                ..
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn too_many_qubits() -> Result<(), Box<dyn std::error::Error>> {
+        let h = circuit(MAX_QUBITS + 1, &[])?;
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_matches!(
+            sample(&h, h.root(), [], 1, &mut rng, None),
+            Err(InterpretError::TooManyQubits { n_qubits, .. }) if n_qubits == MAX_QUBITS + 1
+        );
+        Ok(())
+    }
+}
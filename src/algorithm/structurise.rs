@@ -0,0 +1,695 @@
+//! Structurisation of control flow graphs.
+//!
+//! Converts [`CFG`] nodes into equivalent structured dataflow, made of nested
+//! [`Conditional`], [`TailLoop`] and [`DFG`] nodes, as required by targets
+//! that only support structured control flow.
+//!
+//! The SESE regions of the CFG are first nested with [`nest_cfg`], and each
+//! resulting CFG is then reduced, innermost first, by repeatedly applying the
+//! following patterns until a single block remains:
+//!
+//! - *Sequence*: a block with a unique successor, which has no other
+//!   predecessor, is merged with it.
+//! - *Branch*: a block whose successors are either a common join block, or
+//!   blocks whose only predecessor is the branching block and whose only
+//!   successor is the join block, becomes a block containing a [`Conditional`]
+//!   and branching to the join block.
+//! - *Loop*: a block whose successors are either itself, blocks whose only
+//!   predecessor and successor is the block, or a single exit block, becomes a
+//!   block containing a [`TailLoop`] and branching to the exit block.
+//!
+//! Irreducible control flow is reported as an error, as are reducible CFGs not
+//! matched by these patterns, e.g. loops with multiple exits.
+//!
+//! [`CFG`]: crate::ops::CFG
+//! [`Conditional`]: crate::ops::Conditional
+//! [`TailLoop`]: crate::ops::TailLoop
+//! [`DFG`]: crate::ops::DFG
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::algorithm::nest_cfgs::{nest_cfg, CfgView, NestCfgError, SimpleCfgView};
+use crate::algorithm::simplify_cfg::remove_unreachable;
+use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowSubContainer, SubContainer};
+use crate::hugr::region::{FlatRegionView, Region};
+use crate::hugr::rewrite::simplify_cfg::MergeBasicBlocks;
+use crate::hugr::rewrite::utils::{block_to_dfg, cfg_to_dfg, flatten_dfg, remove_if_unused};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::{self, BasicBlock, Const, OpTag, OpTrait, OpType};
+use crate::types::{ClassicRow, ClassicType, SimpleRow, SimpleType};
+use crate::{type_row, Hugr, Node, Port, Wire};
+
+/// Structurises every [`CFG`] node in the Hugr, see [`structurise_cfg`].
+///
+/// Returns the number of CFG nodes converted, including nested ones.
+///
+/// [`CFG`]: crate::ops::CFG
+pub fn structurise_cfgs(h: &mut Hugr) -> Result<usize, StructuriseError> {
+    let cfgs = h
+        .nodes()
+        .filter(|n| h.get_optype(*n).tag() == OpTag::Cfg)
+        .collect_vec();
+    let mut converted = 0;
+    for cfg in cfgs {
+        // Nested CFGs have already been converted with their ancestor.
+        if h.get_optype(cfg).tag() == OpTag::Cfg {
+            converted += structurise_cfg(h, cfg)?;
+        }
+    }
+    Ok(converted)
+}
+
+/// Converts a [`CFG`] node, along with any CFG nested inside it, into a
+/// [`DFG`] node containing structured control flow.
+///
+/// The node is converted in place, keeping its edges. When the control flow
+/// cannot be structured, the Hugr remains valid and equivalent to the
+/// original, with the CFG partially structured. If building the structured
+/// replacement of a pattern fails, [`StructuriseError::Build`] is returned and
+/// the Hugr may be left invalid.
+///
+/// Returns the number of CFG nodes converted, including nested ones.
+///
+/// [`CFG`]: crate::ops::CFG
+/// [`DFG`]: crate::ops::DFG
+pub fn structurise_cfg(h: &mut Hugr, cfg: Node) -> Result<usize, StructuriseError> {
+    let optype = h.get_optype(cfg);
+    if !matches!(optype, OpType::CFG(_)) {
+        return Err(StructuriseError::NotACfg(cfg, optype.clone()));
+    }
+    remove_unreachable(h, cfg);
+    nest_cfg(h, cfg)?;
+    let mut converted = 0;
+    for nested in nested_cfgs(h, cfg) {
+        converted += structurise_cfg(h, nested)?;
+    }
+    while reduce_once(h, cfg)? {}
+    let region = FlatRegionView::new(h, cfg);
+    let view = SimpleCfgView::new(&region);
+    let entry = view.entry_node();
+    if view.successors(entry).exactly_one().ok() != Some(view.exit_node()) {
+        let blocks = h
+            .children(cfg)
+            .filter(|n| h.get_optype(*n).tag() == OpTag::BasicBlock)
+            .collect_vec();
+        return Err(match is_reducible(&view) {
+            true => StructuriseError::Unstructured(cfg, blocks),
+            false => StructuriseError::Irreducible(cfg, blocks),
+        });
+    }
+    finish_cfg(h, cfg)?;
+    Ok(converted + 1)
+}
+
+/// Error from structurising a CFG.
+#[derive(Debug, Error)]
+pub enum StructuriseError {
+    /// The given node is not a CFG.
+    #[error("Node {0:?} is not a CFG (found {1:?})")]
+    NotACfg(Node, OpType),
+    /// The SESE regions of the CFG could not be nested.
+    #[error("Could not nest the regions of the CFG: {0}")]
+    NestError(#[from] NestCfgError),
+    /// The control flow between the remaining blocks of the CFG is irreducible.
+    #[error("CFG {0:?} has irreducible control flow between blocks {1:?}")]
+    Irreducible(Node, Vec<Node>),
+    /// The control flow between the remaining blocks of the CFG is reducible,
+    /// but does not match any of the supported patterns.
+    #[error("CFG {0:?} has unsupported control flow between blocks {1:?}")]
+    Unstructured(Node, Vec<Node>),
+    /// Building the structured replacement of a pattern failed.
+    #[error("Could not build structured control flow: {0}")]
+    Build(Box<BuildError>),
+}
+
+impl From<BuildError> for StructuriseError {
+    fn from(e: BuildError) -> Self {
+        Self::Build(Box::new(e))
+    }
+}
+
+/// Whether the control flow of a CFG is reducible, i.e. every loop has a
+/// single entry.
+///
+/// The nodes reachable from the entry are collapsed by repeatedly removing
+/// self-loops and merging nodes with a unique predecessor into it; the CFG is
+/// reducible if a single node remains.
+pub fn is_reducible<T: Copy + Eq + Hash>(cfg: &impl CfgView<T>) -> bool {
+    let mut succs: HashMap<T, HashSet<T>> = HashMap::new();
+    let mut pending = vec![cfg.entry_node()];
+    while let Some(n) = pending.pop() {
+        succs.entry(n).or_insert_with(|| {
+            let ss: HashSet<T> = cfg.successors(n).collect();
+            pending.extend(ss.iter().copied());
+            ss
+        });
+    }
+    let mut preds: HashMap<T, HashSet<T>> = succs.keys().map(|n| (*n, HashSet::new())).collect();
+    for (n, ss) in succs.iter() {
+        for s in ss {
+            preds.get_mut(s).unwrap().insert(*n);
+        }
+    }
+    loop {
+        for (n, ss) in succs.iter_mut() {
+            if ss.remove(n) {
+                preds.get_mut(n).unwrap().remove(n);
+            }
+        }
+        let Some(n) = preds
+            .iter()
+            .find(|(n, ps)| **n != cfg.entry_node() && ps.len() == 1)
+            .map(|(n, _)| *n)
+        else {
+            break;
+        };
+        let p = preds.remove(&n).unwrap().into_iter().next().unwrap();
+        let ss = succs.remove(&n).unwrap();
+        succs.get_mut(&p).unwrap().remove(&n);
+        for s in ss {
+            let s_preds = preds.get_mut(&s).unwrap();
+            s_preds.remove(&n);
+            s_preds.insert(p);
+            succs.get_mut(&p).unwrap().insert(s);
+        }
+    }
+    succs.len() == 1
+}
+
+/// The CFG nodes nested in the blocks of `cfg`, excluding those nested
+/// further inside them.
+fn nested_cfgs(h: &Hugr, cfg: Node) -> Vec<Node> {
+    let mut nested = Vec::new();
+    let mut pending = h.children(cfg).collect_vec();
+    while let Some(n) = pending.pop() {
+        match h.get_optype(n).tag() {
+            OpTag::Cfg => nested.push(n),
+            _ => pending.extend(h.children(n)),
+        }
+    }
+    nested
+}
+
+/// Applies the first applicable pattern to a block of the CFG, returning
+/// whether any was found.
+fn reduce_once(h: &mut Hugr, cfg: Node) -> Result<bool, BuildError> {
+    let blocks = h
+        .children(cfg)
+        .filter(|n| h.get_optype(*n).tag() == OpTag::BasicBlock)
+        .collect_vec();
+    for &block in &blocks {
+        let merge = MergeBasicBlocks::new(block);
+        if merge.verify(h).is_ok() {
+            h.apply_rewrite(merge).expect("Verified rewrite failed");
+            return Ok(true);
+        }
+    }
+    for &block in &blocks {
+        if reduce_loop(h, block)? || reduce_branch(h, block)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Replaces a block branching to blocks that all lead to a common join block
+/// by a block containing a [`Conditional`](crate::ops::Conditional).
+fn reduce_branch(h: &mut Hugr, block: Node) -> Result<bool, BuildError> {
+    let targets = successors(h, block);
+    let folded = targets
+        .iter()
+        .map(|t| single_entry_block(h, block, *t))
+        .collect_vec();
+    let joins = targets
+        .iter()
+        .zip(&folded)
+        .map(|(t, f)| f.unwrap_or(*t))
+        .unique()
+        .collect_vec();
+    let [join] = joins[..] else {
+        return Ok(false);
+    };
+    if join == block || (targets.len() < 2 && folded[0].is_none()) {
+        return Ok(false);
+    }
+
+    let (inputs, variants, other_outputs) = dataflow_block(h, block);
+    let join_inputs = block_inputs(h, join);
+    let new_block = replace_block(h, block, &[], inputs, join_inputs.clone());
+    let bodies = targets
+        .iter()
+        .zip(&folded)
+        .map(|(t, f)| f.map(|_| (*t, dataflow_block(h, *t).1.remove(0))))
+        .collect_vec();
+    for (body, _) in bodies.iter().flatten() {
+        block_to_dfg(h, *body);
+    }
+    block_to_dfg(h, block);
+
+    let mut b = DFGBuilder::from_existing(h, new_block);
+    let inputs = b.input_wires().collect_vec();
+    let (pred, outputs) = adopt(&mut b, block, inputs);
+    let mut cond_b = b.conditional_builder(
+        (variants, pred),
+        other_outputs.iter().cloned().zip(outputs),
+        join_inputs,
+    )?;
+    for (i, body) in bodies.iter().enumerate() {
+        let mut case_b = cond_b.case_builder(i)?;
+        let mut outputs = case_b.input_wires().collect_vec();
+        if let Some((body, row)) = body {
+            outputs = adopt_and_unpack(&mut case_b, *body, outputs, row.clone())?;
+        }
+        case_b.finish_with_outputs(outputs)?;
+    }
+    let outputs = cond_b.finish_sub_container()?.outputs();
+    let unit = b.add_load_const(Const::simple_unary_predicate())?;
+    b.set_outputs([unit].into_iter().chain(outputs))?;
+
+    h.connect(new_block, 0, join, 0).unwrap();
+    flatten_bodies(
+        h,
+        bodies.into_iter().flatten().map(|(b, _)| b).chain([block]),
+    );
+    Ok(true)
+}
+
+/// Replaces a block looping back to itself, directly or through blocks
+/// entered only from it, by a block containing a
+/// [`TailLoop`](crate::ops::TailLoop).
+///
+/// The values passed to the exit block are split into the classical values
+/// only output by the loop, and the values common to the inputs and outputs of
+/// the loop. The latter must include any linear value.
+fn reduce_loop(h: &mut Hugr, header: Node) -> Result<bool, BuildError> {
+    let targets = successors(h, header);
+    let latches = targets
+        .iter()
+        .map(|t| (*t != header && single_entry_block(h, header, *t) == Some(header)).then_some(*t))
+        .collect_vec();
+    let exits = targets
+        .iter()
+        .zip(&latches)
+        .filter(|(t, l)| **t != header && l.is_none())
+        .map(|(t, _)| *t)
+        .unique()
+        .collect_vec();
+    let [exit] = exits[..] else {
+        return Ok(false);
+    };
+    if targets.iter().all(|t| *t == exit) {
+        return Ok(false);
+    }
+
+    let (inputs, variants, other_outputs) = dataflow_block(h, header);
+    let exit_inputs = block_inputs(h, exit);
+    let n_rest = inputs[..]
+        .iter()
+        .rev()
+        .zip(exit_inputs[..].iter().rev())
+        .take_while(|(t1, t2)| t1 == t2)
+        .count();
+    let rest: SimpleRow = inputs[inputs.len() - n_rest..].to_vec().into();
+    let (Some(just_inputs), Some(just_outputs)) = (
+        classic_row(&inputs[..inputs.len() - n_rest]),
+        classic_row(&exit_inputs[..exit_inputs.len() - n_rest]),
+    ) else {
+        return Ok(false);
+    };
+
+    let latches = latches
+        .into_iter()
+        .map(|l| l.map(|l| (l, dataflow_block(h, l).1.remove(0))))
+        .collect_vec();
+    let latch_blocks = latches.iter().flatten().map(|(l, _)| *l).collect_vec();
+    let new_block = replace_block(h, header, &latch_blocks, inputs, exit_inputs);
+    for body in latch_blocks.iter().chain([&header]) {
+        block_to_dfg(h, *body);
+    }
+
+    let mut b = DFGBuilder::from_existing(h, new_block);
+    let inputs = b.input_wires().collect_vec();
+    let (just_input_wires, rest_wires) = inputs.split_at(just_inputs.len());
+    let mut loop_b = b.tail_loop_builder(
+        just_inputs
+            .iter()
+            .cloned()
+            .zip(just_input_wires.iter().copied()),
+        rest.iter().cloned().zip(rest_wires.iter().copied()),
+        just_outputs.clone(),
+    )?;
+    let loop_op = loop_b.loop_signature()?.clone();
+    let inputs = loop_b.input_wires().collect_vec();
+    let (pred, outputs) = adopt(&mut loop_b, header, inputs);
+    let mut cond_b = loop_b.conditional_builder(
+        (variants, pred),
+        other_outputs.iter().cloned().zip(outputs),
+        loop_op.body_output_row(),
+    )?;
+    for (i, target) in targets.iter().enumerate() {
+        let mut case_b = cond_b.case_builder(i)?;
+        let mut values = case_b.input_wires().collect_vec();
+        if let Some((latch, row)) = &latches[i] {
+            values = adopt_and_unpack(&mut case_b, *latch, values, row.clone())?;
+        }
+        let rest_values = values.split_off(values.len() - n_rest);
+        let loop_pred = match *target == exit {
+            true => case_b.make_break(loop_op.clone(), values)?,
+            false => case_b.make_continue(loop_op.clone(), values)?,
+        };
+        case_b.finish_with_outputs([loop_pred].into_iter().chain(rest_values))?;
+    }
+    let mut outputs = cond_b.finish_sub_container()?.outputs();
+    let loop_pred = outputs.next().unwrap();
+    let outputs = loop_b.finish_with_outputs(loop_pred, outputs)?.outputs();
+    let unit = b.add_load_const(Const::simple_unary_predicate())?;
+    b.set_outputs([unit].into_iter().chain(outputs))?;
+
+    h.connect(new_block, 0, exit, 0).unwrap();
+    flatten_bodies(h, latch_blocks.into_iter().chain([header]));
+    Ok(true)
+}
+
+/// Replaces the CFG, once reduced to an entry block branching to the exit
+/// block, by a DFG.
+fn finish_cfg(h: &mut Hugr, cfg: Node) -> Result<(), BuildError> {
+    let (entry, exit) = h.children(cfg).take(2).collect_tuple().unwrap();
+    let OpType::CFG(ops::CFG { inputs, outputs }) = h.get_optype(cfg).clone() else {
+        panic!("Not a CFG")
+    };
+    let row = dataflow_block(h, entry).1.remove(0);
+    h.remove_node(exit).unwrap();
+    block_to_dfg(h, entry);
+    cfg_to_dfg(h, cfg);
+    let inp = h
+        .add_op_with_parent(cfg, ops::Input { types: inputs })
+        .unwrap();
+    let out = h
+        .add_op_with_parent(cfg, ops::Output { types: outputs })
+        .unwrap();
+    h.move_before_sibling(inp, entry).unwrap();
+    h.move_after_sibling(out, inp).unwrap();
+
+    let mut b = DFGBuilder::from_existing(h, cfg);
+    let inputs = b.input_wires().collect_vec();
+    let outputs = adopt_and_unpack(&mut b, entry, inputs, row)?;
+    b.set_outputs(outputs)?;
+    flatten_bodies(h, [entry]);
+    Ok(())
+}
+
+/// Whether `target` is a block only entered from `block` and with a single
+/// successor, returning that successor.
+fn single_entry_block(h: &Hugr, block: Node, target: Node) -> Option<Node> {
+    if target == block
+        || h.get_optype(target).tag() != OpTag::BasicBlock
+        || h.children(h.get_parent(target)?).next() == Some(target)
+        || h.linked_ports(target, Port::new_incoming(0)).count() != 1
+    {
+        return None;
+    }
+    successors(h, target)
+        .into_iter()
+        .exactly_one()
+        .ok()
+        .filter(|s| *s != target)
+}
+
+/// The targets of each of the successor ports of a dataflow block.
+fn successors(h: &Hugr, block: Node) -> Vec<Node> {
+    if h.get_optype(block).tag() != OpTag::BasicBlock {
+        return vec![];
+    }
+    h.node_outputs(block)
+        .filter_map(|p| h.linked_ports(block, p).next().map(|(n, _)| n))
+        .collect()
+}
+
+/// The inputs, predicate variants and other outputs of a dataflow block.
+fn dataflow_block(h: &Hugr, block: Node) -> (SimpleRow, Vec<ClassicRow>, SimpleRow) {
+    let OpType::BasicBlock(BasicBlock::DFB {
+        inputs,
+        predicate_variants,
+        other_outputs,
+    }) = h.get_optype(block)
+    else {
+        panic!("Not a dataflow block")
+    };
+    (
+        inputs.clone(),
+        predicate_variants.clone(),
+        other_outputs.clone(),
+    )
+}
+
+/// The inputs of a dataflow or exit block.
+fn block_inputs(h: &Hugr, block: Node) -> SimpleRow {
+    let OpType::BasicBlock(op) = h.get_optype(block) else {
+        panic!("Not a basic block")
+    };
+    op.dataflow_input().clone()
+}
+
+/// The row of classical types, if all the types are classical.
+fn classic_row(types: &[SimpleType]) -> Option<ClassicRow> {
+    types
+        .iter()
+        .map(|t| match t {
+            SimpleType::Classic(t) => Some(t.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<ClassicType>>>()
+        .map(Into::into)
+}
+
+/// Adds an empty block with a single successor, taking over the predecessors
+/// of `block` other than `block` itself and the `internal` blocks.
+fn replace_block(
+    h: &mut Hugr,
+    block: Node,
+    internal: &[Node],
+    inputs: SimpleRow,
+    other_outputs: SimpleRow,
+) -> Node {
+    let cfg = h.get_parent(block).unwrap();
+    let mut outputs = vec![SimpleType::new_simple_predicate(1)];
+    outputs.extend(other_outputs.iter().cloned());
+    let new_block = h
+        .add_op_with_parent(
+            cfg,
+            BasicBlock::DFB {
+                inputs: inputs.clone(),
+                predicate_variants: vec![type_row![]],
+                other_outputs,
+            },
+        )
+        .unwrap();
+    h.add_op_with_parent(new_block, ops::Input { types: inputs })
+        .unwrap();
+    h.add_op_with_parent(
+        new_block,
+        ops::Output {
+            types: outputs.into(),
+        },
+    )
+    .unwrap();
+
+    for (pred, port) in h.linked_ports(block, Port::new_incoming(0)).collect_vec() {
+        if pred != block && !internal.contains(&pred) {
+            h.disconnect(pred, port).unwrap();
+            h.connect(pred, port.index(), new_block, 0).unwrap();
+        }
+    }
+    if h.children(cfg).next() == Some(block) {
+        h.move_before_sibling(new_block, block).unwrap();
+    }
+    new_block
+}
+
+/// Moves a node into the container of the builder, connecting its inputs to
+/// `inputs`. Returns the first output of the node and the remaining ones.
+fn adopt(b: &mut impl Dataflow, node: Node, inputs: Vec<Wire>) -> (Wire, Vec<Wire>) {
+    let parent = b.container_node();
+    let [inp, _] = b.io();
+    let h = b.hugr_mut();
+    h.set_parent(node, parent).unwrap();
+    if inputs.is_empty() {
+        h.add_other_edge(inp, node).unwrap();
+    }
+    for (p, wire) in inputs.into_iter().enumerate() {
+        h.connect(wire.node(), wire.source().index(), node, p)
+            .unwrap();
+    }
+    let num_outputs = h.get_optype(node).signature().output.len();
+    let mut outputs = (0..num_outputs).map(|p| Wire::new(node, Port::new_outgoing(p)));
+    (outputs.next().unwrap(), outputs.collect())
+}
+
+/// Moves the body of a block with a single successor into the container of the
+/// builder, see [`adopt`], and returns the values it passes to its successor,
+/// given the `row` of values carried by its predicate.
+fn adopt_and_unpack(
+    b: &mut impl Dataflow,
+    body: Node,
+    inputs: Vec<Wire>,
+    row: ClassicRow,
+) -> Result<Vec<Wire>, BuildError> {
+    let (pred, others) = adopt(b, body, inputs);
+    let mut values = unpack_unary(b, pred, row)?;
+    values.extend(others);
+    Ok(values)
+}
+
+/// Extracts the values carried by a predicate with a single variant, using a
+/// [`Conditional`](crate::ops::Conditional) with a single case.
+fn unpack_unary(
+    b: &mut impl Dataflow,
+    pred: Wire,
+    row: ClassicRow,
+) -> Result<Vec<Wire>, BuildError> {
+    if row.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut cond_b = b.conditional_builder(([row.clone()], pred), [], row.map_into())?;
+    let case_b = cond_b.case_builder(0)?;
+    let values = case_b.input_wires();
+    case_b.finish_with_outputs(values)?;
+    Ok(cond_b.finish_sub_container()?.outputs().collect())
+}
+
+/// Flattens the DFGs made from block bodies, removing the predicates computed
+/// for their successors if they are no longer used.
+fn flatten_bodies(h: &mut Hugr, bodies: impl IntoIterator<Item = Node>) {
+    for body in bodies {
+        let out = h.children(body).nth(1).unwrap();
+        let (pred, _) = h.linked_ports(out, Port::new_incoming(0)).next().unwrap();
+        flatten_dfg(h, body);
+        if h.get_optype(pred).tag() != OpTag::Input {
+            remove_if_unused(h, pred);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::algorithm::nest_cfgs::test::{
+        build_cond_then_loop_cfg, build_conditional_in_loop_cfg,
+    };
+    use crate::builder::{CFGBuilder, Container, HugrBuilder};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes()
+            .filter(|n| tag.is_superset(h.get_optype(*n).tag()))
+            .count()
+    }
+
+    #[test]
+    fn structurise_cond_then_loop() -> Result<(), Box<dyn std::error::Error>> {
+        for separate in [false, true] {
+            let (mut h, _, _) = build_cond_then_loop_cfg(separate)?;
+            let root = h.root();
+            assert!(structurise_cfg(&mut h, root)? >= 1);
+            h.validate()?;
+            assert_eq!(h.get_optype(root).tag(), OpTag::Dfg);
+            assert_eq!(count_tag(&h, OpTag::Cfg), 0);
+            assert_eq!(count_tag(&h, OpTag::BasicBlock), 0);
+            assert_eq!(count_tag(&h, OpTag::TailLoop), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn structurise_conditional_in_loop() -> Result<(), Box<dyn std::error::Error>> {
+        for separate in [false, true] {
+            let (mut h, _, _) = build_conditional_in_loop_cfg(separate)?;
+            assert!(structurise_cfgs(&mut h)? >= 1);
+            h.validate()?;
+            assert_eq!(count_tag(&h, OpTag::BasicBlock), 0);
+            assert_eq!(count_tag(&h, OpTag::TailLoop), 1);
+            // The conditional in the loop, and the one deciding whether to
+            // continue the loop.
+            assert_eq!(count_tag(&h, OpTag::Conditional), 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn predicate_payloads() -> Result<(), Box<dyn std::error::Error>> {
+        // The entry passes a value to the exit through either variant of its
+        // predicate.
+        let variants: Vec<ClassicRow> = vec![vec![ClassicType::i64()].into(); 2];
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let mut entry_b = cfg_builder.entry_builder(variants.clone(), type_row![])?;
+        let [n] = entry_b.input_wires_arr();
+        let pred = entry_b.make_predicate(0, variants, [n])?;
+        let entry = entry_b.finish_with_outputs(pred, [])?;
+        let exit = cfg_builder.exit_block();
+        cfg_builder.branch(&entry, 0, &exit)?;
+        cfg_builder.branch(&entry, 1, &exit)?;
+        let mut h = cfg_builder.finish_hugr()?;
+
+        assert_eq!(structurise_cfgs(&mut h)?, 1);
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::BasicBlock), 0);
+        assert_eq!(count_tag(&h, OpTag::Conditional), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn irreducible() -> Result<(), Box<dyn std::error::Error>> {
+        // entry -> a, entry -> b, a -> b, b -> a, b -> exit
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let pred_const = cfg_builder.add_constant(Const::simple_predicate(0, 2))?;
+        let unit_const = cfg_builder.add_constant(Const::simple_unary_predicate())?;
+        let mut entry_b = cfg_builder.simple_entry_builder(type_row![NAT], 2)?;
+        let [n] = entry_b.input_wires_arr();
+        let pred = entry_b.load_const(&pred_const)?;
+        let entry = entry_b.finish_with_outputs(pred, [n])?;
+        let mut blocks = Vec::new();
+        for (konst, n_succs) in [(&unit_const, 1), (&pred_const, 2)] {
+            let mut block_b =
+                cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], n_succs)?;
+            let [n] = block_b.input_wires_arr();
+            let pred = block_b.load_const(konst)?;
+            blocks.push(block_b.finish_with_outputs(pred, [n])?);
+        }
+        let exit = cfg_builder.exit_block();
+        cfg_builder.branch(&entry, 0, &blocks[0])?;
+        cfg_builder.branch(&entry, 1, &blocks[1])?;
+        cfg_builder.branch(&blocks[0], 0, &blocks[1])?;
+        cfg_builder.branch(&blocks[1], 0, &blocks[0])?;
+        cfg_builder.branch(&blocks[1], 1, &exit)?;
+        let mut h = cfg_builder.finish_hugr()?;
+
+        let root = h.root();
+        assert!(!is_reducible(&SimpleCfgView::new(&FlatRegionView::new(
+            &h, root
+        ))));
+        assert_matches!(
+            structurise_cfg(&mut h, root),
+            Err(StructuriseError::Irreducible(cfg, _)) => assert_eq!(cfg, root)
+        );
+        h.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn not_a_cfg() {
+        let mut h = Hugr::default();
+        let root = h.root();
+        assert_matches!(
+            structurise_cfg(&mut h, root),
+            Err(StructuriseError::NotACfg(n, _)) => assert_eq!(n, root)
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Unitaries of circuit-like dataflow regions.
+//!
+//! The unitary of a region whose inputs and outputs are all qubits, such as
+//! one built by a [`CircuitBuilder`], is computed by simulating it on every
+//! computational basis state. This is exponential in the number of qubits, so
+//! it is only meant for checking small rewrites in tests.
+//!
+//! [`CircuitBuilder`]: crate::builder::CircuitBuilder
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::hugr::HugrView;
+use crate::ops::OpTrait;
+use crate::types::SimpleType;
+use crate::Node;
+
+use super::interpret::InterpretError;
+use super::simulate::{region_types, statevector, StateVector};
+
+/// The largest number of qubits of a region whose unitary is computed.
+pub const MAX_QUBITS: usize = 10;
+
+/// The default tolerance of [`Unitary::equal_up_to_phase`], used by
+/// [`equivalent`].
+pub const DEFAULT_TOLERANCE: f64 = 1e-8;
+
+/// The unitary matrix of a quantum circuit.
+///
+/// Qubits are numbered as in a [`StateVector`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unitary {
+    /// The images of the computational basis states.
+    columns: Vec<StateVector>,
+}
+
+impl Unitary {
+    /// The number of qubits the unitary acts on.
+    pub fn n_qubits(&self) -> usize {
+        self.columns.len().trailing_zeros() as usize
+    }
+
+    /// The entry of the matrix in the given row and column.
+    pub fn entry(&self, row: usize, column: usize) -> Complex64 {
+        self.columns[column].amplitudes()[row]
+    }
+
+    /// The image of a computational basis state.
+    pub fn column(&self, column: usize) -> &StateVector {
+        &self.columns[column]
+    }
+
+    /// Whether two unitaries are equal up to a global phase, within the given
+    /// tolerance on their normalised overlap `|tr(U†V)| / 2^n`.
+    pub fn equal_up_to_phase(&self, other: &Self, tolerance: f64) -> bool {
+        if self.columns.len() != other.columns.len() {
+            return false;
+        }
+        let trace: Complex64 = self
+            .columns
+            .iter()
+            .zip(&other.columns)
+            .flat_map(|(a, b)| a.amplitudes().iter().zip(b.amplitudes()))
+            .map(|(a, b)| a.conj() * b)
+            .sum();
+        (1.0 - trace.norm() / self.columns.len() as f64).abs() <= tolerance
+    }
+}
+
+/// Computes the unitary of a region of a Hugr whose inputs and outputs are
+/// all qubits, and which contains no measurements.
+pub fn unitary<H: HugrView>(h: &H, node: Node) -> Result<Unitary, UnitaryError> {
+    let (inputs, outputs) = region_types(h, node)
+        .ok_or_else(|| InterpretError::NotARegion(node, h.get_optype(node).tag()))?;
+    let all_qubits = |row: &[SimpleType]| row.iter().all(|t| *t == SimpleType::Qubit);
+    if !all_qubits(&inputs) || !all_qubits(&outputs) || inputs.len() != outputs.len() {
+        return Err(UnitaryError::NotQubitOnly(node));
+    }
+    let n_qubits = inputs.len();
+    if n_qubits > MAX_QUBITS {
+        return Err(UnitaryError::TooManyQubits { node, n_qubits });
+    }
+    let columns = (0..1 << n_qubits)
+        .map(|i| statevector(h, node, StateVector::basis(n_qubits, i)))
+        .collect::<Result<_, _>>()?;
+    Ok(Unitary { columns })
+}
+
+/// Whether two qubit-only regions, possibly of different Hugrs, implement the
+/// same unitary up to a global phase.
+pub fn equivalent(
+    h1: &impl HugrView,
+    node1: Node,
+    h2: &impl HugrView,
+    node2: Node,
+) -> Result<bool, UnitaryError> {
+    let (u1, u2) = (unitary(h1, node1)?, unitary(h2, node2)?);
+    Ok(u1.equal_up_to_phase(&u2, DEFAULT_TOLERANCE))
+}
+
+/// Errors that can occur when computing a unitary.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UnitaryError {
+    /// The region has inputs or outputs that are not qubits.
+    #[error("Region {0:?} has non-qubit inputs or outputs")]
+    NotQubitOnly(Node),
+    /// The region has too many qubits for its unitary to be computed.
+    #[error("Region {node:?} has {n_qubits} qubits, more than the maximum of {MAX_QUBITS}")]
+    TooManyQubits {
+        /// The region.
+        node: Node,
+        /// Its number of qubits.
+        n_qubits: usize,
+    },
+    /// The simulation of the region failed.
+    #[error(transparent)]
+    Simulation(#[from] InterpretError),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::ops::LeafOp;
+    use crate::types::{AbstractSignature, ClassicType};
+    use crate::{type_row, Hugr};
+
+    const QB: SimpleType = SimpleType::Qubit;
+
+    /// A circuit on two qubits.
+    fn circuit(gates: &[(LeafOp, &[usize])]) -> Result<Hugr, BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB],
+        ))?;
+        let mut circ = b.as_circuit(b.input_wires().collect());
+        for (op, qbs) in gates {
+            circ.append(op.clone(), qbs.iter().copied())?;
+        }
+        let outs = circ.finish();
+        b.finish_hugr_with_outputs(outs)
+    }
+
+    #[test]
+    fn cx_unitary() -> Result<(), Box<dyn std::error::Error>> {
+        let h = circuit(&[(LeafOp::CX, &[0, 1])])?;
+        let u = unitary(&h, h.root())?;
+        assert_eq!(u.n_qubits(), 2);
+        // Control qubit 0 is the least significant bit.
+        for (col, row) in [(0, 0), (1, 3), (2, 2), (3, 1)] {
+            assert_eq!(u.entry(row, col), Complex64::new(1.0, 0.0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn equivalence() -> Result<(), Box<dyn std::error::Error>> {
+        let swap_cx = circuit(&[
+            (LeafOp::H, &[0]),
+            (LeafOp::H, &[1]),
+            (LeafOp::CX, &[0, 1]),
+            (LeafOp::H, &[0]),
+            (LeafOp::H, &[1]),
+        ])?;
+        let cx = circuit(&[(LeafOp::CX, &[1, 0])])?;
+        assert!(equivalent(&swap_cx, swap_cx.root(), &cx, cx.root())?);
+
+        let phased = circuit(&[(LeafOp::S, &[0]), (LeafOp::Z, &[0]), (LeafOp::S, &[0])])?;
+        let identity = circuit(&[])?;
+        assert!(equivalent(
+            &phased,
+            phased.root(),
+            &identity,
+            identity.root()
+        )?);
+        assert!(!equivalent(&cx, cx.root(), &identity, identity.root())?);
+
+        let t = circuit(&[(LeafOp::T, &[1])])?;
+        let tadj = circuit(&[(LeafOp::Tadj, &[1])])?;
+        assert!(!equivalent(&t, t.root(), &tadj, tadj.root())?);
+        Ok(())
+    }
+
+    #[test]
+    fn not_qubit_only() -> Result<(), Box<dyn std::error::Error>> {
+        let bit = SimpleType::Classic(ClassicType::bit());
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(type_row![QB], vec![QB, bit]))?;
+        let [q] = b.input_wires_arr();
+        let measure = b.add_dataflow_op(LeafOp::Measure, [q])?;
+        let h = b.finish_hugr_with_outputs(measure.outputs())?;
+        assert_eq!(
+            unitary(&h, h.root()),
+            Err(UnitaryError::NotQubitOnly(h.root()))
+        );
+        Ok(())
+    }
+}
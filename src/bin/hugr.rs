@@ -0,0 +1,565 @@
+//! Command-line tool for validating, converting and rendering HUGR files.
+//!
+//! Built with the `cli` feature. Run `hugr help` for the list of commands.
+//! Files may be given as `-` to read from stdin or write to stdout.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use thiserror::Error;
+
+use hugr::hugr::render::{self, RenderConfig};
+use hugr::hugr::serialize::SerializationVersion;
+use hugr::hugr::validate::Diagnostics;
+use hugr::ops::custom::OpaqueOp;
+use hugr::ops::{LeafOp, OpName, OpTrait, OpType};
+use hugr::resource::ResourceSet;
+use hugr::{Hugr, HugrView};
+
+/// Validate, convert and render HUGR files.
+#[derive(Debug, Parser)]
+#[command(name = "hugr", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Check that HUGR files are well formed and valid, reporting every error
+    /// found along with the path to the nodes involved.
+    Validate {
+        /// The files to validate.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Convert a HUGR file to another format or version of the format.
+    Convert {
+        /// The file to convert.
+        input: PathBuf,
+        /// The file to write.
+        output: PathBuf,
+        #[command(flatten)]
+        input_args: InputArgs,
+        /// The format of the output. Defaults to the one matching the
+        /// extension of the output file, or JSON.
+        #[arg(long, value_enum)]
+        to: Option<Format>,
+        /// The version of the serialization format to write.
+        #[arg(long, value_enum, default_value_t = Version::V1)]
+        version: Version,
+    },
+    /// Render a HUGR as a graphviz dot graph.
+    Dot(RenderArgs),
+    /// Render a HUGR as a Mermaid flowchart.
+    Mermaid(RenderArgs),
+    /// Print the number of nodes for each operation and resource.
+    Stats {
+        /// The file to inspect.
+        input: PathBuf,
+        #[command(flatten)]
+        input_args: InputArgs,
+        /// Print the statistics as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct InputArgs {
+    /// The format of the input. Detected from its contents by default.
+    #[arg(long, value_enum)]
+    from: Option<Format>,
+}
+
+#[derive(Debug, Args)]
+struct RenderArgs {
+    /// The file to render.
+    input: PathBuf,
+    #[command(flatten)]
+    input_args: InputArgs,
+    /// Write the output to a file instead of stdout.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Hide the Input and Output nodes of dataflow regions.
+    #[arg(long)]
+    hide_io: bool,
+    /// Hide the edges between Order ports.
+    #[arg(long)]
+    hide_order_edges: bool,
+    /// Do not label ports with their types.
+    #[arg(long)]
+    no_port_types: bool,
+    /// Do not fill nodes with a colour depending on their operation.
+    #[arg(long)]
+    no_colors: bool,
+    /// Only draw the nodes up to this depth below the root.
+    #[arg(long)]
+    max_depth: Option<usize>,
+}
+
+impl RenderArgs {
+    fn config(&self) -> RenderConfig {
+        RenderConfig {
+            port_types: !self.no_port_types,
+            tag_colors: !self.no_colors,
+            hide_io: self.hide_io,
+            hide_order_edges: self.hide_order_edges,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+/// A serialization format of HUGR files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// JSON serialization.
+    Json,
+    /// MessagePack serialization.
+    Msgpack,
+    /// Compact binary format.
+    Binary,
+    /// Human-readable textual format.
+    Text,
+}
+
+/// A version of the serialization format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Version {
+    V0,
+    V1,
+}
+
+impl From<Version> for SerializationVersion {
+    fn from(version: Version) -> Self {
+        match version {
+            Version::V0 => SerializationVersion::V0,
+            Version::V1 => SerializationVersion::V1,
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "JSON",
+            Format::Msgpack => "msgpack",
+            Format::Binary => "binary",
+            Format::Text => "text",
+        })
+    }
+}
+
+type BoxedError = Box<dyn Error + Send + Sync>;
+
+/// Errors reported by the command-line tool.
+#[derive(Debug, Error)]
+enum CliError {
+    /// A file could not be read.
+    #[error("Could not read {}", .0.display())]
+    Read(PathBuf, #[source] io::Error),
+    /// A file could not be written.
+    #[error("Could not write {}", .0.display())]
+    Write(PathBuf, #[source] io::Error),
+    /// A file is not a HUGR in the expected format.
+    #[error("Could not load {} as {format}", .path.display())]
+    Load {
+        /// The file being loaded.
+        path: PathBuf,
+        /// The format of the file.
+        format: Format,
+        /// The decoding error.
+        #[source]
+        source: BoxedError,
+    },
+    /// The HUGR could not be encoded in the requested format.
+    #[error("Could not encode the HUGR as {format}")]
+    Encode {
+        /// The requested format.
+        format: Format,
+        /// The encoding error.
+        #[source]
+        source: BoxedError,
+    },
+    /// Old versions of the format can only be written in serde formats.
+    #[error("Version {version:?} of the format cannot be written as {format}")]
+    UnsupportedVersion {
+        /// The requested version.
+        version: Version,
+        /// The requested format.
+        format: Format,
+    },
+    /// A HUGR is not valid.
+    #[error("{} is not a valid HUGR\n\n{1}", .0.display())]
+    Invalid(PathBuf, Diagnostics),
+}
+
+impl Format {
+    /// Returns the format matching the extension of a file.
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "msgpack" | "mpk" => Some(Format::Msgpack),
+            "bin" => Some(Format::Binary),
+            "txt" => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of a serialized HUGR from its contents.
+    fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"HUGR") {
+            return Format::Binary;
+        }
+        match data.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => Format::Json,
+            Some(b'%') | Some(b'/') => Format::Text,
+            _ => Format::Msgpack,
+        }
+    }
+
+    fn decode(self, data: &[u8]) -> Result<Hugr, BoxedError> {
+        Ok(match self {
+            Format::Json => serde_json::from_slice(data)?,
+            Format::Msgpack => rmp_serde::from_slice(data)?,
+            Format::Binary => Hugr::read_binary(data)?,
+            Format::Text => Hugr::from_text(std::str::from_utf8(data)?)?,
+        })
+    }
+
+    fn encode(self, hugr: &Hugr, version: Version) -> Result<Vec<u8>, CliError> {
+        let encode = || -> Result<Vec<u8>, BoxedError> {
+            let mut data = Vec::new();
+            match self {
+                Format::Json => {
+                    let mut serializer = serde_json::Serializer::new(&mut data);
+                    hugr.serialize_version(version.into(), &mut serializer)?;
+                }
+                Format::Msgpack => {
+                    let mut serializer = rmp_serde::Serializer::new(&mut data).with_struct_map();
+                    hugr.serialize_version(version.into(), &mut serializer)?;
+                }
+                Format::Binary => hugr.write_binary(&mut data)?,
+                Format::Text => data = hugr.to_text()?.into_bytes(),
+            }
+            Ok(data)
+        };
+        if version != Version::V1 && matches!(self, Format::Binary | Format::Text) {
+            return Err(CliError::UnsupportedVersion {
+                version,
+                format: self,
+            });
+        }
+        encode().map_err(|source| CliError::Encode {
+            format: self,
+            source,
+        })
+    }
+}
+
+fn is_std_stream(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CliError> {
+    let result = if is_std_stream(path) {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        std::fs::read(path)
+    };
+    result.map_err(|e| CliError::Read(path.to_owned(), e))
+}
+
+fn write(path: &Path, data: &[u8]) -> Result<(), CliError> {
+    let result = if is_std_stream(path) {
+        io::stdout().write_all(data)
+    } else {
+        std::fs::write(path, data)
+    };
+    result.map_err(|e| CliError::Write(path.to_owned(), e))
+}
+
+/// Loads a HUGR from a file, detecting its format unless it is given.
+fn load(path: &Path, args: &InputArgs) -> Result<Hugr, CliError> {
+    let data = read(path)?;
+    let format = args.from.unwrap_or_else(|| Format::detect(&data));
+    format.decode(&data).map_err(|source| CliError::Load {
+        path: path.to_owned(),
+        format,
+        source,
+    })
+}
+
+/// Prints an error and the chain of its sources to stderr.
+fn report(err: &dyn Error) {
+    eprintln!("error: {err}");
+    let mut source = err.source();
+    while let Some(err) = source {
+        eprintln!("  caused by: {err}");
+        source = err.source();
+    }
+}
+
+/// Validates each file, reporting the errors. Returns whether they were all
+/// valid.
+fn validate(files: &[PathBuf], args: &InputArgs) -> bool {
+    let mut all_valid = true;
+    for path in files {
+        let result = load(path, args).and_then(|hugr| {
+            hugr.validate_all()
+                .map_err(|e| CliError::Invalid(path.clone(), e))?;
+            Ok(hugr)
+        });
+        match result {
+            Ok(hugr) => println!(
+                "{}: valid ({} nodes, {} edges)",
+                path.display(),
+                hugr.node_count(),
+                hugr.edge_count()
+            ),
+            Err(err) => {
+                report(&err);
+                all_valid = false;
+            }
+        }
+    }
+    all_valid
+}
+
+/// The resources required by an operation, including the resource defining
+/// it if it is an extension operation.
+fn op_resources(op: &OpType) -> ResourceSet {
+    let mut resources = op.signature().resource_reqs;
+    if let OpType::LeafOp(LeafOp::CustomOp(ext)) = op {
+        resources.insert(OpaqueOp::from(ext.clone()).resource());
+    }
+    resources
+}
+
+/// Node counts of a HUGR.
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+struct Stats {
+    nodes: usize,
+    edges: usize,
+    /// The number of nodes with each operation name.
+    operations: BTreeMap<String, usize>,
+    /// The number of nodes whose operation requires each resource.
+    resources: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    fn new(hugr: &Hugr) -> Self {
+        let mut stats = Stats {
+            nodes: hugr.node_count(),
+            edges: hugr.edge_count(),
+            ..Default::default()
+        };
+        for node in hugr.nodes() {
+            let op = hugr.get_optype(node);
+            *stats.operations.entry(op.name().to_string()).or_default() += 1;
+            for resource in op_resources(op).iter() {
+                *stats.resources.entry(resource.to_string()).or_default() += 1;
+            }
+        }
+        stats
+    }
+
+    /// Writes a table of counts, in decreasing order.
+    fn write_counts(
+        out: &mut impl Write,
+        title: &str,
+        counts: &BTreeMap<String, usize>,
+    ) -> io::Result<()> {
+        writeln!(out, "{title}:")?;
+        let width = counts.keys().map(String::len).max().unwrap_or(0);
+        let mut counts: Vec<_> = counts.iter().collect();
+        counts.sort_by_key(|&(name, count)| (std::cmp::Reverse(count), name));
+        for (name, count) in counts {
+            writeln!(out, "  {name:width$}  {count}")?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "nodes: {}", self.nodes)?;
+        writeln!(out, "edges: {}", self.edges)?;
+        Self::write_counts(out, "operations", &self.operations)?;
+        if !self.resources.is_empty() {
+            Self::write_counts(out, "resources", &self.resources)?;
+        }
+        Ok(())
+    }
+}
+
+fn run(cli: Cli) -> Result<bool, CliError> {
+    let stdout = PathBuf::from("-");
+    match cli.command {
+        Command::Validate { files, input } => return Ok(validate(&files, &input)),
+        Command::Convert {
+            input,
+            output,
+            input_args,
+            to,
+            version,
+        } => {
+            let hugr = load(&input, &input_args)?;
+            let format = to
+                .or_else(|| Format::from_extension(&output))
+                .unwrap_or(Format::Json);
+            write(&output, &format.encode(&hugr, version)?)?;
+        }
+        Command::Dot(args) => {
+            let hugr = load(&args.input, &args.input_args)?;
+            let dot = render::dot_string(&hugr, &args.config());
+            write(args.output.as_ref().unwrap_or(&stdout), dot.as_bytes())?;
+        }
+        Command::Mermaid(args) => {
+            let hugr = load(&args.input, &args.input_args)?;
+            let mermaid = render::mermaid_string(&hugr, &args.config());
+            write(args.output.as_ref().unwrap_or(&stdout), mermaid.as_bytes())?;
+        }
+        Command::Stats {
+            input,
+            input_args,
+            json,
+        } => {
+            let stats = Stats::new(&load(&input, &input_args)?);
+            let mut out = Vec::new();
+            if json {
+                serde_json::to_writer_pretty(&mut out, &stats).unwrap();
+                out.push(b'\n');
+            } else {
+                stats.write(&mut out).unwrap();
+            }
+            write(&stdout, &out)?;
+        }
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            report(&err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::CommandFactory;
+    use hugr::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use hugr::types::{AbstractSignature, SimpleType};
+
+    use super::*;
+
+    fn circuit() -> Result<Hugr, BuildError> {
+        let qb = SimpleType::Qubit;
+        let mut dfg = DFGBuilder::new(AbstractSignature::new_df(vec![qb.clone(); 2], vec![qb; 2]))?;
+        let wires = dfg.input_wires().collect();
+        let mut circ = dfg.as_circuit(wires);
+        circ.append(LeafOp::H, [0])?
+            .append(LeafOp::H, [1])?
+            .append(LeafOp::CX, [0, 1])?;
+        let outputs = circ.finish();
+        dfg.finish_hugr_with_outputs(outputs)
+    }
+
+    #[test]
+    fn cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn formats() -> Result<(), Box<dyn Error>> {
+        let hugr = circuit()?;
+        let json = serde_json::to_value(&hugr)?;
+        for format in Format::value_variants() {
+            let data = format.encode(&hugr, Version::V1)?;
+            assert_eq!(Format::detect(&data), *format);
+            let decoded = format.decode(&data).map_err(|e| e as Box<dyn Error>)?;
+            assert_eq!(serde_json::to_value(&decoded)?, json);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn old_version() -> Result<(), Box<dyn Error>> {
+        let hugr = circuit()?;
+        for format in [Format::Json, Format::Msgpack] {
+            let data = format.encode(&hugr, Version::V0)?;
+            let decoded = format.decode(&data).map_err(|e| e as Box<dyn Error>)?;
+            decoded.validate()?;
+            assert_eq!(decoded.node_count(), hugr.node_count());
+        }
+        assert!(matches!(
+            Format::Text.encode(&hugr, Version::V0),
+            Err(CliError::UnsupportedVersion { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn stats() -> Result<(), Box<dyn Error>> {
+        let stats = Stats::new(&circuit()?);
+        assert_eq!(stats.nodes, 6);
+        assert_eq!(stats.operations["H"], 2);
+        assert_eq!(stats.operations["CX"], 1);
+        assert_eq!(stats.operations["DFG"], 1);
+
+        let mut out = Vec::new();
+        stats.write(&mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.starts_with("nodes: 6\n"));
+        assert!(out.contains("\n  H       2\n"), "{out}");
+        Ok(())
+    }
+
+    #[test]
+    fn convert_files() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("hugr-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let json = dir.join("circ.json");
+        let text = dir.join("circ.txt");
+        std::fs::write(&json, serde_json::to_vec(&circuit()?)?)?;
+
+        let cli = Cli::try_parse_from([
+            "hugr",
+            "convert",
+            json.to_str().unwrap(),
+            text.to_str().unwrap(),
+        ])?;
+        assert!(run(cli)?);
+        let hugr = load(&text, &InputArgs { from: None })?;
+        hugr.validate()?;
+
+        let invalid = dir.join("invalid.json");
+        std::fs::write(&invalid, "{}")?;
+        let cli = Cli::try_parse_from(["hugr", "validate", invalid.to_str().unwrap()])?;
+        assert!(!run(cli)?);
+
+        // A well-formed file with unconnected ports.
+        let mut value = serde_json::to_value(circuit()?)?;
+        value["edges"] = serde_json::json!([]);
+        std::fs::write(&invalid, serde_json::to_vec(&value)?)?;
+        let hugr = load(&invalid, &InputArgs { from: None })?;
+        let diagnostics = hugr.validate_all().unwrap_err();
+        assert!(diagnostics.0.len() > 1);
+        let cli = Cli::try_parse_from(["hugr", "validate", invalid.to_str().unwrap()])?;
+        assert!(!run(cli)?);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -577,7 +577,7 @@ pub trait Dataflow: Container {
                 })
             }
         };
-        let const_in_port = signature.output.len();
+        let const_in_port = signature.input.len();
         let op_id = self.add_dataflow_op(ops::Call { signature }, input_wires)?;
         let src_port = self.hugr_mut().num_outputs(function.node()) - 1;
 
@@ -674,21 +674,21 @@ fn wire_up<T: Dataflow + ?Sized>(
             }
 
             let src_parent = src_parent.expect("Node has no parent");
-            let Some(src_sibling) =
-                        iter::successors(dst_parent, |&p| base.get_parent(p))
-                            .tuple_windows()
-                            .find_map(|(ancestor, ancestor_parent)| {
-                                (ancestor_parent == src_parent).then_some(ancestor)
-                            })
-                    else {
-                        let val_err: ValidationError = InterGraphEdgeError::NoRelation {
-                            from: src,
-                            from_offset: Port::new_outgoing(src_port),
-                            to: dst,
-                            to_offset: Port::new_incoming(dst_port),
-                        }.into();
-                        return Err(val_err.into());
-                    };
+            let Some(src_sibling) = iter::successors(dst_parent, |&p| base.get_parent(p))
+                .tuple_windows()
+                .find_map(|(ancestor, ancestor_parent)| {
+                    (ancestor_parent == src_parent).then_some(ancestor)
+                })
+            else {
+                let val_err: ValidationError = InterGraphEdgeError::NoRelation {
+                    from: src,
+                    from_offset: Port::new_outgoing(src_port),
+                    to: dst,
+                    to_offset: Port::new_incoming(dst_port),
+                }
+                .into();
+                return Err(val_err.into());
+            };
 
             // TODO: Avoid adding duplicate edges
             // This should be easy with https://github.com/CQCL-DEV/hugr/issues/130
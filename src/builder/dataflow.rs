@@ -5,7 +5,7 @@ use super::{BuildError, Container, Dataflow, DfgID, FuncID};
 use std::marker::PhantomData;
 
 use crate::hugr::{HugrView, NodeType, ValidationError};
-use crate::ops;
+use crate::ops::{self, OpTrait};
 
 use crate::types::{AbstractSignature, Signature};
 
@@ -75,6 +75,27 @@ impl<T: AsMut<Hugr> + AsRef<Hugr>> DFGBuilder<T> {
     }
 }
 
+impl<'a> DFGBuilder<&'a mut Hugr> {
+    /// Returns a builder adding nodes to an existing dataflow container, whose
+    /// Input and Output nodes are already in place.
+    pub(crate) fn from_existing(base: &'a mut Hugr, parent: Node) -> Self {
+        let [inp, out] = base
+            .children(parent)
+            .take(2)
+            .collect::<Vec<_>>()
+            .try_into()
+            .expect("Dataflow container has no Input and Output nodes");
+        let num_in_wires = base.get_optype(inp).signature().output.len();
+        let num_out_wires = base.get_optype(out).signature().input.len();
+        Self {
+            base,
+            dfg_node: parent,
+            num_in_wires,
+            num_out_wires,
+        }
+    }
+}
+
 impl DFGBuilder<Hugr> {
     /// Begin building a new DFG rooted HUGR.
     ///
@@ -1,3 +1,4 @@
+use crate::ops::handle::NodeHandle;
 use crate::ops::{self, OpType};
 
 use crate::hugr::{view::HugrView, NodeType};
@@ -66,7 +67,12 @@ impl<H: AsMut<Hugr> + AsRef<Hugr>> TailLoopBuilder<H> {
         Self: Sized,
     {
         self.set_outputs(out_variant, rest)?;
-        self.finish_sub_container()
+        // The handle must report the outputs of the loop node, rather than
+        // those of the body.
+        let tail_loop = self.loop_signature()?;
+        let num_outputs = tail_loop.just_outputs.len() + tail_loop.rest.len();
+        let node = self.finish_sub_container()?.node();
+        Ok((node, num_outputs).into())
     }
 }
 
@@ -3,6 +3,7 @@
 mod hugrmut;
 
 pub mod region;
+pub mod render;
 pub mod rewrite;
 pub mod serialize;
 pub mod validate;
@@ -97,6 +98,16 @@ impl NodeType {
     pub fn op_signature(&self) -> AbstractSignature {
         self.op.signature()
     }
+
+    /// The underlying OpType
+    pub fn op(&self) -> &OpType {
+        &self.op
+    }
+
+    /// The input resources of the node, if they have been specified
+    pub fn input_resources(&self) -> Option<&ResourceSet> {
+        self.input_resources.as_ref()
+    }
 }
 
 impl NodeType {
@@ -151,10 +162,12 @@ impl AsMut<Hugr> for Hugr {
     From,
     serde::Serialize,
     serde::Deserialize,
+    schemars::JsonSchema,
 )]
 #[serde(transparent)]
 #[cfg_attr(feature = "pyo3", pyclass)]
 pub struct Node {
+    #[schemars(with = "usize")]
     index: portgraph::NodeIndex,
 }
 
@@ -222,6 +235,14 @@ impl Hugr {
     }
 }
 
+impl Node {
+    /// Returns the index of the node in its HUGR.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.index.index()
+    }
+}
+
 impl Port {
     /// Creates a new port.
     #[inline]
@@ -263,6 +284,7 @@ impl Port {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A DataFlow wire, defined by a Value-kind output port of a node
 // Stores node and offset to output port
+#[cfg_attr(feature = "pyo3", pyclass)]
 pub struct Wire(Node, usize);
 
 impl Wire {
@@ -291,6 +313,7 @@ impl Wire {
 /// Falls back to [`Wire`] if the wire is not linear or if it's not possible to
 /// track the origin.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "pyo3", derive(FromPyObject))]
 pub enum CircuitUnit {
     /// Arbitrary input wire.
     Wire(Wire),
@@ -59,27 +59,29 @@ impl<'g, Base> HugrView for FlatRegionView<'g, Base>
 where
     Base: HugrInternals + HugrView,
 {
-    type Nodes<'a> = iter::Chain<iter::Once<Node>, MapInto<portgraph::hierarchy::Children<'a>, Node>>
+    type Nodes<'a>
+        = iter::Chain<iter::Once<Node>, MapInto<portgraph::hierarchy::Children<'a>, Node>>
     where
         Self: 'a;
 
-    type NodePorts<'a> = MapInto<<FlatRegionGraph<'g, Base> as PortView>::NodePortOffsets<'a>, Port>
+    type NodePorts<'a>
+        = MapInto<<FlatRegionGraph<'g, Base> as PortView>::NodePortOffsets<'a>, Port>
     where
         Self: 'a;
 
-    type Children<'a> = MapInto<portgraph::hierarchy::Children<'a>, Node>
+    type Children<'a>
+        = MapInto<portgraph::hierarchy::Children<'a>, Node>
     where
         Self: 'a;
 
-    type Neighbours<'a> = MapInto<<FlatRegionGraph<'g, Base> as LinkView>::Neighbours<'a>, Node>
+    type Neighbours<'a>
+        = MapInto<<FlatRegionGraph<'g, Base> as LinkView>::Neighbours<'a>, Node>
     where
         Self: 'a;
 
-    type PortLinks<'a> = MapWithCtx<
-        <FlatRegionGraph<'g, Base> as LinkView>::PortLinks<'a>,
-        &'a Self,
-        (Node, Port),
-    > where
+    type PortLinks<'a>
+        = MapWithCtx<<FlatRegionGraph<'g, Base> as LinkView>::PortLinks<'a>, &'a Self, (Node, Port)>
+    where
         Self: 'a;
 
     #[inline]
@@ -213,27 +215,29 @@ impl<'g, Base> HugrView for RegionView<'g, Base>
 where
     Base: HugrInternals + HugrView,
 {
-    type Nodes<'a> = MapInto<<RegionGraph<'g, Base> as PortView>::Nodes<'a>, Node>
+    type Nodes<'a>
+        = MapInto<<RegionGraph<'g, Base> as PortView>::Nodes<'a>, Node>
     where
         Self: 'a;
 
-    type NodePorts<'a> = MapInto<<RegionGraph<'g, Base> as PortView>::NodePortOffsets<'a>, Port>
+    type NodePorts<'a>
+        = MapInto<<RegionGraph<'g, Base> as PortView>::NodePortOffsets<'a>, Port>
     where
         Self: 'a;
 
-    type Children<'a> = MapInto<portgraph::hierarchy::Children<'a>, Node>
+    type Children<'a>
+        = MapInto<portgraph::hierarchy::Children<'a>, Node>
     where
         Self: 'a;
 
-    type Neighbours<'a> = MapInto<<RegionGraph<'g, Base> as LinkView>::Neighbours<'a>, Node>
+    type Neighbours<'a>
+        = MapInto<<RegionGraph<'g, Base> as LinkView>::Neighbours<'a>, Node>
     where
         Self: 'a;
 
-    type PortLinks<'a> = MapWithCtx<
-        <RegionGraph<'g, Base> as LinkView>::PortLinks<'a>,
-        &'a Self,
-        (Node, Port),
-    > where
+    type PortLinks<'a>
+        = MapWithCtx<<RegionGraph<'g, Base> as LinkView>::PortLinks<'a>, &'a Self, (Node, Port)>
+    where
         Self: 'a;
 
     #[inline]
@@ -0,0 +1,448 @@
+//! Configurable rendering of HUGRs as DOT or Mermaid diagrams.
+//!
+//! Unlike [`HugrView::dot_string`], which draws the graph and the hierarchy
+//! side by side, these renderers draw each container node as a cluster
+//! holding its children. Edges to a container are attached to a node for the
+//! container inside its cluster.
+//!
+//! The diagrams are configured with a [`RenderConfig`].
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ops::{OpName, OpTag, OpTrait, OpType};
+use crate::types::EdgeKind;
+use crate::{Direction, Node, Port};
+
+use super::HugrView;
+
+/// Options for rendering a HUGR as a diagram.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderConfig {
+    /// Whether to label ports with their types.
+    pub port_types: bool,
+    /// Whether to fill nodes with a colour depending on their [`OpTag`].
+    pub tag_colors: bool,
+    /// Whether to hide the [`Input`] and [`Output`] nodes of dataflow regions,
+    /// along with their edges.
+    ///
+    /// [`Input`]: crate::ops::Input
+    /// [`Output`]: crate::ops::Output
+    pub hide_io: bool,
+    /// Whether to hide the edges between Order ports.
+    pub hide_order_edges: bool,
+    /// The maximum depth below the root at which nodes are drawn.
+    ///
+    /// Containers at this depth are drawn as single nodes, and edges to their
+    /// descendants are not drawn.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            port_types: true,
+            tag_colors: true,
+            hide_io: false,
+            hide_order_edges: false,
+            max_depth: None,
+        }
+    }
+}
+
+/// Renders the HUGR in the DOT format, with containers drawn as nested
+/// clusters.
+pub fn dot_string<H: HugrView + ?Sized>(h: &H, config: &RenderConfig) -> String {
+    dot_string_highlighted(h, config, |_| None, |_, _| None)
+}
+
+/// Renders the HUGR in the DOT format, as [`dot_string`], drawing the labels
+/// of some nodes and some edges in the given colours.
+pub(crate) fn dot_string_highlighted<H: HugrView + ?Sized>(
+    h: &H,
+    config: &RenderConfig,
+    node_color: impl Fn(Node) -> Option<&'static str>,
+    edge_color: impl Fn((Node, Port), (Node, Port)) -> Option<&'static str>,
+) -> String {
+    let diagram = Diagram::new(h, config);
+    let mut out = String::from("digraph {\n    node [shape=plain]\n");
+    diagram.write_dot_node(&mut out, h.root(), 1, &node_color);
+    for edge in diagram.edges() {
+        let endpoint = |n: Node, port: Port, kind: &Option<EdgeKind>| match kind {
+            Some(EdgeKind::StateOrder) => n.index().to_string(),
+            _ => format!("{}:{}", n.index(), port_id(port)),
+        };
+        let style = match &edge.kind {
+            Some(EdgeKind::StateOrder) => "dotted",
+            _ if h.get_parent(edge.source) != h.get_parent(edge.target) => "dashed",
+            _ => "solid",
+        };
+        let highlight = edge_color(
+            (edge.source, edge.source_port),
+            (edge.target, edge.target_port),
+        )
+        .map_or(String::new(), |color| {
+            format!(" color=\"{color}\" penwidth=2")
+        });
+        writeln!(
+            out,
+            "    {} -> {} [style={style}{highlight}]",
+            endpoint(edge.source, edge.source_port, &edge.kind),
+            endpoint(edge.target, edge.target_port, &edge.kind),
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Renders the HUGR as a Mermaid flowchart, with containers drawn as nested
+/// subgraphs.
+pub fn mermaid_string<H: HugrView + ?Sized>(h: &H, config: &RenderConfig) -> String {
+    let diagram = Diagram::new(h, config);
+    let mut out = String::from("flowchart TB\n");
+    let mut styles = String::new();
+    diagram.write_mermaid_node(&mut out, &mut styles, h.root(), 1);
+    for edge in diagram.edges() {
+        let arrow = match &edge.kind {
+            Some(EdgeKind::StateOrder) => "-.->".to_string(),
+            Some(EdgeKind::Value(ty)) if config.port_types => {
+                format!("-->|\"{}\"|", mermaid_escape(&ty.to_string()))
+            }
+            Some(EdgeKind::Static(ty)) if config.port_types => {
+                format!("-->|\"{}\"|", mermaid_escape(&ty.to_string()))
+            }
+            _ => "-->".to_string(),
+        };
+        writeln!(
+            out,
+            "    n{} {arrow} n{}",
+            edge.source.index(),
+            edge.target.index()
+        )
+        .unwrap();
+    }
+    out.push_str(&styles);
+    out
+}
+
+/// An edge between two drawn nodes.
+struct Edge {
+    source: Node,
+    source_port: Port,
+    target: Node,
+    target_port: Port,
+    kind: Option<EdgeKind>,
+}
+
+/// The nodes and edges of a HUGR that are drawn.
+struct Diagram<'a, H: ?Sized> {
+    h: &'a H,
+    config: &'a RenderConfig,
+    /// The depth of each drawn node below the root.
+    depths: HashMap<Node, usize>,
+}
+
+impl<'a, H: HugrView + ?Sized> Diagram<'a, H> {
+    fn new(h: &'a H, config: &'a RenderConfig) -> Self {
+        let mut depths = HashMap::new();
+        let mut stack = vec![(h.root(), 0)];
+        while let Some((n, depth)) = stack.pop() {
+            depths.insert(n, depth);
+            if config.max_depth.map_or(true, |max| depth < max) {
+                let children = h.children(n).filter(|c| {
+                    let tag = h.get_optype(*c).tag();
+                    !(config.hide_io && matches!(tag, OpTag::Input | OpTag::Output))
+                });
+                stack.extend(children.map(|c| (c, depth + 1)));
+            }
+        }
+        Self { h, config, depths }
+    }
+
+    /// The drawn children of a node.
+    fn children(&self, n: Node) -> Vec<Node> {
+        self.h
+            .children(n)
+            .filter(|c| self.depths.contains_key(c))
+            .collect()
+    }
+
+    /// The edges between drawn nodes, in node order.
+    fn edges(&self) -> Vec<Edge> {
+        let mut nodes: Vec<_> = self.depths.keys().copied().collect();
+        nodes.sort_unstable();
+        let mut edges = vec![];
+        for source in nodes {
+            let optype = self.h.get_optype(source);
+            for source_port in self.h.node_outputs(source) {
+                let kind = optype.port_kind(source_port);
+                if self.config.hide_order_edges && kind == Some(EdgeKind::StateOrder) {
+                    continue;
+                }
+                for (target, target_port) in self.h.linked_ports(source, source_port) {
+                    if self.depths.contains_key(&target) {
+                        edges.push(Edge {
+                            source,
+                            source_port,
+                            target,
+                            target_port,
+                            kind: kind.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    fn label(&self, n: Node) -> String {
+        format!("({}) {}", n.index(), self.h.get_optype(n).name())
+    }
+
+    fn color(&self, n: Node) -> Option<&'static str> {
+        self.config
+            .tag_colors
+            .then(|| tag_color(self.h.get_optype(n)))
+    }
+
+    /// The labels of the ports of a node in a direction, skipping Order ports.
+    fn port_labels(&self, n: Node, dir: Direction) -> Vec<(Port, String)> {
+        let optype = self.h.get_optype(n);
+        self.h
+            .node_ports(n, dir)
+            .filter_map(|port| {
+                let label = match optype.port_kind(port) {
+                    Some(EdgeKind::StateOrder) => return None,
+                    Some(EdgeKind::Value(ty)) if self.config.port_types => {
+                        format!("{}: {ty}", port.index())
+                    }
+                    Some(EdgeKind::Static(ty)) if self.config.port_types => {
+                        format!("{}: {ty}", port.index())
+                    }
+                    _ => port.index().to_string(),
+                };
+                Some((port, label))
+            })
+            .collect()
+    }
+
+    fn write_dot_node(
+        &self,
+        out: &mut String,
+        n: Node,
+        indent: usize,
+        node_color: &dyn Fn(Node) -> Option<&'static str>,
+    ) {
+        let pad = "    ".repeat(indent);
+        let children = self.children(n);
+        if children.is_empty() {
+            let table = self.dot_table(n, node_color(n));
+            writeln!(out, "{pad}{} [label=<{table}>]", n.index()).unwrap();
+            return;
+        }
+
+        writeln!(out, "{pad}subgraph cluster_{} {{", n.index()).unwrap();
+        let label = html_escape::encode_text(&self.label(n)).to_string();
+        match node_color(n) {
+            Some(color) => writeln!(
+                out,
+                "{pad}    label=<<font color=\"{color}\"><b>{label}</b></font>>"
+            )
+            .unwrap(),
+            None => writeln!(out, "{pad}    label=<{label}>").unwrap(),
+        }
+        match self.color(n) {
+            Some(color) => writeln!(
+                out,
+                "{pad}    style=\"rounded,filled\" fillcolor=\"{color}\""
+            )
+            .unwrap(),
+            None => writeln!(out, "{pad}    style=rounded").unwrap(),
+        }
+        // A node for the container itself, for its edges.
+        if self.h.num_inputs(n) + self.h.num_outputs(n) > 0 {
+            let table = self.dot_table(n, node_color(n));
+            writeln!(out, "{pad}    {} [label=<{table}>]", n.index()).unwrap();
+        }
+        for child in children {
+            self.write_dot_node(out, child, indent + 1, node_color);
+        }
+        writeln!(out, "{pad}}}").unwrap();
+    }
+
+    /// An HTML table with the node label between its input and output ports,
+    /// with the label drawn in `label_color` if given.
+    fn dot_table(&self, n: Node, label_color: Option<&str>) -> String {
+        let inputs = self.port_labels(n, Direction::Incoming);
+        let outputs = self.port_labels(n, Direction::Outgoing);
+        // Each port spans as many columns as there are ports on the other side.
+        let width = inputs.len().max(1) * outputs.len().max(1);
+        let port_row = |ports: &[(Port, String)], span: usize| {
+            if ports.is_empty() {
+                return String::new();
+            }
+            let cells: String = ports
+                .iter()
+                .map(|(port, label)| {
+                    format!(
+                        "<td port=\"{}\" colspan=\"{span}\">{}</td>",
+                        port_id(*port),
+                        html_escape::encode_text(label)
+                    )
+                })
+                .collect();
+            format!("<tr>{cells}</tr>")
+        };
+        let bgcolor = self
+            .color(n)
+            .map_or(String::new(), |color| format!(" bgcolor=\"{color}\""));
+        let label = format!("<b>{}</b>", html_escape::encode_text(&self.label(n)));
+        let label = match label_color {
+            Some(color) => format!("<font color=\"{color}\">{label}</font>"),
+            None => label,
+        };
+        format!(
+            "<table border=\"0\" cellborder=\"1\" cellspacing=\"0\"{bgcolor}>{}<tr><td colspan=\"{width}\">{label}</td></tr>{}</table>",
+            port_row(&inputs, outputs.len().max(1)),
+            port_row(&outputs, inputs.len().max(1)),
+        )
+    }
+
+    fn write_mermaid_node(&self, out: &mut String, styles: &mut String, n: Node, indent: usize) {
+        let pad = "    ".repeat(indent);
+        let label = mermaid_escape(&self.label(n));
+        if let Some(color) = self.color(n) {
+            writeln!(styles, "    style n{} fill:{color}", n.index()).unwrap();
+        }
+        let children = self.children(n);
+        if children.is_empty() {
+            writeln!(out, "{pad}n{}[\"{label}\"]", n.index()).unwrap();
+        } else {
+            writeln!(out, "{pad}subgraph n{} [\"{label}\"]", n.index()).unwrap();
+            writeln!(out, "{pad}    direction TB").unwrap();
+            for child in children {
+                self.write_mermaid_node(out, styles, child, indent + 1);
+            }
+            writeln!(out, "{pad}end").unwrap();
+        }
+    }
+}
+
+/// The identifier of a port in the DOT tables.
+fn port_id(port: Port) -> String {
+    match port.direction() {
+        Direction::Incoming => format!("in{}", port.index()),
+        Direction::Outgoing => format!("out{}", port.index()),
+    }
+}
+
+/// Escapes the characters with a special meaning in Mermaid labels.
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+/// The fill colour of a node, depending on the tag of its operation.
+fn tag_color(op: &OpType) -> &'static str {
+    match op.tag() {
+        OpTag::ModuleRoot => "#eeeeee",
+        OpTag::FuncDefn | OpTag::Function => "#cfe2ff",
+        OpTag::Alias => "#e2d9f3",
+        OpTag::Const | OpTag::LoadConst => "#fff3cd",
+        OpTag::Input | OpTag::Output => "#e9ecef",
+        OpTag::FnCall => "#f8d7da",
+        OpTag::Dfg
+        | OpTag::Cfg
+        | OpTag::TailLoop
+        | OpTag::Conditional
+        | OpTag::Case
+        | OpTag::BasicBlock
+        | OpTag::BasicBlockExit => "#d1e7dd",
+        _ => "#ffffff",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::ops::LeafOp;
+    use crate::type_row;
+    use crate::types::{AbstractSignature, SimpleType};
+    use crate::Hugr;
+
+    const QB: SimpleType = SimpleType::Qubit;
+
+    /// A module with a function applying a Hadamard gate in a nested DFG.
+    fn nested_module() -> Result<Hugr, BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let sig = AbstractSignature::new_df(type_row![QB], type_row![QB]);
+        let mut f_build = module_builder.define_function("main", sig.clone().pure())?;
+        let [q] = f_build.input_wires_arr();
+        let mut dfg = f_build.dfg_builder(sig, None, [q])?;
+        let [q] = dfg.input_wires_arr();
+        let h = dfg.add_dataflow_op(LeafOp::H, [q])?;
+        let dfg = dfg.finish_with_outputs(h.outputs())?;
+        f_build.finish_with_outputs(dfg.outputs())?;
+        Ok(module_builder.finish_hugr()?)
+    }
+
+    #[test]
+    fn dot_clusters() -> Result<(), BuildError> {
+        let h = nested_module()?;
+        let dot = dot_string(&h, &RenderConfig::default());
+        assert!(dot.starts_with("digraph {"));
+        // The module, the function and the DFG.
+        assert_eq!(dot.matches("subgraph cluster_").count(), 3);
+        assert!(dot.contains("<td port=\"in0\" colspan=\"1\">0: Qubit</td>"));
+        assert!(dot.contains("bgcolor=\"#cfe2ff\""));
+
+        let config = RenderConfig {
+            port_types: false,
+            tag_colors: false,
+            hide_io: true,
+            ..Default::default()
+        };
+        let dot = dot_string(&h, &config);
+        assert!(!dot.contains("Input") && !dot.contains("Output"));
+        assert!(!dot.contains("bgcolor"));
+        assert!(dot.contains("<td port=\"in0\" colspan=\"1\">0</td>"));
+        // Every edge is connected to an Input or Output node.
+        assert!(!dot.contains(" -> "));
+        Ok(())
+    }
+
+    #[test]
+    fn depth_limit() -> Result<(), BuildError> {
+        let h = nested_module()?;
+        let config = RenderConfig {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let dot = dot_string(&h, &config);
+        assert_eq!(dot.matches("subgraph cluster_").count(), 1);
+        assert!(dot.contains("FuncDefn") && !dot.contains("DFG"));
+        assert!(!dot.contains(" -> "));
+
+        let mermaid = mermaid_string(&h, &config);
+        assert_eq!(mermaid.matches("subgraph").count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn mermaid() -> Result<(), BuildError> {
+        let h = nested_module()?;
+        let mermaid = mermaid_string(&h, &RenderConfig::default());
+        assert!(mermaid.starts_with("flowchart TB\n    subgraph n0 [\"(0) Module\"]\n"));
+        assert_eq!(mermaid.matches("subgraph").count(), 3);
+        assert_eq!(mermaid.matches("end\n").count(), 3);
+        assert_eq!(mermaid.matches(" -->|\"Qubit\"| ").count(), 4);
+        assert!(mermaid.contains("style n1 fill:#cfe2ff"));
+        assert_eq!(mermaid, h.mermaid_string());
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
 //! Rewrite operations on the HUGR - replacement, outlining, etc.
 
+pub mod conditional;
+pub mod inline_call;
+pub mod lower_control_flow;
 pub mod outline_cfg;
 pub mod simple_replace;
+pub mod simplify_cfg;
+pub mod tail_loop;
+pub(crate) mod utils;
 use std::mem;
 
 use crate::Hugr;
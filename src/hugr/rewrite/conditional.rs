@@ -0,0 +1,814 @@
+//! Rewrites simplifying [`Conditional`] nodes.
+
+use std::collections::{HashSet, VecDeque};
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::utils::{
+    case_to_dfg, flatten_dfg, move_link, order_neighbours, predicate_tag, remove_if_unused,
+    remove_subtree, unpack_payload,
+};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::{HugrMut, HugrView, NodeType};
+use crate::ops::dataflow::IOTrait;
+use crate::ops::{Case, Conditional, Input, OpTag, OpTrait, OpType, Output};
+use crate::types::{AbstractSignature, SimpleRow};
+use crate::{Direction, Hugr, Node, Port};
+
+/// Replaces a [`Conditional`] whose predicate is known with the selected
+/// [`Case`].
+///
+/// The predicate must be produced by a [`LeafOp::Tag`] operation or loaded
+/// from a constant, possibly through a [`LeafOp::Noop`]. The values carried by
+/// a tagged predicate are extracted with [`LeafOp::UnpackTuple`].
+///
+/// By default the selected case is turned into a [`DFG`] node that takes the
+/// place of the conditional. If the rewrite is
+/// [flattened](ResolveConditional::flattened), the nodes of the case are
+/// instead moved directly into the sibling graph containing the conditional.
+///
+/// [`LeafOp::Tag`]: crate::ops::LeafOp::Tag
+/// [`LeafOp::Noop`]: crate::ops::LeafOp::Noop
+/// [`LeafOp::UnpackTuple`]: crate::ops::LeafOp::UnpackTuple
+/// [`DFG`]: crate::ops::DFG
+pub struct ResolveConditional {
+    conditional: Node,
+    flatten: bool,
+}
+
+impl ResolveConditional {
+    /// Create a new rewrite replacing `conditional` with a [`DFG`] containing
+    /// its selected case.
+    ///
+    /// [`DFG`]: crate::ops::DFG
+    pub fn new(conditional: Node) -> Self {
+        Self {
+            conditional,
+            flatten: false,
+        }
+    }
+
+    /// Create a new rewrite replacing `conditional` with the nodes of its
+    /// selected case.
+    pub fn flattened(conditional: Node) -> Self {
+        Self {
+            conditional,
+            flatten: true,
+        }
+    }
+
+    /// Returns the index of the case selected by the predicate of the
+    /// conditional.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the node is not a Conditional, or if its predicate cannot be
+    /// determined.
+    pub fn selected_case(&self, h: &Hugr) -> Result<usize, ConditionalRewriteError> {
+        self.predicate(h).map(|(tag, _)| tag)
+    }
+
+    /// The tag of the predicate and the source of the values it carries, if
+    /// any.
+    fn predicate(
+        &self,
+        h: &Hugr,
+    ) -> Result<(usize, Option<(Node, Port)>), ConditionalRewriteError> {
+        let cond_op = conditional_op(h, self.conditional)?;
+        let (tag, payload) = predicate_source(h, self.conditional)
+            .and_then(|(src, _)| predicate_tag(h, src))
+            .ok_or(ConditionalRewriteError::UnknownPredicate(self.conditional))?;
+        if payload.is_none() && !cond_op.predicate_inputs[tag].is_empty() {
+            return Err(ConditionalRewriteError::ConstantWithValues(
+                self.conditional,
+            ));
+        }
+        Ok((tag, payload))
+    }
+}
+
+impl Rewrite for ResolveConditional {
+    type Error = ConditionalRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), ConditionalRewriteError> {
+        self.predicate(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), ConditionalRewriteError> {
+        let (tag, payload) = self.predicate(h)?;
+        let cond = self.conditional;
+        let cond_op = conditional_op(h, cond)?;
+        let resources = h.get_nodetype(cond).input_resources.clone();
+        let (pred_node, _) = predicate_source(h, cond).unwrap();
+        let row = &cond_op.predicate_inputs[tag];
+
+        let mut sources = match payload {
+            Some(payload) if !row.is_empty() => unpack_payload(h, cond, payload, row, &resources),
+            _ => vec![],
+        };
+        sources.extend(
+            (1..=cond_op.other_inputs.len())
+                .map(|p| input_source(h, cond, p).expect("Dataflow input must be connected")),
+        );
+
+        let case = h.children(cond).nth(tag).unwrap();
+        case_to_dfg(h, case);
+        h.move_before_sibling(case, cond).unwrap();
+        for (p, (src, src_port)) in sources.into_iter().enumerate() {
+            h.connect(src, src_port.index(), case, p).unwrap();
+        }
+        if let Some(p) = h.get_optype(cond).other_port_index(Direction::Incoming) {
+            move_link(h, cond, p, case);
+        }
+        // The conditional and the DFG have the same outputs, followed by the
+        // Order port.
+        for p in h.node_outputs(cond).collect_vec() {
+            move_link(h, cond, p, case);
+        }
+        remove_subtree(h, cond);
+        remove_if_unused(h, pred_node);
+
+        if self.flatten {
+            flatten_dfg(h, case);
+        }
+        Ok(())
+    }
+}
+
+/// Merges two [`Conditional`] nodes branching on the same predicate.
+///
+/// The two conditionals are replaced by a single one, where each case
+/// contains the bodies of the corresponding cases of both conditionals. The
+/// first conditional must not depend on the second, and the only paths from
+/// the first to the second must be direct edges. Outputs of the first
+/// conditional that were only used by the second are removed.
+pub struct MergeConditionals {
+    first: Node,
+    second: Node,
+}
+
+impl MergeConditionals {
+    /// Create a new rewrite merging `second` into `first`.
+    pub fn new(first: Node, second: Node) -> Self {
+        Self { first, second }
+    }
+}
+
+impl Rewrite for MergeConditionals {
+    type Error = ConditionalRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), ConditionalRewriteError> {
+        let (first, second) = (self.first, self.second);
+        let first_op = conditional_op(h, first)?;
+        let second_op = conditional_op(h, second)?;
+        let parent = h.get_parent(first);
+        if first == second || parent != h.get_parent(second) {
+            return Err(ConditionalRewriteError::NotSiblings(first, second));
+        }
+        if first_op.predicate_inputs != second_op.predicate_inputs
+            || predicate_source(h, first) != predicate_source(h, second)
+        {
+            return Err(ConditionalRewriteError::DifferentPredicates(first, second));
+        }
+        // Non-local edges from the first conditional into the second cannot
+        // be expressed once they share cases.
+        for p in h.node_outputs(first) {
+            if h.linked_ports(first, p)
+                .any(|(tgt, _)| h.get_parent(tgt) != parent)
+            {
+                return Err(ConditionalRewriteError::NonLocalEdge(first, second));
+            }
+        }
+        let first_succs = h.output_neighbours(first).filter(|n| *n != second);
+        if reaches(h, first_succs, second) || reaches(h, [second], first) {
+            return Err(ConditionalRewriteError::Dependent(first, second));
+        }
+        Ok(())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), ConditionalRewriteError> {
+        self.verify(h)?;
+        let (first, second) = (self.first, self.second);
+        let first_op = conditional_op(h, first)?;
+        let second_op = conditional_op(h, second)?;
+
+        // Outputs of the first conditional used outside the second one.
+        let kept_outputs = (0..first_op.outputs.len())
+            .filter(|&p| {
+                h.linked_ports(first, Port::new_outgoing(p))
+                    .any(|(tgt, _)| tgt != second)
+            })
+            .collect_vec();
+        // Inputs of the second conditional, either coming from an output of
+        // the first or from a new input of the merged conditional.
+        let second_inputs = (1..=second_op.other_inputs.len())
+            .map(|p| input_source(h, second, p).expect("Dataflow input must be connected"))
+            .collect_vec();
+        let external_inputs = second_inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, (src, _))| *src != first)
+            .map(|(k, _)| k)
+            .collect_vec();
+
+        let mut other_inputs = first_op.other_inputs.to_vec();
+        other_inputs.extend(
+            external_inputs
+                .iter()
+                .map(|&k| second_op.other_inputs[k].clone()),
+        );
+        let mut outputs = kept_outputs
+            .iter()
+            .map(|&p| first_op.outputs[p].clone())
+            .collect_vec();
+        outputs.extend(second_op.outputs.iter().cloned());
+        let merged_op = Conditional {
+            predicate_inputs: first_op.predicate_inputs.clone(),
+            other_inputs: other_inputs.into(),
+            outputs: outputs.into(),
+        };
+
+        let resources = h.get_nodetype(first).input_resources.clone();
+        let node_type = |op: OpType| NodeType {
+            op,
+            input_resources: resources.clone(),
+        };
+        let merged = h.add_node(node_type(merged_op.clone().into()));
+        h.move_before_sibling(merged, first).unwrap();
+
+        // Inputs: the predicate, the inputs of the first conditional and the
+        // external inputs of the second.
+        let mut sources = (0..=first_op.other_inputs.len())
+            .map(|p| input_source(h, first, p).expect("Dataflow input must be connected"))
+            .collect_vec();
+        sources.extend(external_inputs.iter().map(|&k| second_inputs[k]));
+        for (p, (src, src_port)) in sources.into_iter().enumerate() {
+            h.connect(src, src_port.index(), merged, p).unwrap();
+        }
+        // Outputs: the kept outputs of the first conditional, then all the
+        // outputs of the second.
+        let output_links = kept_outputs
+            .iter()
+            .map(|&p| (first, p))
+            .chain((0..second_op.outputs.len()).map(|p| (second, p)))
+            .collect_vec();
+        for (new_p, (node, p)) in output_links.into_iter().enumerate() {
+            for (tgt, tgt_port) in h.linked_ports(node, Port::new_outgoing(p)).collect_vec() {
+                if tgt != second {
+                    h.connect(merged, new_p, tgt, tgt_port.index()).unwrap();
+                }
+            }
+        }
+        let order_preds = order_neighbours(h, first, Direction::Incoming)
+            .into_iter()
+            .chain(order_neighbours(h, second, Direction::Incoming))
+            .filter(|n| *n != first)
+            .unique()
+            .collect_vec();
+        let order_succs = order_neighbours(h, first, Direction::Outgoing)
+            .into_iter()
+            .chain(order_neighbours(h, second, Direction::Outgoing))
+            .filter(|n| *n != second)
+            .unique()
+            .collect_vec();
+        for pred in order_preds {
+            h.add_other_edge(pred, merged).unwrap();
+        }
+        for succ in order_succs {
+            h.add_other_edge(merged, succ).unwrap();
+        }
+
+        // Each case contains the bodies of both original cases, in sequence.
+        let first_cases = h.children(first).collect_vec();
+        let second_cases = h.children(second).collect_vec();
+        let num_other = first_op.other_inputs.len();
+        for (i, (c1, c2)) in first_cases.into_iter().zip(second_cases).enumerate() {
+            let inputs = merged_op.case_input_row(i).unwrap();
+            let row_len = merged_op.predicate_inputs[i].len();
+            let case = h
+                .add_node_with_parent(
+                    merged,
+                    node_type(
+                        Case {
+                            signature: AbstractSignature::new_df(
+                                inputs.clone(),
+                                merged_op.outputs.clone(),
+                            ),
+                        }
+                        .into(),
+                    ),
+                )
+                .unwrap();
+            let case_in = h
+                .add_node_with_parent(case, node_type(Input::new(inputs).into()))
+                .unwrap();
+            let case_out = h
+                .add_node_with_parent(
+                    case,
+                    node_type(Output::new(merged_op.outputs.clone()).into()),
+                )
+                .unwrap();
+            for c in [c1, c2] {
+                case_to_dfg(h, c);
+                h.set_parent(c, case).unwrap();
+            }
+
+            for p in 0..row_len + num_other {
+                h.connect(case_in, p, c1, p).unwrap();
+            }
+            for p in 0..row_len {
+                h.connect(case_in, p, c2, p).unwrap();
+            }
+            for (k, &(src, src_port)) in second_inputs.iter().enumerate() {
+                if src == first {
+                    h.connect(c1, src_port.index(), c2, row_len + k).unwrap();
+                } else {
+                    let e = external_inputs.iter().position(|&j| j == k).unwrap();
+                    h.connect(case_in, row_len + num_other + e, c2, row_len + k)
+                        .unwrap();
+                }
+            }
+            for (new_p, &p) in kept_outputs.iter().enumerate() {
+                h.connect(c1, p, case_out, new_p).unwrap();
+            }
+            for p in 0..second_op.outputs.len() {
+                h.connect(c2, p, case_out, kept_outputs.len() + p).unwrap();
+            }
+            flatten_dfg(h, c1);
+            flatten_dfg(h, c2);
+        }
+        h.remove_node(first).unwrap();
+        h.remove_node(second).unwrap();
+        Ok(())
+    }
+}
+
+/// Hoists an operation computed by every [`Case`] of a [`Conditional`] out of
+/// the conditional.
+///
+/// An operation can be hoisted if it appears in all cases, with the same
+/// inputs taken from the conditional's non-predicate inputs and no other
+/// dependencies. Only leaf operations with classical inputs and outputs are
+/// hoisted. The outputs of the hoisted operation are passed to the cases as
+/// new inputs of the conditional.
+pub struct HoistCommonCode {
+    conditional: Node,
+}
+
+impl HoistCommonCode {
+    /// Create a new rewrite hoisting an operation common to all cases out of
+    /// `conditional`.
+    pub fn new(conditional: Node) -> Self {
+        Self { conditional }
+    }
+
+    /// Finds an operation common to all the cases of the conditional, and
+    /// returns its instance in each case.
+    fn find_common(&self, h: &Hugr) -> Result<Vec<Node>, ConditionalRewriteError> {
+        let cond_op = conditional_op(h, self.conditional)?;
+        let cases = h.children(self.conditional).collect_vec();
+        let hoistable_inputs = |case_index: usize, n: Node| -> Option<Vec<usize>> {
+            let row_len = cond_op.predicate_inputs[case_index].len();
+            let case_in = h.children(h.get_parent(n)?).next()?;
+            let sig = h.get_optype(n).signature();
+            if h.children(n).next().is_some()
+                || !sig.input.purely_classical()
+                || !sig.output.purely_classical()
+                || !sig.static_input().is_empty()
+                || order_neighbours(h, n, Direction::Incoming)
+                    .iter()
+                    .any(|pred| *pred != case_in)
+            {
+                return None;
+            }
+            (0..sig.input.len())
+                .map(|p| match input_source(h, n, p)? {
+                    (src, src_port) if src == case_in && src_port.index() >= row_len => {
+                        Some(src_port.index() - row_len)
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        for candidate in h.children(cases[0]).skip(2) {
+            if !matches!(h.get_optype(candidate), OpType::LeafOp(_)) {
+                continue;
+            }
+            let Some(inputs) = hoistable_inputs(0, candidate) else {
+                continue;
+            };
+            let nodetype = h.get_nodetype(candidate);
+            let matching = cases
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, case)| {
+                    h.children(*case).skip(2).find(|n| {
+                        h.get_nodetype(*n) == nodetype
+                            && hoistable_inputs(i, *n).as_ref() == Some(&inputs)
+                    })
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(matching) = matching {
+                return Ok([candidate].into_iter().chain(matching).collect());
+            }
+        }
+        Err(ConditionalRewriteError::NoCommonCode(self.conditional))
+    }
+}
+
+impl Rewrite for HoistCommonCode {
+    type Error = ConditionalRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), ConditionalRewriteError> {
+        self.find_common(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), ConditionalRewriteError> {
+        let common = self.find_common(h)?;
+        let cond = self.conditional;
+        let cond_op = conditional_op(h, cond)?;
+        let resources = h.get_nodetype(cond).input_resources.clone();
+        let num_other = cond_op.other_inputs.len();
+
+        // The hoisted operation takes its inputs from the sources of the
+        // corresponding inputs of the conditional.
+        let first_case_in = h.children(h.get_parent(common[0]).unwrap()).next();
+        let row_len = cond_op.predicate_inputs[0].len();
+        let hoisted = h.add_node(h.get_nodetype(common[0]).clone());
+        h.move_before_sibling(hoisted, cond).unwrap();
+        let sig = h.get_optype(hoisted).signature();
+        for p in 0..sig.input.len() {
+            let (case_in, in_port) = input_source(h, common[0], p).unwrap();
+            debug_assert_eq!(Some(case_in), first_case_in);
+            let (src, src_port) = input_source(h, cond, in_port.index() - row_len + 1).unwrap();
+            h.connect(src, src_port.index(), hoisted, p).unwrap();
+        }
+        for pred in order_neighbours(h, cond, Direction::Incoming) {
+            h.add_other_edge(pred, hoisted).unwrap();
+        }
+
+        // A new conditional with the outputs of the hoisted operation as
+        // additional inputs.
+        let new_op = Conditional {
+            predicate_inputs: cond_op.predicate_inputs.clone(),
+            other_inputs: cond_op
+                .other_inputs
+                .iter()
+                .chain(sig.output.iter())
+                .cloned()
+                .collect_vec()
+                .into(),
+            outputs: cond_op.outputs.clone(),
+        };
+        let new_cond = h.add_node(NodeType {
+            op: new_op.clone().into(),
+            input_resources: resources.clone(),
+        });
+        h.move_before_sibling(new_cond, cond).unwrap();
+        for p in 0..=num_other {
+            let (src, src_port) = input_source(h, cond, p).unwrap();
+            h.connect(src, src_port.index(), new_cond, p).unwrap();
+        }
+        for p in 0..sig.output.len() {
+            h.connect(hoisted, p, new_cond, num_other + 1 + p).unwrap();
+        }
+        if let Some(p) = h.get_optype(cond).other_port_index(Direction::Incoming) {
+            move_link(h, cond, p, new_cond);
+        }
+        for p in h.node_outputs(cond).collect_vec() {
+            move_link(h, cond, p, new_cond);
+        }
+
+        // Each case gets a new Input node, and uses of the common operation
+        // are replaced by the new inputs.
+        for (i, (case, node)) in h
+            .children(cond)
+            .collect_vec()
+            .into_iter()
+            .zip(common)
+            .enumerate()
+        {
+            let inputs: SimpleRow = new_op.case_input_row(i).unwrap();
+            let old_len = inputs.len() - sig.output.len();
+            h.set_parent(case, new_cond).unwrap();
+            let case_resources = h.get_nodetype(case).input_resources.clone();
+            h.replace_op(
+                case,
+                NodeType {
+                    op: Case {
+                        signature: AbstractSignature::new_df(
+                            inputs.clone(),
+                            new_op.outputs.clone(),
+                        ),
+                    }
+                    .into(),
+                    input_resources: case_resources.clone(),
+                },
+            );
+            let old_in = h.children(case).next().unwrap();
+            let new_in = h.add_node(NodeType {
+                op: Input::new(inputs).into(),
+                input_resources: case_resources,
+            });
+            h.move_before_sibling(new_in, old_in).unwrap();
+            for p in h.node_outputs(old_in).collect_vec() {
+                move_link(h, old_in, p, new_in);
+            }
+            for p in 0..sig.output.len() {
+                for (tgt, tgt_port) in h.linked_ports(node, Port::new_outgoing(p)).collect_vec() {
+                    h.disconnect(tgt, tgt_port).unwrap();
+                    h.connect(new_in, old_len + p, tgt, tgt_port.index())
+                        .unwrap();
+                }
+            }
+            for succ in order_neighbours(h, node, Direction::Outgoing) {
+                h.add_other_edge(new_in, succ).unwrap();
+            }
+            h.remove_node(node).unwrap();
+            h.remove_node(old_in).unwrap();
+        }
+        h.remove_node(cond).unwrap();
+        Ok(())
+    }
+}
+
+/// Error from a rewrite on [`Conditional`] nodes.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConditionalRewriteError {
+    /// The node is not a Conditional.
+    #[error("Node {0:?} is not a Conditional (found {1:?})")]
+    NotAConditional(Node, OpTag),
+    /// The predicate of the conditional is not known.
+    #[error("The predicate of Conditional {0:?} cannot be determined")]
+    UnknownPredicate(Node),
+    /// The predicate is loaded from a constant carrying values, which cannot
+    /// be extracted.
+    #[error("The predicate of Conditional {0:?} is a constant carrying values")]
+    ConstantWithValues(Node),
+    /// The conditionals to merge are not distinct siblings.
+    #[error("Conditionals {0:?} and {1:?} are not distinct siblings")]
+    NotSiblings(Node, Node),
+    /// The conditionals to merge do not branch on the same predicate.
+    #[error("Conditionals {0:?} and {1:?} do not share their predicate")]
+    DifferentPredicates(Node, Node),
+    /// The second conditional depends on the first through other nodes, or
+    /// the first depends on the second.
+    #[error("Conditionals {0:?} and {1:?} cannot be merged without reordering other nodes")]
+    Dependent(Node, Node),
+    /// The first conditional has non-local edges into the second.
+    #[error("Conditional {0:?} has non-local edges into {1:?}")]
+    NonLocalEdge(Node, Node),
+    /// No operation is common to all the cases of the conditional.
+    #[error("Conditional {0:?} has no operation common to all its cases")]
+    NoCommonCode(Node),
+}
+
+fn conditional_op(h: &Hugr, node: Node) -> Result<Conditional, ConditionalRewriteError> {
+    match h.get_optype(node) {
+        OpType::Conditional(op) => Ok(op.clone()),
+        op => Err(ConditionalRewriteError::NotAConditional(node, op.tag())),
+    }
+}
+
+/// The source of the dataflow input `p` of `n`.
+fn input_source(h: &Hugr, n: Node, p: usize) -> Option<(Node, Port)> {
+    h.linked_ports(n, Port::new_incoming(p)).next()
+}
+
+/// The source of the predicate of a conditional.
+fn predicate_source(h: &Hugr, cond: Node) -> Option<(Node, Port)> {
+    input_source(h, cond, 0)
+}
+
+/// Whether `target` can be reached from any of the `from` nodes by following
+/// edges in their sibling graph.
+fn reaches(h: &Hugr, from: impl IntoIterator<Item = Node>, target: Node) -> bool {
+    let mut queue: VecDeque<Node> = from.into_iter().collect();
+    let parent = h.get_parent(target);
+    let mut visited = HashSet::new();
+    while let Some(n) = queue.pop_front() {
+        if n == target {
+            return true;
+        }
+        if h.get_parent(n) != parent || !visited.insert(n) {
+            continue;
+        }
+        queue.extend(h.output_neighbours(n));
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer,
+    };
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::{Const, ConstValue, LeafOp};
+    use crate::type_row;
+    use crate::types::{ClassicType, SimpleType};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes().filter(|n| h.get_optype(*n).tag() == tag).count()
+    }
+
+    /// A conditional over two variants, the first carrying a bit and the
+    /// second nothing, with an additional bit input. Case 0 computes the XOR
+    /// of both bits, case 1 returns the other bit.
+    ///
+    /// The predicate is tagged with `tag`, or loaded from a constant if `tag`
+    /// is `None`.
+    fn build_conditional(tag: Option<usize>) -> Result<(Hugr, Node), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, BIT],
+            type_row![BIT],
+        ))?;
+        let [x, y] = b.input_wires_arr();
+        let variants = vec![vec![ClassicType::bit()].into(), type_row![]];
+        let predicate = match tag {
+            Some(0) => b.make_predicate(0, variants.clone(), [x])?,
+            Some(tag) => b.make_predicate(tag, variants.clone(), [])?,
+            None => b.add_load_const(Const::predicate(1, ConstValue::unit(), variants.clone())?)?,
+        };
+        let mut cond_b =
+            b.conditional_builder((variants, predicate), [(BIT, y)], type_row![BIT])?;
+        let mut case0 = cond_b.case_builder(0)?;
+        let [x, y] = case0.input_wires_arr();
+        let xor = case0.add_dataflow_op(LeafOp::Xor, [x, y])?;
+        case0.finish_with_outputs(xor.outputs())?;
+        let case1 = cond_b.case_builder(1)?;
+        let [y] = case1.input_wires_arr();
+        case1.finish_with_outputs([y])?;
+        let cond = cond_b.finish_sub_container()?;
+        let h = b.finish_hugr_with_outputs(cond.outputs())?;
+        Ok((h, cond.node()))
+    }
+
+    #[test]
+    fn resolve_tag() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, cond) = build_conditional(Some(0))?;
+        let rw = ResolveConditional::new(cond);
+        assert_eq!(rw.selected_case(&h), Ok(0));
+        h.apply_rewrite(rw)?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 0);
+        // The root and the selected case.
+        assert_eq!(count_tag(&h, OpTag::Dfg), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_const_flattened() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, cond) = build_conditional(None)?;
+        h.apply_rewrite(ResolveConditional::flattened(cond))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 0);
+        assert_eq!(count_tag(&h, OpTag::Dfg), 1);
+        // Only the root DFG with its Input and Output, and the constant, are
+        // left.
+        assert_eq!(h.node_count(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_unknown() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![SimpleType::new_simple_predicate(2), BIT],
+            type_row![BIT],
+        ))?;
+        let [pred, x] = b.input_wires_arr();
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(BIT, x)],
+            type_row![BIT],
+        )?;
+        for i in 0..2 {
+            let case = cond_b.case_builder(i)?;
+            let [x] = case.input_wires_arr();
+            case.finish_with_outputs([x])?;
+        }
+        let cond = cond_b.finish_sub_container()?;
+        let h = b.finish_hugr_with_outputs(cond.outputs())?;
+        assert_eq!(
+            ResolveConditional::new(cond.node()).verify(&h),
+            Err(ConditionalRewriteError::UnknownPredicate(cond.node()))
+        );
+        Ok(())
+    }
+
+    /// Two conditionals branching on the same predicate input, the second
+    /// taking the output of the first.
+    fn build_sequence() -> Result<(Hugr, [Node; 2]), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![SimpleType::new_simple_predicate(2), NAT],
+            type_row![NAT],
+        ))?;
+        let [pred, n] = b.input_wires_arr();
+        let mut conds = Vec::new();
+        let mut n = n;
+        for _ in 0..2 {
+            let mut cond_b = b.conditional_builder(
+                ([type_row![], type_row![]], pred),
+                [(NAT, n)],
+                type_row![NAT],
+            )?;
+            for i in 0..2 {
+                let mut case = cond_b.case_builder(i)?;
+                let [n] = case.input_wires_arr();
+                let noop = case.add_dataflow_op(LeafOp::Noop { ty: NAT }, [n])?;
+                case.finish_with_outputs(noop.outputs())?;
+            }
+            let cond = cond_b.finish_sub_container()?;
+            [n] = cond.outputs_arr();
+            conds.push(cond.node());
+        }
+        let h = b.finish_hugr_with_outputs([n])?;
+        Ok((h, conds.try_into().unwrap()))
+    }
+
+    #[test]
+    fn merge() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, [c1, c2]) = build_sequence()?;
+        assert_matches!(
+            MergeConditionals::new(c2, c1).verify(&h),
+            Err(ConditionalRewriteError::Dependent(..))
+        );
+        h.apply_rewrite(MergeConditionals::new(c1, c2))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::Conditional), 1);
+        // Both case bodies are flattened into the merged cases.
+        assert_eq!(count_tag(&h, OpTag::Dfg), 1);
+        let cond = h
+            .nodes()
+            .find(|n| h.get_optype(*n).tag() == OpTag::Conditional)
+            .unwrap();
+        // The output of the first conditional is now internal to the cases.
+        assert_eq!(h.get_optype(cond).signature().output.len(), 1);
+        for case in h.children(cond) {
+            assert_eq!(h.children(case).count(), 4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hoist() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![SimpleType::new_simple_predicate(2), BIT, BIT],
+            type_row![BIT, BIT],
+        ))?;
+        let [pred, x, y] = b.input_wires_arr();
+        let mut cond_b = b.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            [(BIT, x), (BIT, y)],
+            type_row![BIT, BIT],
+        )?;
+        for i in 0..2 {
+            let mut case = cond_b.case_builder(i)?;
+            let [x, y] = case.input_wires_arr();
+            let [xor] = case.add_dataflow_op(LeafOp::Xor, [x, y])?.outputs_arr();
+            let [other] = case.add_dataflow_op(LeafOp::Xor, [xor, x])?.outputs_arr();
+            if i == 0 {
+                case.finish_with_outputs([xor, other])?;
+            } else {
+                case.finish_with_outputs([other, xor])?;
+            }
+        }
+        let cond = cond_b.finish_sub_container()?;
+        let mut h = b.finish_hugr_with_outputs(cond.outputs())?;
+
+        h.apply_rewrite(HoistCommonCode::new(cond.node()))?;
+        h.validate()?;
+        let cond = h
+            .nodes()
+            .find(|n| h.get_optype(*n).tag() == OpTag::Conditional)
+            .unwrap();
+        // The second XOR depends on the first, and is now hoisted too.
+        h.apply_rewrite(HoistCommonCode::new(cond))?;
+        h.validate()?;
+        let cond = h
+            .nodes()
+            .find(|n| h.get_optype(*n).tag() == OpTag::Conditional)
+            .unwrap();
+        assert_eq!(h.get_optype(cond).signature().input.len(), 5);
+        for case in h.children(cond) {
+            assert_eq!(h.children(case).count(), 2);
+        }
+        assert_eq!(
+            HoistCommonCode::new(cond).verify(&h),
+            Err(ConditionalRewriteError::NoCommonCode(cond))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! Rewrite for replacing a [`Call`] node with a copy of the body of the
+//! function it calls.
+//!
+//! [`Call`]: crate::ops::Call
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::utils::{copy_descendants, flatten_dfg};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::{HugrMut, HugrView, NodeType};
+use crate::ops::{OpType, DFG};
+use crate::{Direction, Hugr, Node, Port};
+
+/// Replaces a [`Call`] node with a copy of the callee's body.
+///
+/// By default the body is placed inside a new [`DFG`] node that takes the
+/// place of the call. If the rewrite is [flattened](InlineCall::flattened),
+/// the nodes of the body are instead moved directly into the sibling graph
+/// containing the call.
+///
+/// Edges from the function body to nodes outside of it, such as module-level
+/// constants or other functions, are preserved in the copy.
+///
+/// [`Call`]: crate::ops::Call
+pub struct InlineCall {
+    call: Node,
+    flatten: bool,
+}
+
+impl InlineCall {
+    /// Create a new rewrite that replaces `call` with a nested [`DFG`]
+    /// containing the body of the called function.
+    pub fn new(call: Node) -> Self {
+        Self {
+            call,
+            flatten: false,
+        }
+    }
+
+    /// Create a new rewrite that replaces `call` with the nodes of the body of
+    /// the called function, without a surrounding [`DFG`].
+    pub fn flattened(call: Node) -> Self {
+        Self {
+            call,
+            flatten: true,
+        }
+    }
+
+    /// The call node to be replaced.
+    pub fn call(&self) -> Node {
+        self.call
+    }
+
+    /// Returns the function definition called by the node.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the node is not a Call, or if it does not call a function
+    /// definition with a body that can be inlined.
+    pub fn callee(&self, h: &Hugr) -> Result<Node, InlineCallError> {
+        let OpType::Call(call) = h.get_optype(self.call) else {
+            return Err(InlineCallError::NotACall(
+                self.call,
+                h.get_optype(self.call).clone(),
+            ));
+        };
+        let static_port = Port::new_incoming(call.signature.input.len());
+        let (callee, _) = h
+            .linked_ports(self.call, static_port)
+            .exactly_one()
+            .map_err(|_| InlineCallError::UnknownCallee(self.call))?;
+        match h.get_optype(callee) {
+            OpType::FuncDefn(_) => Ok(callee),
+            op => Err(InlineCallError::NotADefinition(callee, op.clone())),
+        }
+    }
+}
+
+impl Rewrite for InlineCall {
+    type Error = InlineCallError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), InlineCallError> {
+        self.callee(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), InlineCallError> {
+        let callee = self.callee(h)?;
+        let OpType::Call(call_op) = h.get_optype(self.call) else {
+            unreachable!()
+        };
+        let signature = call_op.signature.clone();
+        let num_inputs = signature.input.len();
+        let call_nodetype = h.get_nodetype(self.call);
+        let dfg_nodetype = NodeType {
+            op: DFG { signature }.into(),
+            input_resources: call_nodetype.input_resources.clone(),
+        };
+
+        let dfg = h.add_node(dfg_nodetype);
+        h.move_before_sibling(dfg, self.call).unwrap();
+        copy_descendants(h, callee, dfg);
+
+        // Move the value edges of the call to the DFG. The static input from
+        // the function definition is dropped with the call node.
+        let call_order_in = h
+            .get_optype(self.call)
+            .other_port_index(Direction::Incoming);
+        let call_order_out = h
+            .get_optype(self.call)
+            .other_port_index(Direction::Outgoing);
+        for p in h.node_inputs(self.call).collect_vec() {
+            for (src, src_port) in h.linked_ports(self.call, p).collect_vec() {
+                if p.index() < num_inputs {
+                    h.connect(src, src_port.index(), dfg, p.index()).unwrap();
+                } else if Some(p) == call_order_in {
+                    h.add_other_edge(src, dfg).unwrap();
+                }
+            }
+        }
+        for p in h.node_outputs(self.call).collect_vec() {
+            for (tgt, tgt_port) in h.linked_ports(self.call, p).collect_vec() {
+                if Some(p) == call_order_out {
+                    h.add_other_edge(dfg, tgt).unwrap();
+                } else {
+                    h.connect(dfg, p.index(), tgt, tgt_port.index()).unwrap();
+                }
+            }
+        }
+        let metadata = h.get_metadata(self.call).clone();
+        h.remove_node(self.call).unwrap();
+        h.set_metadata(dfg, metadata);
+
+        if self.flatten {
+            flatten_dfg(h, dfg);
+        }
+        Ok(())
+    }
+}
+
+/// Error from an [`InlineCall`] rewrite.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum InlineCallError {
+    /// The node to inline is not a Call.
+    #[error("Node {0:?} is not a Call (found {1:?})")]
+    NotACall(Node, OpType),
+    /// The call is not connected to a single function.
+    #[error("Call node {0:?} is not connected to a function")]
+    UnknownCallee(Node),
+    /// The called function has no body, e.g. it is a declaration.
+    #[error("Called node {0:?} is not a function definition (found {1:?})")]
+    NotADefinition(Node, OpType),
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, Container, Dataflow, DataflowSubContainer, HugrBuilder, ModuleBuilder,
+    };
+    use crate::hugr::HugrView;
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::{Const, LeafOp, OpTag, OpTrait};
+    use crate::type_row;
+    use crate::types::{AbstractSignature, ClassicType, SimpleType};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    /// A module with a function `double` using a module-level constant, and a
+    /// `main` that calls it twice.
+    fn build_module() -> Result<(Hugr, [Node; 2]), BuildError> {
+        let mut module_builder = ModuleBuilder::new();
+        let konst = module_builder.add_constant(Const::i64(1).unwrap())?;
+        let mut double = module_builder.define_function(
+            "double",
+            AbstractSignature::new_df(type_row![NAT], type_row![NAT, NAT]).pure(),
+        )?;
+        let [i] = double.input_wires_arr();
+        let one = double.load_const(&konst)?;
+        let noop = double.add_dataflow_op(LeafOp::Noop { ty: NAT }, [one])?;
+        let double = double.finish_with_outputs([i, noop.out_wire(0)])?;
+
+        let mut main = module_builder.define_function(
+            "main",
+            AbstractSignature::new_df(type_row![NAT], type_row![NAT, NAT, NAT, NAT]).pure(),
+        )?;
+        let [i] = main.input_wires_arr();
+        let c1 = main.call(double.handle(), [i])?;
+        let c2 = main.call(double.handle(), [i])?;
+        let outs = c1.outputs().chain(c2.outputs()).collect_vec();
+        main.finish_with_outputs(outs)?;
+        let h = module_builder.finish_hugr()?;
+        Ok((h, [c1.node(), c2.node()]))
+    }
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes().filter(|n| h.get_optype(*n).tag() == tag).count()
+    }
+
+    #[test]
+    fn inline_nested() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, [c1, _]) = build_module()?;
+        let num_nodes = h.node_count();
+        h.apply_rewrite(InlineCall::new(c1))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::FnCall), 1);
+        assert_eq!(count_tag(&h, OpTag::Dfg), 1);
+        // The call is replaced by a DFG, with a copy of the body of `double`:
+        // Input, Output, LoadConstant and Noop.
+        assert_eq!(h.node_count(), num_nodes + 4);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_flattened() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, [c1, c2]) = build_module()?;
+        let num_nodes = h.node_count();
+        h.apply_rewrite(InlineCall::flattened(c1))?;
+        h.apply_rewrite(InlineCall::flattened(c2))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::FnCall), 0);
+        assert_eq!(count_tag(&h, OpTag::Dfg), 0);
+        // Each call is replaced by a LoadConstant and a Noop.
+        assert_eq!(h.node_count(), num_nodes + 2);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_recursive() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module_builder = ModuleBuilder::new();
+        let decl = module_builder.declare(
+            "f",
+            AbstractSignature::new_df(type_row![BIT], type_row![BIT]).pure(),
+        )?;
+        let mut f = module_builder.define_function(
+            "g",
+            AbstractSignature::new_df(type_row![BIT], type_row![BIT]).pure(),
+        )?;
+        let [i] = f.input_wires_arr();
+        let call_f = f.call(&decl, [i])?;
+        let f_id = f.finish_with_outputs(call_f.outputs())?;
+        let mut h = module_builder.finish_hugr()?;
+
+        // Calling a declaration cannot be inlined.
+        assert_matches!(
+            InlineCall::new(call_f.node()).verify(&h),
+            Err(InlineCallError::NotADefinition(n, _)) => assert_eq!(n, decl.node())
+        );
+        assert_matches!(
+            InlineCall::new(f_id.node()).verify(&h),
+            Err(InlineCallError::NotACall(..))
+        );
+
+        // Make `g` call itself, then inline that call into its own body.
+        let static_port = Port::new_incoming(1);
+        h.disconnect(call_f.node(), static_port)?;
+        h.connect(f_id.node(), 0, call_f.node(), 1)?;
+        h.validate()?;
+        h.apply_rewrite(InlineCall::new(call_f.node()))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::FnCall), 1);
+        assert_eq!(count_tag(&h, OpTag::Dfg), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_flat_linear() -> Result<(), Box<dyn std::error::Error>> {
+        let qb = SimpleType::Qubit;
+        let mut module_builder = ModuleBuilder::new();
+        let f = module_builder.define_function(
+            "id",
+            AbstractSignature::new_df(vec![qb.clone()], vec![qb.clone()]).pure(),
+        )?;
+        let w = f.input_wires();
+        let f_id = f.finish_with_outputs(w)?;
+        let mut main = module_builder.define_function(
+            "main",
+            AbstractSignature::new_df(vec![qb.clone()], vec![qb]).pure(),
+        )?;
+        let [q] = main.input_wires_arr();
+        let [q] = main.add_dataflow_op(LeafOp::H, [q])?.outputs_arr();
+        let call = main.call(f_id.handle(), [q])?;
+        let [q] = main
+            .add_dataflow_op(LeafOp::H, call.outputs())?
+            .outputs_arr();
+        main.finish_with_outputs([q])?;
+        let mut h = module_builder.finish_hugr()?;
+
+        h.apply_rewrite(InlineCall::flattened(call.node()))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::FnCall), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+//! Rewrites lowering [`Conditional`] and [`TailLoop`] nodes to [`CFG`]
+//! nodes.
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::utils::{add_unary_predicate, is_order, order_neighbours};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::{HugrMut, HugrView, NodeType};
+use crate::ops::dataflow::IOTrait;
+use crate::ops::{
+    BasicBlock, Case, Conditional, Input, OpTag, OpTrait, OpType, Output, TailLoop, CFG,
+};
+use crate::types::{ClassicRow, SimpleRow, SimpleType};
+use crate::{Direction, Hugr, Node};
+
+/// Replaces a [`Conditional`] with a [`CFG`] node with the same signature.
+///
+/// The entry block of the CFG branches on the predicate input, passing the
+/// other inputs along. Each [`Case`] becomes a basic block, branching
+/// unconditionally to the exit block.
+pub struct LowerConditional {
+    conditional: Node,
+}
+
+impl LowerConditional {
+    /// Create a new rewrite lowering `conditional` to a CFG.
+    pub fn new(conditional: Node) -> Self {
+        Self { conditional }
+    }
+}
+
+impl Rewrite for LowerConditional {
+    type Error = LowerControlFlowError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), LowerControlFlowError> {
+        conditional_op(h, self.conditional).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), LowerControlFlowError> {
+        let cond_op = conditional_op(h, self.conditional)?;
+        let cond = self.conditional;
+        let resources = h.get_nodetype(cond).input_resources.clone();
+        let node_type = |op: OpType| NodeType {
+            op,
+            input_resources: resources.clone(),
+        };
+        let signature = cond_op.signature();
+        let cases = h.children(cond).collect_vec();
+        h.replace_op(
+            cond,
+            node_type(
+                CFG {
+                    inputs: signature.input.clone(),
+                    outputs: signature.output.clone(),
+                }
+                .into(),
+            ),
+        );
+
+        // The entry block just branches on the predicate.
+        let Conditional {
+            predicate_inputs,
+            other_inputs,
+            outputs,
+        } = cond_op;
+        let entry = h
+            .add_node_with_parent(
+                cond,
+                node_type(
+                    BasicBlock::DFB {
+                        inputs: signature.input.clone(),
+                        predicate_variants: predicate_inputs,
+                        other_outputs: other_inputs,
+                    }
+                    .into(),
+                ),
+            )
+            .unwrap();
+        let inp = h
+            .add_node_with_parent(entry, node_type(Input::new(signature.input.clone()).into()))
+            .unwrap();
+        let out = h
+            .add_node_with_parent(
+                entry,
+                node_type(Output::new(signature.input.clone()).into()),
+            )
+            .unwrap();
+        for p in 0..signature.input.len() {
+            h.connect(inp, p, out, p).unwrap();
+        }
+        let exit = h
+            .add_node_with_parent(
+                cond,
+                node_type(
+                    BasicBlock::Exit {
+                        cfg_outputs: outputs,
+                    }
+                    .into(),
+                ),
+            )
+            .unwrap();
+        if let Some(&first) = cases.first() {
+            h.move_before_sibling(entry, first).unwrap();
+            h.move_before_sibling(exit, first).unwrap();
+        }
+
+        for (i, case) in cases.into_iter().enumerate() {
+            case_to_block(h, case);
+            h.connect(entry, i, case, 0).unwrap();
+            h.connect(case, 0, exit, 0).unwrap();
+        }
+        Ok(())
+    }
+}
+
+/// Replaces a [`TailLoop`] with a [`CFG`] node with the same signature.
+///
+/// The body of the loop becomes the entry block of the CFG, branching back to
+/// itself or to the exit block.
+pub struct LowerTailLoop {
+    tail_loop: Node,
+}
+
+impl LowerTailLoop {
+    /// Create a new rewrite lowering `tail_loop` to a CFG.
+    pub fn new(tail_loop: Node) -> Self {
+        Self { tail_loop }
+    }
+}
+
+impl Rewrite for LowerTailLoop {
+    type Error = LowerControlFlowError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), LowerControlFlowError> {
+        tail_loop_op(h, self.tail_loop).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), LowerControlFlowError> {
+        let loop_op = tail_loop_op(h, self.tail_loop)?;
+        let tail_loop = self.tail_loop;
+        let resources = h.get_nodetype(tail_loop).input_resources.clone();
+        let node_type = |op: OpType| NodeType {
+            op,
+            input_resources: resources.clone(),
+        };
+        let signature = loop_op.signature();
+        let children = h.children(tail_loop).collect_vec();
+        h.replace_op(
+            tail_loop,
+            node_type(
+                CFG {
+                    inputs: signature.input,
+                    outputs: signature.output.clone(),
+                }
+                .into(),
+            ),
+        );
+
+        // The body's outputs are already those of a block branching on the
+        // loop predicate.
+        let block = h
+            .add_node_with_parent(
+                tail_loop,
+                node_type(
+                    BasicBlock::DFB {
+                        inputs: loop_op.body_input_row(),
+                        predicate_variants: vec![loop_op.just_inputs, loop_op.just_outputs],
+                        other_outputs: loop_op.rest,
+                    }
+                    .into(),
+                ),
+            )
+            .unwrap();
+        for child in children {
+            h.set_parent(child, block).unwrap();
+        }
+        let exit = h
+            .add_node_with_parent(
+                tail_loop,
+                node_type(
+                    BasicBlock::Exit {
+                        cfg_outputs: signature.output,
+                    }
+                    .into(),
+                ),
+            )
+            .unwrap();
+        h.connect(block, 0, block, 0).unwrap();
+        h.connect(block, 1, exit, 0).unwrap();
+        Ok(())
+    }
+}
+
+/// Error from a rewrite lowering structured control flow.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LowerControlFlowError {
+    /// The node is not a conditional.
+    #[error("Node {0:?} is not a Conditional (found {1:?})")]
+    NotAConditional(Node, OpTag),
+    /// The node is not a tail loop.
+    #[error("Node {0:?} is not a TailLoop (found {1:?})")]
+    NotATailLoop(Node, OpTag),
+}
+
+fn conditional_op(h: &Hugr, n: Node) -> Result<Conditional, LowerControlFlowError> {
+    match h.get_optype(n) {
+        OpType::Conditional(op) => Ok(op.clone()),
+        op => Err(LowerControlFlowError::NotAConditional(n, op.tag())),
+    }
+}
+
+fn tail_loop_op(h: &Hugr, n: Node) -> Result<TailLoop, LowerControlFlowError> {
+    match h.get_optype(n) {
+        OpType::TailLoop(op) => Ok(op.clone()),
+        op => Err(LowerControlFlowError::NotATailLoop(n, op.tag())),
+    }
+}
+
+/// Turns a [`Case`] into a basic block with a single successor, outputting a
+/// constant unary predicate in front of the outputs of the case.
+fn case_to_block(h: &mut Hugr, case: Node) {
+    let OpType::Case(Case { signature }) = h.get_optype(case).clone() else {
+        panic!("Node is not a Case")
+    };
+    let resources = h.get_nodetype(case).input_resources.clone();
+    let out = h.children(case).nth(1).unwrap();
+
+    // Shift the outputs of the case by one, to make room for the predicate.
+    let sources = h
+        .node_inputs(out)
+        .filter(|p| !is_order(h, out, *p))
+        .map(|p| h.linked_ports(out, p).exactly_one().ok().unwrap())
+        .collect_vec();
+    let order_preds = order_neighbours(h, out, Direction::Incoming);
+    for p in h.node_inputs(out).collect_vec() {
+        h.disconnect(out, p).unwrap();
+    }
+    let mut out_types = vec![SimpleType::new_simple_predicate(1)];
+    out_types.extend(signature.output.iter().cloned());
+    let out_type = NodeType {
+        op: Output::new(SimpleRow::from(out_types)).into(),
+        input_resources: h.get_nodetype(out).input_resources.clone(),
+    };
+    h.set_num_ports(out, out_type.input_count(), out_type.output_count());
+    h.replace_op(out, out_type);
+    for (p, (src, src_port)) in sources.into_iter().enumerate() {
+        h.connect(src, src_port.index(), out, p + 1).unwrap();
+    }
+    for pred in order_preds {
+        h.add_other_edge(pred, out).unwrap();
+    }
+    let load = add_unary_predicate(h, case, &resources);
+    h.connect(load, 0, out, 0).unwrap();
+
+    let block = NodeType {
+        op: BasicBlock::DFB {
+            inputs: signature.input,
+            predicate_variants: vec![ClassicRow::new()],
+            other_outputs: signature.output,
+        }
+        .into(),
+        input_resources: resources,
+    };
+    h.set_num_ports(case, block.input_count(), block.output_count());
+    h.replace_op(case, block);
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{
+        BuildError, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, SubContainer,
+    };
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::LeafOp;
+    use crate::types::{AbstractSignature, ClassicType};
+    use crate::{classic_row, type_row};
+
+    const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+    const QB: SimpleType = SimpleType::Qubit;
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes()
+            .filter(|n| tag.is_superset(h.get_optype(*n).tag()))
+            .count()
+    }
+
+    /// A conditional on a predicate carrying a bit in its first variant,
+    /// outputting that bit or the other input.
+    fn build_conditional() -> Result<(Hugr, Node), BuildError> {
+        let variants = vec![classic_row![ClassicType::bit()], type_row![]];
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![SimpleType::new_predicate(variants.clone()), BIT],
+            type_row![BIT],
+        ))?;
+        let [pred, x] = b.input_wires_arr();
+        let mut cond_b = b.conditional_builder((variants, pred), [(BIT, x)], type_row![BIT])?;
+        let case_b = cond_b.case_builder(0)?;
+        let [y, _] = case_b.input_wires_arr();
+        case_b.finish_with_outputs([y])?;
+        let mut case_b = cond_b.case_builder(1)?;
+        let [x] = case_b.input_wires_arr();
+        let [x] = case_b.add_dataflow_op(LeafOp::Xor, [x, x])?.outputs_arr();
+        case_b.finish_with_outputs([x])?;
+        let cond = cond_b.finish_sub_container()?;
+        let h = b.finish_hugr_with_outputs(cond.outputs())?;
+        Ok((h, cond.node()))
+    }
+
+    #[test]
+    fn lower_conditional() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, cond) = build_conditional()?;
+        h.apply_rewrite(LowerConditional::new(cond))?;
+        h.validate()?;
+        assert_eq!(h.get_optype(cond).tag(), OpTag::Cfg);
+        assert_eq!(count_tag(&h, OpTag::Conditional), 0);
+        // The entry, the two cases, and the exit.
+        assert_eq!(count_tag(&h, OpTag::BasicBlock), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn lower_tail_loop() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![BIT, QB],
+            type_row![BIT, QB],
+        ))?;
+        let [x, q] = b.input_wires_arr();
+        let mut loop_b = b.tail_loop_builder(
+            vec![(ClassicType::bit(), x)],
+            vec![(QB, q)],
+            classic_row![ClassicType::bit()],
+        )?;
+        let signature = loop_b.loop_signature()?.clone();
+        let [x, q] = loop_b.input_wires_arr();
+        let [q] = loop_b.add_dataflow_op(LeafOp::H, [q])?.outputs_arr();
+        let break_wire = loop_b.make_break(signature, [x])?;
+        let loop_id = loop_b.finish_with_outputs(break_wire, [q])?;
+        let mut h = b.finish_hugr_with_outputs(loop_id.outputs())?;
+
+        h.apply_rewrite(LowerTailLoop::new(loop_id.node()))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::TailLoop), 0);
+        assert_eq!(count_tag(&h, OpTag::BasicBlock), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn lower_wrong_node() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, cond) = build_conditional()?;
+        let root = h.root();
+        assert_matches!(
+            h.apply_rewrite(LowerTailLoop::new(cond)),
+            Err(LowerControlFlowError::NotATailLoop(n, OpTag::Conditional)) => assert_eq!(n, cond)
+        );
+        assert_matches!(
+            h.apply_rewrite(LowerConditional::new(root)),
+            Err(LowerControlFlowError::NotAConditional(n, _)) => assert_eq!(n, root)
+        );
+        Ok(())
+    }
+}
@@ -4,7 +4,11 @@ use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
 
-use crate::hugr::{HugrMut, HugrView, NodeMetadata};
+use crate::algorithm::dataflow::dataflow_order;
+use crate::algorithm::unitary::{equivalent, UnitaryError};
+use crate::builder::{DFGBuilder, Dataflow, DataflowHugr};
+use crate::hugr::{HugrMut, HugrView, NodeMetadata, Wire};
+use crate::types::AbstractSignature;
 use crate::{
     hugr::{Node, Rewrite},
     ops::{OpTag, OpTrait, OpType},
@@ -12,8 +16,12 @@ use crate::{
 };
 use thiserror::Error;
 
+#[cfg(feature = "pyo3")]
+use pyo3::prelude::*;
+
 /// Specification of a simple replacement operation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "pyo3", pyclass)]
 pub struct SimpleReplacement {
     /// The common DataflowParent of all nodes to be replaced.
     pub parent: Node,
@@ -46,17 +54,87 @@ impl SimpleReplacement {
             nu_out,
         }
     }
+
+    /// Checks that the replacement implements the same unitary as the removed
+    /// nodes, up to a global phase. Both must be qubit-only circuits.
+    ///
+    /// This is not part of [`Rewrite::verify`], which only checks that the
+    /// replacement can be applied.
+    pub fn verify_unitary(&self, h: &Hugr) -> Result<(), SimpleReplacementError> {
+        let removed = self
+            .removed_circuit(h)
+            .ok_or(SimpleReplacementError::UnitaryCheckUnsupported())?;
+        let root = self.replacement.root();
+        if !equivalent(&removed, removed.root(), &self.replacement, root)? {
+            return Err(SimpleReplacementError::NotEquivalent());
+        }
+        Ok(())
+    }
+
+    /// Copies the nodes to be removed into a new DFG-rooted Hugr, with the
+    /// same boundary as the replacement.
+    fn removed_circuit(&self, h: &Hugr) -> Option<Hugr> {
+        let mut io = self.replacement.children(self.replacement.root());
+        let (rep_input, rep_output) = (io.next()?, io.next()?);
+        let (OpType::Input(inputs), OpType::Output(outputs)) = (
+            self.replacement.get_optype(rep_input),
+            self.replacement.get_optype(rep_output),
+        ) else {
+            return None;
+        };
+        // The replacement input feeding each removed input port.
+        let mut input_index = HashMap::new();
+        for (rep_target, rem_target) in &self.nu_inp {
+            let (_, port) = self
+                .replacement
+                .linked_ports(rep_target.0, rep_target.1)
+                .exactly_one()
+                .ok()?;
+            input_index.insert(*rem_target, port.index());
+        }
+        let mut builder = DFGBuilder::new(AbstractSignature::new_df(
+            inputs.types.clone(),
+            outputs.types.clone(),
+        ))
+        .ok()?;
+        let input_wires = builder.input_wires().collect::<Vec<_>>();
+        let mut wires: HashMap<(Node, Port), Wire> = HashMap::new();
+        for node in dataflow_order(h, self.parent) {
+            if !self.removal.contains(&node) {
+                continue;
+            }
+            let op = h.get_optype(node);
+            let ins = (0..op.signature().input.len())
+                .map(|i| {
+                    let port = Port::new_incoming(i);
+                    match input_index.get(&(node, port)) {
+                        Some(&index) => Some(input_wires[index]),
+                        None => wires.get(&h.linked_ports(node, port).next()?).copied(),
+                    }
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let outs = builder.add_dataflow_op(op.clone(), ins).ok()?.outputs();
+            for (i, wire) in outs.enumerate() {
+                wires.insert((node, Port::new_outgoing(i)), wire);
+            }
+        }
+        let outs = (0..outputs.types.len())
+            .map(|i| {
+                let (rem_target, _) = self.nu_out.iter().find(|(_, port)| port.index() == i)?;
+                wires
+                    .get(&h.linked_ports(rem_target.0, rem_target.1).next()?)
+                    .copied()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        builder.finish_hugr_with_outputs(outs).ok()
+    }
 }
 
 impl Rewrite for SimpleReplacement {
     type Error = SimpleReplacementError;
     const UNCHANGED_ON_FAILURE: bool = true;
 
-    fn verify(&self, _h: &Hugr) -> Result<(), SimpleReplacementError> {
-        unimplemented!()
-    }
-
-    fn apply(self, h: &mut Hugr) -> Result<(), SimpleReplacementError> {
+    fn verify(&self, h: &Hugr) -> Result<(), SimpleReplacementError> {
         // 1. Check the parent node exists and is a DataflowParent.
         if !OpTag::DataflowParent.is_superset(h.get_optype(self.parent).tag()) {
             return Err(SimpleReplacementError::InvalidParentNode());
@@ -67,18 +145,8 @@ impl Rewrite for SimpleReplacement {
                 return Err(SimpleReplacementError::InvalidRemovedNode());
             }
         }
-        // 3. Do the replacement.
-        // 3.1. Add copies of all replacement nodes and edges to h. Exclude Input/Output nodes.
-        // Create map from old NodeIndex (in self.replacement) to new NodeIndex (in self).
-        let mut index_map: HashMap<Node, Node> = HashMap::new();
-        let replacement_nodes = self
-            .replacement
-            .children(self.replacement.root())
-            .collect::<Vec<Node>>();
-        // slice of nodes omitting Input and Output:
-        let replacement_inner_nodes = &replacement_nodes[2..];
-        for &node in replacement_inner_nodes {
-            // Check there are no const inputs.
+        // 3. Check there are no const inputs in the replacement.
+        for node in self.replacement.children(self.replacement.root()).skip(2) {
             if !self
                 .replacement
                 .get_optype(node)
@@ -89,6 +157,22 @@ impl Rewrite for SimpleReplacement {
                 return Err(SimpleReplacementError::InvalidReplacementNode());
             }
         }
+        Ok(())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), SimpleReplacementError> {
+        // 1. Check the replacement is valid.
+        self.verify(h)?;
+        // 2. Do the replacement.
+        // 2.1. Add copies of all replacement nodes and edges to h. Exclude Input/Output nodes.
+        // Create map from old NodeIndex (in self.replacement) to new NodeIndex (in self).
+        let mut index_map: HashMap<Node, Node> = HashMap::new();
+        let replacement_nodes = self
+            .replacement
+            .children(self.replacement.root())
+            .collect::<Vec<Node>>();
+        // slice of nodes omitting Input and Output:
+        let replacement_inner_nodes = &replacement_nodes[2..];
         let self_output_node = h.children(self.parent).nth(1).unwrap();
         let replacement_output_node = *replacement_nodes.get(1).unwrap();
         for &node in replacement_inner_nodes {
@@ -115,7 +199,7 @@ impl Rewrite for SimpleReplacement {
                 }
             }
         }
-        // 3.2. For each p = self.nu_inp[q] such that q is not an Output port, add an edge from the
+        // 2.2. For each p = self.nu_inp[q] such that q is not an Output port, add an edge from the
         // predecessor of p to (the new copy of) q.
         for ((rep_inp_node, rep_inp_port), (rem_inp_node, rem_inp_port)) in &self.nu_inp {
             if self.replacement.get_optype(*rep_inp_node).tag() != OpTag::Output {
@@ -135,7 +219,7 @@ impl Rewrite for SimpleReplacement {
                 .unwrap();
             }
         }
-        // 3.3. For each q = self.nu_out[p] such that the predecessor of q is not an Input port, add an
+        // 2.3. For each q = self.nu_out[p] such that the predecessor of q is not an Input port, add an
         // edge from (the new copy of) the predecessor of q to p.
         for ((rem_out_node, rem_out_port), rep_out_port) in &self.nu_out {
             let (rep_out_pred_node, rep_out_pred_port) = self
@@ -155,7 +239,7 @@ impl Rewrite for SimpleReplacement {
                 .unwrap();
             }
         }
-        // 3.4. For each q = self.nu_out[p1], p0 = self.nu_inp[q], add an edge from the predecessor of p0
+        // 2.4. For each q = self.nu_out[p1], p0 = self.nu_inp[q], add an edge from the predecessor of p0
         // to p1.
         for ((rem_out_node, rem_out_port), &rep_out_port) in &self.nu_out {
             let rem_inp_nodeport = self.nu_inp.get(&(replacement_output_node, rep_out_port));
@@ -176,7 +260,7 @@ impl Rewrite for SimpleReplacement {
                 .unwrap();
             }
         }
-        // 3.5. Remove all nodes in self.removal and edges between them.
+        // 2.5. Remove all nodes in self.removal and edges between them.
         for node in &self.removal {
             h.remove_node(*node).unwrap();
         }
@@ -196,6 +280,25 @@ pub enum SimpleReplacementError {
     /// Node in replacement graph is invalid.
     #[error("A node in the replacement graph is invalid.")]
     InvalidReplacementNode(),
+    /// The replacement does not implement the same unitary as the removed
+    /// nodes.
+    #[error("The replacement is not equivalent to the removed nodes.")]
+    NotEquivalent(),
+    /// The removed nodes cannot be extracted as a circuit with the boundary of
+    /// the replacement, so their unitary cannot be checked.
+    #[error("The removed nodes cannot be extracted as a circuit for the unitary check.")]
+    UnitaryCheckUnsupported(),
+    /// The unitaries of the replacement and removed nodes could not be
+    /// compared.
+    #[error("Unitary check failed: {0}")]
+    UnitaryCheck(#[from] UnitaryError),
+}
+
+#[cfg(feature = "pyo3")]
+impl From<SimpleReplacementError> for PyErr {
+    fn from(err: SimpleReplacementError) -> Self {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(err.to_string())
+    }
 }
 
 #[cfg(test)]
@@ -216,7 +319,7 @@ mod test {
     use crate::types::{AbstractSignature, ClassicType, SimpleType};
     use crate::{type_row, Port};
 
-    use super::SimpleReplacement;
+    use super::{SimpleReplacement, SimpleReplacementError};
 
     const QB: SimpleType = SimpleType::Qubit;
 
@@ -369,13 +472,22 @@ mod test {
         nu_out.insert((h_outp_node, h_port_2), n_port_2);
         nu_out.insert((h_outp_node, h_port_3), n_port_3);
         // 5. Define the replacement
-        let r = SimpleReplacement {
-            parent: p,
-            removal: s,
-            replacement: n,
-            nu_inp,
-            nu_out,
+        let r = SimpleReplacement::new(p, s, n, nu_inp, nu_out);
+        // The gates are reordered, which changes the unitary.
+        assert_eq!(
+            r.verify_unitary(&h),
+            Err(SimpleReplacementError::NotEquivalent())
+        );
+        // Without a matching output boundary the removed nodes cannot be
+        // extracted.
+        let unmatched = SimpleReplacement {
+            nu_out: HashMap::new(),
+            ..r.clone()
         };
+        assert_eq!(
+            unmatched.verify_unitary(&h),
+            Err(SimpleReplacementError::UnitaryCheckUnsupported())
+        );
         h.apply_rewrite(r).unwrap();
         // Expect [DFG] to be replaced with:
         // ┌───┐┌───┐
@@ -446,13 +558,7 @@ mod test {
         nu_out.insert((h_node_h0, h_port_2), n_port_0);
         nu_out.insert((h_node_h1, h_port_3), n_port_1);
         // 5. Define the replacement
-        let r = SimpleReplacement {
-            parent: p,
-            removal: s,
-            replacement: n,
-            nu_inp,
-            nu_out,
-        };
+        let r = SimpleReplacement::new(p, s, n, nu_inp, nu_out);
         h.apply_rewrite(r).unwrap();
         // Expect [DFG] to be replaced with:
         // ┌───┐┌───┐
@@ -494,14 +600,9 @@ mod test {
             .filter(|&p| h.get_optype(output).signature().get(p).is_some())
             .map(|p| ((output, p), p))
             .collect();
-        h.apply_rewrite(SimpleReplacement::new(
-            parent,
-            removal,
-            replacement,
-            inputs,
-            outputs,
-        ))
-        .unwrap();
+        let r = SimpleReplacement::new(parent, removal, replacement, inputs, outputs);
+        r.verify_unitary(&h).unwrap();
+        h.apply_rewrite(r).unwrap();
 
         // They should be the same, up to node indices
         assert_eq!(h.edge_count(), orig.edge_count());
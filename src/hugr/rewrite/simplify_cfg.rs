@@ -0,0 +1,521 @@
+//! Rewrites simplifying the control flow graph of a [`CFG`] node.
+//!
+//! [`CFG`]: crate::ops::CFG
+
+use std::collections::HashSet;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::utils::{
+    add_unary_predicate, is_order, predicate_tag, remove_if_unused, remove_subtree, unpack_payload,
+};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::{HugrMut, HugrView, NodeType};
+use crate::ops::dataflow::IOTrait;
+use crate::ops::{BasicBlock, OpTag, OpTrait, OpType, Output};
+use crate::types::{ClassicRow, SimpleRow, SimpleType};
+use crate::{Direction, Hugr, Node, Port};
+
+/// Merges a basic block with its unique successor, when the block is that
+/// successor's only predecessor.
+///
+/// The body of the successor is moved into the block, after the block's own
+/// body. If the predicate carries values to the successor, it must be produced
+/// by a [`LeafOp::Tag`] operation so that the values can be extracted.
+///
+/// [`LeafOp::Tag`]: crate::ops::LeafOp::Tag
+pub struct MergeBasicBlocks {
+    block: Node,
+}
+
+impl MergeBasicBlocks {
+    /// Create a new rewrite merging the unique successor of `block` into it.
+    pub fn new(block: Node) -> Self {
+        Self { block }
+    }
+
+    /// Returns the successor that will be merged into the block.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the block does not have a unique successor, or if the successor
+    /// cannot be merged into it.
+    pub fn successor(&self, h: &Hugr) -> Result<Node, CfgRewriteError> {
+        let (_, variants, _) = dataflow_block(h, self.block)?;
+        let succ = unique_successor(h, self.block)
+            .ok_or(CfgRewriteError::MultipleSuccessors(self.block))?;
+        dataflow_block(h, succ)?;
+        if succ == self.block
+            || is_entry(h, succ)
+            || h.linked_ports(succ, Port::new_incoming(0)).count() != 1
+        {
+            return Err(CfgRewriteError::MultiplePredecessors(succ));
+        }
+        if !variants[0].is_empty() {
+            let (_, out) = io_nodes(h, self.block);
+            let payload = predicate_source(h, out)
+                .and_then(|(src, _)| predicate_tag(h, src))
+                .and_then(|(_, payload)| payload);
+            if payload.is_none() {
+                return Err(CfgRewriteError::UnknownPredicate(self.block));
+            }
+        }
+        Ok(succ)
+    }
+}
+
+impl Rewrite for MergeBasicBlocks {
+    type Error = CfgRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), CfgRewriteError> {
+        self.successor(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), CfgRewriteError> {
+        let succ = self.successor(h)?;
+        let block = self.block;
+        let (inputs, variants, _) = dataflow_block(h, block)?;
+        let (_, succ_variants, succ_outputs) = dataflow_block(h, succ)?;
+        let (block_in, block_out) = io_nodes(h, block);
+        let (succ_in, succ_out) = io_nodes(h, succ);
+        let resources = h.get_nodetype(block).input_resources.clone();
+
+        // The inputs of the successor come from the values carried by the
+        // predicate, followed by the other outputs of the block.
+        let (pred_node, _) = predicate_source(h, block_out).unwrap();
+        let row = &variants[0];
+        let mut sources = if row.is_empty() {
+            vec![]
+        } else {
+            let (_, payload) = predicate_tag(h, pred_node).unwrap();
+            unpack_payload(h, block_out, payload.unwrap(), row, &resources)
+        };
+        sources.extend(
+            h.node_inputs(block_out)
+                .skip(1)
+                .filter(|p| !is_order(h, block_out, *p))
+                .map(|p| h.linked_ports(block_out, p).next().unwrap())
+                .collect_vec(),
+        );
+        for p in h.node_outputs(succ_in).collect_vec() {
+            for (tgt, tgt_port) in h.linked_ports(succ_in, p).collect_vec() {
+                if is_order(h, succ_in, p) {
+                    h.add_other_edge(block_in, tgt).unwrap();
+                } else {
+                    let (src, src_port) = sources[p.index()];
+                    h.connect(src, src_port.index(), tgt, tgt_port.index())
+                        .unwrap();
+                }
+            }
+        }
+        // Nodes that had to complete before the end of the block now have to
+        // complete before the end of the merged block.
+        if let Some(p) = h
+            .get_optype(block_out)
+            .other_port_index(Direction::Incoming)
+        {
+            for (src, _) in h.linked_ports(block_out, p).collect_vec() {
+                h.add_other_edge(src, succ_out).unwrap();
+            }
+        }
+        h.remove_node(block_out).unwrap();
+        h.remove_node(succ_in).unwrap();
+        remove_if_unused(h, pred_node);
+
+        h.move_after_sibling(succ_out, block_in).unwrap();
+        for child in h.children(succ).collect_vec() {
+            h.set_parent(child, block).unwrap();
+        }
+
+        // The merged block branches like the successor.
+        let successors = h
+            .node_outputs(succ)
+            .map(|p| {
+                h.linked_ports(succ, p)
+                    .exactly_one()
+                    .ok()
+                    .map(|(n, _)| if n == succ { block } else { n })
+            })
+            .collect_vec();
+        let merged = NodeType {
+            op: BasicBlock::DFB {
+                inputs,
+                predicate_variants: succ_variants,
+                other_outputs: succ_outputs,
+            }
+            .into(),
+            input_resources: resources,
+        };
+        set_block_op(h, block, merged, successors);
+        h.remove_node(succ).unwrap();
+        Ok(())
+    }
+}
+
+/// Removes an empty basic block that forwards its inputs unchanged to its
+/// unique successor, redirecting its predecessors to the successor.
+///
+/// The block may only contain the nodes computing its predicate, which must
+/// not depend on the block's inputs.
+pub struct BypassBlock {
+    block: Node,
+}
+
+impl BypassBlock {
+    /// Create a new rewrite removing `block`.
+    pub fn new(block: Node) -> Self {
+        Self { block }
+    }
+
+    /// Returns the successor the predecessors of the block are redirected to.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the block does not have a unique successor, or if it does not
+    /// just forward its inputs.
+    pub fn successor(&self, h: &Hugr) -> Result<Node, CfgRewriteError> {
+        let (inputs, variants, outputs) = dataflow_block(h, self.block)?;
+        let succ = unique_successor(h, self.block)
+            .ok_or(CfgRewriteError::MultipleSuccessors(self.block))?;
+        if succ == self.block || is_entry(h, self.block) {
+            return Err(CfgRewriteError::NotForwarding(self.block));
+        }
+        if !variants[0].is_empty() || inputs != outputs {
+            return Err(CfgRewriteError::NotForwarding(self.block));
+        }
+
+        let (block_in, block_out) = io_nodes(h, self.block);
+        let forwards_inputs = (0..outputs.len()).all(|p| {
+            h.linked_ports(block_out, Port::new_incoming(p + 1))
+                .exactly_one()
+                .ok()
+                == Some((block_in, Port::new_outgoing(p)))
+        });
+        // All the other nodes must be used to compute the predicate, from
+        // values independent of the inputs. Order edges are ignored.
+        let mut predicate_cone = HashSet::new();
+        let mut stack = predicate_source(h, block_out)
+            .map(|(n, _)| n)
+            .into_iter()
+            .collect_vec();
+        while let Some(n) = stack.pop() {
+            if h.get_parent(n) == Some(self.block) && predicate_cone.insert(n) {
+                for p in h.node_inputs(n).filter(|p| !is_order(h, n, *p)) {
+                    stack.extend(h.linked_ports(n, p).map(|(src, _)| src));
+                }
+            }
+        }
+        let only_predicate = !predicate_cone.contains(&block_in)
+            && h.children(self.block)
+                .skip(2)
+                .all(|n| predicate_cone.contains(&n));
+        if !forwards_inputs || !only_predicate {
+            return Err(CfgRewriteError::NotForwarding(self.block));
+        }
+        Ok(succ)
+    }
+}
+
+impl Rewrite for BypassBlock {
+    type Error = CfgRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), CfgRewriteError> {
+        self.successor(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), CfgRewriteError> {
+        let succ = self.successor(h)?;
+        for (pred, pred_port) in h
+            .linked_ports(self.block, Port::new_incoming(0))
+            .collect_vec()
+        {
+            h.connect(pred, pred_port.index(), succ, 0).unwrap();
+        }
+        remove_subtree(h, self.block);
+        Ok(())
+    }
+}
+
+/// Replaces the branching of a basic block whose successors are all the same
+/// block with an unconditional branch.
+///
+/// The predicate of the block becomes a load of
+/// [`Const::simple_unary_predicate`]. The predicate must not carry any
+/// values.
+///
+/// [`Const::simple_unary_predicate`]: crate::ops::Const::simple_unary_predicate
+pub struct SimplifyBranch {
+    block: Node,
+}
+
+impl SimplifyBranch {
+    /// Create a new rewrite simplifying the branching of `block`.
+    pub fn new(block: Node) -> Self {
+        Self { block }
+    }
+
+    /// Returns the unique successor of the block.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the block has distinct successors, if the predicate carries
+    /// values, or if it is already a constant unary predicate.
+    pub fn successor(&self, h: &Hugr) -> Result<Node, CfgRewriteError> {
+        let (_, variants, _) = dataflow_block(h, self.block)?;
+        let succ = h
+            .output_neighbours(self.block)
+            .unique()
+            .exactly_one()
+            .map_err(|_| CfgRewriteError::MultipleSuccessors(self.block))?;
+        if variants.iter().any(|row| !row.is_empty()) {
+            return Err(CfgRewriteError::NotUnconditional(self.block));
+        }
+        let (_, block_out) = io_nodes(h, self.block);
+        let constant_predicate = predicate_source(h, block_out)
+            .is_some_and(|(src, _)| h.get_optype(src).tag() == OpTag::LoadConst);
+        if variants.len() == 1 && constant_predicate {
+            return Err(CfgRewriteError::NotUnconditional(self.block));
+        }
+        Ok(succ)
+    }
+}
+
+impl Rewrite for SimplifyBranch {
+    type Error = CfgRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), CfgRewriteError> {
+        self.successor(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), CfgRewriteError> {
+        let succ = self.successor(h)?;
+        let block = self.block;
+        let (inputs, _, other_outputs) = dataflow_block(h, block)?;
+        let (_, block_out) = io_nodes(h, block);
+        let resources = h.get_nodetype(block).input_resources.clone();
+        let node_type = |op: OpType| NodeType {
+            op,
+            input_resources: resources.clone(),
+        };
+
+        // Load the unary predicate from a constant in the block.
+        let (old_pred, _) = predicate_source(h, block_out).unwrap();
+        h.disconnect(block_out, Port::new_incoming(0)).unwrap();
+        let load = add_unary_predicate(h, block, &resources);
+        h.connect(load, 0, block_out, 0).unwrap();
+        remove_if_unused(h, old_pred);
+
+        let variants = vec![ClassicRow::new()];
+        let mut out_types = SimpleRow::from(vec![SimpleType::new_simple_predicate(1)]);
+        out_types.to_mut().extend(other_outputs.iter().cloned());
+        let out_resources = h.get_nodetype(block_out).input_resources.clone();
+        h.replace_op(
+            block_out,
+            NodeType {
+                op: Output::new(out_types).into(),
+                input_resources: out_resources,
+            },
+        );
+        let new_op = node_type(
+            BasicBlock::DFB {
+                inputs,
+                predicate_variants: variants,
+                other_outputs,
+            }
+            .into(),
+        );
+        set_block_op(h, block, new_op, vec![Some(succ)]);
+        Ok(())
+    }
+}
+
+/// Error from a rewrite simplifying a CFG.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum CfgRewriteError {
+    /// The node is not a dataflow basic block.
+    #[error("Node {0:?} is not a dataflow basic block (found {1:?})")]
+    NotADataflowBlock(Node, OpTag),
+    /// The block has more than one successor.
+    #[error("Block {0:?} does not have a unique successor")]
+    MultipleSuccessors(Node),
+    /// The block has other predecessors, or is the entry block.
+    #[error("Block {0:?} has other predecessors")]
+    MultiplePredecessors(Node),
+    /// The values carried by the predicate of the block cannot be determined.
+    #[error("The predicate of block {0:?} cannot be determined")]
+    UnknownPredicate(Node),
+    /// The block does not just forward its inputs to its successor.
+    #[error("Block {0:?} does not just forward its inputs")]
+    NotForwarding(Node),
+    /// The branching of the block cannot be made unconditional.
+    #[error("Block {0:?} cannot branch unconditionally")]
+    NotUnconditional(Node),
+}
+
+/// The inputs, predicate variants and other outputs of a dataflow block.
+fn dataflow_block(
+    h: &Hugr,
+    block: Node,
+) -> Result<(SimpleRow, Vec<ClassicRow>, SimpleRow), CfgRewriteError> {
+    match h.get_optype(block) {
+        OpType::BasicBlock(BasicBlock::DFB {
+            inputs,
+            predicate_variants,
+            other_outputs,
+        }) => Ok((
+            inputs.clone(),
+            predicate_variants.clone(),
+            other_outputs.clone(),
+        )),
+        op => Err(CfgRewriteError::NotADataflowBlock(block, op.tag())),
+    }
+}
+
+/// Whether the block is the entry of its CFG.
+fn is_entry(h: &Hugr, block: Node) -> bool {
+    h.get_parent(block)
+        .and_then(|cfg| h.children(cfg).next())
+        .is_some_and(|entry| entry == block)
+}
+
+/// The successor of a block with a single successor port.
+fn unique_successor(h: &Hugr, block: Node) -> Option<Node> {
+    let port = h.node_outputs(block).exactly_one().ok()?;
+    h.linked_ports(block, port).next().map(|(n, _)| n)
+}
+
+/// The Input and Output nodes of a dataflow parent.
+fn io_nodes(h: &Hugr, parent: Node) -> (Node, Node) {
+    h.children(parent).take(2).collect_tuple().unwrap()
+}
+
+/// The source of the predicate of a block, given its Output node.
+fn predicate_source(h: &Hugr, out: Node) -> Option<(Node, Port)> {
+    h.linked_ports(out, Port::new_incoming(0)).next()
+}
+
+/// Replaces the operation of a block, and connects its successor ports.
+fn set_block_op(h: &mut Hugr, block: Node, op: NodeType, successors: Vec<Option<Node>>) {
+    for p in h.node_outputs(block).collect_vec() {
+        h.disconnect(block, p).unwrap();
+    }
+    h.set_num_ports(block, op.input_count(), op.output_count());
+    h.replace_op(block, op);
+    for (p, succ) in successors.into_iter().enumerate() {
+        if let Some(succ) = succ {
+            h.connect(block, p, succ, 0).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, CFGBuilder, Container, Dataflow, HugrBuilder};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::{Const, LeafOp};
+    use crate::type_row;
+    use crate::types::ClassicType;
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    fn count_blocks(h: &Hugr) -> usize {
+        h.nodes()
+            .filter(|n| OpTag::BasicBlock.is_superset(h.get_optype(*n).tag()))
+            .count()
+    }
+
+    /// entry -> middle -> exit, where the entry passes a value to `middle`
+    /// through its predicate.
+    fn build_chain() -> Result<(Hugr, [Node; 2]), BuildError> {
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let mut entry_b =
+            cfg_builder.entry_builder(vec![vec![ClassicType::i64()].into()], type_row![])?;
+        let [n] = entry_b.input_wires_arr();
+        let pred = entry_b.make_predicate(0, [vec![ClassicType::i64()].into()], [n])?;
+        let entry = entry_b.finish_with_outputs(pred, [])?;
+
+        let mut middle_b = cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], 1)?;
+        let [n] = middle_b.input_wires_arr();
+        let [n] = middle_b
+            .add_dataflow_op(LeafOp::Noop { ty: NAT }, [n])?
+            .outputs_arr();
+        let unit = middle_b.add_load_const(Const::simple_unary_predicate())?;
+        let middle = middle_b.finish_with_outputs(unit, [n])?;
+
+        let exit = cfg_builder.exit_block();
+        cfg_builder.branch(&entry, 0, &middle)?;
+        cfg_builder.branch(&middle, 0, &exit)?;
+        let h = cfg_builder.finish_hugr()?;
+        Ok((h, [entry.node(), middle.node()]))
+    }
+
+    #[test]
+    fn merge_chain() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, [entry, middle]) = build_chain()?;
+        let rw = MergeBasicBlocks::new(entry);
+        assert_eq!(rw.successor(&h), Ok(middle));
+        h.apply_rewrite(rw)?;
+        h.validate()?;
+        assert_eq!(count_blocks(&h), 2);
+        assert_matches!(
+            MergeBasicBlocks::new(entry).verify(&h),
+            Err(CfgRewriteError::NotADataflowBlock(..))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bypass_and_simplify_branch() -> Result<(), Box<dyn std::error::Error>> {
+        //         /-> a -\
+        // entry -<        >-> exit
+        //         \-> b -/
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let pred_const = cfg_builder.add_constant(Const::simple_predicate(0, 2))?;
+        let unit_const = cfg_builder.add_constant(Const::simple_unary_predicate())?;
+        let mut entry_b = cfg_builder.simple_entry_builder(type_row![NAT], 2)?;
+        let [n] = entry_b.input_wires_arr();
+        let pred = entry_b.load_const(&pred_const)?;
+        let entry = entry_b.finish_with_outputs(pred, [n])?;
+        let mut blocks = Vec::new();
+        for _ in 0..2 {
+            let mut block_b =
+                cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], 1)?;
+            let [n] = block_b.input_wires_arr();
+            let unit = block_b.load_const(&unit_const)?;
+            blocks.push(block_b.finish_with_outputs(unit, [n])?);
+        }
+        let exit = cfg_builder.exit_block();
+        cfg_builder.branch(&entry, 0, &blocks[0])?;
+        cfg_builder.branch(&entry, 1, &blocks[1])?;
+        cfg_builder.branch(&blocks[0], 0, &exit)?;
+        cfg_builder.branch(&blocks[1], 0, &exit)?;
+        let mut h = cfg_builder.finish_hugr()?;
+
+        assert_eq!(
+            SimplifyBranch::new(entry.node()).verify(&h),
+            Err(CfgRewriteError::MultipleSuccessors(entry.node()))
+        );
+        for block in &blocks {
+            h.apply_rewrite(BypassBlock::new(block.node()))?;
+        }
+        h.validate()?;
+        assert_eq!(count_blocks(&h), 2);
+
+        // Both branches of the entry now lead to the exit.
+        h.apply_rewrite(SimplifyBranch::new(entry.node()))?;
+        h.validate()?;
+        assert_eq!(h.node_outputs(entry.node()).count(), 1);
+        assert_eq!(
+            SimplifyBranch::new(entry.node()).verify(&h),
+            Err(CfgRewriteError::NotUnconditional(entry.node()))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+//! Rewrites for peeling and unrolling [`TailLoop`] nodes.
+
+use std::iter;
+
+use itertools::Itertools;
+use thiserror::Error;
+
+use super::conditional::{ConditionalRewriteError, ResolveConditional};
+use super::utils::{copy_descendants, flatten_dfg, move_link, order_neighbours, predicate_tag};
+use crate::hugr::rewrite::Rewrite;
+use crate::hugr::{HugrMut, HugrView, NodeType};
+use crate::ops::dataflow::IOTrait;
+use crate::ops::{Case, Conditional, Input, LeafOp, OpTag, OpTrait, OpType, Output, TailLoop, DFG};
+use crate::types::AbstractSignature;
+use crate::{Direction, Hugr, Node, Port};
+
+/// The maximum number of iterations [`UnrollTailLoop`] will unroll, unless
+/// configured otherwise.
+pub const DEFAULT_MAX_ITERATIONS: usize = 1024;
+
+/// Peels the first iteration of a [`TailLoop`].
+///
+/// The loop is replaced by a copy of its body, followed by a [`Conditional`]
+/// on the predicate output by that body. The first case of the conditional
+/// contains the original loop, which is only entered if the first iteration
+/// requested another. The second case passes the outputs through.
+pub struct PeelTailLoop {
+    tail_loop: Node,
+}
+
+impl PeelTailLoop {
+    /// Create a new rewrite peeling the first iteration of `tail_loop`.
+    pub fn new(tail_loop: Node) -> Self {
+        Self { tail_loop }
+    }
+}
+
+impl Rewrite for PeelTailLoop {
+    type Error = TailLoopRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), TailLoopRewriteError> {
+        tail_loop_op(h, self.tail_loop).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), TailLoopRewriteError> {
+        let loop_op = tail_loop_op(h, self.tail_loop)?;
+        let tail_loop = self.tail_loop;
+        let resources = h.get_nodetype(tail_loop).input_resources.clone();
+        let node_type = |op: OpType| NodeType {
+            op,
+            input_resources: resources.clone(),
+        };
+        let order_preds = order_neighbours(h, tail_loop, Direction::Incoming);
+        let outputs = h.get_optype(tail_loop).signature().output;
+
+        // The first iteration, as a DFG in front of the loop.
+        let body = h.add_node(node_type(
+            DFG {
+                signature: AbstractSignature::new_df(
+                    loop_op.body_input_row(),
+                    loop_op.body_output_row(),
+                ),
+            }
+            .into(),
+        ));
+        h.move_before_sibling(body, tail_loop).unwrap();
+        copy_descendants(h, tail_loop, body);
+        for p in h.node_inputs(tail_loop).collect_vec() {
+            move_link(h, tail_loop, p, body);
+        }
+
+        // The conditional replacing the loop, with a case for each variant of
+        // the body's predicate.
+        let cond_op = Conditional {
+            predicate_inputs: vec![loop_op.just_inputs.clone(), loop_op.just_outputs.clone()],
+            other_inputs: loop_op.rest.clone(),
+            outputs: outputs.clone(),
+        };
+        let cond = h.add_node(node_type(cond_op.clone().into()));
+        h.move_before_sibling(cond, tail_loop).unwrap();
+        for p in 0..loop_op.body_output_row().len() {
+            h.connect(body, p, cond, p).unwrap();
+        }
+        for p in h.node_outputs(tail_loop).collect_vec() {
+            move_link(h, tail_loop, p, cond);
+        }
+        // Non-local edges into the loop body require order edges to the
+        // conditional now containing it.
+        for pred in order_preds {
+            h.add_other_edge(pred, cond).unwrap();
+        }
+
+        for (case_index, inner) in [(0, Some(tail_loop)), (1, None)] {
+            let inputs = cond_op.case_input_row(case_index).unwrap();
+            let case = h
+                .add_node_with_parent(
+                    cond,
+                    node_type(
+                        Case {
+                            signature: AbstractSignature::new_df(inputs.clone(), outputs.clone()),
+                        }
+                        .into(),
+                    ),
+                )
+                .unwrap();
+            let case_in = h
+                .add_node_with_parent(case, node_type(Input::new(inputs.clone()).into()))
+                .unwrap();
+            let case_out = h
+                .add_node_with_parent(case, node_type(Output::new(outputs.clone()).into()))
+                .unwrap();
+            match inner {
+                Some(inner) => {
+                    h.set_parent(inner, case).unwrap();
+                    for p in 0..inputs.len() {
+                        h.connect(case_in, p, inner, p).unwrap();
+                    }
+                    for p in 0..outputs.len() {
+                        h.connect(inner, p, case_out, p).unwrap();
+                    }
+                }
+                None => {
+                    for p in 0..inputs.len() {
+                        h.connect(case_in, p, case_out, p).unwrap();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fully unrolls a [`TailLoop`] whose trip count can be determined from
+/// constant predicates.
+///
+/// The loop body is repeatedly copied into the parent sibling graph, until
+/// the copy produces the "break" predicate. At each iteration the predicate
+/// output by the body must be produced by a [`LeafOp::Tag`] operation or
+/// loaded from a constant, possibly through a [`LeafOp::Noop`]. The values
+/// carried by the predicate are extracted with [`LeafOp::UnpackTuple`].
+///
+/// Unrolling fails if the trip count cannot be determined, or if it exceeds
+/// a maximum number of iterations ([`DEFAULT_MAX_ITERATIONS`] by default).
+/// The trip count is computed without modifying the Hugr, see
+/// [`UnrollTailLoop::trip_count`], and the loop is then unrolled in place by
+/// peeling one iteration at a time with [`PeelTailLoop`].
+pub struct UnrollTailLoop {
+    tail_loop: Node,
+    max_iterations: usize,
+}
+
+impl UnrollTailLoop {
+    /// Create a new rewrite fully unrolling `tail_loop`.
+    pub fn new(tail_loop: Node) -> Self {
+        Self {
+            tail_loop,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+
+    /// Set the maximum number of iterations that may be unrolled.
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Computes the number of iterations of the loop, without modifying the
+    /// Hugr.
+    ///
+    /// The values carried between iterations are tracked symbolically, either
+    /// as the wire of the Hugr they are copied from or, for the values
+    /// extracted from a predicate, as unknown.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the node is not a TailLoop, if the trip count cannot be
+    /// determined or if it exceeds the maximum number of iterations.
+    pub fn trip_count(&self, h: &Hugr) -> Result<usize, TailLoopRewriteError> {
+        let loop_op = tail_loop_op(h, self.tail_loop)?;
+        let tail_loop = self.tail_loop;
+        let [body_in, body_out] = h
+            .children(tail_loop)
+            .take(2)
+            .collect_vec()
+            .try_into()
+            .unwrap();
+        // The source of a wire of the body at the current iteration, given the
+        // values of its inputs. Noops in the body are skipped, so that the
+        // result never refers to the body's Input node.
+        let resolve = |inputs: &[Option<(Node, Port)>], (mut src, mut src_port): (Node, Port)| loop {
+            if src == body_in {
+                return inputs[src_port.index()];
+            }
+            match h.get_optype(src) {
+                OpType::LeafOp(LeafOp::Noop { .. }) if h.get_parent(src) == Some(tail_loop) => {
+                    (src, src_port) = h
+                        .linked_ports(src, Port::new_incoming(0))
+                        .exactly_one()
+                        .ok()?;
+                }
+                _ => return Some((src, src_port)),
+            }
+        };
+
+        let mut inputs: Vec<Option<(Node, Port)>> = (0..loop_op.body_input_row().len())
+            .map(|p| h.linked_ports(tail_loop, Port::new_incoming(p)).next())
+            .collect();
+        for iteration in 0..self.max_iterations {
+            let outputs = h
+                .node_inputs(body_out)
+                .take(loop_op.body_output_row().len())
+                .map(|p| {
+                    let src = h.linked_ports(body_out, p).exactly_one().ok().unwrap();
+                    resolve(&inputs, src)
+                })
+                .collect_vec();
+            let (pred, _) =
+                outputs[0].ok_or(TailLoopRewriteError::UnknownTripCount { iteration })?;
+            let (tag, payload) = predicate_tag(h, pred)
+                .ok_or(TailLoopRewriteError::UnknownTripCount { iteration })?;
+            let row = match tag {
+                0 => &loop_op.just_inputs,
+                _ => &loop_op.just_outputs,
+            };
+            if payload.is_none() && !row.is_empty() {
+                return Err(TailLoopRewriteError::ConstantWithValues(pred));
+            }
+            if tag != 0 {
+                return Ok(iteration + 1);
+            }
+            inputs = iter::repeat(None)
+                .take(row.len())
+                .chain(outputs.into_iter().skip(1))
+                .collect();
+        }
+        Err(TailLoopRewriteError::TooManyIterations(self.max_iterations))
+    }
+}
+
+impl Rewrite for UnrollTailLoop {
+    type Error = TailLoopRewriteError;
+    const UNCHANGED_ON_FAILURE: bool = true;
+
+    fn verify(&self, h: &Hugr) -> Result<(), TailLoopRewriteError> {
+        self.trip_count(h).map(|_| ())
+    }
+
+    fn apply(self, h: &mut Hugr) -> Result<(), TailLoopRewriteError> {
+        let iterations = self.trip_count(h)?;
+        // Each iteration is peeled off the loop, and the conditional deciding
+        // whether to run the rest of the loop is resolved. The last one
+        // removes the loop.
+        for iteration in 0..iterations {
+            PeelTailLoop::new(self.tail_loop).apply(h)?;
+            let case = h.get_parent(self.tail_loop).unwrap();
+            let cond = h.get_parent(case).unwrap();
+            let (body, _) = h
+                .linked_ports(cond, Port::new_incoming(0))
+                .exactly_one()
+                .ok()
+                .unwrap();
+            flatten_dfg(h, body);
+            ResolveConditional::flattened(cond)
+                .apply(h)
+                .map_err(|error| TailLoopRewriteError::UnresolvedIteration { iteration, error })?;
+        }
+        Ok(())
+    }
+}
+
+/// Error from a [`PeelTailLoop`] or [`UnrollTailLoop`] rewrite.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TailLoopRewriteError {
+    /// The node to rewrite is not a TailLoop.
+    #[error("Node {0:?} is not a TailLoop (found {1:?})")]
+    NotATailLoop(Node, OpTag),
+    /// The predicate of the loop body at some iteration is not a constant.
+    #[error("Could not determine whether the loop exits after iteration {iteration}")]
+    UnknownTripCount {
+        /// The iteration whose predicate is not known, starting from 0.
+        iteration: usize,
+    },
+    /// The loop did not exit within the maximum number of iterations.
+    #[error("The loop did not exit within {0} iterations")]
+    TooManyIterations(usize),
+    /// A constant predicate carries values, which cannot be extracted.
+    #[error("Constant predicate {0:?} carries values")]
+    ConstantWithValues(Node),
+    /// The conditional following a peeled iteration could not be resolved.
+    ///
+    /// This is not expected once the trip count has been determined, as the
+    /// peeled predicates are copies of the ones it inspected.
+    #[error("Could not resolve the conditional after iteration {iteration}: {error}")]
+    UnresolvedIteration {
+        /// The peeled iteration, starting from 0.
+        iteration: usize,
+        /// The error resolving its conditional.
+        error: ConditionalRewriteError,
+    },
+}
+
+fn tail_loop_op(h: &Hugr, n: Node) -> Result<TailLoop, TailLoopRewriteError> {
+    match h.get_optype(n) {
+        OpType::TailLoop(op) => Ok(op.clone()),
+        op => Err(TailLoopRewriteError::NotATailLoop(n, op.tag())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{BuildError, DFGBuilder, Dataflow, DataflowHugr};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::{Const, LeafOp};
+    use crate::types::{ClassicType, SimpleType};
+    use crate::{classic_row, type_row};
+
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+    const QB: SimpleType = SimpleType::Qubit;
+
+    fn count_tag(h: &Hugr, tag: OpTag) -> usize {
+        h.nodes().filter(|n| h.get_optype(*n).tag() == tag).count()
+    }
+
+    /// A loop with a natural number as only input and output, and a qubit
+    /// carried through, applying `H` to the qubit and breaking immediately.
+    fn build_break_loop() -> Result<(Hugr, Node), BuildError> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![NAT, QB],
+            type_row![NAT, QB],
+        ))?;
+        let [n, q] = b.input_wires_arr();
+        let mut loop_b = b.tail_loop_builder(
+            vec![(ClassicType::i64(), n)],
+            vec![(QB, q)],
+            classic_row![ClassicType::i64()],
+        )?;
+        let signature = loop_b.loop_signature()?.clone();
+        let [n, q] = loop_b.input_wires_arr();
+        let [q] = loop_b.add_dataflow_op(LeafOp::H, [q])?.outputs_arr();
+        let break_wire = loop_b.make_break(signature, [n])?;
+        let loop_id = loop_b.finish_with_outputs(break_wire, [q])?;
+        let h = b.finish_hugr_with_outputs(loop_id.outputs())?;
+        Ok((h, loop_id.node()))
+    }
+
+    #[test]
+    fn peel() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, tail_loop) = build_break_loop()?;
+        h.apply_rewrite(PeelTailLoop::new(tail_loop))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::TailLoop), 1);
+        assert_eq!(count_tag(&h, OpTag::Conditional), 1);
+        assert_eq!(count_tag(&h, OpTag::Dfg), 2);
+        // The loop now lives in the first case of the conditional.
+        let case = h.get_parent(tail_loop).unwrap();
+        let cond = h.get_parent(case).unwrap();
+        assert_eq!(h.children(cond).next(), Some(case));
+        Ok(())
+    }
+
+    #[test]
+    fn unroll_once() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, tail_loop) = build_break_loop()?;
+        h.apply_rewrite(UnrollTailLoop::new(tail_loop))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::TailLoop), 0);
+        assert_eq!(count_tag(&h, OpTag::Dfg), 1);
+        let num_h = h
+            .nodes()
+            .filter(|n| h.get_optype(*n) == &LeafOp::H.into())
+            .count();
+        assert_eq!(num_h, 1);
+        Ok(())
+    }
+
+    /// A loop carrying three simple predicates, which are shifted at each
+    /// iteration with the first one deciding whether to exit.
+    fn build_shift_loop(flags: [usize; 3]) -> Result<(Hugr, Node), BuildError> {
+        let pred = SimpleType::new_simple_predicate(2);
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB],
+            vec![QB, pred.clone(), pred.clone()],
+        ))?;
+        let [q] = b.input_wires_arr();
+        let mut inputs = vec![(QB, q)];
+        for flag in flags {
+            let w = b.add_load_const(Const::simple_predicate(flag, 2))?;
+            inputs.push((pred.clone(), w));
+        }
+        let mut loop_b = b.tail_loop_builder(vec![], inputs, classic_row![])?;
+        let [q, p0, p1, p2] = loop_b.input_wires_arr();
+        let [q] = loop_b.add_dataflow_op(LeafOp::H, [q])?.outputs_arr();
+        let exit = loop_b.add_load_const(Const::simple_predicate(1, 2))?;
+        let loop_id = loop_b.finish_with_outputs(p0, [q, p1, p2, exit])?;
+        let [q, _, p1, p2] = loop_id.outputs_arr();
+        let h = b.finish_hugr_with_outputs([q, p1, p2])?;
+        Ok((h, loop_id.node()))
+    }
+
+    #[test]
+    fn unroll_shift() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, tail_loop) = build_shift_loop([0, 0, 1])?;
+        assert_eq!(UnrollTailLoop::new(tail_loop).trip_count(&h), Ok(3));
+        h.apply_rewrite(UnrollTailLoop::new(tail_loop))?;
+        h.validate()?;
+        assert_eq!(count_tag(&h, OpTag::TailLoop), 0);
+        let num_h = h
+            .nodes()
+            .filter(|n| h.get_optype(*n) == &LeafOp::H.into())
+            .count();
+        assert_eq!(num_h, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn unroll_failure() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut h, tail_loop) = build_shift_loop([0, 0, 1])?;
+        let backup = h.clone();
+        assert_eq!(
+            h.apply_rewrite(UnrollTailLoop::new(tail_loop).with_max_iterations(2)),
+            Err(TailLoopRewriteError::TooManyIterations(2))
+        );
+        assert_eq!(h, backup);
+
+        // The predicate comes from the inputs of the DFG.
+        let pred = SimpleType::new_simple_predicate(2);
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            vec![pred.clone()],
+            vec![pred.clone()],
+        ))?;
+        let [p] = b.input_wires_arr();
+        let loop_b = b.tail_loop_builder(vec![], vec![(pred, p)], classic_row![])?;
+        let [p] = loop_b.input_wires_arr();
+        let loop_id = loop_b.finish_with_outputs(p, [p])?;
+        let mut h = b.finish_hugr_with_outputs(loop_id.outputs())?;
+        assert_matches!(
+            h.apply_rewrite(UnrollTailLoop::new(loop_id.node())),
+            Err(TailLoopRewriteError::UnknownTripCount { iteration: 0 })
+        );
+        assert_matches!(
+            PeelTailLoop::new(h.root()).verify(&h),
+            Err(TailLoopRewriteError::NotATailLoop(..))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+//! Utilities shared by the rewrites operating on dataflow regions.
+
+use std::collections::{HashMap, VecDeque};
+
+use itertools::Itertools;
+
+use crate::hugr::{HugrMut, HugrView, NodeType};
+use crate::ops::{BasicBlock, Const, ConstValue, LeafOp, LoadConstant, OpType, DFG};
+use crate::resource::ResourceSet;
+use crate::types::{AbstractSignature, ClassicRow, EdgeKind, SimpleType};
+use crate::values::{ContainerValue, HashableValue};
+use crate::{Direction, Hugr, Node, Port};
+
+/// Copies all the descendants of `src_root` to be descendants of `dst_root`,
+/// along with the edges between them. Edges coming from outside the copied
+/// hierarchy are connected to the same source as the original.
+///
+/// Returns a map from the original nodes to their copies.
+pub(crate) fn copy_descendants(
+    h: &mut Hugr,
+    src_root: Node,
+    dst_root: Node,
+) -> HashMap<Node, Node> {
+    // Collect all the nodes before inserting any, as `dst_root` may itself be
+    // a descendant of `src_root` for recursive functions.
+    let mut nodes = Vec::new();
+    let mut queue = VecDeque::from([src_root]);
+    while let Some(parent) = queue.pop_front() {
+        for child in h.children(parent).filter(|c| *c != dst_root) {
+            nodes.push((child, parent));
+            queue.push_back(child);
+        }
+    }
+
+    let mut node_map = HashMap::from([(src_root, dst_root)]);
+    for (old, old_parent) in nodes {
+        let new = h
+            .add_node_with_parent(node_map[&old_parent], h.get_nodetype(old).clone())
+            .unwrap();
+        h.set_metadata(new, h.get_metadata(old).clone());
+        node_map.insert(old, new);
+    }
+    node_map.remove(&src_root);
+
+    for (&old, &new) in node_map.iter() {
+        for p in h.node_inputs(old).collect_vec() {
+            for (src, src_port) in h.linked_ports(old, p).collect_vec() {
+                let new_src = node_map.get(&src).copied().unwrap_or(src);
+                h.connect(new_src, src_port.index(), new, p.index())
+                    .unwrap();
+            }
+        }
+    }
+    node_map
+}
+
+/// Replaces a [`DFG`](crate::ops::DFG) node with its children, connecting the edges to its
+/// Input and Output nodes directly to the sources and targets of the DFG.
+pub(crate) fn flatten_dfg(h: &mut Hugr, dfg: Node) {
+    let parent = h.get_parent(dfg).unwrap();
+    let [inp, out] = h.children(dfg).take(2).collect_vec().try_into().unwrap();
+    // Order edges from the DFG's Input (resp. to its Output) are replaced by
+    // edges from the DFG's Order predecessors (resp. to its successors), or
+    // the parent's Input (resp. Output) so that the nodes stay in the causal
+    // cone of the sibling graph.
+    let [parent_inp, parent_out] = h.children(parent).take(2).collect_vec().try_into().unwrap();
+    let mut order_preds = order_neighbours(h, dfg, Direction::Incoming);
+    if order_preds.is_empty() {
+        order_preds.push(parent_inp);
+    }
+    let mut order_succs = order_neighbours(h, dfg, Direction::Outgoing);
+    if order_succs.is_empty() {
+        order_succs.push(parent_out);
+    }
+
+    // Uses of the DFG's inputs are connected to the sources of those inputs.
+    for p in h.node_outputs(inp).collect_vec() {
+        let targets = h.linked_ports(inp, p).collect_vec();
+        if is_order(h, inp, p) {
+            for (tgt, _) in targets {
+                for &pred in &order_preds {
+                    h.add_other_edge(pred, tgt).unwrap();
+                }
+            }
+            continue;
+        }
+        let (src, src_port) = h
+            .linked_ports(dfg, Port::new_incoming(p.index()))
+            .exactly_one()
+            .ok()
+            .unwrap();
+        for (tgt, tgt_port) in targets {
+            h.disconnect(tgt, tgt_port).unwrap();
+            h.connect(src, src_port.index(), tgt, tgt_port.index())
+                .unwrap();
+            // A non-local edge from the parent's sibling graph needs an order
+            // edge to the ancestor of its target that is now a sibling.
+            if tgt != out && h.get_parent(tgt) != Some(dfg) && h.get_parent(src) == Some(parent) {
+                let ancestor = std::iter::successors(Some(tgt), |n| h.get_parent(*n))
+                    .find(|n| h.get_parent(*n) == Some(dfg))
+                    .unwrap();
+                h.add_other_edge(src, ancestor).unwrap();
+            }
+        }
+    }
+
+    // Outputs of the DFG come from the sources of the Output node.
+    for p in h.node_inputs(out).collect_vec() {
+        let sources = h.linked_ports(out, p).collect_vec();
+        if is_order(h, out, p) {
+            for (src, _) in sources {
+                for &succ in &order_succs {
+                    h.add_other_edge(src, succ).unwrap();
+                }
+            }
+            continue;
+        }
+        let (src, src_port) = sources.into_iter().exactly_one().ok().unwrap();
+        for (tgt, tgt_port) in h
+            .linked_ports(dfg, Port::new_outgoing(p.index()))
+            .collect_vec()
+        {
+            h.disconnect(tgt, tgt_port).unwrap();
+            h.connect(src, src_port.index(), tgt, tgt_port.index())
+                .unwrap();
+        }
+    }
+
+    for child in h.children(dfg).skip(2).collect_vec() {
+        h.move_before_sibling(child, dfg).unwrap();
+    }
+    h.remove_node(inp).unwrap();
+    h.remove_node(out).unwrap();
+    h.remove_node(dfg).unwrap();
+}
+
+/// Whether the port is the Order port of the node.
+pub(crate) fn is_order(h: &Hugr, n: Node, p: Port) -> bool {
+    h.get_optype(n).port_kind(p) == Some(EdgeKind::StateOrder)
+}
+
+/// The nodes connected to the Order port of `n` in the given direction.
+pub(crate) fn order_neighbours(h: &Hugr, n: Node, dir: Direction) -> Vec<Node> {
+    h.get_optype(n)
+        .other_port_index(dir)
+        .into_iter()
+        .flat_map(|p| h.linked_ports(n, p).map(|(m, _)| m))
+        .collect()
+}
+
+/// Moves all the links of port `p` of `old` to the same port of `new`.
+pub(crate) fn move_link(h: &mut Hugr, old: Node, p: Port, new: Node) {
+    let order = h.get_optype(old).other_port_index(p.direction()) == Some(p);
+    let links = h.linked_ports(old, p).collect_vec();
+    h.disconnect(old, p).unwrap();
+    for (other, other_port) in links {
+        match (order, p.direction()) {
+            (true, Direction::Incoming) => h.add_other_edge(other, new).map(|_| ()),
+            (true, Direction::Outgoing) => h.add_other_edge(new, other).map(|_| ()),
+            (false, Direction::Incoming) => h.connect(other, other_port.index(), new, p.index()),
+            (false, Direction::Outgoing) => h.connect(new, p.index(), other, other_port.index()),
+        }
+        .unwrap();
+    }
+}
+
+/// Removes a node if none of its outputs are connected.
+pub(crate) fn remove_if_unused(h: &mut Hugr, n: Node) {
+    if h.node_outputs(n).all(|p| !h.is_linked(n, p)) {
+        remove_subtree(h, n);
+    }
+}
+
+/// Turns a [`Case`](crate::ops::Case) node into a [`DFG`](crate::ops::DFG)
+/// with the same signature, with no connected ports.
+pub(crate) fn case_to_dfg(h: &mut Hugr, case: Node) {
+    let OpType::Case(case_op) = h.get_optype(case) else {
+        panic!("Node is not a Case")
+    };
+    let dfg = NodeType {
+        op: DFG {
+            signature: case_op.signature.clone(),
+        }
+        .into(),
+        input_resources: h.get_nodetype(case).input_resources.clone(),
+    };
+    h.set_num_ports(case, dfg.input_count(), dfg.output_count());
+    h.replace_op(case, dfg);
+}
+
+/// Turns a dataflow [`BasicBlock`] node into a [`DFG`] computing the
+/// predicate and other outputs of the block, disconnecting it from the other
+/// blocks.
+pub(crate) fn block_to_dfg(h: &mut Hugr, block: Node) {
+    let OpType::BasicBlock(BasicBlock::DFB {
+        inputs,
+        predicate_variants,
+        other_outputs,
+    }) = h.get_optype(block)
+    else {
+        panic!("Node is not a dataflow block")
+    };
+    let outputs = std::iter::once(SimpleType::new_predicate(predicate_variants.clone()))
+        .chain(other_outputs.iter().cloned())
+        .collect_vec();
+    let dfg = NodeType {
+        op: DFG {
+            signature: AbstractSignature::new_df(inputs.clone(), outputs),
+        }
+        .into(),
+        input_resources: h.get_nodetype(block).input_resources.clone(),
+    };
+    for p in h.all_node_ports(block).collect_vec() {
+        h.disconnect(block, p).unwrap();
+    }
+    h.set_num_ports(block, dfg.input_count(), dfg.output_count());
+    h.replace_op(block, dfg);
+}
+
+/// Turns a [`CFG`](crate::ops::CFG) node into a [`DFG`] with the same
+/// signature, keeping its edges but not its children.
+pub(crate) fn cfg_to_dfg(h: &mut Hugr, cfg: Node) {
+    let OpType::CFG(cfg_op) = h.get_optype(cfg) else {
+        panic!("Node is not a CFG")
+    };
+    let dfg = NodeType {
+        op: DFG {
+            signature: AbstractSignature::new_df(cfg_op.inputs.clone(), cfg_op.outputs.clone()),
+        }
+        .into(),
+        input_resources: h.get_nodetype(cfg).input_resources.clone(),
+    };
+    h.replace_op(cfg, dfg);
+}
+
+/// Adds a load of [`Const::simple_unary_predicate`] to a dataflow parent,
+/// from a constant added to the same parent. Returns the load node.
+pub(crate) fn add_unary_predicate(
+    h: &mut Hugr,
+    parent: Node,
+    resources: &Option<ResourceSet>,
+) -> Node {
+    let node_type = |op: OpType| NodeType {
+        op,
+        input_resources: resources.clone(),
+    };
+    let konst = Const::simple_unary_predicate();
+    let load = LoadConstant {
+        datatype: konst.const_type().clone(),
+    };
+    let konst = h
+        .add_node_with_parent(parent, node_type(konst.into()))
+        .unwrap();
+    let load = h
+        .add_node_with_parent(parent, node_type(load.into()))
+        .unwrap();
+    h.connect(konst, 0, load, 0).unwrap();
+    // Keep the load in the causal cone of the Input.
+    let inp = h.children(parent).next().unwrap();
+    h.add_other_edge(inp, load).unwrap();
+    load
+}
+
+/// Removes a node along with all its descendants.
+pub(crate) fn remove_subtree(h: &mut Hugr, root: Node) {
+    let mut stack = vec![root];
+    while let Some(n) = stack.pop() {
+        stack.extend(h.children(n));
+        h.remove_node(n).unwrap();
+    }
+}
+
+/// Returns the tag of the predicate output by `n`, along with the source of
+/// the tuple of values it carries if `n` is a [`LeafOp::Tag`].
+pub(crate) fn predicate_tag(h: &Hugr, n: Node) -> Option<(usize, Option<(Node, Port)>)> {
+    let input_src = |n: Node, p: usize| h.linked_ports(n, Port::new_incoming(p)).exactly_one().ok();
+    match h.get_optype(n) {
+        OpType::LeafOp(LeafOp::Tag { tag, .. }) => Some((*tag, Some(input_src(n, 0)?))),
+        OpType::LeafOp(LeafOp::Noop { .. }) => {
+            let (src, _) = input_src(n, 0)?;
+            predicate_tag(h, src)
+        }
+        OpType::LoadConstant(_) => {
+            let (konst, _) = input_src(n, 0)?;
+            let OpType::Const(konst) = h.get_optype(konst) else {
+                return None;
+            };
+            match konst.value() {
+                ConstValue::Hashable(HashableValue::Container(ContainerValue::Sum(tag, _)))
+                | ConstValue::Container(ContainerValue::Sum(tag, _)) => Some((*tag, None)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Extracts the values of `row` from the tuple carried by a predicate, with an
+/// `UnpackTuple` added before `before`.
+pub(crate) fn unpack_payload(
+    h: &mut Hugr,
+    before: Node,
+    (src, src_port): (Node, Port),
+    row: &ClassicRow,
+    resources: &Option<ResourceSet>,
+) -> Vec<(Node, Port)> {
+    let unpack = h.add_node(NodeType {
+        op: LeafOp::UnpackTuple {
+            tys: row.clone().map_into(),
+        }
+        .into(),
+        input_resources: resources.clone(),
+    });
+    h.move_before_sibling(unpack, before).unwrap();
+    h.connect(src, src_port.index(), unpack, 0).unwrap();
+    (0..row.len())
+        .map(|p| (unpack, Port::new_outgoing(p)))
+        .collect()
+}
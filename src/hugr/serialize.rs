@@ -1,44 +1,68 @@
 //! Serialization definition for [`Hugr`]
 //! [`Hugr`]: crate::hugr::Hugr
 
-use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use thiserror::Error;
 
 #[cfg(feature = "pyo3")]
 use pyo3::prelude::*;
 
-use crate::hugr::{Hugr, HugrMut, NodeType};
-use crate::ops::OpTrait;
-use crate::ops::OpType;
-use crate::resource::ResourceSet;
+use crate::hugr::{Hugr, HugrMut, NodeMetadata, NodeType};
+use crate::ops::custom::OpaqueOp;
+use crate::ops::{LeafOp, OpTrait, OpType};
+use crate::resource::{ResourceId, ResourceSet};
 use crate::Node;
 use portgraph::hierarchy::AttachError;
 use portgraph::{Direction, LinkError, NodeIndex, PortView};
 
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{RootSchema, Schema};
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize};
 
 use super::{HugrError, HugrView};
 
+pub mod binary;
+
+pub use binary::BinarySerializationError;
+pub mod text;
+pub use text::TextFormatError;
+
 /// A wrapper over the available HUGR serialization formats.
 ///
 /// The implementation of `Serialize` for `Hugr` encodes the graph in the most
 /// recent version of the format. We keep the `Deserialize` implementations for
-/// older versions to allow for backwards compatibility.
+/// older versions to allow for backwards compatibility, upgrading them to the
+/// most recent version when loading.
 ///
 /// Make sure to order the variants from newest to oldest, as the deserializer
 /// will try to deserialize them in order.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "version", rename_all = "lowercase")]
+#[schemars(rename = "Hugr")]
 enum Versioned {
+    /// Version 1 of the HUGR serialization format.
+    V1(SerHugrV1),
     /// Version 0 of the HUGR serialization format.
     V0(SerHugrV0),
 
     #[serde(other)]
+    #[schemars(skip)]
     Unsupported,
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+/// A version of the HUGR serialization format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SerializationVersion {
+    /// Version 0, which cannot record the number of ports of the nodes.
+    V0,
+    /// Version 1, the most recent version.
+    #[default]
+    V1,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
 struct NodeSer {
     parent: Node,
     input_resources: Option<ResourceSet>,
@@ -47,7 +71,7 @@ struct NodeSer {
 }
 
 /// Version 0 of the HUGR serialization format.
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
 struct SerHugrV0 {
     /// For each node: (parent, node_operation)
     nodes: Vec<NodeSer>,
@@ -58,6 +82,37 @@ struct SerHugrV0 {
     metadata: Vec<serde_json::Value>,
 }
 
+#[derive(Clone, Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
+struct NodeSerV1 {
+    parent: Node,
+    input_resources: Option<ResourceSet>,
+    /// The number of incoming ports of the node.
+    num_inputs: usize,
+    /// The number of outgoing ports of the node.
+    num_outputs: usize,
+    /// The metadata of the node, omitted when null.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<NodeMetadata>,
+    #[serde(flatten)]
+    op: OpType,
+}
+
+/// Version 1 of the HUGR serialization format.
+///
+/// Unlike version 0, nodes record their number of ports and their metadata,
+/// edges always give the offsets of their ports, and the resources used by
+/// the nodes are declared upfront.
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
+struct SerHugrV1 {
+    /// The resources used by the nodes, sorted.
+    #[schemars(with = "Vec<String>")]
+    resources: Vec<ResourceId>,
+    /// For each node: (parent, port counts, metadata, node_operation)
+    nodes: Vec<NodeSerV1>,
+    /// for each edge: (src, src_offset, tgt, tgt_offset)
+    edges: Vec<[(Node, usize); 2]>,
+}
+
 /// Errors that can occur while serializing a HUGR.
 #[derive(Debug, Clone, PartialEq, Error)]
 pub enum HUGRSerializationError {
@@ -84,9 +139,32 @@ pub enum HUGRSerializationError {
     /// Error building HUGR.
     #[error("HugrError: {0:?}")]
     HugrError(#[from] HugrError),
+    /// The node list is empty.
+    #[error("The node list is empty, missing the root node.")]
+    MissingRoot,
     /// First node in node list must be the HUGR root.
     #[error("The first node in the node list has parent {0:?}, should be itself (index 0)")]
     FirstNodeNotRoot(Node),
+    /// A node cannot be written in version 0 of the format, as its number of
+    /// ports differs from the default of its operation.
+    #[error("Node {0:?} has a number of ports that version 0 of the format cannot represent.")]
+    UnsupportedPortsInV0(Node),
+    /// A node uses a resource that is not declared.
+    #[error("Node {node:?} uses the undeclared resource {resource}.")]
+    UndeclaredResource {
+        /// The node using the resource.
+        node: Node,
+        /// The undeclared resource.
+        resource: ResourceId,
+    },
+    /// A node has more ports than a HUGR can represent.
+    #[error("Node {node:?} has {num_ports} ports, more than the maximum of {MAX_PORTS}.")]
+    TooManyPorts {
+        /// The node with too many ports.
+        node: Node,
+        /// Its number of ports.
+        num_ports: usize,
+    },
 }
 
 #[cfg(feature = "pyo3")]
@@ -101,162 +179,435 @@ impl Serialize for Hugr {
     where
         S: serde::Serializer,
     {
-        let shg: SerHugrV0 = self.try_into().map_err(serde::ser::Error::custom)?;
-        let versioned = Versioned::V0(shg);
+        self.serialize_version(SerializationVersion::default(), serializer)
+    }
+}
+
+impl Hugr {
+    /// Serializes the HUGR in the given version of the format.
+    ///
+    /// The [`Serialize`] implementation uses the most recent version; older
+    /// versions may not be able to represent every HUGR.
+    pub fn serialize_version<S>(
+        &self,
+        version: SerializationVersion,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let shg: SerHugrV1 = self.try_into().map_err(serde::ser::Error::custom)?;
+        let versioned = match version {
+            SerializationVersion::V0 => {
+                Versioned::V0(shg.try_into().map_err(serde::ser::Error::custom)?)
+            }
+            SerializationVersion::V1 => Versioned::V1(shg),
+        };
         versioned.serialize(serializer)
     }
 }
 
+impl JsonSchema for Hugr {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        "Hugr".into()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        gen.subschema_for::<Versioned>()
+    }
+}
+
+/// Returns the JSON Schema of the HUGR serialization format.
+///
+/// The schema accepts every supported version of the format, so it can be
+/// used to validate both the output of the current serializer and older
+/// serialized HUGRs that can still be loaded.
+pub fn json_schema() -> RootSchema {
+    schemars::schema_for!(Hugr)
+}
+
 impl<'de> Deserialize<'de> for Hugr {
     fn deserialize<D>(deserializer: D) -> Result<Hugr, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let shg = Versioned::deserialize(deserializer)?;
-        match shg {
-            Versioned::V0(shg) => shg.try_into().map_err(serde::de::Error::custom),
-            Versioned::Unsupported => Err(serde::de::Error::custom(
-                "Unsupported HUGR serialization format.",
-            )),
+        let shg = match Versioned::deserialize(deserializer)? {
+            Versioned::V1(shg) => shg,
+            Versioned::V0(shg) => shg.try_into().map_err(serde::de::Error::custom)?,
+            Versioned::Unsupported => {
+                return Err(serde::de::Error::custom(
+                    "Unsupported HUGR serialization format.",
+                ))
+            }
+        };
+        shg.try_into().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The resources used by a node: its input resources, the resource
+/// requirements of its operation, and the resource defining it if it is an
+/// extension operation.
+fn node_resources(input_resources: Option<&ResourceSet>, op: &OpType) -> ResourceSet {
+    let mut resources = op.signature().resource_reqs;
+    if let OpType::LeafOp(LeafOp::CustomOp(ext)) = op {
+        resources.insert(OpaqueOp::from(ext.clone()).resource());
+    }
+    match input_resources {
+        Some(input_resources) => resources.union(input_resources),
+        None => resources,
+    }
+}
+
+/// The number of ports of a node when they are not recorded, as in version 0
+/// of the format.
+///
+/// The root has no ports, and other nodes have those of their operation.
+fn default_ports(index: usize, op: &OpType) -> (usize, usize) {
+    match index {
+        0 => (0, 0),
+        _ => (
+            op.port_count(Direction::Incoming),
+            op.port_count(Direction::Outgoing),
+        ),
+    }
+}
+
+impl TryFrom<SerHugrV0> for SerHugrV1 {
+    type Error = HUGRSerializationError;
+
+    fn try_from(
+        SerHugrV0 {
+            nodes,
+            edges,
+            metadata,
+        }: SerHugrV0,
+    ) -> Result<Self, Self::Error> {
+        let nodes: Vec<_> = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let (num_inputs, num_outputs) = default_ports(i, &node.op);
+                NodeSerV1 {
+                    parent: node.parent,
+                    num_inputs,
+                    num_outputs,
+                    metadata: metadata.get(i).filter(|m| !m.is_null()).cloned(),
+                    input_resources: node.input_resources,
+                    op: node.op,
+                }
+            })
+            .collect();
+        let mut resources = BTreeSet::new();
+        for node in &nodes {
+            let used = node_resources(node.input_resources.as_ref(), &node.op);
+            resources.extend(used.iter().cloned());
+        }
+
+        let resolve_offset = |(node, offset): (Node, Option<u16>), dir| -> Result<_, Self::Error> {
+            match offset {
+                Some(offset) => Ok((node, offset as usize)),
+                None => {
+                    let op = &nodes
+                        .get(node.index.index())
+                        .ok_or(HUGRSerializationError::UnknownEdgeNode { node })?
+                        .op;
+                    let port = op.other_port_index(dir).ok_or_else(|| {
+                        HUGRSerializationError::MissingPortOffset {
+                            node,
+                            op_type: op.clone(),
+                        }
+                    })?;
+                    Ok((node, port.index()))
+                }
+            }
+        };
+        let edges = edges
+            .into_iter()
+            .map(|[src, tgt]| {
+                Ok([
+                    resolve_offset(src, Direction::Outgoing)?,
+                    resolve_offset(tgt, Direction::Incoming)?,
+                ])
+            })
+            .collect::<Result<_, Self::Error>>()?;
+
+        Ok(Self {
+            resources: resources.into_iter().collect(),
+            nodes,
+            edges,
+        })
+    }
+}
+
+impl TryFrom<SerHugrV1> for SerHugrV0 {
+    type Error = HUGRSerializationError;
+
+    fn try_from(SerHugrV1 { nodes, edges, .. }: SerHugrV1) -> Result<Self, Self::Error> {
+        let node = |i| Node::from(NodeIndex::new(i));
+        for (i, n) in nodes.iter().enumerate() {
+            if (n.num_inputs, n.num_outputs) != default_ports(i, &n.op) {
+                return Err(HUGRSerializationError::UnsupportedPortsInV0(node(i)));
+            }
         }
+
+        // As in the files written by version 0, the offsets of non-dataflow
+        // ports are omitted.
+        let offset = |(node, offset): (Node, usize), dir| {
+            let op = &nodes[node.index.index()].op;
+            match op.other_port_index(dir) {
+                Some(port) if port.index() == offset => Ok((node, None)),
+                _ => u16::try_from(offset)
+                    .map(|offset| (node, Some(offset)))
+                    .map_err(|_| HUGRSerializationError::UnsupportedPortsInV0(node)),
+            }
+        };
+        let edges = edges
+            .into_iter()
+            .map(|[src, tgt]| {
+                Ok([
+                    offset(src, Direction::Outgoing)?,
+                    offset(tgt, Direction::Incoming)?,
+                ])
+            })
+            .collect::<Result<_, Self::Error>>()?;
+
+        let metadata = nodes
+            .iter()
+            .map(|n| n.metadata.clone().unwrap_or_default())
+            .collect();
+        let nodes = nodes
+            .into_iter()
+            .map(|n| NodeSer {
+                parent: n.parent,
+                input_resources: n.input_resources,
+                op: n.op,
+            })
+            .collect();
+        Ok(Self {
+            nodes,
+            edges,
+            metadata,
+        })
+    }
+}
+
+/// Maps the nodes of a HUGR to their indices in the serialized formats, where
+/// they are compacted and listed in canonical order.
+fn node_rekey(hugr: &Hugr) -> HashMap<Node, Node> {
+    let mut node_rekey: HashMap<Node, Node> = HashMap::with_capacity(hugr.node_count());
+    for (order, node) in hugr.canonical_order().enumerate() {
+        node_rekey.insert(node, NodeIndex::new(order).into());
     }
+    node_rekey
 }
 
-impl TryFrom<&Hugr> for SerHugrV0 {
+/// The resources used by the nodes of a HUGR, sorted.
+fn used_resources(hugr: &Hugr) -> BTreeSet<ResourceId> {
+    let mut resources = BTreeSet::new();
+    for n in hugr.nodes() {
+        let opt = hugr.get_nodetype(n);
+        resources.extend(
+            node_resources(opt.input_resources.as_ref(), &opt.op)
+                .iter()
+                .cloned(),
+        );
+    }
+    resources
+}
+
+/// The edges of a HUGR as (src, src_offset, tgt, tgt_offset), with the nodes
+/// renamed by `node_rekey`.
+fn serialized_edges<'a>(
+    hugr: &'a Hugr,
+    node_rekey: &'a HashMap<Node, Node>,
+) -> impl Iterator<Item = [(Node, usize); 2]> + 'a {
+    hugr.nodes().flat_map(move |node| {
+        hugr.node_ports(node, Direction::Outgoing)
+            .enumerate()
+            .flat_map(move |(src_offset, port)| {
+                let src = (node_rekey[&node], src_offset);
+                hugr.linked_ports(node, port)
+                    .map(move |(tgt_node, tgt)| [src, (node_rekey[&tgt_node], tgt.offset.index())])
+            })
+    })
+}
+
+impl TryFrom<&Hugr> for SerHugrV1 {
     type Error = HUGRSerializationError;
 
     fn try_from(hugr: &Hugr) -> Result<Self, Self::Error> {
         // We compact the operation nodes during the serialization process,
         // and ignore the copy nodes.
-        let mut node_rekey: HashMap<Node, Node> = HashMap::with_capacity(hugr.node_count());
-        for (order, node) in hugr.canonical_order().enumerate() {
-            node_rekey.insert(node, NodeIndex::new(order).into());
-        }
+        let node_rekey = node_rekey(hugr);
 
         let mut nodes = vec![None; hugr.node_count()];
-        let mut metadata = vec![json!(null); hugr.node_count()];
         for n in hugr.nodes() {
             let parent = node_rekey[&hugr.get_parent(n).unwrap_or(n)];
             let opt = hugr.get_nodetype(n);
             let new_node = node_rekey[&n].index.index();
-            nodes[new_node] = Some(NodeSer {
+            let metadata = hugr.get_metadata(n);
+            nodes[new_node] = Some(NodeSerV1 {
                 parent,
                 input_resources: opt.input_resources.clone(),
+                num_inputs: hugr.graph.num_inputs(n.index),
+                num_outputs: hugr.graph.num_outputs(n.index),
+                metadata: (!metadata.is_null()).then(|| metadata.clone()),
                 op: opt.op.clone(),
             });
-            metadata[new_node] = hugr.get_metadata(n).clone();
         }
         let nodes = nodes
             .into_iter()
             .collect::<Option<Vec<_>>>()
             .expect("Could not reach one of the nodes");
 
-        let find_offset = |node: Node, offset: usize, dir: Direction, hugr: &Hugr| {
-            let sig = hugr.get_optype(node).signature();
-            let offset = match offset < sig.port_count(dir) {
-                true => Some(offset as u16),
-                false => None,
-            };
-            (node_rekey[&node], offset)
-        };
-
-        let edges: Vec<_> = hugr
-            .nodes()
-            .flat_map(|node| {
-                hugr.node_ports(node, Direction::Outgoing)
-                    .enumerate()
-                    .flat_map(move |(src_offset, port)| {
-                        let src = find_offset(node, src_offset, Direction::Outgoing, hugr);
-                        hugr.linked_ports(node, port).map(move |(tgt_node, tgt)| {
-                            let tgt = find_offset(
-                                tgt_node,
-                                tgt.offset.index(),
-                                Direction::Incoming,
-                                hugr,
-                            );
-                            [src, tgt]
-                        })
-                    })
-            })
-            .collect();
+        let edges: Vec<_> = serialized_edges(hugr, &node_rekey).collect();
 
         Ok(Self {
+            resources: used_resources(hugr).into_iter().collect(),
             nodes,
             edges,
-            metadata,
         })
     }
 }
 
-impl TryFrom<SerHugrV0> for Hugr {
+/// Checks that the resources used by the serialized node at `index` are
+/// declared.
+fn check_declared_resources(
+    declared: &ResourceSet,
+    index: usize,
+    node: &NodeSerV1,
+) -> Result<(), HUGRSerializationError> {
+    let used = node_resources(node.input_resources.as_ref(), &node.op);
+    let undeclared = used.iter().find(|r| !declared.contains(r)).cloned();
+    match undeclared {
+        Some(resource) => Err(HUGRSerializationError::UndeclaredResource {
+            node: NodeIndex::new(index).into(),
+            resource,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The largest total number of ports of a deserialized node, as portgraph
+/// cannot allocate more.
+const MAX_PORTS: usize = 1 << 15;
+
+/// Checks that a deserialized node has few enough ports, including the
+/// default ports of its operation, to be added to the HUGR.
+fn check_num_ports(index: usize, node: &NodeSerV1) -> Result<(), HUGRSerializationError> {
+    let (default_inputs, default_outputs) = default_ports(index, &node.op);
+    let num_ports =
+        (node.num_inputs.saturating_add(node.num_outputs)).max(default_inputs + default_outputs);
+    match num_ports > MAX_PORTS {
+        true => Err(HUGRSerializationError::TooManyPorts {
+            node: NodeIndex::new(index).into(),
+            num_ports,
+        }),
+        false => Ok(()),
+    }
+}
+
+/// The node type of a deserialized node.
+fn node_type(input_resources: Option<ResourceSet>, op: OpType) -> NodeType {
+    match input_resources {
+        None => NodeType::open_resources(op),
+        Some(rs) => NodeType::new(op, rs),
+    }
+}
+
+/// Sets the recorded number of ports and metadata of a deserialized node.
+fn set_ports_and_metadata(
+    hugr: &mut Hugr,
+    node: Node,
+    num_inputs: usize,
+    num_outputs: usize,
+    metadata: Option<NodeMetadata>,
+) {
+    if (num_inputs, num_outputs)
+        != (
+            hugr.graph.num_inputs(node.index),
+            hugr.graph.num_outputs(node.index),
+        )
+    {
+        hugr.set_num_ports(node, num_inputs, num_outputs);
+    }
+    if let Some(metadata) = metadata {
+        hugr.set_metadata(node, metadata);
+    }
+}
+
+/// Adds a deserialized edge to the HUGR.
+fn connect_edge(
+    hugr: &mut Hugr,
+    [(src, src_port), (dst, dst_port)]: [(Node, usize); 2],
+) -> Result<(), HUGRSerializationError> {
+    for node in [src, dst] {
+        if !hugr.graph.contains_node(node.index) {
+            return Err(HUGRSerializationError::UnknownEdgeNode { node });
+        }
+    }
+    hugr.connect(src, src_port, dst, dst_port)?;
+    Ok(())
+}
+
+impl TryFrom<SerHugrV1> for Hugr {
     type Error = HUGRSerializationError;
     fn try_from(
-        SerHugrV0 {
+        SerHugrV1 {
+            resources,
             nodes,
             edges,
-            metadata,
-        }: SerHugrV0,
+        }: SerHugrV1,
     ) -> Result<Self, Self::Error> {
+        let declared: ResourceSet = resources.into_iter().collect();
+        for (i, node) in nodes.iter().enumerate() {
+            check_declared_resources(&declared, i, node)?;
+            check_num_ports(i, node)?;
+        }
+
         // Root must be first node
         let mut nodes = nodes.into_iter();
-        let NodeSer {
-            parent: root_parent,
-            input_resources,
-            op: root_type,
-        } = nodes.next().unwrap();
-        if root_parent.index.index() != 0 {
-            return Err(HUGRSerializationError::FirstNodeNotRoot(root_parent));
+        let root = nodes.next().ok_or(HUGRSerializationError::MissingRoot)?;
+        if root.parent.index.index() != 0 {
+            return Err(HUGRSerializationError::FirstNodeNotRoot(root.parent));
         }
         // if there are any unconnected ports or copy nodes the capacity will be
         // an underestimate
         let mut hugr = Hugr::with_capacity(
-            match input_resources {
-                None => NodeType::open_resources(root_type),
-                Some(rs) => NodeType::new(root_type, rs),
-            },
+            node_type(root.input_resources, root.op),
             nodes.len(),
             edges.len() * 2,
         );
+        let mut ports_and_metadata = vec![(
+            hugr.root(),
+            root.num_inputs,
+            root.num_outputs,
+            root.metadata,
+        )];
 
         for node_ser in nodes {
-            hugr.add_node_with_parent(
+            let node = hugr.add_node_with_parent(
                 node_ser.parent,
-                match node_ser.input_resources {
-                    None => NodeType::open_resources(node_ser.op),
-                    Some(rs) => NodeType::new(node_ser.op, rs),
-                },
+                node_type(node_ser.input_resources, node_ser.op),
             )?;
+            ports_and_metadata.push((
+                node,
+                node_ser.num_inputs,
+                node_ser.num_outputs,
+                node_ser.metadata,
+            ));
         }
 
-        for (node, metadata) in metadata.into_iter().enumerate() {
-            let node = NodeIndex::new(node).into();
-            hugr.set_metadata(node, metadata);
+        for (node, num_inputs, num_outputs, metadata) in ports_and_metadata {
+            set_ports_and_metadata(&mut hugr, node, num_inputs, num_outputs, metadata);
         }
 
-        let unwrap_offset = |node: Node, offset, dir, hugr: &Hugr| -> Result<usize, Self::Error> {
-            if !hugr.graph.contains_node(node.index) {
-                return Err(HUGRSerializationError::UnknownEdgeNode { node });
-            }
-            let offset = match offset {
-                Some(offset) => offset as usize,
-                None => {
-                    let op_type = hugr.get_optype(node);
-                    op_type
-                        .other_port_index(dir)
-                        .ok_or(HUGRSerializationError::MissingPortOffset {
-                            node,
-                            op_type: op_type.clone(),
-                        })?
-                        .index()
-                }
-            };
-            Ok(offset)
-        };
-        for [(src, from_offset), (dst, to_offset)] in edges {
-            let src_port = unwrap_offset(src, from_offset, Direction::Outgoing, &hugr)?;
-            let dst_port = unwrap_offset(dst, to_offset, Direction::Incoming, &hugr)?;
-
-            hugr.connect(src, src_port, dst, dst_port)?;
+        for edge in edges {
+            connect_edge(&mut hugr, edge)?;
         }
 
         Ok(hugr)
@@ -273,14 +624,18 @@ pub mod test {
             ModuleBuilder,
         },
         hugr::NodeType,
-        ops::{dataflow::IOTrait, Input, LeafOp, Module, Output, DFG},
-        types::{AbstractSignature, ClassicType, SimpleType},
+        ops::{dataflow::IOTrait, Const, ConstValue, Input, LeafOp, Module, Output, DFG},
+        types::{
+            type_param::TypeArg, AbstractSignature, ClassicType, CustomType, SimpleType, TypeTag,
+        },
         Port,
     };
+    use cool_asserts::assert_matches;
     use itertools::Itertools;
     use portgraph::{
         multiportgraph::MultiPortGraph, Hierarchy, LinkMut, PortMut, PortView, UnmanagedDenseMap,
     };
+    use serde_json::json;
 
     const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
     const QB: SimpleType = SimpleType::Qubit;
@@ -388,9 +743,9 @@ pub mod test {
             module_builder.finish_hugr().unwrap()
         };
 
-        let ser_hugr: SerHugrV0 = (&hugr).try_into().unwrap();
+        let ser_hugr: SerHugrV1 = (&hugr).try_into().unwrap();
         // HUGR internal structures are not preserved across serialization, so
-        // test equality on SerHugrV0 instead.
+        // test equality on SerHugrV1 instead.
         assert_eq!(ser_roundtrip(&ser_hugr), ser_hugr);
     }
 
@@ -425,9 +780,9 @@ pub mod test {
             module_builder.finish_hugr().unwrap()
         };
 
-        let ser_hugr: SerHugrV0 = (&hugr).try_into().unwrap();
+        let ser_hugr: SerHugrV1 = (&hugr).try_into().unwrap();
         // HUGR internal structures are not preserved across serialization, so
-        // test equality on SerHugrV0 instead.
+        // test equality on SerHugrV1 instead.
         assert_eq!(ser_roundtrip(&ser_hugr), ser_hugr);
     }
 
@@ -492,4 +847,181 @@ pub mod test {
             assert_eq!(new_hugr.get_parent(node), h_canon.get_parent(node));
         }
     }
+
+    const FIXTURE_V0: &str = include_str!("../../resources/test/hugr_v0.json");
+    const FIXTURE_V1: &str = include_str!("../../resources/test/hugr_v1.json");
+
+    #[test]
+    fn upgrade_v0_fixture() -> Result<(), Box<dyn std::error::Error>> {
+        let hugr: Hugr = serde_json::from_str(FIXTURE_V0)?;
+        hugr.validate()?;
+        let func = hugr.children(hugr.root()).next().unwrap();
+        assert_eq!(
+            hugr.get_metadata(func),
+            &json!({"name": "main", "gates": 4})
+        );
+
+        // Hugrs are written in the latest version.
+        let upgraded = serde_json::to_value(&hugr)?;
+        assert_eq!(
+            upgraded,
+            serde_json::from_str::<serde_json::Value>(FIXTURE_V1)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn v1_fixture_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let hugr: Hugr = serde_json::from_str(FIXTURE_V1)?;
+        hugr.validate()?;
+        assert_eq!(
+            serde_json::to_value(&hugr)?,
+            serde_json::from_str::<serde_json::Value>(FIXTURE_V1)?
+        );
+
+        let ser_hugr: SerHugrV1 = (&hugr).try_into()?;
+        assert_eq!(ser_roundtrip(&ser_hugr), ser_hugr);
+        Ok(())
+    }
+
+    #[test]
+    fn downgrade_v1_fixture() -> Result<(), Box<dyn std::error::Error>> {
+        let hugr: Hugr = serde_json::from_str(FIXTURE_V1)?;
+        let v0 = hugr.serialize_version(SerializationVersion::V0, serde_json::value::Serializer)?;
+        assert_eq!(v0["version"], "v0");
+        let fixture_v0: serde_json::Value = serde_json::from_str(FIXTURE_V0)?;
+        assert_eq!(v0["metadata"], fixture_v0["metadata"]);
+
+        let reloaded: Hugr = serde_json::from_value(v0)?;
+        assert_eq!(
+            serde_json::to_value(&reloaded)?,
+            serde_json::to_value(&hugr)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn undeclared_resource() -> Result<(), Box<dyn std::error::Error>> {
+        let mut ser_hugr: SerHugrV1 =
+            serde_json::from_str::<Versioned>(FIXTURE_V1).map(|v| match v {
+                Versioned::V1(ser_hugr) => ser_hugr,
+                _ => panic!("Expected a version 1 fixture"),
+            })?;
+        ser_hugr.resources.clear();
+        assert_matches!(
+            Hugr::try_from(ser_hugr),
+            Err(HUGRSerializationError::UndeclaredResource { resource, .. }) => {
+                assert_eq!(resource, "qasm")
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_nodes() {
+        let fixture = || match serde_json::from_str::<Versioned>(FIXTURE_V1).unwrap() {
+            Versioned::V1(ser_hugr) => ser_hugr,
+            _ => panic!("Expected a version 1 fixture"),
+        };
+        let mut ser_hugr = fixture();
+        ser_hugr.nodes[1].num_inputs = 70000;
+        assert_matches!(
+            Hugr::try_from(ser_hugr),
+            Err(HUGRSerializationError::TooManyPorts { num_ports, .. }) => {
+                assert!(num_ports > 70000)
+            }
+        );
+        let mut ser_hugr = fixture();
+        ser_hugr.nodes.clear();
+        assert_matches!(
+            Hugr::try_from(ser_hugr),
+            Err(HUGRSerializationError::MissingRoot)
+        );
+    }
+
+    #[test]
+    fn extra_ports_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let dfg = DFGBuilder::new(AbstractSignature::new_df(vec![QB], vec![QB]))?;
+        let [input, _] = dfg.io();
+        let wires = dfg.input_wires();
+        let mut hugr = dfg.finish_hugr_with_outputs(wires)?;
+        hugr.add_ports(input, Direction::Outgoing, 2);
+
+        let new_hugr = ser_roundtrip(&hugr);
+        let new_input = new_hugr.children(new_hugr.root()).next().unwrap();
+        assert_eq!(
+            new_hugr.graph.num_outputs(new_input.index),
+            hugr.graph.num_outputs(input.index)
+        );
+
+        // Version 0 has no way of recording the extra ports.
+        assert_matches!(
+            hugr.serialize_version(SerializationVersion::V0, serde_json::value::Serializer),
+            Err(e) => assert!(e.to_string().contains("version 0"))
+        );
+        Ok(())
+    }
+
+    /// Validates a serialized HUGR against the JSON Schema of the format.
+    fn validate_schema(ser: &serde_json::Value) {
+        let schema = serde_json::to_value(json_schema()).unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+        let result = schema.validate(ser).map_err(|errors| {
+            errors
+                .map(|e| format!("{e} at {}", e.instance_path))
+                .join("\n")
+        });
+        if let Err(errors) = result {
+            panic!("Serialized HUGR does not match the schema:\n{errors}");
+        }
+    }
+
+    #[test]
+    fn schema_fixtures() {
+        validate_schema(&serde_json::from_str(FIXTURE_V0).unwrap());
+        validate_schema(&serde_json::from_str(FIXTURE_V1).unwrap());
+    }
+
+    #[test]
+    fn schema_module() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module_builder = ModuleBuilder::new();
+        let array = CustomType::new(
+            "array",
+            [
+                TypeArg::Type(QB),
+                TypeArg::Int(4),
+                TypeArg::CustomValue(serde_yaml::Value::String("meta".into())),
+            ],
+            "collections",
+            TypeTag::Simple,
+        );
+        let alias = module_builder.add_alias_declare("opaque", TypeTag::Classic)?;
+        module_builder.add_alias_def(
+            "pair",
+            SimpleType::new_tuple(vec![NAT, ClassicType::F64.into()]),
+        )?;
+        module_builder.declare(
+            "consume",
+            AbstractSignature::new_df(vec![array.into(), alias.get_alias_type()], vec![]).pure(),
+        )?;
+        module_builder.add_constant(Const::new_tuple([
+            Const::i64(3)?,
+            Const::new(ConstValue::F64(1.5), ClassicType::F64)?,
+            Const::simple_predicate(1, 3),
+        ]))?;
+        let hugr = module_builder.finish_hugr()?;
+
+        validate_schema(&serde_json::to_value(&hugr)?);
+        Ok(())
+    }
+
+    #[test]
+    fn schema_rejects_invalid() {
+        let mut ser: serde_json::Value = serde_json::from_str(FIXTURE_V1).unwrap();
+        ser["nodes"][1]["op"] = json!("NotAnOp");
+        let schema = serde_json::to_value(json_schema()).unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+        assert!(!schema.is_valid(&ser));
+        assert!(!schema.is_valid(&json!({"version": "v1", "nodes": []})));
+    }
 }
@@ -0,0 +1,1249 @@
+//! Compact binary serialization format for [`Hugr`].
+//!
+//! The format stores the same information as [`SerHugrV1`], but avoids
+//! repeating data that is shared between nodes. Strings (operation names,
+//! resource ids, symbol names) and [`SimpleType`]s are interned into tables,
+//! and each distinct operation is stored once in an operation table, so that
+//! a node is just a reference to an operation together with its parent, port
+//! counts, input resources and metadata. Integers are written as LEB128
+//! varints, and node indices are delta-encoded so that the edges of
+//! circuit-like graphs take a handful of bytes each.
+//!
+//! Both reading and writing are done in a single pass over the data: the
+//! entries of the tables are written when the first node referencing them is,
+//! in a block of definitions preceding that node, and nodes and edges are
+//! decoded directly into the [`Hugr`]. The layout of a serialized HUGR is:
+//!
+//! ```text
+//! magic "HUGR", format version (u8)
+//! resources:   count, [len, utf-8 bytes]
+//! nodes:       count, [flags, definitions?, parent delta, op index,
+//!                      num inputs, num outputs, input resources?, metadata?]
+//! edges:       count, [source delta, source offset, target delta, target offset]
+//!
+//! definitions: new strings: count, [len, utf-8 bytes]
+//!              new types:   count, [len, msgpack-encoded SimpleType]
+//!              new ops:     count, [len, encoded OpType]
+//! ```
+//!
+//! The resources are the first entries of the string table, and the entries
+//! of each table are numbered in order of definition.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, BufWriter, Read, Write};
+
+use portgraph::NodeIndex;
+use smol_str::SmolStr;
+use thiserror::Error;
+
+#[cfg(feature = "pyo3")]
+use pyo3::prelude::*;
+
+#[cfg(doc)]
+use super::SerHugrV1;
+use super::{
+    check_declared_resources, check_num_ports, connect_edge, node_rekey, node_type,
+    serialized_edges, set_ports_and_metadata, used_resources, HUGRSerializationError, NodeSerV1,
+};
+use crate::hugr::{HugrMut, HugrView};
+use crate::ops::custom::{ExternalOp, OpaqueOp};
+use crate::ops::{
+    AliasDecl, AliasDefn, BasicBlock, Call, CallIndirect, Case, Conditional, Const, FuncDecl,
+    FuncDefn, Input, LeafOp, LoadConstant, Module, OpType, Output, TailLoop, CFG, DFG,
+};
+use crate::resource::ResourceSet;
+use crate::types::type_param::TypeArg;
+use crate::types::{AbstractSignature, ClassicRow, ClassicType, SimpleRow, SimpleType, TypeTag};
+use crate::Hugr;
+
+/// The first bytes of a binary serialized HUGR.
+const MAGIC: &[u8; 4] = b"HUGR";
+/// The version of the binary format written by [`Hugr::write_binary`].
+const FORMAT_VERSION: u8 = 1;
+
+/// Node flag marking the presence of input resources.
+const HAS_INPUT_RESOURCES: u8 = 1;
+/// Node flag marking the presence of metadata.
+const HAS_METADATA: u8 = 2;
+/// Node flag marking the presence of new table entries before the node.
+const HAS_DEFINITIONS: u8 = 4;
+
+/// Upper bound for the capacity preallocated from counts read from the input,
+/// so that corrupted inputs fail with an error rather than an allocation
+/// failure.
+const MAX_PREALLOCATION: usize = 1 << 16;
+
+/// Errors that can occur while reading or writing a binary serialized HUGR.
+#[derive(Debug, Error)]
+pub enum BinarySerializationError {
+    /// Error reading from or writing to the underlying stream.
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    /// The input does not start with the binary HUGR header.
+    #[error("The input is not a binary serialized HUGR.")]
+    InvalidMagic,
+    /// The input was written in an unknown version of the format.
+    #[error("Unsupported binary HUGR format version {0}.")]
+    UnsupportedVersion(u8),
+    /// A reference to an entry of one of the interned tables is out of bounds.
+    #[error("Invalid index {index} into the {table} table.")]
+    InvalidIndex {
+        /// The table being indexed.
+        table: &'static str,
+        /// The invalid index.
+        index: usize,
+    },
+    /// The input is malformed.
+    #[error("Invalid binary HUGR: {0}.")]
+    InvalidEncoding(String),
+    /// Error encoding a type, constant or type argument.
+    #[error("Failed to encode value: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+    /// Error decoding a type, constant or type argument.
+    #[error("Failed to decode value: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+    /// Error encoding or decoding node metadata.
+    #[error("Invalid node metadata: {0}")]
+    Metadata(#[from] serde_json::Error),
+    /// Error converting between the HUGR and its serialized form.
+    #[error("Failed to convert the HUGR: {0}")]
+    Hugr(#[source] Box<HUGRSerializationError>),
+}
+
+impl From<HUGRSerializationError> for BinarySerializationError {
+    fn from(err: HUGRSerializationError) -> Self {
+        Self::Hugr(Box::new(err))
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl From<BinarySerializationError> for PyErr {
+    fn from(err: BinarySerializationError) -> Self {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(err.to_string())
+    }
+}
+
+impl Hugr {
+    /// Writes the HUGR to `writer` in the compact binary format.
+    ///
+    /// The output is written incrementally, without building it whole in
+    /// memory. See [`Hugr::read_binary`] for the inverse operation.
+    pub fn write_binary(&self, writer: impl Write) -> Result<(), BinarySerializationError> {
+        let mut writer = BufWriter::new(writer);
+        let mut interner = Interner::default();
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(FORMAT_VERSION);
+        let resources = used_resources(self);
+        write_varint(&mut buf, resources.len());
+        for resource in resources {
+            write_bytes(&mut buf, resource.as_bytes());
+            interner.strings.intern(resource);
+        }
+        interner.strings.take_pending();
+        writer.write_all(&buf)?;
+
+        // We compact the operation nodes during the serialization process,
+        // and ignore the copy nodes.
+        let node_rekey = node_rekey(self);
+        let rekey = |node| node_rekey[&node].index.index();
+        let mut op_buf = Vec::new();
+        let mut resources_buf = Vec::new();
+        let mut prev_parent = 0;
+        buf.clear();
+        write_varint(&mut buf, self.node_count());
+        writer.write_all(&buf)?;
+        for node in self.canonical_order() {
+            let node_type = self.get_nodetype(node);
+            op_buf.clear();
+            interner.encode_op(&node_type.op, &mut op_buf)?;
+            let op = interner.ops.intern_with(op_buf.as_slice(), <[u8]>::to_vec);
+            resources_buf.clear();
+            if let Some(resources) = &node_type.input_resources {
+                interner.encode_resource_set(resources, &mut resources_buf);
+            }
+            let metadata = self.get_metadata(node);
+
+            let mut flags = 0;
+            if node_type.input_resources.is_some() {
+                flags |= HAS_INPUT_RESOURCES;
+            }
+            if !metadata.is_null() {
+                flags |= HAS_METADATA;
+            }
+            if interner.has_pending() {
+                flags |= HAS_DEFINITIONS;
+            }
+            buf.clear();
+            buf.push(flags);
+            if flags & HAS_DEFINITIONS != 0 {
+                interner.write_definitions(&mut buf);
+            }
+            let parent = rekey(self.get_parent(node).unwrap_or(node));
+            write_delta(&mut buf, prev_parent, parent);
+            prev_parent = parent;
+            write_varint(&mut buf, op);
+            write_varint(&mut buf, self.num_inputs(node));
+            write_varint(&mut buf, self.num_outputs(node));
+            buf.extend_from_slice(&resources_buf);
+            if !metadata.is_null() {
+                write_bytes(&mut buf, &serde_json::to_vec(metadata)?);
+            }
+            writer.write_all(&buf)?;
+        }
+
+        // The edges are counted in a separate pass so that they need not be
+        // buffered.
+        let mut prev_src = 0;
+        buf.clear();
+        write_varint(&mut buf, serialized_edges(self, &node_rekey).count());
+        writer.write_all(&buf)?;
+        for [(src, src_offset), (tgt, tgt_offset)] in serialized_edges(self, &node_rekey) {
+            let (src, tgt) = (src.index.index(), tgt.index.index());
+            buf.clear();
+            write_delta(&mut buf, prev_src, src);
+            write_varint(&mut buf, src_offset);
+            write_delta(&mut buf, src, tgt);
+            write_varint(&mut buf, tgt_offset);
+            prev_src = src;
+            writer.write_all(&buf)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a HUGR in the compact binary format from `reader`.
+    ///
+    /// The input is read incrementally, without loading it whole in memory.
+    /// The reader should be buffered, e.g. with a [`std::io::BufReader`], as
+    /// it is consumed in small chunks. Exactly the bytes of the serialized
+    /// HUGR are consumed from it.
+    pub fn read_binary(reader: impl Read) -> Result<Self, BinarySerializationError> {
+        let mut reader = Reader(reader);
+        let mut magic = [0; 4];
+        reader.0.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(BinarySerializationError::InvalidMagic);
+        }
+        match reader.read_u8()? {
+            FORMAT_VERSION => {}
+            version => return Err(BinarySerializationError::UnsupportedVersion(version)),
+        }
+
+        let mut tables = Tables::default();
+        let num_resources = reader.read_varint()?;
+        tables.read_strings(&mut reader, num_resources)?;
+        let declared: ResourceSet = tables.strings.iter().cloned().collect();
+
+        let num_nodes = reader.read_varint()?;
+        if num_nodes == 0 {
+            return Err(invalid("missing root node"));
+        }
+        let root = tables.read_node(&mut reader, 0)?;
+        check_declared_resources(&declared, 0, &root)?;
+        check_num_ports(0, &root)?;
+        if root.parent.index.index() != 0 {
+            return Err(HUGRSerializationError::FirstNodeNotRoot(root.parent).into());
+        }
+        let capacity = num_nodes.min(MAX_PREALLOCATION);
+        let mut hugr = Hugr::with_capacity(node_type(root.input_resources, root.op), capacity, 0);
+        let root_node = hugr.root();
+        set_ports_and_metadata(
+            &mut hugr,
+            root_node,
+            root.num_inputs,
+            root.num_outputs,
+            root.metadata,
+        );
+        let mut prev_parent = 0;
+        for index in 1..num_nodes {
+            let node = tables.read_node(&mut reader, prev_parent)?;
+            prev_parent = node.parent.index.index();
+            check_declared_resources(&declared, index, &node)?;
+            check_num_ports(index, &node)?;
+            if prev_parent >= index {
+                return Err(invalid(format!(
+                    "node {index} has parent {prev_parent}, which is not defined before it"
+                )));
+            }
+            let new_node = hugr
+                .add_node_with_parent(node.parent, node_type(node.input_resources, node.op))
+                .map_err(HUGRSerializationError::from)?;
+            set_ports_and_metadata(
+                &mut hugr,
+                new_node,
+                node.num_inputs,
+                node.num_outputs,
+                node.metadata,
+            );
+        }
+
+        let num_edges = reader.read_varint()?;
+        let mut prev_src = 0;
+        for _ in 0..num_edges {
+            let src = reader.read_delta(prev_src)?;
+            let src_offset = reader.read_varint()?;
+            let tgt = reader.read_delta(src.index())?;
+            let tgt_offset = reader.read_varint()?;
+            prev_src = src.index();
+            connect_edge(
+                &mut hugr,
+                [(src.into(), src_offset), (tgt.into(), tgt_offset)],
+            )?;
+        }
+        Ok(hugr)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> BinarySerializationError {
+    BinarySerializationError::InvalidEncoding(msg.into())
+}
+
+/// A table of interned values, indexed in order of first insertion.
+struct Table<T> {
+    indices: HashMap<T, usize>,
+    entries: Vec<T>,
+    /// The number of entries already written to the output.
+    written: usize,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self {
+            indices: HashMap::new(),
+            entries: Vec::new(),
+            written: 0,
+        }
+    }
+}
+
+impl<T> Table<T> {
+    /// Returns the entries that have not been written yet, marking them as
+    /// written.
+    fn take_pending(&mut self) -> &[T] {
+        let pending = &self.entries[self.written..];
+        self.written = self.entries.len();
+        pending
+    }
+}
+
+impl<T: Hash + Eq + Clone> Table<T> {
+    /// Returns the index of `value`, adding it to the table if needed.
+    fn intern(&mut self, value: T) -> usize {
+        if let Some(&index) = self.indices.get(&value) {
+            return index;
+        }
+        self.insert(value)
+    }
+
+    /// Returns the index of `value`, adding an owned copy of it to the table if
+    /// needed.
+    fn intern_with<Q>(&mut self, value: &Q, to_owned: impl FnOnce(&Q) -> T) -> usize
+    where
+        T: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if let Some(&index) = self.indices.get(value) {
+            return index;
+        }
+        self.insert(to_owned(value))
+    }
+
+    fn insert(&mut self, value: T) -> usize {
+        let index = self.entries.len();
+        self.indices.insert(value.clone(), index);
+        self.entries.push(value);
+        index
+    }
+}
+
+/// The interned tables built while writing a HUGR.
+#[derive(Default)]
+struct Interner {
+    strings: Table<SmolStr>,
+    /// The msgpack encodings of the types.
+    types: Table<Vec<u8>>,
+    /// The encodings of the operations.
+    ops: Table<Vec<u8>>,
+    /// Scratch buffer for encoding types.
+    type_buf: Vec<u8>,
+}
+
+impl Interner {
+    /// Returns whether some entries have been interned since the last call to
+    /// [`Interner::write_definitions`].
+    fn has_pending(&self) -> bool {
+        self.strings.written < self.strings.entries.len()
+            || self.types.written < self.types.entries.len()
+            || self.ops.written < self.ops.entries.len()
+    }
+
+    /// Writes the entries interned since the last call.
+    fn write_definitions(&mut self, buf: &mut Vec<u8>) {
+        let strings = self.strings.take_pending();
+        write_varint(buf, strings.len());
+        for string in strings {
+            write_bytes(buf, string.as_bytes());
+        }
+        let types = self.types.take_pending();
+        write_varint(buf, types.len());
+        for ty in types {
+            write_bytes(buf, ty);
+        }
+        let ops = self.ops.take_pending();
+        write_varint(buf, ops.len());
+        for op in ops {
+            write_bytes(buf, op);
+        }
+    }
+
+    fn encode_string(&mut self, string: impl Into<SmolStr>, buf: &mut Vec<u8>) {
+        write_varint(buf, self.strings.intern(string.into()));
+    }
+
+    fn encode_type(
+        &mut self,
+        ty: &SimpleType,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        self.type_buf.clear();
+        rmp_serde::encode::write_named(&mut self.type_buf, ty)?;
+        let index = self
+            .types
+            .intern_with(self.type_buf.as_slice(), <[u8]>::to_vec);
+        write_varint(buf, index);
+        Ok(())
+    }
+
+    fn encode_classic_type(
+        &mut self,
+        ty: &ClassicType,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        self.encode_type(&ty.clone().into(), buf)
+    }
+
+    fn encode_row(
+        &mut self,
+        row: &SimpleRow,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        write_varint(buf, row.len());
+        row.iter().try_for_each(|ty| self.encode_type(ty, buf))
+    }
+
+    fn encode_classic_row(
+        &mut self,
+        row: &ClassicRow,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        write_varint(buf, row.len());
+        row.iter()
+            .try_for_each(|ty| self.encode_classic_type(ty, buf))
+    }
+
+    fn encode_classic_rows(
+        &mut self,
+        rows: &[ClassicRow],
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        write_varint(buf, rows.len());
+        rows.iter()
+            .try_for_each(|row| self.encode_classic_row(row, buf))
+    }
+
+    fn encode_resource_set(&mut self, resources: &ResourceSet, buf: &mut Vec<u8>) {
+        let mut resources: Vec<_> = resources.iter().collect();
+        resources.sort();
+        write_varint(buf, resources.len());
+        for resource in resources {
+            self.encode_string(resource.clone(), buf);
+        }
+    }
+
+    fn encode_signature(
+        &mut self,
+        signature: &AbstractSignature,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        self.encode_row(&signature.input, buf)?;
+        self.encode_row(&signature.output, buf)?;
+        self.encode_classic_row(&signature.static_input, buf)?;
+        self.encode_resource_set(&signature.resource_reqs, buf);
+        Ok(())
+    }
+
+    fn encode_op(
+        &mut self,
+        op: &OpType,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        match op {
+            OpType::Module(Module) => buf.push(0),
+            OpType::FuncDefn(FuncDefn { name, signature }) => {
+                buf.push(1);
+                self.encode_string(name.as_str(), buf);
+                self.encode_signature(signature, buf)?;
+            }
+            OpType::FuncDecl(FuncDecl { name, signature }) => {
+                buf.push(2);
+                self.encode_string(name.as_str(), buf);
+                self.encode_signature(signature, buf)?;
+            }
+            OpType::AliasDecl(AliasDecl { name, tag }) => {
+                buf.push(3);
+                self.encode_string(name.clone(), buf);
+                buf.push(*tag as u8);
+            }
+            OpType::AliasDefn(AliasDefn { name, definition }) => {
+                buf.push(4);
+                self.encode_string(name.clone(), buf);
+                self.encode_type(definition, buf)?;
+            }
+            OpType::Const(c) => {
+                buf.push(5);
+                write_bytes(buf, &rmp_serde::to_vec_named(c)?);
+            }
+            OpType::Input(Input { types }) => {
+                buf.push(6);
+                self.encode_row(types, buf)?;
+            }
+            OpType::Output(Output { types }) => {
+                buf.push(7);
+                self.encode_row(types, buf)?;
+            }
+            OpType::Call(Call { signature }) => {
+                buf.push(8);
+                self.encode_signature(signature, buf)?;
+            }
+            OpType::CallIndirect(CallIndirect { signature }) => {
+                buf.push(9);
+                self.encode_signature(signature, buf)?;
+            }
+            OpType::LoadConstant(LoadConstant { datatype }) => {
+                buf.push(10);
+                self.encode_classic_type(datatype, buf)?;
+            }
+            OpType::DFG(DFG { signature }) => {
+                buf.push(11);
+                self.encode_signature(signature, buf)?;
+            }
+            OpType::LeafOp(leaf) => {
+                buf.push(12);
+                self.encode_leaf_op(leaf, buf)?;
+            }
+            OpType::BasicBlock(BasicBlock::DFB {
+                inputs,
+                other_outputs,
+                predicate_variants,
+            }) => {
+                buf.push(13);
+                self.encode_row(inputs, buf)?;
+                self.encode_row(other_outputs, buf)?;
+                self.encode_classic_rows(predicate_variants, buf)?;
+            }
+            OpType::BasicBlock(BasicBlock::Exit { cfg_outputs }) => {
+                buf.push(14);
+                self.encode_row(cfg_outputs, buf)?;
+            }
+            OpType::TailLoop(TailLoop {
+                just_inputs,
+                just_outputs,
+                rest,
+            }) => {
+                buf.push(15);
+                self.encode_classic_row(just_inputs, buf)?;
+                self.encode_classic_row(just_outputs, buf)?;
+                self.encode_row(rest, buf)?;
+            }
+            OpType::CFG(CFG { inputs, outputs }) => {
+                buf.push(16);
+                self.encode_row(inputs, buf)?;
+                self.encode_row(outputs, buf)?;
+            }
+            OpType::Conditional(Conditional {
+                predicate_inputs,
+                other_inputs,
+                outputs,
+            }) => {
+                buf.push(17);
+                self.encode_classic_rows(predicate_inputs, buf)?;
+                self.encode_row(other_inputs, buf)?;
+                self.encode_row(outputs, buf)?;
+            }
+            OpType::Case(Case { signature }) => {
+                buf.push(18);
+                self.encode_signature(signature, buf)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_leaf_op(
+        &mut self,
+        op: &LeafOp,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), BinarySerializationError> {
+        match op {
+            LeafOp::CustomOp(ext) => {
+                buf.push(0);
+                let opaque = OpaqueOp::from(ext.clone());
+                self.encode_string(opaque.resource().clone(), buf);
+                self.encode_string(opaque.name().clone(), buf);
+                self.encode_string(opaque.description(), buf);
+                write_bytes(buf, &rmp_serde::to_vec_named(opaque.args())?);
+                match opaque.signature() {
+                    None => buf.push(0),
+                    Some(signature) => {
+                        buf.push(1);
+                        self.encode_signature(signature, buf)?;
+                    }
+                }
+            }
+            LeafOp::H => buf.push(1),
+            LeafOp::T => buf.push(2),
+            LeafOp::S => buf.push(3),
+            LeafOp::X => buf.push(4),
+            LeafOp::Y => buf.push(5),
+            LeafOp::Z => buf.push(6),
+            LeafOp::Tadj => buf.push(7),
+            LeafOp::Sadj => buf.push(8),
+            LeafOp::CX => buf.push(9),
+            LeafOp::ZZMax => buf.push(10),
+            LeafOp::Reset => buf.push(11),
+            LeafOp::Noop { ty } => {
+                buf.push(12);
+                self.encode_type(ty, buf)?;
+            }
+            LeafOp::Measure => buf.push(13),
+            LeafOp::RzF64 => buf.push(14),
+            LeafOp::Xor => buf.push(15),
+            LeafOp::MakeTuple { tys } => {
+                buf.push(16);
+                self.encode_row(tys, buf)?;
+            }
+            LeafOp::UnpackTuple { tys } => {
+                buf.push(17);
+                self.encode_row(tys, buf)?;
+            }
+            LeafOp::Tag { tag, variants } => {
+                buf.push(18);
+                write_varint(buf, *tag);
+                self.encode_row(variants, buf)?;
+            }
+            LeafOp::Lift {
+                type_row,
+                new_resource,
+            } => {
+                buf.push(19);
+                self.encode_row(type_row, buf)?;
+                self.encode_string(new_resource.clone(), buf);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The interned tables read back from a binary serialized HUGR.
+#[derive(Default)]
+struct Tables {
+    strings: Vec<SmolStr>,
+    types: Vec<SimpleType>,
+    ops: Vec<OpType>,
+}
+
+impl Tables {
+    /// Reads `count` new entries of the string table.
+    fn read_strings(
+        &mut self,
+        r: &mut Reader<impl Read>,
+        count: usize,
+    ) -> Result<(), BinarySerializationError> {
+        self.strings.reserve(count.min(MAX_PREALLOCATION));
+        for _ in 0..count {
+            let bytes = r.read_bytes()?;
+            let string = String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))?;
+            self.strings.push(string.into());
+        }
+        Ok(())
+    }
+
+    /// Reads a block of new entries of the tables.
+    fn read_definitions(
+        &mut self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<(), BinarySerializationError> {
+        let num_strings = r.read_varint()?;
+        self.read_strings(r, num_strings)?;
+        let num_types = r.read_varint()?;
+        self.types.reserve(num_types.min(MAX_PREALLOCATION));
+        for _ in 0..num_types {
+            let ty: SimpleType = rmp_serde::from_slice(&r.read_bytes()?)?;
+            self.types.push(ty);
+        }
+        let num_ops = r.read_varint()?;
+        self.ops.reserve(num_ops.min(MAX_PREALLOCATION));
+        for _ in 0..num_ops {
+            let bytes = r.read_bytes()?;
+            let mut op_reader = Reader(bytes.as_slice());
+            let op = self.decode_op(&mut op_reader)?;
+            if !op_reader.0.is_empty() {
+                return Err(invalid("trailing bytes after operation"));
+            }
+            self.ops.push(op);
+        }
+        Ok(())
+    }
+
+    /// Reads a node, along with the table entries defined before it.
+    /// `prev_parent` is the parent of the previous node.
+    fn read_node(
+        &mut self,
+        r: &mut Reader<impl Read>,
+        prev_parent: usize,
+    ) -> Result<NodeSerV1, BinarySerializationError> {
+        let flags = r.read_u8()?;
+        if flags & HAS_DEFINITIONS != 0 {
+            self.read_definitions(r)?;
+        }
+        let parent = r.read_delta(prev_parent)?;
+        let op = r.read_varint()?;
+        let op = self
+            .ops
+            .get(op)
+            .ok_or(BinarySerializationError::InvalidIndex {
+                table: "operation",
+                index: op,
+            })?
+            .clone();
+        let num_inputs = r.read_varint()?;
+        let num_outputs = r.read_varint()?;
+        let input_resources = match flags & HAS_INPUT_RESOURCES {
+            0 => None,
+            _ => Some(self.read_resource_set(r)?),
+        };
+        let metadata = match flags & HAS_METADATA {
+            0 => None,
+            _ => Some(serde_json::from_slice(&r.read_bytes()?)?),
+        };
+        Ok(NodeSerV1 {
+            parent: parent.into(),
+            input_resources,
+            num_inputs,
+            num_outputs,
+            metadata,
+            op,
+        })
+    }
+
+    fn read_string(&self, r: &mut Reader<impl Read>) -> Result<SmolStr, BinarySerializationError> {
+        let index = r.read_varint()?;
+        self.strings
+            .get(index)
+            .cloned()
+            .ok_or(BinarySerializationError::InvalidIndex {
+                table: "string",
+                index,
+            })
+    }
+
+    fn read_type(&self, r: &mut Reader<impl Read>) -> Result<SimpleType, BinarySerializationError> {
+        let index = r.read_varint()?;
+        self.types
+            .get(index)
+            .cloned()
+            .ok_or(BinarySerializationError::InvalidIndex {
+                table: "type",
+                index,
+            })
+    }
+
+    fn read_classic_type(
+        &self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<ClassicType, BinarySerializationError> {
+        self.read_type(r)?.try_into().map_err(invalid)
+    }
+
+    fn read_row(&self, r: &mut Reader<impl Read>) -> Result<SimpleRow, BinarySerializationError> {
+        let len = r.read_varint()?;
+        (0..len)
+            .map(|_| self.read_type(r))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Into::into)
+    }
+
+    fn read_classic_row(
+        &self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<ClassicRow, BinarySerializationError> {
+        let len = r.read_varint()?;
+        (0..len)
+            .map(|_| self.read_classic_type(r))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Into::into)
+    }
+
+    fn read_classic_rows(
+        &self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<Vec<ClassicRow>, BinarySerializationError> {
+        let len = r.read_varint()?;
+        (0..len).map(|_| self.read_classic_row(r)).collect()
+    }
+
+    fn read_resource_set(
+        &self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<ResourceSet, BinarySerializationError> {
+        let len = r.read_varint()?;
+        (0..len).map(|_| self.read_string(r)).collect()
+    }
+
+    fn read_signature(
+        &self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<AbstractSignature, BinarySerializationError> {
+        Ok(AbstractSignature {
+            input: self.read_row(r)?,
+            output: self.read_row(r)?,
+            static_input: self.read_classic_row(r)?,
+            resource_reqs: self.read_resource_set(r)?,
+        })
+    }
+
+    fn decode_op(&self, r: &mut Reader<impl Read>) -> Result<OpType, BinarySerializationError> {
+        let op: OpType = match r.read_u8()? {
+            0 => Module.into(),
+            1 => FuncDefn {
+                name: self.read_string(r)?.into(),
+                signature: self.read_signature(r)?,
+            }
+            .into(),
+            2 => FuncDecl {
+                name: self.read_string(r)?.into(),
+                signature: self.read_signature(r)?,
+            }
+            .into(),
+            3 => AliasDecl {
+                name: self.read_string(r)?,
+                tag: match r.read_u8()? {
+                    0 => TypeTag::Simple,
+                    1 => TypeTag::Classic,
+                    2 => TypeTag::Hashable,
+                    tag => return Err(invalid(format!("unknown type tag {tag}"))),
+                },
+            }
+            .into(),
+            4 => AliasDefn {
+                name: self.read_string(r)?,
+                definition: self.read_type(r)?,
+            }
+            .into(),
+            5 => rmp_serde::from_slice::<Const>(&r.read_bytes()?)?.into(),
+            6 => Input {
+                types: self.read_row(r)?,
+            }
+            .into(),
+            7 => Output {
+                types: self.read_row(r)?,
+            }
+            .into(),
+            8 => Call {
+                signature: self.read_signature(r)?,
+            }
+            .into(),
+            9 => CallIndirect {
+                signature: self.read_signature(r)?,
+            }
+            .into(),
+            10 => LoadConstant {
+                datatype: self.read_classic_type(r)?,
+            }
+            .into(),
+            11 => DFG {
+                signature: self.read_signature(r)?,
+            }
+            .into(),
+            12 => self.decode_leaf_op(r)?.into(),
+            13 => BasicBlock::DFB {
+                inputs: self.read_row(r)?,
+                other_outputs: self.read_row(r)?,
+                predicate_variants: self.read_classic_rows(r)?,
+            }
+            .into(),
+            14 => BasicBlock::Exit {
+                cfg_outputs: self.read_row(r)?,
+            }
+            .into(),
+            15 => TailLoop {
+                just_inputs: self.read_classic_row(r)?,
+                just_outputs: self.read_classic_row(r)?,
+                rest: self.read_row(r)?,
+            }
+            .into(),
+            16 => CFG {
+                inputs: self.read_row(r)?,
+                outputs: self.read_row(r)?,
+            }
+            .into(),
+            17 => Conditional {
+                predicate_inputs: self.read_classic_rows(r)?,
+                other_inputs: self.read_row(r)?,
+                outputs: self.read_row(r)?,
+            }
+            .into(),
+            18 => Case {
+                signature: self.read_signature(r)?,
+            }
+            .into(),
+            tag => return Err(invalid(format!("unknown operation tag {tag}"))),
+        };
+        Ok(op)
+    }
+
+    fn decode_leaf_op(
+        &self,
+        r: &mut Reader<impl Read>,
+    ) -> Result<LeafOp, BinarySerializationError> {
+        let op = match r.read_u8()? {
+            0 => {
+                let resource = self.read_string(r)?;
+                let name = self.read_string(r)?;
+                let description = self.read_string(r)?.into();
+                let args: Vec<TypeArg> = rmp_serde::from_slice(&r.read_bytes()?)?;
+                let signature = match r.read_u8()? {
+                    0 => None,
+                    _ => Some(self.read_signature(r)?),
+                };
+                let opaque = OpaqueOp::new(resource, name, description, args, signature);
+                LeafOp::CustomOp(ExternalOp::from(opaque))
+            }
+            1 => LeafOp::H,
+            2 => LeafOp::T,
+            3 => LeafOp::S,
+            4 => LeafOp::X,
+            5 => LeafOp::Y,
+            6 => LeafOp::Z,
+            7 => LeafOp::Tadj,
+            8 => LeafOp::Sadj,
+            9 => LeafOp::CX,
+            10 => LeafOp::ZZMax,
+            11 => LeafOp::Reset,
+            12 => LeafOp::Noop {
+                ty: self.read_type(r)?,
+            },
+            13 => LeafOp::Measure,
+            14 => LeafOp::RzF64,
+            15 => LeafOp::Xor,
+            16 => LeafOp::MakeTuple {
+                tys: self.read_row(r)?,
+            },
+            17 => LeafOp::UnpackTuple {
+                tys: self.read_row(r)?,
+            },
+            18 => LeafOp::Tag {
+                tag: r.read_varint()?,
+                variants: self.read_row(r)?,
+            },
+            19 => LeafOp::Lift {
+                type_row: self.read_row(r)?,
+                new_resource: self.read_string(r)?,
+            },
+            tag => return Err(invalid(format!("unknown leaf operation tag {tag}"))),
+        };
+        Ok(op)
+    }
+}
+
+/// Writes `value` as an unsigned LEB128 varint.
+fn write_varint(buf: &mut Vec<u8>, value: usize) {
+    let mut value = value as u64;
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Writes the difference `value - base` as a zigzag-encoded varint.
+fn write_delta(buf: &mut Vec<u8>, base: usize, value: usize) {
+    let delta = value as i64 - base as i64;
+    write_varint(buf, ((delta << 1) ^ (delta >> 63)) as usize);
+}
+
+/// Writes a length-prefixed byte string.
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buf, bytes.len());
+    buf.extend_from_slice(bytes);
+}
+
+/// A wrapper over a reader decoding the primitives of the format.
+struct Reader<R>(R);
+
+impl<R: Read> Reader<R> {
+    fn read_u8(&mut self) -> Result<u8, BinarySerializationError> {
+        let mut byte = [0];
+        self.0.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_varint(&mut self) -> Result<usize, BinarySerializationError> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return value
+                    .try_into()
+                    .map_err(|_| invalid(format!("integer {value} out of range")));
+            }
+        }
+        Err(invalid("varint too long"))
+    }
+
+    /// Reads a node index delta-encoded against `base`.
+    fn read_delta(&mut self, base: usize) -> Result<NodeIndex, BinarySerializationError> {
+        let zigzag = self.read_varint()? as u64;
+        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        (base as i64)
+            .checked_add(delta)
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| NodeIndex::try_from(index).ok())
+            .ok_or_else(|| invalid(format!("node index delta {delta} out of range")))
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, BinarySerializationError> {
+        let len = self.read_varint()?;
+        let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+        (&mut self.0).take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, HugrBuilder,
+        ModuleBuilder,
+    };
+    use crate::ops::ConstValue;
+    use crate::qasm::import_qasm;
+    use crate::types::CustomType;
+    use cool_asserts::assert_matches;
+    use serde_json::json;
+
+    const QB: SimpleType = SimpleType::Qubit;
+
+    fn binary_roundtrip(hugr: &Hugr) -> Hugr {
+        let mut bytes = Vec::new();
+        hugr.write_binary(&mut bytes).unwrap();
+        let mut reader = bytes.as_slice();
+        let new_hugr = Hugr::read_binary(&mut reader).unwrap();
+        assert!(reader.is_empty(), "the whole input should be consumed");
+        assert_eq!(
+            serde_json::to_value(&new_hugr).unwrap(),
+            serde_json::to_value(hugr).unwrap()
+        );
+        new_hugr
+    }
+
+    /// A circuit applying `layers` layers of gates to `width` qubits.
+    fn circuit(width: usize, layers: usize) -> Hugr {
+        let mut dfg =
+            DFGBuilder::new(AbstractSignature::new_df(vec![QB; width], vec![QB; width])).unwrap();
+        let mut qbs = dfg.input_wires().collect::<Vec<_>>();
+        for _ in 0..layers {
+            for q in qbs.iter_mut() {
+                *q = dfg.add_dataflow_op(LeafOp::H, [*q]).unwrap().out_wire(0);
+            }
+            for pair in qbs.chunks_mut(2) {
+                if let [a, b] = pair {
+                    let cx = dfg.add_dataflow_op(LeafOp::CX, [*a, *b]).unwrap();
+                    [*a, *b] = cx.outputs_arr();
+                }
+            }
+        }
+        dfg.finish_hugr_with_outputs(qbs).unwrap()
+    }
+
+    #[test]
+    fn fixture_roundtrip() {
+        let hugr: Hugr =
+            serde_json::from_str(include_str!("../../../resources/test/hugr_v1.json")).unwrap();
+        let new_hugr = binary_roundtrip(&hugr);
+        new_hugr.validate().unwrap();
+    }
+
+    #[test]
+    fn module_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module_builder = ModuleBuilder::new();
+        let array = CustomType::new(
+            "array",
+            [TypeArg::Type(QB), TypeArg::Int(u64::MAX.into())],
+            "collections",
+            TypeTag::Simple,
+        );
+        module_builder.add_alias_declare("opaque", TypeTag::Classic)?;
+        module_builder.add_alias_def("qubits", array.clone().into())?;
+        module_builder.add_constant(Const::new_tuple([
+            Const::i64(3)?,
+            Const::new(ConstValue::F64(1.5), ClassicType::F64)?,
+        ]))?;
+        let row = vec![array.into()];
+        let signature = AbstractSignature::new_df(row.clone(), row);
+        let mut f_build = module_builder.define_function("reverse", signature.clone().pure())?;
+        let op = OpaqueOp::new(
+            "collections".into(),
+            "reverse",
+            "Reverses an array".into(),
+            [TypeArg::Int(4)],
+            Some(signature),
+        );
+        let inputs = f_build.input_wires();
+        let reverse = f_build.add_dataflow_op(LeafOp::CustomOp(op.into()), inputs)?;
+        f_build.set_metadata(json!({"name": "reverse"}));
+        f_build.finish_with_outputs(reverse.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        binary_roundtrip(&hugr);
+        Ok(())
+    }
+
+    #[test]
+    fn qasm_roundtrip() {
+        let hugr = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[2];
+            h q[0];
+            rz(pi/4) q[1];
+            cx q[0], q[1];
+            measure q -> c;
+            if (c == 1) x q[0];
+            "#,
+        )
+        .unwrap();
+        binary_roundtrip(&hugr);
+    }
+
+    #[test]
+    fn sequential() {
+        let (first, second) = (circuit(2, 3), circuit(3, 2));
+        let mut bytes = Vec::new();
+        first.write_binary(&mut bytes).unwrap();
+        second.write_binary(&mut bytes).unwrap();
+
+        let mut reader = io::BufReader::new(bytes.as_slice());
+        for hugr in [first, second] {
+            let new_hugr = Hugr::read_binary(&mut reader).unwrap();
+            assert_eq!(
+                serde_json::to_value(&new_hugr).unwrap(),
+                serde_json::to_value(&hugr).unwrap()
+            );
+        }
+        assert!(reader.buffer().is_empty());
+    }
+
+    #[test]
+    fn compact() {
+        let hugr = circuit(16, 64);
+        let json = serde_json::to_vec(&hugr).unwrap();
+        let mut bytes = Vec::new();
+        hugr.write_binary(&mut bytes).unwrap();
+        assert!(
+            bytes.len() * 10 <= json.len(),
+            "binary size {} should be at most a tenth of the JSON size {}",
+            bytes.len(),
+            json.len()
+        );
+        binary_roundtrip(&hugr);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut bytes = Vec::new();
+        circuit(2, 1).write_binary(&mut bytes).unwrap();
+
+        assert_matches!(
+            Hugr::read_binary(&b"not a hugr"[..]),
+            Err(BinarySerializationError::InvalidMagic)
+        );
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 42;
+        assert_matches!(
+            Hugr::read_binary(wrong_version.as_slice()),
+            Err(BinarySerializationError::UnsupportedVersion(42))
+        );
+
+        assert_matches!(
+            Hugr::read_binary(&bytes[..bytes.len() - 1]),
+            Err(BinarySerializationError::Io(e)) => {
+                assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+            }
+        );
+    }
+
+    #[test]
+    fn corrupted_delta() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        // No resources, and a module root defining the module operation.
+        bytes.extend([0, 1, HAS_DEFINITIONS, 0, 0, 1, 1, 0]);
+        let root = bytes.len();
+        write_delta(&mut bytes, 0, 0);
+        bytes.extend([0, 0, 0]);
+        write_varint(&mut bytes, 0);
+        Hugr::read_binary(bytes.as_slice()).unwrap();
+
+        // A parent index past the largest node index.
+        let mut out_of_range = bytes[..root].to_vec();
+        write_delta(&mut out_of_range, 0, i64::MAX as usize);
+        out_of_range.extend(&bytes[root + 1..]);
+        assert_matches!(
+            Hugr::read_binary(out_of_range.as_slice()),
+            Err(BinarySerializationError::InvalidEncoding(_))
+        );
+
+        // Deltas overflowing the index arithmetic or going below zero.
+        let mut overflow = Vec::new();
+        write_delta(&mut overflow, 0, i64::MAX as usize);
+        assert_matches!(
+            Reader(overflow.as_slice()).read_delta(i64::MAX as usize),
+            Err(BinarySerializationError::InvalidEncoding(_))
+        );
+        let mut negative = Vec::new();
+        write_delta(&mut negative, 1, 0);
+        assert_matches!(
+            Reader(negative.as_slice()).read_delta(0),
+            Err(BinarySerializationError::InvalidEncoding(_))
+        );
+    }
+
+    #[test]
+    fn too_many_ports() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        // A module root with more input ports than a node can have.
+        bytes.extend([0, 1, HAS_DEFINITIONS, 0, 0, 1, 1, 0]);
+        write_delta(&mut bytes, 0, 0);
+        bytes.push(0);
+        write_varint(&mut bytes, 70000);
+        bytes.push(0);
+        write_varint(&mut bytes, 0);
+        assert_matches!(
+            Hugr::read_binary(bytes.as_slice()),
+            Err(BinarySerializationError::Hugr(e)) => {
+                assert_matches!(*e, HUGRSerializationError::TooManyPorts { num_ports: 70000, .. })
+            }
+        );
+    }
+}
@@ -0,0 +1,1687 @@
+//! Human-readable textual format for [`Hugr`].
+//!
+//! Each node is written as a statement naming it, giving its operation and
+//! the wires connected to its incoming ports, followed by its children in a
+//! nested block:
+//!
+//! ```text
+//! %0 = DFG [Qubit, Qubit] -> [Qubit, Qubit] {
+//!   %1 = Input [Qubit, Qubit]
+//!   %2 = Output [Qubit, Qubit] (%4.0, %4.1)
+//!   %3 = H (%1.0)
+//!   %4 = CX (%3.0, %1.1)
+//! }
+//! ```
+//!
+//! A wire `%n.p` is the outgoing port `p` of node `%n`, and `%n` alone is its
+//! port 0. Node names may be any alphanumeric identifier. The sources of the
+//! incoming ports are listed in order; unconnected ports are written `_`, and
+//! ports linked to several sources (such as the input of a basic block with
+//! several predecessors) list them in brackets. Nodes may be followed by
+//! annotations:
+//!
+//! - `ports(i, o)` when their number of ports differs from the operation's,
+//!   or from zero for the root.
+//! - `open_resources`, or `input_resources {"r", ...}` when they have input
+//!   resources (by default, nodes have an empty set of input resources).
+//! - `meta <json>` with their metadata.
+//!
+//! Types, type arguments and constant values have a readable syntax, such as
+//! `Tuple(I64, List(F64))` or `Sum(1, Tuple())`. Anything that cannot be
+//! expressed in it, such as custom constants, is written as `json <json>`
+//! using the serialized representation.
+//!
+//! Parsing the printed text of a HUGR produces an identical HUGR, up to the
+//! indices of its nodes. Line comments start with `//`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use portgraph::NodeIndex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+#[cfg(feature = "pyo3")]
+use pyo3::prelude::*;
+
+use super::{default_ports, node_resources, HUGRSerializationError, NodeSerV1, SerHugrV1};
+use crate::hugr::NodeMetadata;
+use crate::ops::custom::{ExternalOp, OpaqueOp};
+use crate::ops::{
+    AliasDecl, AliasDefn, BasicBlock, Call, CallIndirect, Case, Conditional, Const, ConstValue,
+    FuncDecl, FuncDefn, Input, LeafOp, LoadConstant, Module, OpType, Output, TailLoop, CFG, DFG,
+};
+use crate::resource::ResourceSet;
+use crate::types::type_param::TypeArg;
+use crate::types::{
+    AbstractSignature, ClassicRow, ClassicType, Container, CustomType, HashableType, SimpleRow,
+    SimpleType, TypeTag,
+};
+use crate::values::{ContainerValue, HashableValue};
+use crate::{Hugr, Node};
+
+/// Errors that can occur while printing or parsing the textual format.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum TextFormatError {
+    /// The text is not syntactically valid.
+    #[error("Line {line}, column {column}: {message}")]
+    Syntax {
+        /// The line of the error.
+        line: usize,
+        /// The column of the error.
+        column: usize,
+        /// A description of the error.
+        message: String,
+    },
+    /// A wire refers to a node that is not defined.
+    #[error("Line {line}: unknown node %{name}")]
+    UnknownNode {
+        /// The line of the reference.
+        line: usize,
+        /// The name of the node.
+        name: String,
+    },
+    /// Two nodes have the same name.
+    #[error("Line {line}: node %{name} is defined twice")]
+    DuplicateNode {
+        /// The line of the second definition.
+        line: usize,
+        /// The name of the node.
+        name: String,
+    },
+    /// A value could not be printed in its serialized representation.
+    #[error("Failed to print {0}")]
+    Print(String),
+    /// The parsed HUGR could not be built.
+    #[error("Failed to build the HUGR: {0}")]
+    Hugr(Box<HUGRSerializationError>),
+}
+
+impl From<HUGRSerializationError> for TextFormatError {
+    fn from(err: HUGRSerializationError) -> Self {
+        Self::Hugr(Box::new(err))
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl From<TextFormatError> for PyErr {
+    fn from(err: TextFormatError) -> Self {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(err.to_string())
+    }
+}
+
+impl Hugr {
+    /// Prints the HUGR in the textual format.
+    ///
+    /// See the [module-level documentation](self) for a description of the
+    /// format, and [`Hugr::from_text`] for the inverse operation.
+    pub fn to_text(&self) -> Result<String, TextFormatError> {
+        let ser: SerHugrV1 = self.try_into()?;
+        Printer::new(&ser).print()
+    }
+
+    /// Parses a HUGR in the textual format.
+    pub fn from_text(text: &str) -> Result<Self, TextFormatError> {
+        let ser = Parser::new(text).hugr()?;
+        Ok(ser.try_into()?)
+    }
+}
+
+/// Parses an operation in the textual format, such as `H` or `Noop Qubit`.
+#[cfg(feature = "pyo3")]
+pub(crate) fn parse_op(text: &str) -> Result<OpType, TextFormatError> {
+    Parser::new(text).complete(Parser::op)
+}
+
+/// Prints an operation in the textual format.
+#[cfg(feature = "pyo3")]
+pub(crate) fn op_to_text(op: &OpType) -> Result<String, TextFormatError> {
+    let mut out = String::new();
+    write_op(&mut out, op)?;
+    Ok(out)
+}
+
+/// Parses a type in the textual format, such as `Tuple(Qubit, F64)`.
+#[cfg(feature = "pyo3")]
+pub(crate) fn parse_type(text: &str) -> Result<SimpleType, TextFormatError> {
+    Parser::new(text).complete(Parser::simple_type)
+}
+
+/// Prints a type in the textual format.
+#[cfg(feature = "pyo3")]
+pub(crate) fn type_to_text(ty: &SimpleType) -> Result<String, TextFormatError> {
+    let mut out = String::new();
+    write_type(&mut out, ty)?;
+    Ok(out)
+}
+
+/// Prints a serialized HUGR in the textual format.
+struct Printer<'a> {
+    ser: &'a SerHugrV1,
+    children: Vec<Vec<usize>>,
+    /// For each node, the sources linked to each of its incoming ports.
+    sources: Vec<Vec<Vec<(usize, usize)>>>,
+    /// The printed name of each node, given in order of appearance.
+    names: Vec<usize>,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn new(ser: &'a SerHugrV1) -> Self {
+        let mut children = vec![vec![]; ser.nodes.len()];
+        for (n, node) in ser.nodes.iter().enumerate().skip(1) {
+            children[node.parent.index.index()].push(n);
+        }
+        let mut sources: Vec<_> = ser
+            .nodes
+            .iter()
+            .map(|node| vec![vec![]; node.num_inputs])
+            .collect();
+        for [(src, src_port), (tgt, tgt_port)] in &ser.edges {
+            sources[tgt.index.index()][*tgt_port].push((src.index.index(), *src_port));
+        }
+
+        // Nodes are named in the order they are printed.
+        let mut names = vec![0; ser.nodes.len()];
+        let mut stack = vec![0];
+        let mut count = 0;
+        while let Some(n) = stack.pop() {
+            names[n] = count;
+            count += 1;
+            stack.extend(children[n].iter().rev());
+        }
+
+        Self {
+            ser,
+            children,
+            sources,
+            names,
+            out: String::new(),
+        }
+    }
+
+    fn print(mut self) -> Result<String, TextFormatError> {
+        if !self.ser.nodes.is_empty() {
+            self.node(0, 0)?;
+        }
+        Ok(self.out)
+    }
+
+    fn node(&mut self, n: usize, depth: usize) -> Result<(), TextFormatError> {
+        let node = &self.ser.nodes[n];
+        let indent = "  ".repeat(depth);
+        write!(self.out, "{indent}%{} = ", self.names[n]).unwrap();
+        write_op(&mut self.out, &node.op)?;
+
+        let sources = &self.sources[n];
+        let connected = sources
+            .iter()
+            .rposition(|s| !s.is_empty())
+            .map_or(0, |i| i + 1);
+        if connected > 0 {
+            let wire = |&(src, port): &(usize, usize)| format!("%{}.{port}", self.names[src]);
+            let links = sources[..connected]
+                .iter()
+                .map(|srcs| match srcs.as_slice() {
+                    [] => "_".to_string(),
+                    [src] => wire(src),
+                    srcs => format!("[{}]", srcs.iter().map(wire).collect::<Vec<_>>().join(", ")),
+                });
+            write!(self.out, " ({})", links.collect::<Vec<_>>().join(", ")).unwrap();
+        }
+
+        if (node.num_inputs, node.num_outputs) != default_ports(n, &node.op) {
+            write!(
+                self.out,
+                " ports({}, {})",
+                node.num_inputs, node.num_outputs
+            )
+            .unwrap();
+        }
+        match &node.input_resources {
+            None => self.out.push_str(" open_resources"),
+            Some(rs) if rs.iter().next().is_some() => {
+                self.out.push_str(" input_resources ");
+                write_resources(&mut self.out, rs)?;
+            }
+            Some(_) => {}
+        }
+        if let Some(metadata) = &node.metadata {
+            write!(self.out, " meta {}", to_json(metadata)?).unwrap();
+        }
+
+        if self.children[n].is_empty() {
+            self.out.push('\n');
+        } else {
+            self.out.push_str(" {\n");
+            for child in self.children[n].clone() {
+                self.node(child, depth + 1)?;
+            }
+            writeln!(self.out, "{indent}}}").unwrap();
+        }
+        Ok(())
+    }
+}
+
+fn to_json(value: &impl Serialize) -> Result<String, TextFormatError> {
+    serde_json::to_string(value).map_err(|e| TextFormatError::Print(e.to_string()))
+}
+
+fn write_op(out: &mut String, op: &OpType) -> Result<(), TextFormatError> {
+    match op {
+        OpType::Module(Module) => out.push_str("Module"),
+        OpType::FuncDefn(FuncDefn { name, signature }) => {
+            write!(out, "FuncDefn {} ", to_json(name)?).unwrap();
+            write_signature(out, signature)?;
+        }
+        OpType::FuncDecl(FuncDecl { name, signature }) => {
+            write!(out, "FuncDecl {} ", to_json(name)?).unwrap();
+            write_signature(out, signature)?;
+        }
+        OpType::AliasDecl(AliasDecl { name, tag }) => {
+            write!(out, "AliasDecl {} {tag}", to_json(name)?).unwrap();
+        }
+        OpType::AliasDefn(AliasDefn { name, definition }) => {
+            write!(out, "AliasDefn {} = ", to_json(name)?).unwrap();
+            write_type(out, definition)?;
+        }
+        OpType::Const(c) => {
+            out.push_str("Const ");
+            write_classic_type(out, c.const_type())?;
+            out.push_str(" = ");
+            write_value(out, c.value())?;
+        }
+        OpType::Input(Input { types }) => {
+            out.push_str("Input ");
+            write_row(out, types)?;
+        }
+        OpType::Output(Output { types }) => {
+            out.push_str("Output ");
+            write_row(out, types)?;
+        }
+        OpType::Call(Call { signature }) => {
+            out.push_str("Call ");
+            write_signature(out, signature)?;
+        }
+        OpType::CallIndirect(CallIndirect { signature }) => {
+            out.push_str("CallIndirect ");
+            write_signature(out, signature)?;
+        }
+        OpType::LoadConstant(LoadConstant { datatype }) => {
+            out.push_str("LoadConstant ");
+            write_classic_type(out, datatype)?;
+        }
+        OpType::DFG(DFG { signature }) => {
+            out.push_str("DFG ");
+            write_signature(out, signature)?;
+        }
+        OpType::LeafOp(leaf) => write_leaf_op(out, leaf)?,
+        OpType::BasicBlock(BasicBlock::DFB {
+            inputs,
+            other_outputs,
+            predicate_variants,
+        }) => {
+            out.push_str("DFB inputs ");
+            write_row(out, inputs)?;
+            out.push_str(" predicate_variants ");
+            write_classic_rows(out, predicate_variants)?;
+            out.push_str(" other_outputs ");
+            write_row(out, other_outputs)?;
+        }
+        OpType::BasicBlock(BasicBlock::Exit { cfg_outputs }) => {
+            out.push_str("Exit ");
+            write_row(out, cfg_outputs)?;
+        }
+        OpType::TailLoop(TailLoop {
+            just_inputs,
+            just_outputs,
+            rest,
+        }) => {
+            out.push_str("TailLoop just_inputs ");
+            write_classic_row(out, just_inputs)?;
+            out.push_str(" just_outputs ");
+            write_classic_row(out, just_outputs)?;
+            out.push_str(" rest ");
+            write_row(out, rest)?;
+        }
+        OpType::CFG(CFG { inputs, outputs }) => {
+            out.push_str("CFG ");
+            write_row(out, inputs)?;
+            out.push_str(" -> ");
+            write_row(out, outputs)?;
+        }
+        OpType::Conditional(Conditional {
+            predicate_inputs,
+            other_inputs,
+            outputs,
+        }) => {
+            out.push_str("Conditional predicate_inputs ");
+            write_classic_rows(out, predicate_inputs)?;
+            out.push_str(" other_inputs ");
+            write_row(out, other_inputs)?;
+            out.push_str(" outputs ");
+            write_row(out, outputs)?;
+        }
+        OpType::Case(Case { signature }) => {
+            out.push_str("Case ");
+            write_signature(out, signature)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_leaf_op(out: &mut String, op: &LeafOp) -> Result<(), TextFormatError> {
+    match op {
+        LeafOp::CustomOp(ext) => {
+            let opaque = OpaqueOp::from(ext.clone());
+            write!(
+                out,
+                "Custom {} {} ",
+                to_json(opaque.resource())?,
+                to_json(opaque.name())?
+            )
+            .unwrap();
+            write_list(out, "[", opaque.args(), "]", write_type_arg)?;
+            write!(out, " {}", to_json(&opaque.description())?).unwrap();
+            if let Some(signature) = opaque.signature() {
+                out.push(' ');
+                write_signature(out, signature)?;
+            }
+        }
+        LeafOp::Noop { ty } => {
+            out.push_str("Noop ");
+            write_type(out, ty)?;
+        }
+        LeafOp::MakeTuple { tys } => {
+            out.push_str("MakeTuple ");
+            write_row(out, tys)?;
+        }
+        LeafOp::UnpackTuple { tys } => {
+            out.push_str("UnpackTuple ");
+            write_row(out, tys)?;
+        }
+        LeafOp::Tag { tag, variants } => {
+            write!(out, "Tag {tag} ").unwrap();
+            write_row(out, variants)?;
+        }
+        LeafOp::Lift {
+            type_row,
+            new_resource,
+        } => {
+            out.push_str("Lift ");
+            write_row(out, type_row)?;
+            write!(out, " {}", to_json(new_resource)?).unwrap();
+        }
+        // The remaining operations have no parameters.
+        op => write!(out, "{op:?}").unwrap(),
+    }
+    Ok(())
+}
+
+fn write_list<T>(
+    out: &mut String,
+    open: &str,
+    items: &[T],
+    close: &str,
+    mut write_item: impl FnMut(&mut String, &T) -> Result<(), TextFormatError>,
+) -> Result<(), TextFormatError> {
+    out.push_str(open);
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_item(out, item)?;
+    }
+    out.push_str(close);
+    Ok(())
+}
+
+fn write_resources(out: &mut String, resources: &ResourceSet) -> Result<(), TextFormatError> {
+    let mut resources: Vec<_> = resources.iter().collect();
+    resources.sort();
+    write_list(out, "{", &resources, "}", |out, r| {
+        out.push_str(&to_json(r)?);
+        Ok(())
+    })
+}
+
+fn write_signature(out: &mut String, signature: &AbstractSignature) -> Result<(), TextFormatError> {
+    write_row(out, &signature.input)?;
+    out.push_str(" -> ");
+    write_row(out, &signature.output)?;
+    if !signature.static_input.is_empty() {
+        out.push_str(" static ");
+        write_classic_row(out, &signature.static_input)?;
+    }
+    if signature.resource_reqs.iter().next().is_some() {
+        out.push_str(" reqs ");
+        write_resources(out, &signature.resource_reqs)?;
+    }
+    Ok(())
+}
+
+fn write_row(out: &mut String, row: &SimpleRow) -> Result<(), TextFormatError> {
+    write_list(out, "[", row, "]", write_type)
+}
+
+fn write_classic_row(out: &mut String, row: &ClassicRow) -> Result<(), TextFormatError> {
+    write_list(out, "[", row, "]", write_classic_type)
+}
+
+fn write_classic_rows(out: &mut String, rows: &[ClassicRow]) -> Result<(), TextFormatError> {
+    write_list(out, "[", rows, "]", write_classic_row)
+}
+
+fn write_classic_type(out: &mut String, ty: &ClassicType) -> Result<(), TextFormatError> {
+    write_type(out, &ty.clone().into())
+}
+
+/// Writes a type in the readable syntax if it parses back to the same type,
+/// and in its serialized representation otherwise.
+fn write_type(out: &mut String, ty: &SimpleType) -> Result<(), TextFormatError> {
+    let mut readable = String::new();
+    write_readable_type(&mut readable, ty)?;
+    if Parser::new(&readable)
+        .complete(Parser::simple_type)
+        .as_ref()
+        == Ok(ty)
+    {
+        out.push_str(&readable);
+    } else {
+        write!(out, "json {}", to_json(ty)?).unwrap();
+    }
+    Ok(())
+}
+
+fn write_readable_type(out: &mut String, ty: &SimpleType) -> Result<(), TextFormatError> {
+    match ty {
+        SimpleType::Qubit => out.push_str("Qubit"),
+        SimpleType::Qontainer(c) => write_container(out, c, TypeTag::Simple)?,
+        SimpleType::Classic(ClassicType::F64) => out.push_str("F64"),
+        SimpleType::Classic(ClassicType::Graph(signature)) => {
+            out.push_str("Graph(");
+            write_signature(out, signature)?;
+            out.push(')');
+        }
+        SimpleType::Classic(ClassicType::Container(c)) => {
+            write_container(out, c, TypeTag::Classic)?
+        }
+        SimpleType::Classic(ClassicType::Hashable(h)) => match h {
+            HashableType::Variable(name) => write!(out, "Var({})", to_json(name)?).unwrap(),
+            HashableType::Int(width) => write!(out, "I{width}").unwrap(),
+            HashableType::String => out.push_str("String"),
+            HashableType::Container(c) => write_container(out, c, TypeTag::Hashable)?,
+        },
+    }
+    Ok(())
+}
+
+fn write_container<T: Clone + Into<SimpleType> + 'static>(
+    out: &mut String,
+    container: &Container<T>,
+    tag: TypeTag,
+) -> Result<(), TextFormatError> {
+    let write_elem = |out: &mut String, ty: &T| write_type(out, &ty.clone().into());
+    match container {
+        Container::List(ty) => write_list(out, "List(", &[ty.as_ref()], ")", |out, ty| {
+            write_elem(out, ty)
+        })?,
+        Container::Map(kv) => {
+            out.push_str("Map(");
+            write_type(out, &kv.0.clone().into())?;
+            out.push_str(", ");
+            write_elem(out, &kv.1)?;
+            out.push(')');
+        }
+        Container::Tuple(row) => write_list(out, "Tuple(", row, ")", write_elem)?,
+        Container::Sum(row) => write_list(out, "Sum(", row, ")", write_elem)?,
+        Container::Array(ty, len) => {
+            out.push_str("Array(");
+            write_elem(out, ty)?;
+            write!(out, ", {len})").unwrap();
+        }
+        Container::Alias(name) => write!(out, "Alias({}, {tag})", to_json(name)?).unwrap(),
+        Container::Opaque(custom) => {
+            write!(
+                out,
+                "Opaque({}, {}, ",
+                to_json(custom.resource())?,
+                to_json(custom.name())?
+            )
+            .unwrap();
+            write_list(out, "[", custom.args(), "]", write_type_arg)?;
+            write!(out, ", {})", custom.tag()).unwrap();
+        }
+    }
+    Ok(())
+}
+
+fn write_type_arg(out: &mut String, arg: &TypeArg) -> Result<(), TextFormatError> {
+    let write_wrapped = |out: &mut String, name: &str, ty: SimpleType| {
+        write!(out, "{name}(").unwrap();
+        write_type(out, &ty)?;
+        out.push(')');
+        Ok::<_, TextFormatError>(())
+    };
+    match arg {
+        TypeArg::Type(ty) => write_wrapped(out, "Type", ty.clone())?,
+        TypeArg::ClassicType(ty) => write_wrapped(out, "ClassicType", ty.clone().into())?,
+        TypeArg::HashableType(ty) => write_wrapped(out, "HashableType", ty.clone().into())?,
+        TypeArg::Int(i) => write!(out, "{i}").unwrap(),
+        TypeArg::String(s) => out.push_str(&to_json(s)?),
+        TypeArg::List(args) => write_list(out, "[", args, "]", write_type_arg)?,
+        TypeArg::CustomValue(value) => {
+            let yaml =
+                serde_yaml::to_string(value).map_err(|e| TextFormatError::Print(e.to_string()))?;
+            write!(out, "Yaml({})", to_json(&yaml)?).unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// Writes a constant value in the readable syntax if it parses back to the
+/// same value, and in its serialized representation otherwise.
+fn write_value(out: &mut String, value: &ConstValue) -> Result<(), TextFormatError> {
+    let mut readable = String::new();
+    // Compare the debug representations, as `NaN`s are not equal to themselves.
+    let roundtrips = write_readable_value(&mut readable, value)?
+        && Parser::new(&readable)
+            .complete(Parser::value)
+            .is_ok_and(|parsed| format!("{parsed:?}") == format!("{value:?}"));
+    if roundtrips {
+        out.push_str(&readable);
+    } else {
+        write!(out, "json {}", to_json(value)?).unwrap();
+    }
+    Ok(())
+}
+
+/// Writes a constant value in the readable syntax, returning `false` if it
+/// cannot be expressed in it.
+fn write_readable_value(out: &mut String, value: &ConstValue) -> Result<bool, TextFormatError> {
+    match value {
+        ConstValue::Hashable(HashableValue::String(s)) => out.push_str(&to_json(s)?),
+        ConstValue::Hashable(HashableValue::Int(i)) => write!(out, "{i}").unwrap(),
+        ConstValue::Hashable(HashableValue::Container(c)) => {
+            let c = match c {
+                ContainerValue::Sequence(vs) => {
+                    ContainerValue::Sequence(vs.iter().cloned().map(ConstValue::Hashable).collect())
+                }
+                ContainerValue::Map(kvs) => ContainerValue::Map(
+                    kvs.iter()
+                        .map(|(k, v)| (k.clone(), ConstValue::Hashable(v.clone())))
+                        .collect(),
+                ),
+                ContainerValue::Sum(tag, v) => {
+                    ContainerValue::Sum(*tag, Box::new(ConstValue::Hashable((**v).clone())))
+                }
+            };
+            return write_readable_container_value(out, &c);
+        }
+        ConstValue::Container(c) => return write_readable_container_value(out, c),
+        ConstValue::F64(f) => write!(out, "{f:?}").unwrap(),
+        ConstValue::Opaque(_) => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn write_readable_container_value(
+    out: &mut String,
+    container: &ContainerValue<ConstValue>,
+) -> Result<bool, TextFormatError> {
+    let mut readable = true;
+    match container {
+        ContainerValue::Sequence(vs) => write_list(out, "Tuple(", vs, ")", |out, v| {
+            readable &= write_readable_value(out, v)?;
+            Ok(())
+        })?,
+        ContainerValue::Map(kvs) => write_list(out, "Map(", kvs, ")", |out, (k, v)| {
+            readable &= write_readable_value(out, &ConstValue::Hashable(k.clone()))?;
+            out.push_str(": ");
+            readable &= write_readable_value(out, v)?;
+            Ok(())
+        })?,
+        ContainerValue::Sum(tag, v) => {
+            write!(out, "Sum({tag}, ").unwrap();
+            readable &= write_readable_value(out, v)?;
+            out.push(')');
+        }
+    }
+    Ok(readable)
+}
+
+/// Builds a container of `$ty`, with the most specific tag allowed by `$ty`.
+macro_rules! natural_container {
+    ($ty:expr, |$t:ident| $container:expr) => {
+        match $ty {
+            SimpleType::Classic(ClassicType::Hashable($t)) => {
+                HashableType::Container($container).into()
+            }
+            SimpleType::Classic($t) => ClassicType::Container($container).into(),
+            $t => SimpleType::Qontainer($container),
+        }
+    };
+}
+#[cfg(feature = "pyo3")]
+pub(crate) use natural_container;
+
+/// A numeric literal.
+enum Number {
+    Int(u128),
+    Float(f64),
+}
+
+/// A reference to an outgoing port, to be resolved once all the nodes are
+/// parsed.
+struct Link<'a> {
+    source: &'a str,
+    source_port: usize,
+    target: usize,
+    target_port: usize,
+    /// The position of the reference in the text.
+    pos: usize,
+}
+
+/// Parses the textual format into a serialized HUGR.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    nodes: Vec<NodeSerV1>,
+    names: HashMap<&'a str, usize>,
+    links: Vec<Link<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            nodes: vec![],
+            names: HashMap::new(),
+            links: vec![],
+        }
+    }
+
+    /// The line and column of a position in the text.
+    fn location(&self, pos: usize) -> (usize, usize) {
+        let before = &self.text[..pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        (line, column)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, TextFormatError> {
+        let (line, column) = self.location(self.pos);
+        Err(TextFormatError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with("//") {
+                break;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ws();
+        self.rest().chars().next()
+    }
+
+    /// Consumes the symbol if it is next.
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.skip_ws();
+        let found = self.rest().starts_with(symbol);
+        if found {
+            self.pos += symbol.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), TextFormatError> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => self.error(format!("expected {symbol:?}")),
+        }
+    }
+
+    /// Parses `f` and checks that it consumes the whole text.
+    fn complete<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, TextFormatError>,
+    ) -> Result<T, TextFormatError> {
+        let result = f(self)?;
+        match self.peek() {
+            None => Ok(result),
+            Some(_) => self.error("expected the end of the input"),
+        }
+    }
+
+    /// Returns the identifier starting at the current position, if any.
+    fn peek_word(&mut self) -> Option<&'a str> {
+        self.skip_ws();
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        (end > 0 && !rest.starts_with(|c: char| c.is_ascii_digit())).then(|| &rest[..end])
+    }
+
+    fn word(&mut self) -> Result<&'a str, TextFormatError> {
+        match self.peek_word() {
+            Some(word) => {
+                self.pos += word.len();
+                Ok(word)
+            }
+            None => self.error("expected an identifier"),
+        }
+    }
+
+    /// Consumes the keyword if it is next.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_word() == Some(keyword);
+        if found {
+            self.pos += keyword.len();
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), TextFormatError> {
+        match self.eat_keyword(keyword) {
+            true => Ok(()),
+            false => self.error(format!("expected {keyword:?}")),
+        }
+    }
+
+    /// Parses a node name, `%` followed by an alphanumeric identifier.
+    fn name(&mut self) -> Result<&'a str, TextFormatError> {
+        self.expect("%")?;
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return self.error("expected a node name");
+        }
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    /// Parses a JSON value.
+    fn json<T: DeserializeOwned>(&mut self) -> Result<T, TextFormatError> {
+        self.skip_ws();
+        let mut stream = serde_json::Deserializer::from_str(self.rest()).into_iter();
+        match stream.next() {
+            Some(Ok(value)) => {
+                self.pos += stream.byte_offset();
+                Ok(value)
+            }
+            Some(Err(e)) => self.error(format!("invalid JSON: {e}")),
+            None => self.error("expected a JSON value"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, TextFormatError> {
+        match self.peek() {
+            Some('"') => self.json(),
+            _ => self.error("expected a string"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Number, TextFormatError> {
+        self.skip_ws();
+        let rest = self.rest();
+        let sign = usize::from(rest.starts_with('-'));
+        if rest[sign..].starts_with("inf") {
+            self.pos += sign + 3;
+            let inf = if sign == 1 {
+                -f64::INFINITY
+            } else {
+                f64::INFINITY
+            };
+            return Ok(Number::Float(inf));
+        }
+        let mut end = sign;
+        let mut float = false;
+        for (i, c) in rest[sign..].char_indices() {
+            let exponent_sign = matches!(c, '+' | '-') && rest[..sign + i].ends_with(['e', 'E']);
+            if c.is_ascii_digit() || exponent_sign {
+            } else if matches!(c, '.' | 'e' | 'E') {
+                float = true;
+            } else {
+                break;
+            }
+            end = sign + i + c.len_utf8();
+        }
+        let text = &rest[..end];
+        let number = match float {
+            false if sign == 0 => text.parse().ok().map(Number::Int),
+            _ => text.parse().ok().map(Number::Float),
+        };
+        match number {
+            Some(number) => {
+                self.pos += end;
+                Ok(number)
+            }
+            None => self.error("expected a number"),
+        }
+    }
+
+    fn int<T: TryFrom<u128>>(&mut self) -> Result<T, TextFormatError> {
+        let pos = self.pos;
+        match self.number() {
+            Ok(Number::Int(i)) => {
+                if let Ok(i) = i.try_into() {
+                    return Ok(i);
+                }
+            }
+            Ok(Number::Float(_)) | Err(_) => {}
+        }
+        self.pos = pos;
+        self.error("expected an integer")
+    }
+
+    /// Parses a comma-separated list of items delimited by `open` and `close`.
+    fn list<T>(
+        &mut self,
+        open: &str,
+        close: &str,
+        mut item: impl FnMut(&mut Self) -> Result<T, TextFormatError>,
+    ) -> Result<Vec<T>, TextFormatError> {
+        self.expect(open)?;
+        let mut items = vec![];
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn hugr(mut self) -> Result<SerHugrV1, TextFormatError> {
+        self.complete(|p| p.node(None))?;
+        if self.nodes.is_empty() {
+            return self.error("expected a node");
+        }
+
+        let mut edges = Vec::with_capacity(self.links.len());
+        for link in &self.links {
+            let Some(&source) = self.names.get(link.source) else {
+                return Err(TextFormatError::UnknownNode {
+                    line: self.location(link.pos).0,
+                    name: link.source.to_string(),
+                });
+            };
+            let node = |index| Node::from(NodeIndex::new(index));
+            edges.push([
+                (node(source), link.source_port),
+                (node(link.target), link.target_port),
+            ]);
+        }
+
+        let mut resources = ResourceSet::new();
+        for node in &self.nodes {
+            resources = resources.union(&node_resources(node.input_resources.as_ref(), &node.op));
+        }
+        let mut resources: Vec<_> = resources.iter().cloned().collect();
+        resources.sort();
+        Ok(SerHugrV1 {
+            resources,
+            nodes: self.nodes,
+            edges,
+        })
+    }
+
+    /// Parses a node statement and its children.
+    fn node(&mut self, parent: Option<usize>) -> Result<(), TextFormatError> {
+        self.skip_ws();
+        let name_pos = self.pos;
+        let name = self.name()?;
+        let index = self.nodes.len();
+        if self.names.insert(name, index).is_some() {
+            return Err(TextFormatError::DuplicateNode {
+                line: self.location(name_pos).0,
+                name: name.to_string(),
+            });
+        }
+        self.expect("=")?;
+        let op = self.op()?;
+
+        if self.peek() == Some('(') {
+            let sources = self.list("(", ")", |p| {
+                if p.eat("_") {
+                    Ok(vec![])
+                } else if p.peek() == Some('[') {
+                    p.list("[", "]", Self::wire)
+                } else {
+                    Ok(vec![p.wire()?])
+                }
+            })?;
+            for (port, sources) in sources.into_iter().enumerate() {
+                for (pos, source, source_port) in sources {
+                    self.links.push(Link {
+                        source,
+                        source_port,
+                        target: index,
+                        target_port: port,
+                        pos,
+                    });
+                }
+            }
+        }
+
+        let (num_inputs, num_outputs) = default_ports(index, &op);
+        let mut node = NodeSerV1 {
+            parent: NodeIndex::new(parent.unwrap_or(index)).into(),
+            input_resources: Some(ResourceSet::new()),
+            num_inputs,
+            num_outputs,
+            metadata: None,
+            op,
+        };
+        loop {
+            if self.eat_keyword("ports") {
+                self.expect("(")?;
+                node.num_inputs = self.int()?;
+                self.expect(",")?;
+                node.num_outputs = self.int()?;
+                self.expect(")")?;
+            } else if self.eat_keyword("open_resources") {
+                node.input_resources = None;
+            } else if self.eat_keyword("input_resources") {
+                node.input_resources = Some(self.resources()?);
+            } else if self.eat_keyword("meta") {
+                node.metadata = Some(self.json::<NodeMetadata>()?);
+            } else {
+                break;
+            }
+        }
+        self.nodes.push(node);
+
+        if self.eat("{") {
+            while !self.eat("}") {
+                if self.peek().is_none() {
+                    return self.error("expected \"}\"");
+                }
+                self.node(Some(index))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a reference to an outgoing port, returning its position, node
+    /// name and port.
+    fn wire(&mut self) -> Result<(usize, &'a str, usize), TextFormatError> {
+        self.skip_ws();
+        let pos = self.pos;
+        let name = self.name()?;
+        // The port is attached to the name, without whitespace.
+        let port = match self.rest().starts_with('.') {
+            true => {
+                self.pos += 1;
+                self.int()?
+            }
+            false => 0,
+        };
+        Ok((pos, name, port))
+    }
+
+    fn op(&mut self) -> Result<OpType, TextFormatError> {
+        self.skip_ws();
+        let pos = self.pos;
+        let op: OpType = match self.word()? {
+            "Module" => Module.into(),
+            "FuncDefn" => FuncDefn {
+                name: self.string()?,
+                signature: self.signature()?,
+            }
+            .into(),
+            "FuncDecl" => FuncDecl {
+                name: self.string()?,
+                signature: self.signature()?,
+            }
+            .into(),
+            "AliasDecl" => AliasDecl {
+                name: self.string()?.into(),
+                tag: self.type_tag()?,
+            }
+            .into(),
+            "AliasDefn" => {
+                let name = self.string()?.into();
+                self.expect("=")?;
+                AliasDefn {
+                    name,
+                    definition: self.simple_type()?,
+                }
+                .into()
+            }
+            "Const" => {
+                let typ = self.classic_type()?;
+                self.expect("=")?;
+                let value = self.value()?;
+                match Const::new(value, typ) {
+                    Ok(c) => c.into(),
+                    Err(e) => return self.error(format!("invalid constant: {e}")),
+                }
+            }
+            "Input" => Input { types: self.row()? }.into(),
+            "Output" => Output { types: self.row()? }.into(),
+            "Call" => Call {
+                signature: self.signature()?,
+            }
+            .into(),
+            "CallIndirect" => CallIndirect {
+                signature: self.signature()?,
+            }
+            .into(),
+            "LoadConstant" => LoadConstant {
+                datatype: self.classic_type()?,
+            }
+            .into(),
+            "DFG" => DFG {
+                signature: self.signature()?,
+            }
+            .into(),
+            "DFB" => {
+                self.expect_keyword("inputs")?;
+                let inputs = self.row()?;
+                self.expect_keyword("predicate_variants")?;
+                let predicate_variants = self.classic_rows()?;
+                self.expect_keyword("other_outputs")?;
+                BasicBlock::DFB {
+                    inputs,
+                    other_outputs: self.row()?,
+                    predicate_variants,
+                }
+                .into()
+            }
+            "Exit" => BasicBlock::Exit {
+                cfg_outputs: self.row()?,
+            }
+            .into(),
+            "TailLoop" => {
+                self.expect_keyword("just_inputs")?;
+                let just_inputs = self.classic_row()?;
+                self.expect_keyword("just_outputs")?;
+                let just_outputs = self.classic_row()?;
+                self.expect_keyword("rest")?;
+                TailLoop {
+                    just_inputs,
+                    just_outputs,
+                    rest: self.row()?,
+                }
+                .into()
+            }
+            "CFG" => {
+                let inputs = self.row()?;
+                self.expect("->")?;
+                CFG {
+                    inputs,
+                    outputs: self.row()?,
+                }
+                .into()
+            }
+            "Conditional" => {
+                self.expect_keyword("predicate_inputs")?;
+                let predicate_inputs = self.classic_rows()?;
+                self.expect_keyword("other_inputs")?;
+                let other_inputs = self.row()?;
+                self.expect_keyword("outputs")?;
+                Conditional {
+                    predicate_inputs,
+                    other_inputs,
+                    outputs: self.row()?,
+                }
+                .into()
+            }
+            "Case" => Case {
+                signature: self.signature()?,
+            }
+            .into(),
+            "Custom" => {
+                let resource = self.string()?.into();
+                let name = self.string()?;
+                let args = self.list("[", "]", Self::type_arg)?;
+                let description = self.string()?;
+                let signature = match self.peek() {
+                    Some('[') => Some(self.signature()?),
+                    _ => None,
+                };
+                let op = OpaqueOp::new(resource, name, description, args, signature);
+                LeafOp::CustomOp(ExternalOp::from(op)).into()
+            }
+            "H" => LeafOp::H.into(),
+            "T" => LeafOp::T.into(),
+            "S" => LeafOp::S.into(),
+            "X" => LeafOp::X.into(),
+            "Y" => LeafOp::Y.into(),
+            "Z" => LeafOp::Z.into(),
+            "Tadj" => LeafOp::Tadj.into(),
+            "Sadj" => LeafOp::Sadj.into(),
+            "CX" => LeafOp::CX.into(),
+            "ZZMax" => LeafOp::ZZMax.into(),
+            "Reset" => LeafOp::Reset.into(),
+            "Measure" => LeafOp::Measure.into(),
+            "RzF64" => LeafOp::RzF64.into(),
+            "Xor" => LeafOp::Xor.into(),
+            "Noop" => LeafOp::Noop {
+                ty: self.simple_type()?,
+            }
+            .into(),
+            "MakeTuple" => LeafOp::MakeTuple { tys: self.row()? }.into(),
+            "UnpackTuple" => LeafOp::UnpackTuple { tys: self.row()? }.into(),
+            "Tag" => LeafOp::Tag {
+                tag: self.int()?,
+                variants: self.row()?,
+            }
+            .into(),
+            "Lift" => LeafOp::Lift {
+                type_row: self.row()?,
+                new_resource: self.string()?.into(),
+            }
+            .into(),
+            op => {
+                self.pos = pos;
+                return self.error(format!("unknown operation {op}"));
+            }
+        };
+        Ok(op)
+    }
+
+    fn resources(&mut self) -> Result<ResourceSet, TextFormatError> {
+        let resources = self.list("{", "}", Self::string)?;
+        Ok(resources.into_iter().map(Into::into).collect())
+    }
+
+    fn signature(&mut self) -> Result<AbstractSignature, TextFormatError> {
+        let input = self.row()?;
+        self.expect("->")?;
+        let output = self.row()?;
+        let static_input = match self.eat_keyword("static") {
+            true => self.classic_row()?,
+            false => ClassicRow::new(),
+        };
+        let resource_reqs = match self.eat_keyword("reqs") {
+            true => self.resources()?,
+            false => ResourceSet::new(),
+        };
+        Ok(AbstractSignature {
+            input,
+            output,
+            static_input,
+            resource_reqs,
+        })
+    }
+
+    fn row(&mut self) -> Result<SimpleRow, TextFormatError> {
+        Ok(self.list("[", "]", Self::simple_type)?.into())
+    }
+
+    fn classic_row(&mut self) -> Result<ClassicRow, TextFormatError> {
+        Ok(self.list("[", "]", Self::classic_type)?.into())
+    }
+
+    fn classic_rows(&mut self) -> Result<Vec<ClassicRow>, TextFormatError> {
+        self.list("[", "]", Self::classic_row)
+    }
+
+    fn type_tag(&mut self) -> Result<TypeTag, TextFormatError> {
+        let pos = self.pos;
+        match self.word()? {
+            "Simple" => Ok(TypeTag::Simple),
+            "Classic" => Ok(TypeTag::Classic),
+            "Hashable" => Ok(TypeTag::Hashable),
+            _ => {
+                self.pos = pos;
+                self.error("expected a type tag")
+            }
+        }
+    }
+
+    fn classic_type(&mut self) -> Result<ClassicType, TextFormatError> {
+        self.skip_ws();
+        let pos = self.pos;
+        match self.simple_type()? {
+            SimpleType::Classic(ty) => Ok(ty),
+            _ => {
+                self.pos = pos;
+                self.error("expected a classic type")
+            }
+        }
+    }
+
+    fn hashable_type(&mut self) -> Result<HashableType, TextFormatError> {
+        self.skip_ws();
+        let pos = self.pos;
+        match self.simple_type()? {
+            SimpleType::Classic(ClassicType::Hashable(ty)) => Ok(ty),
+            _ => {
+                self.pos = pos;
+                self.error("expected a hashable type")
+            }
+        }
+    }
+
+    fn simple_type(&mut self) -> Result<SimpleType, TextFormatError> {
+        self.skip_ws();
+        let pos = self.pos;
+        let ty = match self.word()? {
+            "json" => self.json()?,
+            "Qubit" => SimpleType::Qubit,
+            "F64" => ClassicType::F64.into(),
+            "String" => HashableType::String.into(),
+            "Var" => {
+                self.expect("(")?;
+                let name = self.string()?;
+                self.expect(")")?;
+                HashableType::Variable(name.into()).into()
+            }
+            "Graph" => {
+                self.expect("(")?;
+                let signature = self.signature()?;
+                self.expect(")")?;
+                ClassicType::graph_from_sig(signature).into()
+            }
+            "List" => {
+                self.expect("(")?;
+                let ty = self.simple_type()?;
+                self.expect(")")?;
+                natural_container!(ty, |t| Container::List(Box::new(t)))
+            }
+            "Map" => {
+                self.expect("(")?;
+                let key = self.hashable_type()?;
+                self.expect(",")?;
+                let ty = self.simple_type()?;
+                self.expect(")")?;
+                natural_container!(ty, |t| Container::Map(Box::new((key, t))))
+            }
+            "Tuple" => SimpleType::new_tuple(self.list("(", ")", Self::simple_type)?),
+            "Sum" => SimpleType::new_sum(self.list("(", ")", Self::simple_type)?),
+            "Array" => {
+                self.expect("(")?;
+                let ty = self.simple_type()?;
+                self.expect(",")?;
+                let len = self.int()?;
+                self.expect(")")?;
+                natural_container!(ty, |t| Container::Array(Box::new(t), len))
+            }
+            "Alias" => {
+                self.expect("(")?;
+                let name = self.string()?.into();
+                self.expect(",")?;
+                let tag = self.type_tag()?;
+                self.expect(")")?;
+                match tag {
+                    TypeTag::Simple => SimpleType::Qontainer(Container::Alias(name)),
+                    TypeTag::Classic => ClassicType::Container(Container::Alias(name)).into(),
+                    TypeTag::Hashable => HashableType::Container(Container::Alias(name)).into(),
+                }
+            }
+            "Opaque" => {
+                self.expect("(")?;
+                let resource = self.string()?;
+                self.expect(",")?;
+                let id = self.string()?;
+                self.expect(",")?;
+                let args = self.list("[", "]", Self::type_arg)?;
+                self.expect(",")?;
+                let tag = self.type_tag()?;
+                self.expect(")")?;
+                CustomType::new(id, args, resource, tag).into()
+            }
+            word => match word.strip_prefix('I').map(str::parse) {
+                Some(Ok(width)) => HashableType::Int(width).into(),
+                _ => {
+                    self.pos = pos;
+                    return self.error(format!("unknown type {word}"));
+                }
+            },
+        };
+        Ok(ty)
+    }
+
+    fn type_arg(&mut self) -> Result<TypeArg, TextFormatError> {
+        match self.peek() {
+            Some('"') => return Ok(TypeArg::String(self.string()?)),
+            Some('[') => return Ok(TypeArg::List(self.list("[", "]", Self::type_arg)?)),
+            Some(c) if c.is_ascii_digit() => return Ok(TypeArg::Int(self.int()?)),
+            _ => {}
+        }
+        let pos = self.pos;
+        let word = self.word()?;
+        self.expect("(")?;
+        let arg = match word {
+            "Type" => TypeArg::Type(self.simple_type()?),
+            "ClassicType" => TypeArg::ClassicType(self.classic_type()?),
+            "HashableType" => TypeArg::HashableType(self.hashable_type()?),
+            "Yaml" => match serde_yaml::from_str(&self.string()?) {
+                Ok(value) => TypeArg::CustomValue(value),
+                Err(e) => return self.error(format!("invalid YAML: {e}")),
+            },
+            _ => {
+                self.pos = pos;
+                return self.error(format!("unknown type argument {word}"));
+            }
+        };
+        self.expect(")")?;
+        Ok(arg)
+    }
+
+    fn value(&mut self) -> Result<ConstValue, TextFormatError> {
+        match self.peek() {
+            Some('"') => return Ok(HashableValue::String(self.string()?).into()),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                return Ok(match self.number()? {
+                    Number::Int(i) => HashableValue::Int(i).into(),
+                    Number::Float(f) => ConstValue::F64(f),
+                })
+            }
+            _ => {}
+        }
+        let pos = self.pos;
+        let value = match self.word()? {
+            "json" => self.json()?,
+            "inf" => ConstValue::F64(f64::INFINITY),
+            "NaN" => ConstValue::F64(f64::NAN),
+            "Tuple" => ConstValue::sequence(&self.list("(", ")", Self::value)?),
+            "Sum" => {
+                self.expect("(")?;
+                let tag = self.int()?;
+                self.expect(",")?;
+                let value = self.value()?;
+                self.expect(")")?;
+                ConstValue::sum(tag, value)
+            }
+            "Map" => {
+                let entries = self.list("(", ")", |p| {
+                    let key = match p.value()? {
+                        ConstValue::Hashable(key) => key,
+                        _ => return p.error("expected a hashable key"),
+                    };
+                    p.expect(":")?;
+                    Ok((key, p.value()?))
+                })?;
+                let hashable: Option<Vec<_>> = entries
+                    .iter()
+                    .map(|(k, v)| match v {
+                        ConstValue::Hashable(v) => Some((k.clone(), v.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                match hashable {
+                    Some(entries) => HashableValue::Container(ContainerValue::Map(entries)).into(),
+                    None => ConstValue::Container(ContainerValue::Map(entries)),
+                }
+            }
+            word => {
+                self.pos = pos;
+                return self.error(format!("unknown value {word}"));
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::{
+        BuildError, CFGBuilder, Container as _, Dataflow, DataflowSubContainer, HugrBuilder,
+        ModuleBuilder,
+    };
+    use crate::hugr::HugrView;
+    use crate::qasm::import_qasm;
+    use crate::{classic_row, type_row};
+    use cool_asserts::assert_matches;
+    use serde_json::json;
+
+    const QB: SimpleType = SimpleType::Qubit;
+    const NAT: SimpleType = SimpleType::Classic(ClassicType::i64());
+
+    /// Checks that printing and parsing `hugr` produces an identical HUGR.
+    fn text_roundtrip(hugr: &Hugr) -> Hugr {
+        let text = hugr.to_text().unwrap();
+        let new_hugr = Hugr::from_text(&text).unwrap_or_else(|e| panic!("{e}\n{text}"));
+        assert_eq!(new_hugr.to_text().unwrap(), text);
+
+        // Compare the serialized HUGRs, ignoring the order of the edges and of
+        // the elements of resource sets. Their JSON encodings are compared as
+        // `NaN`s are not equal to themselves.
+        let ser = |h: &Hugr| {
+            let mut ser = SerHugrV1::try_from(h).unwrap();
+            ser.edges.sort();
+            let json = serde_json::to_string(&ser).unwrap();
+            let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            sort_resource_sets(&mut value);
+            value.to_string()
+        };
+        assert_eq!(ser(&new_hugr), ser(hugr));
+        new_hugr
+    }
+
+    /// Sorts the resource sets in an encoded HUGR, as their serialization
+    /// follows the iteration order of a `HashSet`.
+    fn sort_resource_sets(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    match (key.as_str(), value) {
+                        ("input_resources" | "resource_reqs", serde_json::Value::Array(set)) => {
+                            set.sort_by_key(|r| r.to_string());
+                        }
+                        (_, value) => sort_resource_sets(value),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(sort_resource_sets),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn parse_dfg() {
+        let text = r#"
+            // A Bell pair.
+            %dfg = DFG [Qubit, Qubit] -> [Qubit, Qubit] meta {"name": "bell"} {
+              %in = Input [Qubit, Qubit]
+              %out = Output [Qubit, Qubit] (%cx, %cx.1)
+              %h = H (%in.0)
+              %cx = CX (%h, %in.1)
+            }
+        "#;
+        let hugr = Hugr::from_text(text).unwrap();
+        hugr.validate().unwrap();
+        assert_eq!(hugr.node_count(), 5);
+        assert_eq!(hugr.get_metadata(hugr.root()), &json!({"name": "bell"}));
+        assert_eq!(
+            hugr.to_text().unwrap(),
+            r#"%0 = DFG [Qubit, Qubit] -> [Qubit, Qubit] meta {"name":"bell"} {
+  %1 = Input [Qubit, Qubit]
+  %2 = Output [Qubit, Qubit] (%4.0, %4.1)
+  %3 = H (%1.0)
+  %4 = CX (%3.0, %1.1)
+}
+"#
+        );
+        text_roundtrip(&hugr);
+    }
+
+    #[test]
+    fn fixture_roundtrip() {
+        let hugr: Hugr =
+            serde_json::from_str(include_str!("../../../resources/test/hugr_v1.json")).unwrap();
+        text_roundtrip(&hugr).validate().unwrap();
+    }
+
+    #[test]
+    fn qasm_roundtrip() {
+        let hugr = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[2];
+            h q[0];
+            rz(pi/4) q[1];
+            cx q[0], q[1];
+            measure q -> c;
+            if (c == 1) x q[0];
+            "#,
+        )
+        .unwrap();
+        text_roundtrip(&hugr);
+    }
+
+    #[test]
+    fn cfg_roundtrip() -> Result<(), BuildError> {
+        let mut cfg_builder = CFGBuilder::new(type_row![NAT], type_row![NAT])?;
+        let variants = vec![classic_row![ClassicType::i64()]; 2];
+        let mut entry_b = cfg_builder.entry_builder(variants.clone(), type_row![])?;
+        let [inw] = entry_b.input_wires_arr();
+        let pred = entry_b.make_predicate(1, variants, [inw])?;
+        let entry = entry_b.finish_with_outputs(pred, [])?;
+        let mut middle_b = cfg_builder.simple_block_builder(type_row![NAT], type_row![NAT], 1)?;
+        let c = middle_b.add_load_const(Const::simple_unary_predicate())?;
+        let [inw] = middle_b.input_wires_arr();
+        let middle = middle_b.finish_with_outputs(c, [inw])?;
+        let exit = cfg_builder.exit_block();
+        cfg_builder.branch(&entry, 0, &middle)?;
+        cfg_builder.branch(&middle, 0, &exit)?;
+        cfg_builder.branch(&entry, 1, &exit)?;
+        let hugr = cfg_builder.finish_hugr()?;
+
+        // The exit block has two predecessors.
+        assert!(hugr.to_text().unwrap().contains("Exit [I64] ([%"));
+        text_roundtrip(&hugr);
+        Ok(())
+    }
+
+    #[test]
+    fn module_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module_builder = ModuleBuilder::new();
+        let array = CustomType::new(
+            "collections",
+            [
+                TypeArg::Type(QB),
+                TypeArg::Int(u128::MAX),
+                TypeArg::List(vec![TypeArg::String("x".into())]),
+                TypeArg::CustomValue(serde_yaml::from_str("{a: [1, 2]}")?),
+            ],
+            "collections",
+            TypeTag::Simple,
+        );
+        module_builder.add_alias_declare("opaque", TypeTag::Classic)?;
+        module_builder.add_alias_def(
+            "data",
+            SimpleType::new_tuple(vec![
+                ClassicType::F64.into(),
+                HashableType::Container(Container::Alias("opaque".into())).into(),
+                SimpleType::Qontainer(Container::List(Box::new(ClassicType::F64.into()))),
+                ClassicType::graph_from_sig(AbstractSignature::new_df(type_row![NAT], type_row![]))
+                    .into(),
+            ]),
+        )?;
+        module_builder.add_constant(Const::new_tuple([
+            Const::i64(3)?,
+            Const::new(ConstValue::F64(-1.5e-7), ClassicType::F64)?,
+            Const::new(ConstValue::F64(f64::NAN), ClassicType::F64)?,
+            Const::new(
+                HashableValue::String("a \"string\"".into()).into(),
+                HashableType::String.into(),
+            )?,
+            Const::simple_predicate(1, 3),
+        ]))?;
+        let row = vec![array.into()];
+        let signature = AbstractSignature::new_df(row.clone(), row);
+        let mut f_build = module_builder.define_function("reverse", signature.clone().pure())?;
+        let op = OpaqueOp::new(
+            "collections".into(),
+            "reverse",
+            "Reverses an array".into(),
+            [TypeArg::Int(4)],
+            Some(signature),
+        );
+        let inputs = f_build.input_wires();
+        let reverse = f_build.add_dataflow_op(LeafOp::CustomOp(op.into()), inputs)?;
+        f_build.set_metadata(json!({"name": "reverse", "params": [1, 2]}));
+        f_build.finish_with_outputs(reverse.outputs())?;
+        let hugr = module_builder.finish_hugr()?;
+
+        let text = hugr.to_text()?;
+        assert!(text.contains(r#"Opaque("collections", "collections", [Type(Qubit), "#));
+        // Non-normalised types are printed in their serialized form.
+        assert!(text.contains(r#"json {"t":"List""#));
+        text_roundtrip(&hugr);
+        Ok(())
+    }
+
+    #[test]
+    fn ports_and_resources() {
+        let text = r#"
+            %0 = DFG [Qubit] -> [Qubit] open_resources {
+              %1 = Input [Qubit] ports(0, 3)
+              %2 = Output [Qubit] (%1.2) input_resources {"b", "a"}
+            }
+        "#;
+        let hugr = Hugr::from_text(text).unwrap();
+        let output = hugr.children(hugr.root()).nth(1).unwrap();
+        assert_eq!(
+            hugr.get_nodetype(output).input_resources,
+            Some(["a".into(), "b".into()].into_iter().collect())
+        );
+        let printed = text_roundtrip(&hugr).to_text().unwrap();
+        assert!(printed.contains(r#"%1 = Input [Qubit] ports(0, 3)"#));
+        assert!(printed.contains(r#"(%1.2) input_resources {"a", "b"}"#));
+    }
+
+    #[test]
+    fn errors() {
+        assert_matches!(
+            Hugr::from_text("%0 = DFG [Qubit] -> [Qubit] {\n  %1 = Foo\n}"),
+            Err(TextFormatError::Syntax { line: 2, column: 8, message }) => {
+                assert_eq!(message, "unknown operation Foo")
+            }
+        );
+        assert_matches!(
+            Hugr::from_text("%0 = Module {\n %1 = Module\n %1 = Module\n}"),
+            Err(TextFormatError::DuplicateNode { line: 3, name }) => assert_eq!(name, "1")
+        );
+        assert_matches!(
+            Hugr::from_text("%0 = DFG [Qubit] -> [Qubit] {\n %1 = Output [Qubit] (%2)\n}"),
+            Err(TextFormatError::UnknownNode { line: 2, name }) => assert_eq!(name, "2")
+        );
+        assert_matches!(
+            Hugr::from_text("%0 = Module\n%1 = Module"),
+            Err(TextFormatError::Syntax { line: 2, .. })
+        );
+        assert_matches!(
+            Hugr::from_text("%0 = Const I1 = 2"),
+            Err(TextFormatError::Syntax { .. })
+        );
+        assert_matches!(
+            Hugr::from_text("%0 = DFG [Qubit] -> [Qubit] {\n %1 = Input [Qubit] ports(99999999999, 1)\n}"),
+            Err(TextFormatError::Hugr(e)) => {
+                assert_matches!(*e, HUGRSerializationError::TooManyPorts { .. })
+            }
+        );
+    }
+}
@@ -4,7 +4,6 @@ use std::collections::HashMap;
 use std::iter;
 
 use itertools::Itertools;
-use petgraph::algo::dominators::{self, Dominators};
 use petgraph::visit::{DfsPostOrder, Walker};
 use portgraph::{LinkView, PortView};
 use thiserror::Error;
@@ -12,6 +11,7 @@ use thiserror::Error;
 #[cfg(feature = "pyo3")]
 use pyo3::prelude::*;
 
+use crate::algorithm::dominators::DominatorTree;
 use crate::ops::validate::{ChildrenEdgeData, ChildrenValidationError, EdgeValidationError};
 use crate::ops::{OpTag, OpTrait, OpType, ValidateOp};
 use crate::resource::ResourceSet;
@@ -21,17 +21,21 @@ use crate::{Direction, Hugr, Node, Port};
 use super::region::{FlatRegionView, Region};
 use super::view::HugrView;
 
+pub mod diagnostic;
+
+pub use diagnostic::{Diagnostic, Diagnostics};
+
 /// Structure keeping track of pre-computed information used in the validation
 /// process.
-///
-/// TODO: Consider implementing updatable dominator trees and storing it in the
-/// Hugr to avoid recomputing it every time.
 struct ValidationContext<'a> {
     hugr: &'a Hugr,
     /// Dominator tree for each CFG region, using the container node as index.
-    dominators: HashMap<Node, Dominators<Node>>,
+    dominators: HashMap<Node, DominatorTree>,
     /// Resource requirements associated with each edge
     resources: HashMap<(Node, Direction), ResourceSet>,
+    /// The errors found so far, when collecting all of them rather than
+    /// stopping at the first one.
+    errors: Option<Vec<ValidationError>>,
 }
 
 impl Hugr {
@@ -40,6 +44,24 @@ impl Hugr {
         let mut validator = ValidationContext::new(self);
         validator.validate()
     }
+
+    /// Check the validity of the HUGR, collecting all the errors rather than
+    /// stopping at the first one.
+    ///
+    /// Each error is reported as a [`Diagnostic`] describing the nodes
+    /// involved, see [`Diagnostics`] for how they are rendered.
+    pub fn validate_all(&self) -> Result<(), Diagnostics> {
+        let mut validator = ValidationContext::new(self);
+        validator.errors = Some(vec![]);
+        validator
+            .validate()
+            .expect("Errors are collected rather than returned");
+        let errors = validator.errors.unwrap_or_default();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(Diagnostics::new(self, errors))
+    }
 }
 
 impl<'a> ValidationContext<'a> {
@@ -49,6 +71,19 @@ impl<'a> ValidationContext<'a> {
             hugr,
             dominators: HashMap::new(),
             resources: HashMap::new(),
+            errors: None,
+        }
+    }
+
+    /// Handles the result of a check. When collecting all the errors, the
+    /// error is recorded and `Ok` is returned so that validation continues.
+    fn report(&mut self, result: Result<(), ValidationError>) -> Result<(), ValidationError> {
+        match (result, &mut self.errors) {
+            (Err(err), Some(errors)) => {
+                errors.push(err);
+                Ok(())
+            }
+            (result, _) => result,
         }
     }
 
@@ -56,18 +91,20 @@ impl<'a> ValidationContext<'a> {
     pub fn validate(&mut self) -> Result<(), ValidationError> {
         // Root node must be a root in the hierarchy.
         if !self.hugr.hierarchy.is_root(self.hugr.root) {
-            return Err(ValidationError::RootNotRoot {
+            self.report(Err(ValidationError::RootNotRoot {
                 node: self.hugr.root(),
-            });
+            }))?;
         }
 
         for node in self.hugr.graph.nodes_iter().map_into() {
-            self.gather_resources(&node)?;
+            let result = self.gather_resources(&node);
+            self.report(result)?;
         }
 
         // Node-specific checks
         for node in self.hugr.graph.nodes_iter().map_into() {
-            self.validate_node(node)?;
+            let result = self.validate_node(node);
+            self.report(result)?;
         }
 
         Ok(())
@@ -99,10 +136,8 @@ impl<'a> ValidationContext<'a> {
     ///
     /// The results of this computation should be cached in `self.dominators`.
     /// We don't do it here to avoid mutable borrows.
-    fn compute_dominator(&self, parent: Node) -> Dominators<Node> {
-        let region = FlatRegionView::new(self.hugr, parent);
-        let entry_node = self.hugr.children(parent).next().unwrap();
-        dominators::simple_fast(&region, entry_node)
+    fn compute_dominator(&self, parent: Node) -> DominatorTree {
+        DominatorTree::dominators(self.hugr, parent).expect("Parent was checked to be a CFG")
     }
 
     /// Check the constraints on a single node.
@@ -153,15 +188,14 @@ impl<'a> ValidationContext<'a> {
                 // Check port connections
                 for (i, port_index) in self.hugr.graph.ports(node.index, dir).enumerate() {
                     let port = Port::new(dir, i);
-                    self.validate_port(node, port, port_index, op_type)?;
+                    let result = self.validate_port(node, port, port_index, op_type);
+                    self.report(result)?;
                 }
             }
         }
 
         // Check operation-specific constraints
-        self.validate_operation(node, op_type)?;
-
-        Ok(())
+        self.validate_operation(node, op_type)
     }
 
     /// Check that two `PortIndex` have compatible resource requirements,
@@ -177,8 +211,14 @@ impl<'a> ValidationContext<'a> {
         src: &(Node, Port),
         tgt: &(Node, Port),
     ) -> Result<(), ValidationError> {
-        let rs_src = self.resources.get(&(src.0, Direction::Outgoing)).unwrap();
-        let rs_tgt = self.resources.get(&(tgt.0, Direction::Incoming)).unwrap();
+        // Nodes without resources have already been reported when collecting
+        // all the errors.
+        let (Some(rs_src), Some(rs_tgt)) = (
+            self.resources.get(&(src.0, Direction::Outgoing)),
+            self.resources.get(&(tgt.0, Direction::Incoming)),
+        ) else {
+            return Ok(());
+        };
 
         if rs_src == rs_tgt {
             Ok(())
@@ -256,37 +296,56 @@ impl<'a> ValidationContext<'a> {
 
             let other_node: Node = self.hugr.graph.port_node(link).unwrap().into();
             let other_offset = self.hugr.graph.port_offset(link).unwrap().into();
+            let result =
+                self.validate_link(node, port, &port_kind, op_type, other_node, other_offset);
+            self.report(result)?;
+        }
 
-            self.check_resources_compatible(&(node, port), &(other_node, other_offset))?;
+        Ok(())
+    }
 
-            let other_op = self.hugr.get_optype(other_node);
-            let Some(other_kind) = other_op.port_kind(other_offset) else {
-                // The number of ports in `other_node` does not match the operation definition.
-                // This should be caught by `validate_node`.
-                return Err(self.validate_node(other_node).unwrap_err());
-            };
-            // TODO: We will require some "unifiable" comparison instead of strict equality, to allow for pre-type inference hugrs.
-            if other_kind != port_kind {
-                return Err(ValidationError::IncompatiblePorts {
-                    from: node,
-                    from_port: port,
-                    from_kind: port_kind,
-                    to: other_node,
-                    to_port: other_offset,
-                    to_kind: other_kind,
-                });
+    /// Check a link from an outgoing port of `node` to an incoming port of
+    /// `other_node`.
+    fn validate_link(
+        &mut self,
+        node: Node,
+        port: Port,
+        port_kind: &EdgeKind,
+        op_type: &OpType,
+        other_node: Node,
+        other_offset: Port,
+    ) -> Result<(), ValidationError> {
+        self.check_resources_compatible(&(node, port), &(other_node, other_offset))?;
+
+        let other_op = self.hugr.get_optype(other_node);
+        let Some(other_kind) = other_op.port_kind(other_offset) else {
+            // The number of ports in `other_node` does not match the operation definition.
+            // This should be caught by `validate_node`.
+            if self.errors.is_some() {
+                // The error is reported when validating `other_node`.
+                return Ok(());
             }
-
-            self.validate_edge(node, port, op_type, other_node, other_offset)?;
+            return Err(self.validate_node(other_node).unwrap_err());
+        };
+        // TODO: We will require some "unifiable" comparison instead of strict equality, to allow for pre-type inference hugrs.
+        if &other_kind != port_kind {
+            return Err(ValidationError::IncompatiblePorts {
+                from: node,
+                from_port: port,
+                from_kind: port_kind.clone(),
+                to: other_node,
+                to_port: other_offset,
+                to_kind: other_kind,
+            });
         }
 
-        Ok(())
+        self.validate_edge(node, port, op_type, other_node, other_offset)
     }
 
     /// Check operation-specific constraints.
     ///
     /// These are flags defined for each operation type as an [`OpValidityFlags`] object.
-    fn validate_operation(&self, node: Node, op_type: &OpType) -> Result<(), ValidationError> {
+    fn validate_operation(&mut self, node: Node, op_type: &OpType) -> Result<(), ValidationError> {
         let flags = op_type.validity_flags();
 
         if self.hugr.hierarchy.child_count(node.index) > 0 {
@@ -301,13 +360,13 @@ impl<'a> ValidationContext<'a> {
             let mut first_two_children = all_children.clone().take(2);
             let first_child = self.hugr.get_optype(first_two_children.next().unwrap());
             if !flags.allowed_first_child.is_superset(first_child.tag()) {
-                return Err(ValidationError::InvalidInitialChild {
+                self.report(Err(ValidationError::InvalidInitialChild {
                     parent: node,
                     parent_optype: op_type.clone(),
                     optype: first_child.clone(),
                     expected: flags.allowed_first_child,
                     position: "first",
-                });
+                }))?;
             }
 
             if let Some(second_child) = first_two_children
@@ -315,23 +374,23 @@ impl<'a> ValidationContext<'a> {
                 .map(|child| self.hugr.get_optype(child))
             {
                 if !flags.allowed_second_child.is_superset(second_child.tag()) {
-                    return Err(ValidationError::InvalidInitialChild {
+                    self.report(Err(ValidationError::InvalidInitialChild {
                         parent: node,
                         parent_optype: op_type.clone(),
                         optype: second_child.clone(),
                         expected: flags.allowed_second_child,
                         position: "second",
-                    });
+                    }))?;
                 }
             }
             // Additional validations running over the full list of children optypes
             let children_optypes = all_children.map(|c| (c.index, self.hugr.get_optype(c)));
             if let Err(source) = op_type.validate_children(children_optypes) {
-                return Err(ValidationError::InvalidChildren {
+                self.report(Err(ValidationError::InvalidChildren {
                     parent: node,
                     parent_optype: op_type.clone(),
                     source,
-                });
+                }))?;
             }
 
             // Additional validations running over the edges of the contained graph
@@ -355,11 +414,11 @@ impl<'a> ValidationContext<'a> {
                                 target_op: target_op.clone(),
                             };
                             if let Err(source) = edge_check(edge_data) {
-                                return Err(ValidationError::InvalidEdges {
+                                self.report(Err(ValidationError::InvalidEdges {
                                     parent: node,
                                     parent_optype: op_type.clone(),
                                     source,
-                                });
+                                }))?;
                             }
                         }
                     }
@@ -471,10 +530,12 @@ impl<'a> ValidationContext<'a> {
         // case), or the parent of `from_parent` (in the dominator case).
         //
         // This search could be sped-up with a pre-computed LCA structure, but
-        // for valid Hugrs this search should be very short.
+        // for valid Hugrs this search should be very short. It is bounded as
+        // `validate_all` may reach it in a hierarchy with cycles.
         let from_parent_parent = self.hugr.get_parent(from_parent);
-        for (ancestor, ancestor_parent) in
-            iter::successors(to_parent, |&p| self.hugr.get_parent(p)).tuple_windows()
+        for (ancestor, ancestor_parent) in iter::successors(to_parent, |&p| self.hugr.get_parent(p))
+            .take(self.hugr.node_count())
+            .tuple_windows()
         {
             if ancestor_parent == from_parent {
                 // External edge.
@@ -519,10 +580,7 @@ impl<'a> ValidationContext<'a> {
                         self.dominators.get(&ancestor_parent).unwrap()
                     }
                 };
-                if !dominator_tree
-                    .dominators(ancestor)
-                    .map_or(false, |mut ds| ds.any(|n| n == from_parent))
-                {
+                if !dominator_tree.dominates(from_parent, ancestor) {
                     return Err(InterGraphEdgeError::NonDominatedAncestor {
                         from,
                         from_offset,
@@ -671,6 +729,38 @@ pub enum ValidationError {
     MissingInputResources(Node),
 }
 
+impl ValidationError {
+    /// The nodes involved in the error, starting with the node where it was
+    /// detected.
+    pub fn nodes(&self) -> Vec<Node> {
+        match self {
+            ValidationError::RootNotRoot { node }
+            | ValidationError::RootWithEdges { node }
+            | ValidationError::WrongNumberOfPorts { node, .. }
+            | ValidationError::UnconnectedPort { node, .. }
+            | ValidationError::TooManyConnections { node, .. }
+            | ValidationError::NoParent { node }
+            | ValidationError::NonContainerWithChildren { node, .. }
+            | ValidationError::ContainerWithoutChildren { node, .. }
+            | ValidationError::NotABoundedDag { node, .. }
+            | ValidationError::MissingInputResources(node) => vec![*node],
+            ValidationError::IncompatiblePorts { from, to, .. }
+            | ValidationError::TgtExceedsSrcResources { from, to, .. }
+            | ValidationError::SrcExceedsTgtResources { from, to, .. } => vec![*from, *to],
+            ValidationError::InvalidParentOp { child, parent, .. } => vec![*child, *parent],
+            ValidationError::InvalidInitialChild { parent, .. } => vec![*parent],
+            ValidationError::InvalidChildren { parent, source, .. } => {
+                vec![*parent, source.child().into()]
+            }
+            ValidationError::InvalidEdges { parent, source, .. } => {
+                let edge = source.edge();
+                vec![*parent, edge.source.into(), edge.target.into()]
+            }
+            ValidationError::InterGraphEdgeError(err) => err.nodes(),
+        }
+    }
+}
+
 #[cfg(feature = "pyo3")]
 impl From<ValidationError> for PyErr {
     fn from(err: ValidationError) -> Self {
@@ -738,6 +828,31 @@ pub enum InterGraphEdgeError {
     },
 }
 
+impl InterGraphEdgeError {
+    /// The nodes involved in the error, starting with the source of the edge.
+    pub fn nodes(&self) -> Vec<Node> {
+        match self {
+            InterGraphEdgeError::NonClassicalData { from, to, .. }
+            | InterGraphEdgeError::NonCFGAncestor { from, to, .. }
+            | InterGraphEdgeError::NoRelation { from, to, .. } => vec![*from, *to],
+            InterGraphEdgeError::MissingOrderEdge {
+                from,
+                to,
+                to_ancestor,
+                ..
+            } => vec![*from, *to, *to_ancestor],
+            InterGraphEdgeError::NonDominatedAncestor {
+                from,
+                to,
+                from_parent,
+                ancestor,
+                ..
+            } => vec![*from, *to, *from_parent, *ancestor],
+            InterGraphEdgeError::InvalidConstSrc { from, .. } => vec![*from],
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use cool_asserts::assert_matches;
@@ -1280,4 +1395,72 @@ mod test {
         assert_matches!(handle, Err(ValidationError::TgtExceedsSrcResources { .. }));
         Ok(())
     }
+
+    #[test]
+    fn validate_all() {
+        let (mut b, def) = make_simple_hugr(1);
+        assert_eq!(b.validate_all(), Ok(()));
+
+        b.set_metadata(
+            def,
+            serde_json::json!({"location": {"file": "main.py", "line": 3}}),
+        );
+        // An unconnected node inside the function, and one outside the hierarchy.
+        let noop = b
+            .add_op_with_parent(
+                def,
+                LeafOp::Noop {
+                    ty: ClassicType::bit().into(),
+                },
+            )
+            .unwrap();
+        let orphan = b.add_op(ops::Module);
+
+        let first_error = b.validate().unwrap_err();
+        let diagnostics = b.validate_all().unwrap_err();
+        assert_eq!(diagnostics.errors().next(), Some(&first_error));
+        let errors = diagnostics.errors().collect_vec();
+        assert!(errors.contains(&&ValidationError::NoParent { node: orphan }));
+        assert!(errors.contains(&&ValidationError::UnconnectedPort {
+            node: noop,
+            port: Port::new_incoming(0),
+            port_kind: EdgeKind::Value(B),
+        }));
+
+        let diagnostic = diagnostics
+            .0
+            .iter()
+            .find(|d| d.nodes[0].node == noop)
+            .unwrap();
+        let context = &diagnostic.nodes[0];
+        assert_eq!(context.op_name, "Noop");
+        assert_eq!(
+            context.path_string(),
+            r#"Module -> FuncDefn "main" -> Noop #3"#
+        );
+        assert_eq!(context.location.as_deref(), Some("main.py:3"));
+        assert!(diagnostic
+            .to_string()
+            .contains(r#"Module -> FuncDefn "main" -> Noop #3 (at main.py:3)"#));
+
+        let rendered = diagnostics.to_string();
+        assert!(rendered.ends_with(&format!("{} validation errors found", diagnostics.0.len())));
+    }
+
+    #[test]
+    fn validate_all_hierarchy_cycle() {
+        let (b, _) = make_simple_hugr(1);
+        let mut ser = serde_json::to_value(&b).unwrap();
+        ser["nodes"][3]["parent"] = 3.into();
+        let h: Hugr = serde_json::from_value(ser).unwrap();
+        let diagnostics = h.validate_all().unwrap_err();
+        let node = h.nodes().nth(3).unwrap();
+        let context = diagnostics
+            .0
+            .iter()
+            .flat_map(|d| &d.nodes)
+            .find(|c| c.node == node)
+            .unwrap();
+        assert_eq!(context.path.len(), 1);
+    }
 }
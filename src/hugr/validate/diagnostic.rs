@@ -0,0 +1,235 @@
+//! Human-readable reports of validation errors.
+//!
+//! A [`Diagnostic`] wraps a [`ValidationError`] with some context about the
+//! nodes involved: the hierarchical path leading to each of them, their
+//! operation names and the source location recorded in their metadata.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use thiserror::Error;
+
+use crate::hugr::{HugrView, NodeMetadata};
+use crate::ops::{OpName, OpType};
+use crate::Node;
+
+use super::ValidationError;
+
+/// The metadata key holding the source location of a node.
+///
+/// The value can either be a string, or an object with a `file` and
+/// optional `line` and `column` fields.
+pub const LOCATION_KEY: &str = "location";
+
+/// A validation error, along with the context of the nodes involved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The error found during validation.
+    pub error: ValidationError,
+    /// The nodes involved in the error, starting with the node where it was
+    /// detected.
+    pub nodes: Vec<NodeContext>,
+}
+
+impl Diagnostic {
+    /// Describe a validation error found in `hugr`.
+    pub fn new(hugr: &impl HugrView, error: ValidationError) -> Self {
+        ContextBuilder::new(hugr).diagnostic(error)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {}", self.error)?;
+        for node in &self.nodes {
+            write!(f, "\n  {node}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The position of a node in the hierarchy of a Hugr.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeContext {
+    /// The node.
+    pub node: Node,
+    /// The name of the node's operation.
+    pub op_name: String,
+    /// The path from the root of the hierarchy to the node, inclusive.
+    pub path: Vec<PathSegment>,
+    /// The source location of the node, taken from the metadata of the node
+    /// or of its closest ancestor that has one.
+    pub location: Option<String>,
+}
+
+impl NodeContext {
+    /// The path from the root to the node, as a string.
+    pub fn path_string(&self) -> String {
+        self.path
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+}
+
+impl Display for NodeContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}: {}", self.node.index(), self.path_string())?;
+        if let Some(location) = &self.location {
+            write!(f, " (at {location})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A node in the path from the root of a Hugr.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathSegment {
+    /// The node.
+    pub node: Node,
+    /// The name of the node's operation.
+    pub op_name: String,
+    /// How the node is identified among its siblings, either by the name of
+    /// the definition or by its position. `None` for the root.
+    pub label: Option<SegmentLabel>,
+}
+
+/// How a node is identified among its siblings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentLabel {
+    /// The name of a function or alias.
+    Name(String),
+    /// The position of the node among its siblings.
+    Index(usize),
+}
+
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.op_name)?;
+        match &self.label {
+            Some(SegmentLabel::Name(name)) => write!(f, " {name:?}"),
+            Some(SegmentLabel::Index(i)) => write!(f, " #{i}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A list of diagnostics, returned by [`Hugr::validate_all`].
+///
+/// [`Hugr::validate_all`]: crate::Hugr::validate_all
+#[derive(Debug, Clone, PartialEq, Error)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// Describe a list of validation errors found in `hugr`.
+    pub fn new(hugr: &impl HugrView, errors: impl IntoIterator<Item = ValidationError>) -> Self {
+        let mut builder = ContextBuilder::new(hugr);
+        Self(errors.into_iter().map(|e| builder.diagnostic(e)).collect())
+    }
+
+    /// The validation errors, without their context.
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationError> {
+        self.0.iter().map(|d| &d.error)
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in &self.0 {
+            writeln!(f, "{diagnostic}\n")?;
+        }
+        match self.0.len() {
+            1 => write!(f, "1 validation error found"),
+            n => write!(f, "{n} validation errors found"),
+        }
+    }
+}
+
+/// Computes the context of nodes, caching the position of each visited node
+/// among its siblings so that reporting many errors in a wide region stays
+/// linear.
+struct ContextBuilder<'a, H> {
+    hugr: &'a H,
+    positions: HashMap<Node, usize>,
+}
+
+impl<'a, H: HugrView> ContextBuilder<'a, H> {
+    fn new(hugr: &'a H) -> Self {
+        Self {
+            hugr,
+            positions: HashMap::new(),
+        }
+    }
+
+    fn diagnostic(&mut self, error: ValidationError) -> Diagnostic {
+        let mut nodes = error.nodes();
+        // Some errors mention the same node more than once.
+        let mut seen = Vec::with_capacity(nodes.len());
+        nodes.retain(|n| {
+            let new = !seen.contains(n);
+            seen.push(*n);
+            new
+        });
+        let nodes = nodes.into_iter().map(|n| self.node_context(n)).collect();
+        Diagnostic { error, nodes }
+    }
+
+    fn node_context(&mut self, node: Node) -> NodeContext {
+        let mut path = Vec::new();
+        let mut location = None;
+        let mut current = Some(node);
+        // The hierarchy of an invalid HUGR may have cycles, which cut the path.
+        let mut visited = HashSet::new();
+        while let Some(n) = current.filter(|&n| visited.insert(n)) {
+            if location.is_none() {
+                location = metadata_location(self.hugr.get_metadata(n));
+            }
+            current = self.hugr.get_parent(n);
+            let label = current.map(|parent| self.label(n, parent));
+            path.push(PathSegment {
+                node: n,
+                op_name: self.hugr.get_optype(n).name().to_string(),
+                label,
+            });
+        }
+        path.reverse();
+        NodeContext {
+            node,
+            op_name: self.hugr.get_optype(node).name().to_string(),
+            path,
+            location,
+        }
+    }
+
+    fn label(&mut self, node: Node, parent: Node) -> SegmentLabel {
+        match self.hugr.get_optype(node) {
+            OpType::FuncDefn(op) => return SegmentLabel::Name(op.name.clone()),
+            OpType::FuncDecl(op) => return SegmentLabel::Name(op.name.clone()),
+            OpType::AliasDefn(op) => return SegmentLabel::Name(op.name.to_string()),
+            OpType::AliasDecl(op) => return SegmentLabel::Name(op.name.to_string()),
+            _ => {}
+        }
+        if !self.positions.contains_key(&node) {
+            let siblings = self.hugr.children(parent).enumerate();
+            self.positions.extend(siblings.map(|(i, n)| (n, i)));
+        }
+        SegmentLabel::Index(self.positions[&node])
+    }
+}
+
+/// Reads the source location stored in some node metadata.
+fn metadata_location(metadata: &NodeMetadata) -> Option<String> {
+    let location = metadata.get(LOCATION_KEY)?;
+    if let Some(location) = location.as_str() {
+        return Some(location.to_string());
+    }
+    let mut location_str = location.get("file")?.as_str()?.to_string();
+    for key in ["line", "column"] {
+        match location.get(key).and_then(|v| v.as_u64()) {
+            Some(v) => location_str += &format!(":{v}"),
+            None => break,
+        }
+    }
+    Some(location_str)
+}
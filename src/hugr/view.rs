@@ -189,6 +189,14 @@ pub trait HugrView: sealed::HugrInternals {
             })
             .finish()
     }
+
+    /// Return a Mermaid flowchart of the graph, with containers drawn as
+    /// nested subgraphs.
+    ///
+    /// See [`render`](super::render) for configurable DOT and Mermaid renderings.
+    fn mermaid_string(&self) -> String {
+        super::render::mermaid_string(self, &Default::default())
+    }
 }
 
 impl<T> HugrView for T
@@ -196,19 +204,32 @@ where
     T: AsRef<Hugr>,
 {
     /// An Iterator over the nodes in a Hugr(View)
-    type Nodes<'a> = MapInto<multiportgraph::Nodes<'a>, Node> where Self: 'a;
+    type Nodes<'a>
+        = MapInto<multiportgraph::Nodes<'a>, Node>
+    where
+        Self: 'a;
 
     /// An Iterator over (some or all) ports of a node
-    type NodePorts<'a> = MapInto<portgraph::portgraph::NodePortOffsets, Port> where Self: 'a;
+    type NodePorts<'a>
+        = MapInto<portgraph::portgraph::NodePortOffsets, Port>
+    where
+        Self: 'a;
 
     /// An Iterator over the children of a node
-    type Children<'a> = MapInto<portgraph::hierarchy::Children<'a>, Node> where Self: 'a;
+    type Children<'a>
+        = MapInto<portgraph::hierarchy::Children<'a>, Node>
+    where
+        Self: 'a;
 
     /// An Iterator over (some or all) the nodes neighbouring a node
-    type Neighbours<'a> = MapInto<multiportgraph::Neighbours<'a>, Node> where Self: 'a;
+    type Neighbours<'a>
+        = MapInto<multiportgraph::Neighbours<'a>, Node>
+    where
+        Self: 'a;
 
     /// Iterator over the children of a node
-    type PortLinks<'a> = MapWithCtx<multiportgraph::PortLinks<'a>, &'a Hugr, (Node, Port)>
+    type PortLinks<'a>
+        = MapWithCtx<multiportgraph::PortLinks<'a>, &'a Hugr, (Node, Port)>
     where
         Self: 'a;
 
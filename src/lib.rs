@@ -7,6 +7,8 @@
 //!
 //! - `serde` enables serialization and deserialization of the components and
 //!   structures.
+//! - `pyo3` enables the [`python`] bindings.
+//! - `cli` builds the `hugr` command-line tool.
 //!
 
 pub mod algorithm;
@@ -15,6 +17,9 @@ pub mod extensions;
 pub mod hugr;
 pub mod macros;
 pub mod ops;
+#[cfg(feature = "pyo3")]
+pub mod python;
+pub mod qasm;
 pub mod resource;
 pub mod types;
 mod utils;
@@ -19,7 +19,7 @@ use super::{OpName, OpTrait, StaticTag};
 
 pub mod typecheck;
 /// A constant value definition.
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct Const {
     value: ConstValue,
     typ: ClassicType,
@@ -129,8 +129,50 @@ pub(crate) type HugrIntWidthStore = u8;
 pub(crate) const HUGR_MAX_INT_WIDTH: HugrIntWidthStore =
     HugrIntValueStore::BITS as HugrIntWidthStore;
 
+/// Deserializes a [`HugrIntValueStore`] via `deserialize_any`, as serde
+/// cannot buffer `u128`s when deserializing flattened or internally tagged
+/// data, such as the operations of a serialized Hugr.
+pub(crate) fn deserialize_int<'de, D>(deserializer: D) -> Result<HugrIntValueStore, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Error, Unexpected, Visitor};
+
+    struct IntVisitor;
+
+    impl<'de> Visitor<'de> for IntVisitor {
+        type Value = HugrIntValueStore;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a non-negative integer")
+        }
+
+        fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+            Ok(v.into())
+        }
+
+        fn visit_u128<E: Error>(self, v: u128) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+            v.try_into()
+                .map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+        }
+
+        // MessagePack encodes `u128`s as big-endian bytes.
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            v.try_into()
+                .map(HugrIntValueStore::from_be_bytes)
+                .map_err(|_| E::invalid_value(Unexpected::Bytes(v), &self))
+        }
+    }
+
+    deserializer.deserialize_any(IntVisitor)
+}
+
 /// Value constants. (This could be "ClassicValue" to parallel [HashableValue])
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 #[non_exhaustive]
 #[allow(missing_docs)]
 pub enum ConstValue {
@@ -268,6 +310,28 @@ impl ConstValue {
             _ => ConstValue::Container(ContainerValue::Sum(tag, Box::new(value))),
         }
     }
+
+    /// If this is a Sum value, returns its tag and the value of the variant.
+    pub fn as_sum(&self) -> Option<(usize, ConstValue)> {
+        match self {
+            ConstValue::Hashable(HashableValue::Container(ContainerValue::Sum(tag, v))) => {
+                Some((*tag, ConstValue::Hashable((**v).clone())))
+            }
+            ConstValue::Container(ContainerValue::Sum(tag, v)) => Some((*tag, (**v).clone())),
+            _ => None,
+        }
+    }
+
+    /// If this is a sequence of values (e.g. a tuple), returns its elements.
+    pub fn as_sequence(&self) -> Option<Vec<ConstValue>> {
+        match self {
+            ConstValue::Hashable(HashableValue::Container(ContainerValue::Sequence(vs))) => {
+                Some(vs.iter().cloned().map(ConstValue::Hashable).collect())
+            }
+            ConstValue::Container(ContainerValue::Sequence(vs)) => Some(vs.clone()),
+            _ => None,
+        }
+    }
 }
 
 impl From<HashableValue> for ConstValue {
@@ -306,6 +370,28 @@ pub trait CustomConst:
 impl_downcast!(CustomConst);
 impl_box_clone!(CustomConst, CustomConstBoxClone);
 
+/// Custom constants are serialized as an object with a single property, named
+/// after the implementing type.
+impl schemars::JsonSchema for dyn CustomConst {
+    fn schema_name() -> String {
+        "CustomConst".into()
+    }
+
+    fn json_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, ObjectValidation, SchemaObject};
+        SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            object: Some(Box::new(ObjectValidation {
+                min_properties: Some(1),
+                max_properties: Some(1),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 // Don't derive Eq here - the yaml could contain floats etc.
 // (Perhaps we could derive Eq if-and-only-if "typ.tag() == TypeTag::Hashable"!)
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -440,8 +526,9 @@ mod test {
             typ: typ_int.clone(),
             value: Value::Number(6.into()),
         }),));
-        let SimpleType::Classic(classic_t) = typ_int.clone().into()
-            else {panic!("Hashable CustomType returned as non-Classic");};
+        let SimpleType::Classic(classic_t) = typ_int.clone().into() else {
+            panic!("Hashable CustomType returned as non-Classic");
+        };
         assert_matches!(classic_t, ClassicType::Hashable(_));
         val.check_type(&classic_t).unwrap();
 
@@ -9,7 +9,9 @@ use super::OpTag;
 use super::{impl_op_name, OpName, OpTrait, StaticTag};
 
 /// Tail-controlled loop.
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct TailLoop {
     /// Types that are only input
     pub just_inputs: ClassicRow,
@@ -52,7 +54,9 @@ impl TailLoop {
 }
 
 /// Conditional operation, defined by child `Case` nodes for each branch.
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct Conditional {
     /// The possible rows of the predicate input
     pub predicate_inputs: Vec<ClassicRow>,
@@ -91,7 +95,9 @@ impl Conditional {
 }
 
 /// A dataflow node which is defined by a child CFG.
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 #[allow(missing_docs)]
 pub struct CFG {
     pub inputs: SimpleRow,
@@ -112,7 +118,9 @@ impl DataflowOpTrait for CFG {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 #[serde(tag = "block")]
 /// Basic block ops - nodes valid in control flow graphs.
 #[allow(missing_docs)]
@@ -190,7 +198,9 @@ impl BasicBlock {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 /// Case ops - nodes valid inside Conditional nodes.
 pub struct Case {
     /// The signature of the contained dataflow graph.
@@ -48,6 +48,20 @@ impl From<OpaqueOp> for ExternalOp {
     }
 }
 
+impl schemars::JsonSchema for ExternalOp {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        "ExternalOp".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        gen.subschema_for::<OpaqueOp>()
+    }
+}
+
 impl From<ExternalOp> for LeafOp {
     fn from(value: ExternalOp) -> Self {
         LeafOp::CustomOp(value)
@@ -150,9 +164,13 @@ impl PartialEq for ResourceOp {
 impl Eq for ResourceOp {}
 
 /// An opaquely-serialized op that refers to an as-yet-unresolved [`OpDef`]
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct OpaqueOp {
+    #[schemars(with = "String")]
     resource: ResourceId,
+    #[schemars(with = "String")]
     op_name: SmolStr,
     description: String, // cache in advance so description() can return &str
     args: Vec<TypeArg>,
@@ -197,6 +215,16 @@ impl OpaqueOp {
     pub fn resource(&self) -> &ResourceId {
         &self.resource
     }
+
+    /// Human readable description of the operation.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The signature of the operation, if stored.
+    pub fn signature(&self) -> Option<&AbstractSignature> {
+        self.signature.as_ref()
+    }
 }
 
 /// Resolve serialized names of operations into concrete implementation (OpDefs) where possible
@@ -211,7 +239,10 @@ pub fn resolve_extension_ops(
             if let Some(r) = resource_registry.get(&opaque.resource) {
                 // Fail if the Resource was found but did not have the expected operation
                 let Some(def) = r.get_op(&opaque.op_name) else {
-                    return Err(CustomOpError::OpNotFoundInResource(opaque.op_name.to_string(), r.name().to_string()));
+                    return Err(CustomOpError::OpNotFoundInResource(
+                        opaque.op_name.to_string(),
+                        r.name().to_string(),
+                    ));
                 };
                 // TODO input resources. From type checker, or just drop by storing only delta in Signature.
                 let op = ExternalOp::Resource(ResourceOp::new(def.clone(), &opaque.args).unwrap());
@@ -36,7 +36,9 @@ pub trait IOTrait {
 
 /// An input node.
 /// The outputs of this node are the inputs to the function.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct Input {
     /// Input value types
     pub types: SimpleRow,
@@ -53,7 +55,9 @@ impl IOTrait for Input {
 }
 
 /// An output node. The inputs are the outputs of the function.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct Output {
     /// Output value types
     pub types: SimpleRow,
@@ -130,7 +134,9 @@ impl<T: DataflowOpTrait> StaticTag for T {
 /// The first ports correspond to the signature of the function being
 /// called. Immediately following those ports, the first input port is
 /// connected to the def/declare block with a `ConstE<Graph>` edge.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct Call {
     /// Signature of function being called
     pub signature: AbstractSignature,
@@ -153,7 +159,9 @@ impl DataflowOpTrait for Call {
 }
 
 /// Call a function indirectly. Like call, but the first input is a standard dataflow graph type.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct CallIndirect {
     /// Signature of function being called
     pub signature: AbstractSignature,
@@ -178,7 +186,9 @@ impl DataflowOpTrait for CallIndirect {
 }
 
 /// Load a static constant in to the local dataflow graph.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct LoadConstant {
     /// Constant type
     pub datatype: ClassicType,
@@ -201,7 +211,9 @@ impl DataflowOpTrait for LoadConstant {
 }
 
 /// A simply nested dataflow graph.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct DFG {
     /// Signature of DFG node
     pub signature: AbstractSignature,
@@ -13,7 +13,9 @@ use crate::{
 };
 
 /// Dataflow operations with no children.
-#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 #[non_exhaustive]
 #[serde(tag = "lop")]
 pub enum LeafOp {
@@ -76,6 +78,7 @@ pub enum LeafOp {
         /// The types of the edges
         type_row: SimpleRow,
         /// The resources which we're adding to the inputs
+        #[schemars(with = "String")]
         new_resource: ResourceId,
     },
 }
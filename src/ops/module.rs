@@ -9,7 +9,9 @@ use super::StaticTag;
 use super::{impl_op_name, OpTag, OpTrait};
 
 /// The root of a module, parent of all other `OpType`s.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct Module;
 
 impl_op_name!(Module);
@@ -31,7 +33,9 @@ impl OpTrait for Module {
 /// A function definition.
 ///
 /// Children nodes are the body of the definition.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct FuncDefn {
     /// Name of function
     pub name: String,
@@ -60,7 +64,9 @@ impl OpTrait for FuncDefn {
 }
 
 /// External function declaration, linked at runtime.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct FuncDecl {
     /// Name of function
     pub name: String,
@@ -89,9 +95,12 @@ impl OpTrait for FuncDecl {
 }
 
 /// A type alias definition, used only for debug/metadata.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct AliasDefn {
     /// Alias name
+    #[schemars(with = "String")]
     pub name: SmolStr,
     /// Aliased type
     pub definition: SimpleType,
@@ -111,9 +120,12 @@ impl OpTrait for AliasDefn {
 }
 
 /// A type alias declaration. Resolved at link time.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct AliasDecl {
     /// Alias name
+    #[schemars(with = "String")]
     pub name: SmolStr,
     /// Flag to signify type is classical
     pub tag: TypeTag,
@@ -0,0 +1,896 @@
+//! Python bindings for building and inspecting [`Hugr`]s.
+//!
+//! The bindings are enabled by the `pyo3` feature, and are collected in the
+//! `hugr` Python module initialised by [`hugr`](fn@hugr). Operations and types
+//! may be given either as Python objects or as strings in the [textual
+//! format](crate::hugr::serialize::text), such as `"CX"` or
+//! `"Tuple(Qubit, F64)"`.
+//!
+//! ```python
+//! import hugr
+//!
+//! circ = hugr.CircuitBuilder(["Qubit", "Qubit"])
+//! circ.append("H", [0]).append("CX", [0, 1])
+//! h = circ.finish()
+//! h.validate()
+//! h.save("bell.json")
+//! ```
+
+// The expansion of `#[new]` in pyo3 0.19 trips this lint on recent compilers.
+#![allow(unknown_lints, non_local_definitions)]
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::builder::{Container, DFGBuilder, Dataflow, DataflowHugr};
+use crate::hugr::serialize::text::{
+    natural_container, op_to_text, parse_op, parse_type, type_to_text,
+};
+use crate::hugr::{CircuitUnit, Direction, HugrView, SimpleReplacement};
+use crate::ops::OpName;
+use crate::types::{AbstractSignature, ClassicType, Container as TypeContainer, HashableType};
+use crate::types::{SimpleRow, SimpleType};
+use crate::{Hugr, Node, Port, Wire};
+
+/// The `hugr` Python module.
+#[pymodule]
+pub fn hugr(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Hugr>()?;
+    m.add_class::<Node>()?;
+    m.add_class::<Port>()?;
+    m.add_class::<Wire>()?;
+    m.add_class::<PySimpleType>()?;
+    m.add_class::<PyClassicType>()?;
+    m.add_class::<PyDFGBuilder>()?;
+    m.add_class::<PyCircuitBuilder>()?;
+    m.add_class::<SimpleReplacement>()?;
+    Ok(())
+}
+
+/// Implements `==` and `!=` for the Python class of a Rust value.
+fn compare<T: PartialEq>(py: Python, a: &T, b: &T, op: CompareOp) -> PyObject {
+    match op {
+        CompareOp::Eq => (a == b).into_py(py),
+        CompareOp::Ne => (a != b).into_py(py),
+        _ => py.NotImplemented(),
+    }
+}
+
+/// Implements `hash` for the Python class of a Rust value.
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[pymethods]
+impl Node {
+    /// The index of the node.
+    #[getter(index)]
+    fn py_index(&self) -> usize {
+        self.index()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Node({})", self.index())
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        compare(py, self, other, op)
+    }
+
+    fn __hash__(&self) -> u64 {
+        hash(self)
+    }
+}
+
+#[pymethods]
+impl Port {
+    /// Creates a new incoming port.
+    #[staticmethod]
+    #[pyo3(name = "incoming")]
+    fn py_incoming(index: usize) -> Self {
+        Self::new_incoming(index)
+    }
+
+    /// Creates a new outgoing port.
+    #[staticmethod]
+    #[pyo3(name = "outgoing")]
+    fn py_outgoing(index: usize) -> Self {
+        Self::new_outgoing(index)
+    }
+
+    /// The offset of the port.
+    #[getter(index)]
+    fn py_index(&self) -> usize {
+        self.index()
+    }
+
+    /// The direction of the port, `"incoming"` or `"outgoing"`.
+    #[getter(direction)]
+    fn py_direction(&self) -> &'static str {
+        match self.direction() {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Port.{}({})", self.py_direction(), self.index())
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        compare(py, self, other, op)
+    }
+
+    fn __hash__(&self) -> u64 {
+        hash(self)
+    }
+}
+
+#[pymethods]
+impl Wire {
+    /// The node that this wire is connected to.
+    #[getter(node)]
+    fn py_node(&self) -> Node {
+        self.node()
+    }
+
+    /// The output port that this wire is connected to.
+    #[getter(source)]
+    fn py_source(&self) -> Port {
+        self.source()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Wire(Node({}), {})",
+            self.node().index(),
+            self.source().index()
+        )
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        compare(py, self, other, op)
+    }
+
+    fn __hash__(&self) -> u64 {
+        hash(self)
+    }
+}
+
+/// A serialization format of a HUGR file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Json,
+    Msgpack,
+    Binary,
+    Text,
+}
+
+impl Format {
+    /// Returns the format with the given name or, if no name is given, the
+    /// one matching the extension of `path`, defaulting to JSON.
+    fn new(path: &Path, name: Option<&str>) -> PyResult<Self> {
+        let from_name = |name| match name {
+            "json" => Some(Self::Json),
+            "msgpack" | "mpk" => Some(Self::Msgpack),
+            "binary" | "bin" => Some(Self::Binary),
+            "text" | "txt" => Some(Self::Text),
+            _ => None,
+        };
+        match name {
+            Some(name) => from_name(name)
+                .ok_or_else(|| PyValueError::new_err(format!("Unknown HUGR format {name:?}."))),
+            None => Ok(path
+                .extension()
+                .and_then(|ext| from_name(ext.to_str()?))
+                .unwrap_or(Self::Json)),
+        }
+    }
+
+    fn decode(self, data: &[u8]) -> PyResult<Hugr> {
+        let invalid = |err: &dyn std::fmt::Display| PyValueError::new_err(err.to_string());
+        match self {
+            Self::Json => serde_json::from_slice(data).map_err(|e| invalid(&e)),
+            Self::Msgpack => rmp_serde::from_slice(data).map_err(|e| invalid(&e)),
+            Self::Binary => Ok(Hugr::read_binary(data)?),
+            Self::Text => Ok(Hugr::from_text(
+                std::str::from_utf8(data).map_err(|e| invalid(&e))?,
+            )?),
+        }
+    }
+
+    fn encode(self, hugr: &Hugr) -> PyResult<Vec<u8>> {
+        let failed = |err: &dyn std::fmt::Display| PyRuntimeError::new_err(err.to_string());
+        match self {
+            Self::Json => serde_json::to_vec(hugr).map_err(|e| failed(&e)),
+            Self::Msgpack => rmp_serde::to_vec_named(hugr).map_err(|e| failed(&e)),
+            Self::Binary => {
+                let mut data = Vec::new();
+                hugr.write_binary(&mut data)?;
+                Ok(data)
+            }
+            Self::Text => Ok(hugr.to_text()?.into_bytes()),
+        }
+    }
+}
+
+#[pymethods]
+impl Hugr {
+    /// Loads a HUGR from a file.
+    ///
+    /// The format is one of `"json"`, `"msgpack"`, `"binary"` or `"text"`,
+    /// and is guessed from the extension of the file if not given.
+    #[staticmethod]
+    #[pyo3(name = "load", signature = (path, format = None))]
+    fn py_load(path: &str, format: Option<&str>) -> PyResult<Self> {
+        let format = Format::new(path.as_ref(), format)?;
+        format.decode(&std::fs::read(path)?)
+    }
+
+    /// Saves the HUGR to a file, in the same formats as `load`.
+    #[pyo3(name = "save", signature = (path, format = None))]
+    fn py_save(&self, path: &str, format: Option<&str>) -> PyResult<()> {
+        let format = Format::new(path.as_ref(), format)?;
+        std::fs::write(path, format.encode(self)?)?;
+        Ok(())
+    }
+
+    /// Parses a HUGR from its JSON serialization.
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    fn py_from_json(json: &str) -> PyResult<Self> {
+        Format::Json.decode(json.as_bytes())
+    }
+
+    /// Serializes the HUGR to JSON.
+    #[pyo3(name = "to_json")]
+    fn py_to_json(&self) -> PyResult<String> {
+        serde_json::to_string(self).map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Parses a HUGR in the textual format.
+    #[staticmethod]
+    #[pyo3(name = "from_text")]
+    fn py_from_text(text: &str) -> PyResult<Self> {
+        Ok(Self::from_text(text)?)
+    }
+
+    /// Prints the HUGR in the textual format.
+    #[pyo3(name = "to_text")]
+    fn py_to_text(&self) -> PyResult<String> {
+        Ok(self.to_text()?)
+    }
+
+    /// Checks the validity of the HUGR, raising an error if it is invalid.
+    #[pyo3(name = "validate")]
+    fn py_validate(&self) -> PyResult<()> {
+        Ok(self.validate()?)
+    }
+
+    /// Applies a simple replacement to the HUGR, optionally checking first
+    /// that it preserves the unitary, see [`SimpleReplacement::verify_unitary`].
+    #[pyo3(name = "apply_replacement", signature = (replacement, check_unitary = false))]
+    fn py_apply_replacement(
+        &mut self,
+        replacement: SimpleReplacement,
+        check_unitary: bool,
+    ) -> PyResult<()> {
+        if check_unitary {
+            replacement.verify_unitary(self)?;
+        }
+        Ok(self.apply_rewrite(replacement)?)
+    }
+
+    /// The root node of the HUGR.
+    #[pyo3(name = "root")]
+    fn py_root(&self) -> Node {
+        self.root()
+    }
+
+    /// The number of nodes in the HUGR.
+    #[pyo3(name = "node_count")]
+    fn py_node_count(&self) -> usize {
+        self.node_count()
+    }
+
+    /// The number of edges in the HUGR.
+    #[pyo3(name = "edge_count")]
+    fn py_edge_count(&self) -> usize {
+        self.edge_count()
+    }
+
+    /// The nodes of the HUGR.
+    #[pyo3(name = "nodes")]
+    fn py_nodes(&self) -> Vec<Node> {
+        self.nodes().collect()
+    }
+
+    /// The children of a node, in order.
+    #[pyo3(name = "children")]
+    fn py_children(&self, node: Node) -> Vec<Node> {
+        self.children(node).collect()
+    }
+
+    /// The parent of a node, or `None` for the root.
+    #[pyo3(name = "parent")]
+    fn py_parent(&self, node: Node) -> Option<Node> {
+        self.get_parent(node)
+    }
+
+    /// The name of the operation of a node.
+    #[pyo3(name = "op_name")]
+    fn py_op_name(&self, node: Node) -> String {
+        self.get_optype(node).name().to_string()
+    }
+
+    /// The operation of a node, in the textual format.
+    #[pyo3(name = "op")]
+    fn py_op(&self, node: Node) -> PyResult<String> {
+        Ok(op_to_text(self.get_optype(node))?)
+    }
+
+    /// The metadata of a node, decoded from JSON.
+    #[pyo3(name = "metadata")]
+    fn py_metadata(&self, node: Node, py: Python) -> PyResult<PyObject> {
+        let json = self.get_metadata(node).to_string();
+        let json_module = py.import("json")?;
+        Ok(json_module.call_method1("loads", (json,))?.into())
+    }
+
+    /// The number of incoming ports of a node.
+    #[pyo3(name = "num_inputs")]
+    fn py_num_inputs(&self, node: Node) -> usize {
+        self.num_inputs(node)
+    }
+
+    /// The number of outgoing ports of a node.
+    #[pyo3(name = "num_outputs")]
+    fn py_num_outputs(&self, node: Node) -> usize {
+        self.num_outputs(node)
+    }
+
+    /// The incoming ports of a node.
+    #[pyo3(name = "node_inputs")]
+    fn py_node_inputs(&self, node: Node) -> Vec<Port> {
+        self.node_inputs(node).collect()
+    }
+
+    /// The outgoing ports of a node.
+    #[pyo3(name = "node_outputs")]
+    fn py_node_outputs(&self, node: Node) -> Vec<Port> {
+        self.node_outputs(node).collect()
+    }
+
+    /// The ports linked to a port, with their nodes.
+    #[pyo3(name = "linked_ports")]
+    fn py_linked_ports(&self, node: Node, port: Port) -> Vec<(Node, Port)> {
+        self.linked_ports(node, port).collect()
+    }
+
+    /// The nodes connected to the incoming ports of a node.
+    #[pyo3(name = "input_neighbours")]
+    fn py_input_neighbours(&self, node: Node) -> Vec<Node> {
+        self.input_neighbours(node).collect()
+    }
+
+    /// The nodes connected to the outgoing ports of a node.
+    #[pyo3(name = "output_neighbours")]
+    fn py_output_neighbours(&self, node: Node) -> Vec<Node> {
+        self.output_neighbours(node).collect()
+    }
+
+    /// The HUGR as a graphviz dot string.
+    #[pyo3(name = "dot_string")]
+    fn py_dot_string(&self) -> String {
+        self.dot_string()
+    }
+
+    /// The HUGR as a Mermaid flowchart.
+    #[pyo3(name = "mermaid_string")]
+    fn py_mermaid_string(&self) -> String {
+        self.mermaid_string()
+    }
+}
+
+#[pymethods]
+impl SimpleReplacement {
+    /// Creates a new simple replacement, see [`SimpleReplacement::new`].
+    #[new]
+    fn py_new(
+        parent: Node,
+        removal: Vec<Node>,
+        replacement: Hugr,
+        nu_inp: HashMap<(Node, Port), (Node, Port)>,
+        nu_out: HashMap<(Node, Port), Port>,
+    ) -> Self {
+        let removal: HashSet<Node> = removal.into_iter().collect();
+        Self::new(parent, removal, replacement, nu_inp, nu_out)
+    }
+}
+
+/// A type given from Python, either as a type object or in the textual
+/// format.
+#[derive(Clone, Debug, FromPyObject)]
+enum TypeLike {
+    Simple(PySimpleType),
+    Classic(PyClassicType),
+    Text(String),
+}
+
+impl TypeLike {
+    fn into_type(self) -> PyResult<SimpleType> {
+        match self {
+            TypeLike::Simple(ty) => Ok(ty.0),
+            TypeLike::Classic(ty) => Ok(ty.0.into()),
+            TypeLike::Text(text) => Ok(parse_type(&text)?),
+        }
+    }
+
+    fn into_classic(self) -> PyResult<ClassicType> {
+        self.into_type()?.try_into().map_err(PyValueError::new_err)
+    }
+}
+
+fn type_row(types: Vec<TypeLike>) -> PyResult<SimpleRow> {
+    let types: PyResult<Vec<_>> = types.into_iter().map(TypeLike::into_type).collect();
+    Ok(types?.into())
+}
+
+fn classic_row(types: Vec<TypeLike>) -> PyResult<Vec<ClassicType>> {
+    types.into_iter().map(TypeLike::into_classic).collect()
+}
+
+/// A type of data, exposed to Python as `SimpleType`.
+#[pyclass(name = "SimpleType")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PySimpleType(SimpleType);
+
+#[pymethods]
+impl PySimpleType {
+    /// Parses a type in the textual format.
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        Ok(Self(parse_type(text)?))
+    }
+
+    /// The qubit type.
+    #[staticmethod]
+    fn qubit() -> Self {
+        Self(SimpleType::Qubit)
+    }
+
+    /// The 64-bit floating point type.
+    #[staticmethod]
+    fn f64() -> Self {
+        Self(ClassicType::F64.into())
+    }
+
+    /// The integer type of the given width.
+    #[staticmethod]
+    fn int(width: u8) -> Self {
+        Self(HashableType::Int(width).into())
+    }
+
+    /// The bit type.
+    #[staticmethod]
+    fn bit() -> Self {
+        Self(ClassicType::bit().into())
+    }
+
+    /// The string type.
+    #[staticmethod]
+    fn string() -> Self {
+        Self(HashableType::String.into())
+    }
+
+    /// The tuple type with the given elements.
+    #[staticmethod]
+    fn tuple(types: Vec<TypeLike>) -> PyResult<Self> {
+        Ok(Self(SimpleType::new_tuple(type_row(types)?)))
+    }
+
+    /// The sum type with the given variants.
+    #[staticmethod]
+    fn sum(types: Vec<TypeLike>) -> PyResult<Self> {
+        Ok(Self(SimpleType::new_sum(type_row(types)?)))
+    }
+
+    /// The predicate type with the given number of empty variants.
+    #[staticmethod]
+    fn predicate(size: usize) -> Self {
+        Self(SimpleType::new_simple_predicate(size))
+    }
+
+    /// The list type of the given elements.
+    #[staticmethod]
+    fn list(ty: TypeLike) -> PyResult<Self> {
+        Ok(Self(natural_container!(ty.into_type()?, |t| {
+            TypeContainer::List(Box::new(t))
+        })))
+    }
+
+    /// The array type of the given elements and length.
+    #[staticmethod]
+    fn array(ty: TypeLike, len: usize) -> PyResult<Self> {
+        Ok(Self(natural_container!(ty.into_type()?, |t| {
+            TypeContainer::Array(Box::new(t), len)
+        })))
+    }
+
+    /// Whether values of the type can be copied and discarded.
+    fn is_classical(&self) -> bool {
+        matches!(self.0, SimpleType::Classic(_))
+    }
+
+    fn __str__(&self) -> PyResult<String> {
+        Ok(type_to_text(&self.0)?)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("SimpleType({})", self.__str__()?))
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        compare(py, self, other, op)
+    }
+}
+
+/// A type of classical data, exposed to Python as `ClassicType`.
+#[pyclass(name = "ClassicType")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PyClassicType(ClassicType);
+
+#[pymethods]
+impl PyClassicType {
+    /// Parses a classical type in the textual format.
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        Ok(Self(TypeLike::Text(text.to_string()).into_classic()?))
+    }
+
+    /// The 64-bit floating point type.
+    #[staticmethod]
+    fn f64() -> Self {
+        Self(ClassicType::F64)
+    }
+
+    /// The integer type of the given width.
+    #[staticmethod]
+    fn int(width: u8) -> Self {
+        Self(HashableType::Int(width).into())
+    }
+
+    /// The bit type.
+    #[staticmethod]
+    fn bit() -> Self {
+        Self(ClassicType::bit())
+    }
+
+    /// The string type.
+    #[staticmethod]
+    fn string() -> Self {
+        Self(HashableType::String.into())
+    }
+
+    /// The tuple type with the given classical elements.
+    #[staticmethod]
+    fn tuple(types: Vec<TypeLike>) -> PyResult<Self> {
+        Ok(Self(ClassicType::new_tuple(classic_row(types)?)))
+    }
+
+    /// The sum type with the given classical variants.
+    #[staticmethod]
+    fn sum(types: Vec<TypeLike>) -> PyResult<Self> {
+        Ok(Self(ClassicType::new_sum(classic_row(types)?)))
+    }
+
+    /// The predicate type with the given number of empty variants.
+    #[staticmethod]
+    fn predicate(size: usize) -> Self {
+        Self(ClassicType::new_simple_predicate(size))
+    }
+
+    /// The function type with the given inputs and outputs.
+    #[staticmethod]
+    fn graph(inputs: Vec<TypeLike>, outputs: Vec<TypeLike>) -> PyResult<Self> {
+        let signature = AbstractSignature::new_df(type_row(inputs)?, type_row(outputs)?);
+        Ok(Self(ClassicType::graph_from_sig(signature)))
+    }
+
+    /// Whether values of the type can be hashed.
+    fn is_hashable(&self) -> bool {
+        self.0.is_hashable()
+    }
+
+    fn __str__(&self) -> PyResult<String> {
+        Ok(type_to_text(&self.0.clone().into())?)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("ClassicType({})", self.__str__()?))
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        compare(py, self, other, op)
+    }
+}
+
+/// Creates a builder for a DFG rooted HUGR with the given signature.
+fn dfg_builder(inputs: Vec<TypeLike>, outputs: Vec<TypeLike>) -> PyResult<DFGBuilder<Hugr>> {
+    let signature = AbstractSignature::new_df(type_row(inputs)?, type_row(outputs)?);
+    Ok(DFGBuilder::new(signature)?)
+}
+
+/// Returns the builder, unless it has already been finished.
+fn unfinished(builder: &mut Option<DFGBuilder<Hugr>>) -> PyResult<&mut DFGBuilder<Hugr>> {
+    builder
+        .as_mut()
+        .ok_or_else(|| PyRuntimeError::new_err("The builder has already been finished."))
+}
+
+/// Builder for a DFG rooted HUGR, exposed to Python as `DFGBuilder`.
+///
+/// The builder is consumed by `finish`, after which it can no longer be used.
+#[pyclass(name = "DFGBuilder")]
+pub struct PyDFGBuilder {
+    builder: Option<DFGBuilder<Hugr>>,
+}
+
+#[pymethods]
+impl PyDFGBuilder {
+    #[new]
+    fn new(inputs: Vec<TypeLike>, outputs: Vec<TypeLike>) -> PyResult<Self> {
+        Ok(Self {
+            builder: Some(dfg_builder(inputs, outputs)?),
+        })
+    }
+
+    /// The wires from the inputs of the graph.
+    fn input_wires(&mut self) -> PyResult<Vec<Wire>> {
+        Ok(unfinished(&mut self.builder)?.input_wires().collect())
+    }
+
+    /// Adds an operation, given in the textual format, to the graph with the
+    /// given input wires. Returns its output wires.
+    fn add_op(&mut self, op: &str, inputs: Vec<Wire>) -> PyResult<Vec<Wire>> {
+        let op = parse_op(op)?;
+        let builder = unfinished(&mut self.builder)?;
+        Ok(builder.add_dataflow_op(op, inputs)?.outputs().collect())
+    }
+
+    /// Inserts a DFG rooted HUGR as a subgraph with the given input wires.
+    /// Returns its output wires.
+    fn add_hugr(&mut self, hugr: Hugr, inputs: Vec<Wire>) -> PyResult<Vec<Wire>> {
+        let builder = unfinished(&mut self.builder)?;
+        Ok(builder
+            .add_hugr_with_wires(hugr, inputs)?
+            .outputs()
+            .collect())
+    }
+
+    /// Sets the metadata of a node added to the graph, from its JSON encoding.
+    fn set_metadata(&mut self, node: Node, json: &str) -> PyResult<()> {
+        let meta = serde_json::from_str(json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        unfinished(&mut self.builder)?.set_child_metadata(node, meta);
+        Ok(())
+    }
+
+    /// Connects the output wires of the graph and returns the finished HUGR.
+    fn finish(&mut self, outputs: Vec<Wire>) -> PyResult<Hugr> {
+        unfinished(&mut self.builder)?;
+        let builder = self.builder.take().unwrap();
+        Ok(builder.finish_hugr_with_outputs(outputs)?)
+    }
+}
+
+/// Builder for circuit-like DFG rooted HUGRs, exposed to Python as
+/// `CircuitBuilder`.
+///
+/// The builder tracks a wire for each input of the graph. Operations are
+/// appended to wires given by their index, and their outputs replace them.
+#[pyclass(name = "CircuitBuilder")]
+pub struct PyCircuitBuilder {
+    builder: Option<DFGBuilder<Hugr>>,
+    wires: Vec<Wire>,
+}
+
+#[pymethods]
+impl PyCircuitBuilder {
+    /// Creates a builder for a graph with the given inputs and, by default,
+    /// the same outputs.
+    #[new]
+    #[pyo3(signature = (inputs, outputs = None))]
+    fn new(inputs: Vec<TypeLike>, outputs: Option<Vec<TypeLike>>) -> PyResult<Self> {
+        let outputs = outputs.unwrap_or_else(|| inputs.clone());
+        let builder = dfg_builder(inputs, outputs)?;
+        let wires = builder.input_wires().collect();
+        Ok(Self {
+            builder: Some(builder),
+            wires,
+        })
+    }
+
+    /// The wires currently tracked by the builder.
+    #[getter]
+    fn wires(&self) -> Vec<Wire> {
+        self.wires.clone()
+    }
+
+    /// Appends an operation to the tracked wires with the given indices.
+    /// Returns the builder to allow chaining.
+    fn append<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        op: &str,
+        indices: Vec<usize>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let inputs = indices.into_iter().map(CircuitUnit::Linear).collect();
+        slf.append_with_outputs(op, inputs)?;
+        Ok(slf)
+    }
+
+    /// Appends an operation whose inputs are either indices of tracked wires
+    /// or other wires. Returns the outputs that do not replace a tracked wire.
+    fn append_with_outputs(&mut self, op: &str, inputs: Vec<CircuitUnit>) -> PyResult<Vec<Wire>> {
+        let op = parse_op(op)?;
+        let builder = unfinished(&mut self.builder)?;
+        let mut circuit = builder.as_circuit(std::mem::take(&mut self.wires));
+        let outputs = circuit.append_with_outputs(op, inputs);
+        self.wires = circuit.finish();
+        Ok(outputs?)
+    }
+
+    /// Connects the tracked wires, followed by `outputs`, to the outputs of
+    /// the graph and returns the finished HUGR.
+    #[pyo3(signature = (outputs = vec![]))]
+    fn finish(&mut self, outputs: Vec<Wire>) -> PyResult<Hugr> {
+        unfinished(&mut self.builder)?;
+        let builder = self.builder.take().unwrap();
+        let wires = std::mem::take(&mut self.wires).into_iter().chain(outputs);
+        Ok(builder.finish_hugr_with_outputs(wires)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pyo3::types::PyDict;
+
+    use super::*;
+
+    /// Runs Python code with the `hugr` module in scope.
+    fn run_python(code: &str) -> PyResult<()> {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "hugr")?;
+            hugr(py, module)?;
+            let globals = PyDict::new(py);
+            globals.set_item("__builtins__", py.import("builtins")?)?;
+            globals.set_item("hugr", module)?;
+            py.run(code, Some(globals), None)
+        })
+    }
+
+    #[test]
+    fn build_circuit() -> PyResult<()> {
+        run_python(
+            r#"
+circ = hugr.CircuitBuilder([hugr.SimpleType.qubit(), "Qubit"])
+circ.append("H", [0]).append("CX", [0, 1])
+h = circ.finish()
+h.validate()
+
+root = h.root()
+assert h.op_name(root) == "DFG", h.op_name(root)
+assert h.parent(root) is None, h.parent(root)
+names = [h.op_name(n) for n in h.children(root)]
+assert names == ["Input", "Output", "H", "CX"], names
+inp, out, had, cx = h.children(root)
+# Two qubits and the state order edge.
+assert h.num_inputs(cx) == 3, h.num_inputs(cx)
+links = h.linked_ports(cx, hugr.Port.incoming(0))
+assert links == [(had, hugr.Port.outgoing(0))], links
+assert h.input_neighbours(had) == [inp], h.input_neighbours(had)
+assert h.op(cx) == "CX", h.op(cx)
+assert "flowchart" in h.mermaid_string()
+"#,
+        )
+    }
+
+    #[test]
+    fn build_dfg() -> PyResult<()> {
+        run_python(
+            r#"
+f64 = hugr.SimpleType.f64()
+tuple_ty = hugr.SimpleType.tuple(["Qubit", f64])
+assert str(tuple_ty) == "Tuple(Qubit, F64)"
+assert hugr.SimpleType.parse(str(tuple_ty)) == tuple_ty
+assert not tuple_ty.is_classical()
+assert hugr.SimpleType.list(hugr.ClassicType.int(64)).is_classical()
+
+b = hugr.DFGBuilder(["Qubit", f64], [tuple_ty])
+q, angle = b.input_wires()
+[q] = b.add_op("RzF64", [q, angle])
+[t] = b.add_op("MakeTuple [Qubit, F64]", [q, angle])
+b.set_metadata(t.node, '{"name": "pack"}')
+h = b.finish([t])
+assert h.metadata(t.node) == {"name": "pack"}
+
+try:
+    b.finish([t])
+    raise AssertionError("finished twice")
+except RuntimeError:
+    pass
+try:
+    hugr.DFGBuilder(["Qbit"], [])
+    raise AssertionError("parsed an unknown type")
+except ValueError:
+    pass
+"#,
+        )
+    }
+
+    #[test]
+    fn serialization() -> PyResult<()> {
+        let dir = std::env::temp_dir().join(format!("hugr-python-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let result = run_python(&format!(
+            r#"
+circ = hugr.CircuitBuilder(["Qubit"])
+h = circ.append("H", [0]).finish()
+assert hugr.Hugr.from_json(h.to_json()).to_json() == h.to_json()
+assert hugr.Hugr.from_text(h.to_text()).to_text() == h.to_text()
+for ext in ["json", "msgpack", "bin", "txt"]:
+    path = {dir:?} + "/circ." + ext
+    h.save(path)
+    loaded = hugr.Hugr.load(path)
+    loaded.validate()
+    assert loaded.to_json() == h.to_json(), ext
+"#,
+            dir = dir.to_str().unwrap()
+        ));
+        std::fs::remove_dir_all(&dir)?;
+        result
+    }
+
+    #[test]
+    fn replacement() -> PyResult<()> {
+        run_python(
+            r#"
+circ = hugr.CircuitBuilder(["Qubit", "Qubit"])
+h = circ.append("H", [0]).append("H", [1]).append("CX", [0, 1]).finish()
+root = h.root()
+inp, out, h0, h1, cx = h.children(root)
+
+# Replace the two Hadamards by a single DFG applying X to both qubits.
+repl_circ = hugr.CircuitBuilder(["Qubit", "Qubit"])
+replacement = repl_circ.append("X", [0]).append("X", [1]).finish()
+r_inp, r_out, x0, x1 = replacement.children(replacement.root())
+nu_inp = {
+    (x0, hugr.Port.incoming(0)): (h0, hugr.Port.incoming(0)),
+    (x1, hugr.Port.incoming(0)): (h1, hugr.Port.incoming(0)),
+}
+nu_out = {
+    (cx, hugr.Port.incoming(0)): hugr.Port.incoming(0),
+    (cx, hugr.Port.incoming(1)): hugr.Port.incoming(1),
+}
+r = hugr.SimpleReplacement(root, [h0, h1], replacement, nu_inp, nu_out)
+try:
+    h.apply_replacement(r, check_unitary=True)
+    raise AssertionError("H is not X")
+except RuntimeError:
+    pass
+h.apply_replacement(r)
+h.validate()
+names = sorted(h.op_name(n) for n in h.children(root))
+assert names == ["CX", "Input", "Output", "X", "X"], names
+"#,
+        )
+    }
+}
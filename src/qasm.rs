@@ -0,0 +1,133 @@
+//! Conversion between Hugrs and OpenQASM circuits.
+//!
+//! Gates of the OpenQASM standard library with a counterpart among the
+//! [`LeafOp`]s are converted to it. Other gates are represented by
+//! [`OpaqueOp`]s of the [`resource_id`] resource, named as in OpenQASM, whose
+//! parameters are [`ClassicType::F64`] inputs following the qubits.
+//!
+//! [`OpaqueOp`]: crate::ops::custom::OpaqueOp
+//! [`ClassicType::F64`]: crate::types::ClassicType::F64
+
+use smol_str::SmolStr;
+
+use crate::ops::LeafOp;
+use crate::types::SimpleType;
+
+pub mod export;
+pub mod import;
+
+pub use export::{export_qasm, QasmExportError, QasmVersion};
+pub use import::{import_qasm, import_qasm_function, QasmImportError};
+
+/// The identifier of the resource of OpenQASM gates with no [`LeafOp`]
+/// counterpart.
+pub const fn resource_id() -> SmolStr {
+    SmolStr::new_inline("qasm")
+}
+
+/// The name of the operation comparing a classical register to an integer,
+/// for `if` statements.
+///
+/// It takes the bits of the register, least significant first, and has a
+/// single [`TypeArg::Int`] argument, the value to compare with. Its output is
+/// a predicate with two unit variants, the second one when the register
+/// holds the value.
+///
+/// [`TypeArg::Int`]: crate::types::type_param::TypeArg::Int
+pub const CREG_EQ: &str = "creg_eq";
+
+/// The standard library gates, with their number of parameters and qubits.
+const STANDARD_GATES: [(&str, usize, usize); 40] = [
+    ("U", 3, 1),
+    ("CX", 0, 2),
+    ("u3", 3, 1),
+    ("u2", 2, 1),
+    ("u1", 1, 1),
+    ("u0", 1, 1),
+    ("u", 3, 1),
+    ("p", 1, 1),
+    ("cx", 0, 2),
+    ("id", 0, 1),
+    ("x", 0, 1),
+    ("y", 0, 1),
+    ("z", 0, 1),
+    ("h", 0, 1),
+    ("s", 0, 1),
+    ("sdg", 0, 1),
+    ("t", 0, 1),
+    ("tdg", 0, 1),
+    ("rx", 1, 1),
+    ("ry", 1, 1),
+    ("rz", 1, 1),
+    ("sx", 0, 1),
+    ("sxdg", 0, 1),
+    ("cz", 0, 2),
+    ("cy", 0, 2),
+    ("ch", 0, 2),
+    ("swap", 0, 2),
+    ("ccx", 0, 3),
+    ("cswap", 0, 3),
+    ("crx", 1, 2),
+    ("cry", 1, 2),
+    ("crz", 1, 2),
+    ("cu1", 1, 2),
+    ("cp", 1, 2),
+    ("cu3", 3, 2),
+    ("csx", 0, 2),
+    ("cu", 4, 2),
+    ("rxx", 1, 2),
+    ("rzz", 1, 2),
+    ("c3x", 0, 4),
+];
+
+/// The number of parameters and qubits of a standard library gate. The
+/// built-in `U` and `CX` gates are always included, while the gates of
+/// `qelib1.inc` are only included if `qelib` is set.
+fn standard_gate(name: &str, qelib: bool) -> Option<(usize, usize)> {
+    STANDARD_GATES
+        .iter()
+        .find(|(n, _, _)| *n == name && (qelib || matches!(name, "U" | "CX")))
+        .map(|&(_, params, qubits)| (params, qubits))
+}
+
+/// The leaf operation of a standard gate, if any. `RzF64` takes its angle as
+/// an input, like the other parametrised gates.
+fn gate_op(name: &str) -> Option<LeafOp> {
+    Some(match name {
+        "h" => LeafOp::H,
+        "x" => LeafOp::X,
+        "y" => LeafOp::Y,
+        "z" => LeafOp::Z,
+        "s" => LeafOp::S,
+        "sdg" => LeafOp::Sadj,
+        "t" => LeafOp::T,
+        "tdg" => LeafOp::Tadj,
+        "cx" | "CX" => LeafOp::CX,
+        "id" => LeafOp::Noop {
+            ty: SimpleType::Qubit,
+        },
+        "rz" => LeafOp::RzF64,
+        _ => return None,
+    })
+}
+
+/// The standard gate of a leaf operation, if any. This is the inverse of
+/// [`gate_op`].
+fn gate_name(op: &LeafOp) -> Option<&'static str> {
+    Some(match op {
+        LeafOp::H => "h",
+        LeafOp::X => "x",
+        LeafOp::Y => "y",
+        LeafOp::Z => "z",
+        LeafOp::S => "s",
+        LeafOp::Sadj => "sdg",
+        LeafOp::T => "t",
+        LeafOp::Tadj => "tdg",
+        LeafOp::CX => "cx",
+        LeafOp::Noop {
+            ty: SimpleType::Qubit,
+        } => "id",
+        LeafOp::RzF64 => "rz",
+        _ => return None,
+    })
+}
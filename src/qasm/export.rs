@@ -0,0 +1,1016 @@
+//! Export of circuit-like Hugrs to OpenQASM 2 or 3 programs.
+//!
+//! A dataflow region whose inputs are all qubits is exported as a program
+//! acting on a single quantum register `q` holding them, in order. As the
+//! program cannot permute the register, the region must output the same
+//! qubits in the same order, optionally followed by classical values. The
+//! region may contain
+//! quantum [`LeafOp`]s, gates of the [`resource_id`] resource, loads of
+//! constant angles and zero bits, and nested [`DFG`]s.
+//!
+//! Each measurement writes to a fresh classical bit. [`Conditional`]s like
+//! those produced by [`import_qasm`], with a [`CREG_EQ`] predicate, a first
+//! case passing its inputs through and a flat second case, become `if`
+//! statements. As OpenQASM 2 only compares whole registers, the bits compared
+//! by each `if` form a register of their own, named `c0`, `c1`, and so on.
+//! The other bits form the register `c`. These registers are declared in
+//! that order, which must match the order of the classical outputs.
+//!
+//! [`DFG`]: crate::ops::DFG
+//! [`Conditional`]: crate::ops::Conditional
+//! [`import_qasm`]: super::import_qasm
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f64::consts::PI;
+use std::fmt;
+
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::algorithm::dataflow::dataflow_order;
+use crate::hugr::HugrView;
+use crate::ops::custom::OpaqueOp;
+use crate::ops::{ConstValue, LeafOp, OpName, OpTag, OpTrait, OpType};
+use crate::types::type_param::TypeArg;
+use crate::types::{ClassicType, SimpleType};
+use crate::values::HashableValue;
+use crate::{Node, Port};
+
+use super::{gate_name, resource_id, standard_gate, CREG_EQ};
+
+/// The gates of the OpenQASM 3 `stdgates.inc` include file.
+const STDGATES: [&str; 32] = [
+    "p", "x", "y", "z", "h", "s", "sdg", "t", "tdg", "sx", "rx", "ry", "rz", "cx", "cy", "cz",
+    "cp", "crx", "cry", "crz", "ch", "swap", "ccx", "cswap", "cu", "CX", "phase", "cphase", "id",
+    "u1", "u2", "u3",
+];
+
+/// The definition of `rzz`, which OpenQASM 3 does not include, in terms of
+/// standard gates.
+const RZZ_DEFINITION: &str = "gate rzz(theta) a, b {\n  cx a, b;\n  rz(theta) b;\n  cx a, b;\n}\n";
+
+/// A version of the OpenQASM language.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum QasmVersion {
+    /// OpenQASM 2.0, using the `qelib1.inc` gates.
+    #[default]
+    V2,
+    /// OpenQASM 3.0, using the `stdgates.inc` gates.
+    V3,
+}
+
+impl fmt::Display for QasmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V2 => write!(f, "2.0"),
+            Self::V3 => write!(f, "3.0"),
+        }
+    }
+}
+
+/// Exports a dataflow region of a Hugr as an OpenQASM program.
+pub fn export_qasm(
+    h: &impl HugrView,
+    node: Node,
+    version: QasmVersion,
+) -> Result<String, QasmExportError> {
+    let Some(OpType::Input(input)) = h.children(node).next().map(|n| h.get_optype(n)) else {
+        return Err(QasmExportError::NotARegion(node, h.get_optype(node).tag()));
+    };
+    let qubits = input
+        .types
+        .iter()
+        .enumerate()
+        .map(|(index, t)| match t {
+            SimpleType::Qubit => Ok(Val::Qubit(index)),
+            _ => Err(QasmExportError::NonQubitInput { node, index }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let n_qubits = qubits.len();
+    let mut exporter = Exporter {
+        h,
+        version,
+        values: HashMap::new(),
+        visited: HashSet::new(),
+        measure_bits: HashMap::new(),
+        n_bits: 0,
+    };
+    let mut instrs = Vec::new();
+    let outputs = exporter.region(node, qubits, &mut instrs)?;
+    let in_order = (0..n_qubits).all(|i| outputs.get(i) == Some(&Val::Qubit(i)))
+        && !outputs[n_qubits..]
+            .iter()
+            .any(|v| matches!(v, Val::Qubit(_)));
+    if !in_order {
+        return Err(QasmExportError::PermutedOutputs(node));
+    }
+    // The classical outputs come first in the registers, in order, so that
+    // the outputs of the imported program are the same.
+    let mut output_bits = Vec::new();
+    for (i, v) in outputs[n_qubits..].iter().enumerate() {
+        let bit = match v {
+            Val::Bit(bit) if !output_bits.contains(bit) => *bit,
+            Val::Zero => exporter.fresh_bit(),
+            Val::Bit(_) => return Err(QasmExportError::UnorderedBits(node)),
+            _ => {
+                return Err(QasmExportError::UnsupportedValue {
+                    node: h.children(node).nth(1).unwrap(),
+                    port: Port::new_incoming(n_qubits + i),
+                })
+            }
+        };
+        output_bits.push(bit);
+    }
+    let bit_order = output_bits
+        .iter()
+        .copied()
+        .chain((0..exporter.n_bits).filter(|b| !output_bits.contains(b)))
+        .collect::<Vec<_>>();
+    let printer = Printer::new(version, &bit_order, &instrs)?;
+    let layout = printer.registers.iter().flat_map(|(_, bits)| bits);
+    if !layout.take(output_bits.len()).eq(&output_bits) {
+        return Err(QasmExportError::UnorderedBits(node));
+    }
+    Ok(printer.print(n_qubits, &instrs))
+}
+
+/// Errors that can occur when exporting a Hugr to OpenQASM.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum QasmExportError {
+    /// The node is not the parent of a dataflow sibling graph.
+    #[error("Node {0:?} with tag {1:?} is not a dataflow region")]
+    NotARegion(Node, OpTag),
+    /// The region does not output its input qubits in order.
+    #[error("Region {0:?} does not output its input qubits in order")]
+    PermutedOutputs(Node),
+    /// The classical outputs of the region cannot be declared in order, as
+    /// the registers of the compared bits come first.
+    #[error("The classical outputs of region {0:?} cannot be declared in order")]
+    UnorderedBits(Node),
+    /// An input of the region is not a qubit.
+    #[error("Input {index} of region {node:?} is not a qubit")]
+    NonQubitInput {
+        /// The region.
+        node: Node,
+        /// The index of the input.
+        index: usize,
+    },
+    /// The operation has no counterpart in the OpenQASM version.
+    #[error("Operation {name} of node {node:?} cannot be expressed in OpenQASM {version}")]
+    UnsupportedOp {
+        /// The node of the operation.
+        node: Node,
+        /// The name of the operation.
+        name: SmolStr,
+        /// The OpenQASM version.
+        version: QasmVersion,
+    },
+    /// A constant is neither an angle nor a zero bit.
+    #[error("Constant loaded by node {0:?} is neither an angle nor a zero bit")]
+    UnsupportedConstant(Node),
+    /// An input of a node is not a qubit, a bit or a constant angle, as
+    /// required by the operation.
+    #[error("Input {port:?} of node {node:?} has an unsupported value")]
+    UnsupportedValue {
+        /// The node.
+        node: Node,
+        /// The input port.
+        port: Port,
+    },
+    /// The conditional cannot be expressed as an `if` statement.
+    #[error("Conditional {node:?} cannot be expressed as an if statement: {reason}")]
+    UnsupportedConditional {
+        /// The conditional node.
+        node: Node,
+        /// Why it cannot be exported.
+        reason: String,
+    },
+}
+
+/// The value of a wire, in terms of the program being exported.
+#[derive(Clone, Debug, PartialEq)]
+enum Val {
+    Qubit(usize),
+    /// A classical bit.
+    Bit(usize),
+    /// A constant zero bit, not stored in any classical bit.
+    Zero,
+    Angle(f64),
+    /// The predicate computed by a [`CREG_EQ`] operation from some bits and a
+    /// value.
+    Condition(Vec<Val>, u128),
+}
+
+/// A statement of the exported program.
+#[derive(Clone, Debug, PartialEq)]
+enum Instr {
+    Gate {
+        name: SmolStr,
+        params: Vec<f64>,
+        qubits: Vec<usize>,
+    },
+    Measure {
+        qubit: usize,
+        bit: usize,
+    },
+    Reset {
+        qubit: usize,
+    },
+    /// Statements applied if some bits, least significant first, hold a
+    /// value.
+    If {
+        node: Node,
+        bits: Vec<usize>,
+        value: u128,
+        body: Vec<Instr>,
+    },
+}
+
+struct Exporter<'a, H> {
+    h: &'a H,
+    version: QasmVersion,
+    values: HashMap<(Node, Port), Val>,
+    /// The nodes whose statements have been emitted.
+    visited: HashSet<Node>,
+    /// The bits written by measurements inside conditionals, when they are
+    /// not fresh.
+    measure_bits: HashMap<Node, usize>,
+    n_bits: usize,
+}
+
+impl<'a, H: HugrView> Exporter<'a, H> {
+    /// Emits the statements of a dataflow sibling graph, returning the values
+    /// reaching its output node.
+    fn region(
+        &mut self,
+        parent: Node,
+        inputs: Vec<Val>,
+        out: &mut Vec<Instr>,
+    ) -> Result<Vec<Val>, QasmExportError> {
+        let mut children = self.h.children(parent);
+        let (input, output) = (children.next().unwrap(), children.next().unwrap());
+        self.set_outputs(input, inputs);
+        for n in dataflow_order(self.h, parent) {
+            if n == input || n == output {
+                continue;
+            }
+            let op = self.h.get_optype(n);
+            if OpTag::ScopedDefn.is_superset(op.tag()) || op.tag() == OpTag::Const {
+                continue;
+            }
+            let ins = self.inputs(n)?;
+            let outs = self.node(n, ins, out)?;
+            self.set_outputs(n, outs);
+            self.visited.insert(n);
+        }
+        self.inputs(output)
+    }
+
+    fn node(
+        &mut self,
+        node: Node,
+        inputs: Vec<Val>,
+        out: &mut Vec<Instr>,
+    ) -> Result<Vec<Val>, QasmExportError> {
+        match self.h.get_optype(node) {
+            OpType::DFG(_) => self.region(node, inputs, out),
+            OpType::LoadConstant(load) => {
+                let value = self
+                    .h
+                    .linked_ports(node, Port::new_incoming(0))
+                    .next()
+                    .and_then(|(c, _)| match self.h.get_optype(c) {
+                        OpType::Const(c) => Some(c.value()),
+                        _ => None,
+                    });
+                match value {
+                    Some(ConstValue::F64(angle)) if angle.is_finite() => {
+                        Ok(vec![Val::Angle(*angle)])
+                    }
+                    Some(ConstValue::Hashable(HashableValue::Int(0)))
+                        if load.datatype == ClassicType::bit() =>
+                    {
+                        Ok(vec![Val::Zero])
+                    }
+                    _ => Err(QasmExportError::UnsupportedConstant(node)),
+                }
+            }
+            OpType::LeafOp(leaf) => self.leaf(node, leaf, inputs, out),
+            OpType::Conditional(_) => self.conditional(node, inputs, out),
+            op => Err(self.unsupported(node, op.name())),
+        }
+    }
+
+    fn leaf(
+        &mut self,
+        node: Node,
+        leaf: &LeafOp,
+        inputs: Vec<Val>,
+        out: &mut Vec<Instr>,
+    ) -> Result<Vec<Val>, QasmExportError> {
+        let (name, mut params) = match leaf {
+            LeafOp::Measure => {
+                let qubit = qubit(node, &inputs, 0)?;
+                let bit = match self.measure_bits.get(&node) {
+                    Some(&bit) => bit,
+                    None => self.fresh_bit(),
+                };
+                out.push(Instr::Measure { qubit, bit });
+                return Ok(vec![Val::Qubit(qubit), Val::Bit(bit)]);
+            }
+            LeafOp::Reset => {
+                let qubit = qubit(node, &inputs, 0)?;
+                out.push(Instr::Reset { qubit });
+                return Ok(inputs);
+            }
+            LeafOp::Noop { ty } if *ty != SimpleType::Qubit => return Ok(inputs),
+            LeafOp::Lift { .. } => return Ok(inputs),
+            LeafOp::ZZMax => (SmolStr::new_inline("rzz"), vec![PI / 2.0]),
+            LeafOp::CustomOp(ext) => {
+                let op = OpaqueOp::from(ext.clone());
+                if *op.resource() != resource_id() {
+                    return Err(self.unsupported(node, leaf.name()));
+                }
+                match op.args() {
+                    [TypeArg::Int(value)] if op.name() == CREG_EQ => {
+                        return Ok(vec![Val::Condition(inputs, *value)]);
+                    }
+                    [] => (op.name().clone(), vec![]),
+                    _ => return Err(self.unsupported(node, leaf.name())),
+                }
+            }
+            _ => match gate_name(leaf) {
+                Some(name) => (SmolStr::new_inline(name), vec![]),
+                None => return Err(self.unsupported(node, leaf.name())),
+            },
+        };
+
+        let mut qubits = Vec::new();
+        for (i, v) in inputs.into_iter().enumerate() {
+            match v {
+                Val::Qubit(q) => qubits.push(q),
+                Val::Angle(a) => params.push(a),
+                _ => {
+                    return Err(QasmExportError::UnsupportedValue {
+                        node,
+                        port: Port::new_incoming(i),
+                    })
+                }
+            }
+        }
+        let arity_matches =
+            standard_gate(&name, true).map_or(true, |arity| arity == (params.len(), qubits.len()));
+        let available = match self.version {
+            QasmVersion::V2 => true,
+            QasmVersion::V3 => STDGATES.contains(&name.as_str()) || name == "rzz",
+        };
+        if !arity_matches || !available {
+            return Err(self.unsupported(node, leaf.name()));
+        }
+        out.push(Instr::Gate {
+            name,
+            params,
+            qubits: qubits.clone(),
+        });
+        Ok(qubits.into_iter().map(Val::Qubit).collect())
+    }
+
+    /// Emits a conditional as an `if` statement.
+    fn conditional(
+        &mut self,
+        node: Node,
+        inputs: Vec<Val>,
+        out: &mut Vec<Instr>,
+    ) -> Result<Vec<Val>, QasmExportError> {
+        let h = self.h;
+        let unsupported = |reason: &str| QasmExportError::UnsupportedConditional {
+            node,
+            reason: reason.into(),
+        };
+        let OpType::Conditional(cond) = h.get_optype(node) else {
+            panic!("Expected a conditional node")
+        };
+        if cond.predicate_inputs.len() != 2 || cond.predicate_inputs.iter().any(|r| !r.is_empty()) {
+            return Err(unsupported("the predicate must have two unit variants"));
+        }
+        let (pred, others) = inputs.split_first().unwrap();
+        let Val::Condition(compared, value) = pred else {
+            return Err(unsupported(
+                "the predicate must be computed by a creg_eq operation",
+            ));
+        };
+
+        // Constant bits are left out of the comparison, which may then always
+        // fail.
+        let mut bits = Vec::new();
+        let mut reg_value = 0;
+        let mut possible = value.checked_shr(compared.len() as u32).unwrap_or(0) == 0;
+        for (i, b) in compared.iter().enumerate() {
+            let set = value.checked_shr(i as u32).unwrap_or(0) & 1 == 1;
+            match b {
+                Val::Zero => possible &= !set,
+                Val::Bit(bit) if !bits.contains(bit) => {
+                    reg_value |= (set as u128) << bits.len();
+                    bits.push(*bit);
+                }
+                _ => return Err(unsupported("the compared bits must be distinct")),
+            }
+        }
+
+        let mut cases = h.children(node);
+        let (case0, case1) = (cases.next().unwrap(), cases.next().unwrap());
+        let mut io0 = h.children(case0);
+        let (input0, output0) = (io0.next().unwrap(), io0.next().unwrap());
+        let identity = (0..others.len()).all(|i| {
+            h.linked_ports(output0, Port::new_incoming(i)).next()
+                == Some((input0, Port::new_outgoing(i)))
+        });
+        if !identity {
+            return Err(unsupported("the first case must pass its inputs through"));
+        }
+        if !possible {
+            return Ok(others.to_vec());
+        }
+
+        // Measurements whose results are outputs of the second case write to
+        // the bit they replace, so that it is unchanged if the case is not
+        // taken.
+        let output1 = h.children(case1).nth(1).unwrap();
+        let mut replaced = HashMap::new();
+        for (i, v) in others.iter().enumerate() {
+            let Some((src, src_port)) = h.linked_ports(output1, Port::new_incoming(i)).next()
+            else {
+                continue;
+            };
+            if src_port.index() != 1
+                || !matches!(h.get_optype(src), OpType::LeafOp(LeafOp::Measure))
+            {
+                continue;
+            }
+            let bit = match v {
+                Val::Bit(bit) => {
+                    // The old value of the bit is lost, so it must not be
+                    // used later.
+                    let (prev, prev_port) = h
+                        .linked_ports(node, Port::new_incoming(i + 1))
+                        .next()
+                        .unwrap();
+                    if h.linked_ports(prev, prev_port)
+                        .any(|(n, _)| n != node && !self.visited.contains(&n))
+                    {
+                        return Err(unsupported("an overwritten bit is used afterwards"));
+                    }
+                    *bit
+                }
+                Val::Zero => self.fresh_bit(),
+                _ => continue,
+            };
+            if self.measure_bits.insert(src, bit).is_some() {
+                return Err(unsupported("a measurement result is output more than once"));
+            }
+            replaced.insert(i, bit);
+        }
+
+        let mut body = Vec::new();
+        let results = self.region(case1, others.to_vec(), &mut body)?;
+        let outputs = others
+            .iter()
+            .zip(results)
+            .enumerate()
+            .map(|(i, (before, after))| {
+                if *before == after || matches!(after, Val::Bit(b) if replaced.get(&i) == Some(&b))
+                {
+                    Ok(after)
+                } else {
+                    Err(unsupported(
+                        "the outputs of the second case must replace its inputs",
+                    ))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.version == QasmVersion::V2 {
+            if body.iter().any(|i| matches!(i, Instr::If { .. })) {
+                return Err(unsupported("nested conditionals require OpenQASM 3"));
+            }
+            // Each statement is conditioned separately.
+            let overwritten = body[..body.len().saturating_sub(1)]
+                .iter()
+                .any(|i| matches!(i, Instr::Measure { bit, .. } if bits.contains(bit)));
+            if overwritten {
+                return Err(unsupported(
+                    "a compared bit is overwritten before the last statement",
+                ));
+            }
+        }
+        if bits.is_empty() {
+            out.extend(body);
+        } else if !body.is_empty() {
+            out.push(Instr::If {
+                node,
+                bits,
+                value: reg_value,
+                body,
+            });
+        }
+        Ok(outputs)
+    }
+
+    fn inputs(&self, node: Node) -> Result<Vec<Val>, QasmExportError> {
+        let count = self.h.get_optype(node).signature().input.len();
+        (0..count)
+            .map(|i| {
+                let port = Port::new_incoming(i);
+                self.h
+                    .linked_ports(node, port)
+                    .next()
+                    .and_then(|src| self.values.get(&src))
+                    .cloned()
+                    .ok_or(QasmExportError::UnsupportedValue { node, port })
+            })
+            .collect()
+    }
+
+    fn set_outputs(&mut self, node: Node, values: Vec<Val>) {
+        for (i, v) in values.into_iter().enumerate() {
+            self.values.insert((node, Port::new_outgoing(i)), v);
+        }
+    }
+
+    fn fresh_bit(&mut self) -> usize {
+        self.n_bits += 1;
+        self.n_bits - 1
+    }
+
+    fn unsupported(&self, node: Node, name: SmolStr) -> QasmExportError {
+        QasmExportError::UnsupportedOp {
+            node,
+            name,
+            version: self.version,
+        }
+    }
+}
+
+/// The qubit of an input of a node.
+fn qubit(node: Node, inputs: &[Val], index: usize) -> Result<usize, QasmExportError> {
+    match inputs.get(index) {
+        Some(Val::Qubit(q)) => Ok(*q),
+        _ => Err(QasmExportError::UnsupportedValue {
+            node,
+            port: Port::new_incoming(index),
+        }),
+    }
+}
+
+/// Prints statements, with the classical bits laid out in registers.
+struct Printer {
+    version: QasmVersion,
+    /// The names and bits of the classical registers.
+    registers: Vec<(String, Vec<usize>)>,
+    /// The register and index of each bit.
+    locations: HashMap<usize, (usize, usize)>,
+}
+
+impl Printer {
+    /// Lays out the bits, in the given order apart from those compared by
+    /// `if` statements.
+    fn new(
+        version: QasmVersion,
+        bit_order: &[usize],
+        instrs: &[Instr],
+    ) -> Result<Self, QasmExportError> {
+        let mut printer = Self {
+            version,
+            registers: Vec::new(),
+            locations: HashMap::new(),
+        };
+        for instr in instrs {
+            printer.add_condition_registers(instr)?;
+        }
+        let rest = bit_order
+            .iter()
+            .copied()
+            .filter(|b| !printer.locations.contains_key(b))
+            .collect::<Vec<_>>();
+        if !rest.is_empty() {
+            printer.add_register("c".into(), rest);
+        }
+        Ok(printer)
+    }
+
+    fn add_condition_registers(&mut self, instr: &Instr) -> Result<(), QasmExportError> {
+        let Instr::If {
+            node, bits, body, ..
+        } = instr
+        else {
+            return Ok(());
+        };
+        match self.locations.get(&bits[0]) {
+            Some(&(reg, _)) if self.registers[reg].1 == *bits => {}
+            None if bits.iter().all(|b| !self.locations.contains_key(b)) => {
+                let name = format!("c{}", self.registers.len());
+                self.add_register(name, bits.clone());
+            }
+            _ => {
+                return Err(QasmExportError::UnsupportedConditional {
+                    node: *node,
+                    reason: "the compared bits overlap those of another comparison".into(),
+                })
+            }
+        }
+        body.iter()
+            .try_for_each(|instr| self.add_condition_registers(instr))
+    }
+
+    fn add_register(&mut self, name: String, bits: Vec<usize>) {
+        let reg = self.registers.len();
+        for (i, &b) in bits.iter().enumerate() {
+            self.locations.insert(b, (reg, i));
+        }
+        self.registers.push((name, bits));
+    }
+
+    fn print(&self, n_qubits: usize, instrs: &[Instr]) -> String {
+        let mut gates = BTreeMap::new();
+        collect_gates(instrs, &mut gates);
+        let mut s = format!("OPENQASM {};\n", self.version);
+        match self.version {
+            QasmVersion::V2 => {
+                s += "include \"qelib1.inc\";\n";
+                for (name, (n_params, n_qubits)) in gates {
+                    if standard_gate(name, true).is_some() {
+                        continue;
+                    }
+                    let params = (0..n_params).map(|i| format!("p{i}")).collect::<Vec<_>>();
+                    let qubits = (0..n_qubits).map(|i| format!("a{i}")).collect::<Vec<_>>();
+                    s += &format!("opaque {name}");
+                    if !params.is_empty() {
+                        s += &format!("({})", params.join(", "));
+                    }
+                    s += &format!(" {};\n", qubits.join(", "));
+                }
+                if n_qubits > 0 {
+                    s += &format!("qreg q[{n_qubits}];\n");
+                }
+                for (name, bits) in &self.registers {
+                    s += &format!("creg {name}[{}];\n", bits.len());
+                }
+            }
+            QasmVersion::V3 => {
+                s += "include \"stdgates.inc\";\n";
+                if gates.contains_key("rzz") {
+                    s += RZZ_DEFINITION;
+                }
+                if n_qubits > 0 {
+                    s += &format!("qubit[{n_qubits}] q;\n");
+                }
+                for (name, bits) in &self.registers {
+                    s += &format!("bit[{}] {name};\n", bits.len());
+                }
+            }
+        }
+        for instr in instrs {
+            self.instr(&mut s, instr, "");
+        }
+        s
+    }
+
+    fn instr(&self, s: &mut String, instr: &Instr, prefix: &str) {
+        match instr {
+            Instr::Gate {
+                name,
+                params,
+                qubits,
+            } => {
+                *s += &format!("{prefix}{name}");
+                if !params.is_empty() {
+                    let params = params.iter().map(|&p| angle(p)).collect::<Vec<_>>();
+                    *s += &format!("({})", params.join(", "));
+                }
+                let qubits = qubits.iter().map(|q| format!("q[{q}]")).collect::<Vec<_>>();
+                *s += &format!(" {};\n", qubits.join(", "));
+            }
+            Instr::Measure { qubit, bit } => {
+                let bit = self.bit(*bit);
+                *s += &match self.version {
+                    QasmVersion::V2 => format!("{prefix}measure q[{qubit}] -> {bit};\n"),
+                    QasmVersion::V3 => format!("{prefix}{bit} = measure q[{qubit}];\n"),
+                };
+            }
+            Instr::Reset { qubit } => *s += &format!("{prefix}reset q[{qubit}];\n"),
+            Instr::If {
+                bits, value, body, ..
+            } => {
+                let reg = &self.registers[self.locations[&bits[0]].0].0;
+                let condition = format!("if ({reg} == {value})");
+                match self.version {
+                    QasmVersion::V2 => {
+                        for instr in body {
+                            self.instr(s, instr, &format!("{prefix}{condition} "));
+                        }
+                    }
+                    QasmVersion::V3 => {
+                        *s += &format!("{prefix}{condition} {{\n");
+                        for instr in body {
+                            self.instr(s, instr, &format!("{prefix}  "));
+                        }
+                        *s += &format!("{prefix}}}\n");
+                    }
+                }
+            }
+        }
+    }
+
+    fn bit(&self, bit: usize) -> String {
+        let (reg, index) = self.locations[&bit];
+        format!("{}[{index}]", self.registers[reg].0)
+    }
+}
+
+/// The names of the gates in some statements, with their number of
+/// parameters and qubits.
+fn collect_gates<'i>(instrs: &'i [Instr], gates: &mut BTreeMap<&'i str, (usize, usize)>) {
+    for instr in instrs {
+        match instr {
+            Instr::Gate {
+                name,
+                params,
+                qubits,
+            } => {
+                gates.insert(name.as_str(), (params.len(), qubits.len()));
+            }
+            Instr::If { body, .. } => collect_gates(body, gates),
+            _ => {}
+        }
+    }
+}
+
+/// Formats a finite angle, as a simple fraction of pi if possible.
+fn angle(a: f64) -> String {
+    if a == 0.0 {
+        return "0".into();
+    }
+    for denom in 1..=16 {
+        let multiple = a * denom as f64 / PI;
+        // Beyond 2^53 every float is an integer, but not an exact multiple.
+        if multiple.abs() > (1u64 << 53) as f64 || (multiple - multiple.round()).abs() > 1e-12 {
+            continue;
+        }
+        let numer = match multiple.round() as i64 {
+            1 => "pi".to_string(),
+            -1 => "-pi".to_string(),
+            n => format!("{n}*pi"),
+        };
+        return match denom {
+            1 => numer,
+            _ => format!("{numer}/{denom}"),
+        };
+    }
+    format!("{a}")
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+
+    use super::*;
+    use crate::builder::{DFGBuilder, Dataflow, DataflowHugr, SubContainer};
+    use crate::ops::handle::NodeHandle;
+    use crate::ops::Const;
+    use crate::qasm::import_qasm;
+    use crate::type_row;
+    use crate::types::AbstractSignature;
+
+    const QB: SimpleType = SimpleType::Qubit;
+
+    #[test]
+    fn bell() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[1];
+            h q[0];
+            cx q[0], q[1];
+            rz(-pi/4) q[1];
+            measure q[1] -> c[0];
+            "#,
+        )?;
+        assert_eq!(
+            export_qasm(&h, h.root(), QasmVersion::V2)?,
+            "OPENQASM 2.0;\n\
+             include \"qelib1.inc\";\n\
+             qreg q[2];\n\
+             creg c[1];\n\
+             h q[0];\n\
+             cx q[0], q[1];\n\
+             rz(-pi/4) q[1];\n\
+             measure q[1] -> c[0];\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            opaque magic(a) p, q;
+            qreg q[3];
+            creg c[2];
+            creg d[1];
+            u3(pi, 0, 0.5) q[0];
+            magic(1.25) q[0], q[2];
+            id q[1];
+            measure q[0] -> c[0];
+            measure q[2] -> c[1];
+            if (c == 2) x q[1];
+            if (c == 2) measure q[1] -> d[0];
+            reset q[0];
+            "#,
+        )?;
+        let exported = export_qasm(&h, h.root(), QasmVersion::V2)?;
+        assert!(exported.contains("opaque magic(p0) a0, a1;\n"));
+        assert!(exported.contains("u3(pi, 0, 0.5) q[0];\n"));
+        assert!(exported.contains("creg c0[2];\n"));
+        assert!(exported.contains("if (c0 == 2) x q[1];\n"));
+        assert!(exported.contains("if (c0 == 2) measure q[1] -> c[0];\n"));
+
+        let h2 = import_qasm(&exported)?;
+        assert_eq!(export_qasm(&h2, h2.root(), QasmVersion::V2)?, exported);
+        Ok(())
+    }
+
+    #[test]
+    fn register_order() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm("OPENQASM 2.0;\nqreg q[2];\ncreg c[2];\nmeasure q -> c;")?;
+        let exported = export_qasm(&h, h.root(), QasmVersion::V2)?;
+        assert!(exported.contains("creg c[2];\n"));
+        assert!(exported.contains("measure q[0] -> c[0];\n"));
+        assert!(exported.contains("measure q[1] -> c[1];\n"));
+        let h2 = import_qasm(&exported)?;
+        assert_eq!(export_qasm(&h2, h2.root(), QasmVersion::V2)?, exported);
+
+        // The compared register is declared first, but output last.
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[1];
+            creg d[1];
+            measure q[0] -> d[0];
+            if (d == 1) x q[1];
+            "#,
+        )?;
+        assert_eq!(
+            export_qasm(&h, h.root(), QasmVersion::V2),
+            Err(QasmExportError::UnorderedBits(h.root()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn version_3() -> Result<(), Box<dyn std::error::Error>> {
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB],
+        ))?;
+        let [q0, q1] = b.input_wires_arr();
+        let [q0, q1] = b.add_dataflow_op(LeafOp::ZZMax, [q0, q1])?.outputs_arr();
+        let angle = b.add_load_const(Const::new(ConstValue::F64(0.1), ClassicType::F64)?)?;
+        let [q1] = b.add_dataflow_op(LeafOp::RzF64, [q1, angle])?.outputs_arr();
+        let h = b.finish_hugr_with_outputs([q0, q1])?;
+        assert_eq!(
+            export_qasm(&h, h.root(), QasmVersion::V3)?,
+            format!(
+                "OPENQASM 3.0;\n\
+                 include \"stdgates.inc\";\n\
+                 {RZZ_DEFINITION}\
+                 qubit[2] q;\n\
+                 rzz(pi/2) q[0], q[1];\n\
+                 rz(0.1) q[1];\n"
+            )
+        );
+
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[1];
+            measure q[0] -> c[0];
+            if (c == 1) x q[1];
+            if (c == 1) measure q[1] -> c[0];
+            "#,
+        )?;
+        assert_eq!(
+            export_qasm(&h, h.root(), QasmVersion::V3)?,
+            "OPENQASM 3.0;\n\
+             include \"stdgates.inc\";\n\
+             qubit[2] q;\n\
+             bit[1] c0;\n\
+             c0[0] = measure q[0];\n\
+             if (c0 == 1) {\n  x q[1];\n}\n\
+             if (c0 == 1) {\n  c0[0] = measure q[1];\n}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn angles() {
+        assert_eq!(angle(-PI / 4.0), "-pi/4");
+        assert_eq!(angle(3.0 * PI), "3*pi");
+        assert_eq!(angle(0.5), "0.5");
+        assert_eq!(angle(1e300), format!("{}", 1e300));
+    }
+
+    #[test]
+    fn errors() -> Result<(), Box<dyn std::error::Error>> {
+        let bit = SimpleType::Classic(ClassicType::bit());
+        let b = DFGBuilder::new(AbstractSignature::new_df(vec![QB, bit], type_row![QB]))?;
+        let [q, _] = b.input_wires_arr();
+        let h = b.finish_hugr_with_outputs([q])?;
+        assert_eq!(
+            export_qasm(&h, h.root(), QasmVersion::V2),
+            Err(QasmExportError::NonQubitInput {
+                node: h.root(),
+                index: 1
+            })
+        );
+
+        // The qubits are swapped by the region.
+        let b = DFGBuilder::new(AbstractSignature::new_df(
+            type_row![QB, QB],
+            type_row![QB, QB],
+        ))?;
+        let [q0, q1] = b.input_wires_arr();
+        let h = b.finish_hugr_with_outputs([q1, q0])?;
+        assert_eq!(
+            export_qasm(&h, h.root(), QasmVersion::V2),
+            Err(QasmExportError::PermutedOutputs(h.root()))
+        );
+
+        let mut b = DFGBuilder::new(AbstractSignature::new_df(type_row![QB], type_row![QB]))?;
+        let [q] = b.input_wires_arr();
+        let mut cfg = b.cfg_builder([(QB, q)], type_row![QB])?;
+        let mut entry = cfg.simple_entry_builder(type_row![QB], 1)?;
+        let [q] = entry.input_wires_arr();
+        let pred = entry.add_load_const(Const::simple_unary_predicate())?;
+        let entry = entry.finish_with_outputs(pred, [q])?;
+        let exit = cfg.exit_block();
+        cfg.branch(&entry, 0, &exit)?;
+        let cfg = cfg.finish_sub_container()?;
+        let h = b.finish_hugr_with_outputs(cfg.outputs())?;
+        assert_matches!(
+            export_qasm(&h, h.root(), QasmVersion::V2),
+            Err(QasmExportError::UnsupportedOp { node, name, .. }) => {
+                assert_eq!(node, cfg.node());
+                assert_eq!(name, "CFG");
+            }
+        );
+
+        let h = import_qasm("OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[1];\nu0(1) q[0];")?;
+        assert!(export_qasm(&h, h.root(), QasmVersion::V2).is_ok());
+        assert_matches!(
+            export_qasm(&h, h.root(), QasmVersion::V3),
+            Err(QasmExportError::UnsupportedOp { name, version: QasmVersion::V3, .. }) => {
+                assert_eq!(name, "qasm.u0");
+            }
+        );
+
+        for value in [f64::INFINITY, f64::NAN] {
+            let mut b = DFGBuilder::new(AbstractSignature::new_df(type_row![QB], type_row![QB]))?;
+            let [q] = b.input_wires_arr();
+            let angle = b.add_load_const(Const::new(ConstValue::F64(value), ClassicType::F64)?)?;
+            let [q] = b.add_dataflow_op(LeafOp::RzF64, [q, angle])?.outputs_arr();
+            let h = b.finish_hugr_with_outputs([q])?;
+            assert_eq!(
+                export_qasm(&h, h.root(), QasmVersion::V2),
+                Err(QasmExportError::UnsupportedConstant(angle.node()))
+            );
+        }
+
+        // The second comparison is on a register made of a compared bit and
+        // a new one.
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[2];
+            measure q -> c;
+            if (c == 1) x q[0];
+            measure q[1] -> c[1];
+            if (c == 1) x q[0];
+            "#,
+        )?;
+        assert_matches!(
+            export_qasm(&h, h.root(), QasmVersion::V2),
+            Err(QasmExportError::UnsupportedConditional { .. })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1228 @@
+//! Import of OpenQASM 2 programs.
+//!
+//! A program is converted to a dataflow region whose inputs are the qubits of
+//! its quantum registers, in declaration order, and whose outputs are the
+//! same qubits followed by the bits of its classical registers. Classical
+//! bits start as zero.
+//!
+//! Gates defined with `gate` are inlined, `opaque` gates and standard gates
+//! without a [`LeafOp`] counterpart become [`OpaqueOp`]s (see the
+//! [module documentation](super)), `barrier`s are dropped and `if` statements
+//! become [`Conditional`]s.
+//!
+//! [`Conditional`]: crate::ops::Conditional
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::f64::consts::PI;
+
+use thiserror::Error;
+
+use crate::builder::{
+    BuildError, Container, DFGBuilder, Dataflow, DataflowHugr, DataflowSubContainer, HugrBuilder,
+    ModuleBuilder, SubContainer,
+};
+use crate::hugr::Wire;
+use crate::ops::custom::{ExternalOp, OpaqueOp};
+use crate::ops::{Const, ConstValue, LeafOp};
+use crate::types::type_param::TypeArg;
+use crate::types::{AbstractSignature, ClassicType, SimpleRow, SimpleType};
+use crate::{type_row, Hugr};
+
+use super::{gate_op, resource_id, standard_gate, CREG_EQ};
+
+const QB: SimpleType = SimpleType::Qubit;
+const BIT: SimpleType = SimpleType::Classic(ClassicType::bit());
+const F64: SimpleType = SimpleType::Classic(ClassicType::F64);
+
+/// The largest total size of the registers of an imported program, so that
+/// the boundary of the Hugr fits in the ports of a node.
+pub const MAX_REGISTERS_SIZE: usize = 1 << 15;
+
+/// Imports an OpenQASM 2 program as a DFG-rooted Hugr.
+pub fn import_qasm(source: &str) -> Result<Hugr, QasmImportError> {
+    let program = Parser::new(source)?.parse_program()?;
+    let mut builder = DFGBuilder::new(program.signature())?;
+    let outputs = program.build(&mut builder)?;
+    Ok(builder.finish_hugr_with_outputs(outputs)?)
+}
+
+/// Imports an OpenQASM 2 program as a function of a module Hugr.
+pub fn import_qasm_function(
+    source: &str,
+    name: impl Into<String>,
+) -> Result<Hugr, QasmImportError> {
+    let program = Parser::new(source)?.parse_program()?;
+    let mut module = ModuleBuilder::new();
+    let mut func = module.define_function(name, program.signature().pure())?;
+    let outputs = program.build(&mut func)?;
+    func.finish_with_outputs(outputs)?;
+    Ok(module.finish_hugr().map_err(BuildError::InvalidHUGR)?)
+}
+
+/// A gate application, measurement or reset, on qubits and bits given by
+/// their global index.
+#[derive(Clone, Debug, PartialEq)]
+enum Op {
+    Gate {
+        name: String,
+        params: Vec<f64>,
+        qubits: Vec<usize>,
+    },
+    Measure {
+        qubit: usize,
+        bit: usize,
+    },
+    Reset {
+        qubit: usize,
+    },
+}
+
+/// A statement of the program, after inlining gate definitions and expanding
+/// register arguments.
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    Op(Op),
+    /// Operations applied if the bits hold the value.
+    If {
+        bits: Vec<usize>,
+        value: u128,
+        ops: Vec<Op>,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+struct Program {
+    n_qubits: usize,
+    n_bits: usize,
+    items: Vec<Item>,
+}
+
+impl Program {
+    fn signature(&self) -> AbstractSignature {
+        let qubits = vec![QB; self.n_qubits];
+        let bits = vec![BIT; self.n_bits];
+        AbstractSignature::new_df(qubits.clone(), [qubits, bits].concat())
+    }
+
+    /// Adds the operations of the program to a dataflow region, returning its
+    /// outputs.
+    fn build(&self, builder: &mut impl Dataflow) -> Result<Vec<Wire>, BuildError> {
+        let mut wires = Wires {
+            qubits: builder.input_wires().enumerate().collect(),
+            bits: BTreeMap::new(),
+        };
+        if self.n_bits > 0 {
+            let zero = builder.add_load_const(Const::int::<1>(0).unwrap())?;
+            wires.bits = (0..self.n_bits).map(|i| (i, zero)).collect();
+        }
+        for item in &self.items {
+            match item {
+                Item::Op(op) => wires.apply(builder, op)?,
+                Item::If { bits, value, ops } => wires.apply_if(builder, bits, *value, ops)?,
+            }
+        }
+        Ok(wires
+            .qubits
+            .into_values()
+            .chain(wires.bits.into_values())
+            .collect())
+    }
+}
+
+/// The current wires of (some of) the qubits and bits of a program.
+struct Wires {
+    qubits: BTreeMap<usize, Wire>,
+    bits: BTreeMap<usize, Wire>,
+}
+
+impl Wires {
+    fn apply(&mut self, builder: &mut impl Dataflow, op: &Op) -> Result<(), BuildError> {
+        match op {
+            Op::Gate {
+                name,
+                params,
+                qubits,
+            } => {
+                let mut inputs = qubits.iter().map(|q| self.qubits[q]).collect::<Vec<_>>();
+                for &p in params {
+                    let angle = Const::new(ConstValue::F64(p), ClassicType::F64).unwrap();
+                    inputs.push(builder.add_load_const(angle)?);
+                }
+                let op = gate_op(name).unwrap_or_else(|| {
+                    let signature = AbstractSignature::new_df(
+                        [vec![QB; qubits.len()], vec![F64; params.len()]].concat(),
+                        vec![QB; qubits.len()],
+                    );
+                    let opaque = OpaqueOp::new(
+                        resource_id(),
+                        name.as_str(),
+                        format!("OpenQASM gate {name}"),
+                        [],
+                        Some(signature),
+                    );
+                    LeafOp::CustomOp(ExternalOp::Opaque(opaque))
+                });
+                let outputs = builder.add_dataflow_op(op, inputs)?.outputs();
+                for (q, w) in qubits.iter().zip(outputs) {
+                    self.qubits.insert(*q, w);
+                }
+            }
+            Op::Measure { qubit, bit } => {
+                let [q, b] = builder
+                    .add_dataflow_op(LeafOp::Measure, [self.qubits[qubit]])?
+                    .outputs_arr();
+                self.qubits.insert(*qubit, q);
+                self.bits.insert(*bit, b);
+            }
+            Op::Reset { qubit } => {
+                let [q] = builder
+                    .add_dataflow_op(LeafOp::Reset, [self.qubits[qubit]])?
+                    .outputs_arr();
+                self.qubits.insert(*qubit, q);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies operations in a [`Conditional`] on the value of some bits.
+    ///
+    /// [`Conditional`]: crate::ops::Conditional
+    fn apply_if(
+        &mut self,
+        builder: &mut impl Dataflow,
+        bits: &[usize],
+        value: u128,
+        ops: &[Op],
+    ) -> Result<(), BuildError> {
+        let pred_type = SimpleType::new_simple_predicate(2);
+        let eq = OpaqueOp::new(
+            resource_id(),
+            CREG_EQ,
+            "Compare a classical register to an integer".into(),
+            [TypeArg::Int(value)],
+            Some(AbstractSignature::new_df(
+                vec![BIT; bits.len()],
+                vec![pred_type],
+            )),
+        );
+        let [pred] = builder
+            .add_dataflow_op(
+                LeafOp::CustomOp(ExternalOp::Opaque(eq)),
+                bits.iter().map(|b| self.bits[b]),
+            )?
+            .outputs_arr();
+
+        // The qubits and bits used by the operations are passed through the
+        // conditional.
+        let mut qubits = BTreeSet::new();
+        let mut touched_bits = BTreeSet::new();
+        for op in ops {
+            match op {
+                Op::Gate { qubits: qs, .. } => qubits.extend(qs),
+                Op::Measure { qubit, bit } => {
+                    qubits.insert(*qubit);
+                    touched_bits.insert(*bit);
+                }
+                Op::Reset { qubit } => {
+                    qubits.insert(*qubit);
+                }
+            }
+        }
+        let types: SimpleRow = [vec![QB; qubits.len()], vec![BIT; touched_bits.len()]]
+            .concat()
+            .into();
+        let inputs = qubits
+            .iter()
+            .map(|q| self.qubits[q])
+            .chain(touched_bits.iter().map(|b| self.bits[b]));
+        let mut cond = builder.conditional_builder(
+            ([type_row![], type_row![]], pred),
+            types.iter().cloned().zip(inputs),
+            types.clone(),
+        )?;
+        for case in 0..2 {
+            let mut case_b = cond.case_builder(case)?;
+            let mut case_inputs = case_b.input_wires();
+            let mut local = Wires {
+                qubits: qubits
+                    .iter()
+                    .map(|&q| (q, case_inputs.next().unwrap()))
+                    .collect(),
+                bits: touched_bits
+                    .iter()
+                    .map(|&b| (b, case_inputs.next().unwrap()))
+                    .collect(),
+            };
+            if case == 1 {
+                for op in ops {
+                    local.apply(&mut case_b, op)?;
+                }
+            }
+            let outputs = local.qubits.into_values().chain(local.bits.into_values());
+            case_b.finish_with_outputs(outputs)?;
+        }
+        let mut outputs = cond.finish_sub_container()?.outputs();
+        for q in qubits {
+            self.qubits.insert(q, outputs.next().unwrap());
+        }
+        for b in touched_bits {
+            self.bits.insert(b, outputs.next().unwrap());
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can occur when importing an OpenQASM program.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum QasmImportError {
+    /// The program is not syntactically valid.
+    #[error("Line {line}: {message}")]
+    Syntax {
+        /// The line of the error.
+        line: usize,
+        /// A description of the error.
+        message: String,
+    },
+    /// The program uses an OpenQASM version or include file that is not
+    /// supported.
+    #[error("Line {line}: unsupported {feature}")]
+    Unsupported {
+        /// The line of the error.
+        line: usize,
+        /// The unsupported feature.
+        feature: String,
+    },
+    /// A gate is used without being defined.
+    #[error("Line {line}: unknown gate {name}")]
+    UnknownGate {
+        /// The line of the error.
+        line: usize,
+        /// The name of the gate.
+        name: String,
+    },
+    /// A register or gate argument is used without being declared.
+    #[error("Line {line}: unknown register {name}")]
+    UnknownRegister {
+        /// The line of the error.
+        line: usize,
+        /// The name of the register.
+        name: String,
+    },
+    /// A register is declared twice, or with an unsupported size.
+    #[error("Line {line}: invalid register {name}: {message}")]
+    InvalidRegister {
+        /// The line of the error.
+        line: usize,
+        /// The name of the register.
+        name: String,
+        /// A description of the error.
+        message: String,
+    },
+    /// A gate or operation is applied to the wrong number or kind of
+    /// arguments.
+    #[error("Line {line}: invalid arguments for {name}: {message}")]
+    InvalidArguments {
+        /// The line of the error.
+        line: usize,
+        /// The name of the gate or operation.
+        name: String,
+        /// A description of the error.
+        message: String,
+    },
+    /// The Hugr could not be built.
+    #[error("Error building the Hugr: {0}")]
+    Build(Box<BuildError>),
+}
+
+impl From<BuildError> for QasmImportError {
+    fn from(e: BuildError) -> Self {
+        Self::Build(Box::new(e))
+    }
+}
+
+/// A lexical token, with the line it starts on.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(u128),
+    Real(f64),
+    Str(String),
+    /// A punctuation or operator symbol, including `->` and `==`.
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 15] = [
+    "->", "==", ";", ",", "(", ")", "[", "]", "{", "}", "+", "-", "*", "/", "^",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, QasmImportError> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let syntax = |message: String| QasmImportError::Syntax { line, message };
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |i| &rest[i..]);
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push((Token::Ident(rest[..end].to_string()), line));
+            rest = &rest[end..];
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let mut end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            // Exponent, as in 1e-3.
+            if rest[end..].starts_with(['e', 'E']) {
+                let exp = &rest[end + 1..];
+                let sign = usize::from(exp.starts_with(['+', '-']));
+                let digits = exp[sign..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(exp.len() - sign);
+                if digits > 0 {
+                    end += 1 + sign + digits;
+                }
+            }
+            let text = &rest[..end];
+            let token = match text.parse::<u128>() {
+                Ok(i) => Token::Int(i),
+                Err(_) => Token::Real(
+                    text.parse()
+                        .map_err(|_| syntax(format!("invalid number {text}")))?,
+                ),
+            };
+            tokens.push((token, line));
+            rest = &rest[end..];
+        } else if c == '"' {
+            let end = rest[1..]
+                .find('"')
+                .ok_or_else(|| syntax("unterminated string".into()))?;
+            tokens.push((Token::Str(rest[1..end + 1].to_string()), line));
+            rest = &rest[end + 2..];
+        } else if let Some(s) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push((Token::Symbol(s), line));
+            rest = &rest[s.len()..];
+        } else {
+            return Err(syntax(format!("unexpected character {c:?}")));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A parameter expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f64),
+    Param(String),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, params: &HashMap<String, f64>) -> Option<f64> {
+        Some(match self {
+            Expr::Number(x) => *x,
+            Expr::Param(p) => *params.get(p)?,
+            Expr::Neg(e) => -e.eval(params)?,
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(params)?, r.eval(params)?);
+                match *op {
+                    "+" => l + r,
+                    "-" => l - r,
+                    "*" => l * r,
+                    "/" => l / r,
+                    _ => l.powf(r),
+                }
+            }
+            Expr::Call(f, e) => {
+                let x = e.eval(params)?;
+                match f.as_str() {
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "tan" => x.tan(),
+                    "exp" => x.exp(),
+                    "ln" => x.ln(),
+                    _ => x.sqrt(),
+                }
+            }
+        })
+    }
+}
+
+/// A gate argument: a register, or one of its elements.
+#[derive(Clone, Debug, PartialEq)]
+struct Arg {
+    name: String,
+    index: Option<usize>,
+}
+
+/// A gate application, before expansion.
+#[derive(Clone, Debug, PartialEq)]
+struct GateCall {
+    name: String,
+    params: Vec<Expr>,
+    args: Vec<Arg>,
+}
+
+/// A gate definition. Opaque gates have no body.
+#[derive(Clone, Debug)]
+struct GateDef {
+    params: Vec<String>,
+    qubits: Vec<String>,
+    body: Option<Vec<GateCall>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Register {
+    offset: usize,
+    size: usize,
+    quantum: bool,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    qelib: bool,
+    registers: HashMap<String, Register>,
+    gates: HashMap<String, GateDef>,
+    program: Program,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, QasmImportError> {
+        Ok(Self {
+            tokens: tokenize(source)?,
+            pos: 0,
+            qelib: false,
+            registers: HashMap::new(),
+            gates: HashMap::new(),
+            program: Program::default(),
+        })
+    }
+
+    /// The line of the current token.
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn syntax_error<T>(&self, message: impl Into<String>) -> Result<T, QasmImportError> {
+        Err(QasmImportError::Syntax {
+            line: self.line(),
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<Token, QasmImportError> {
+        match self.tokens.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => self.syntax_error("unexpected end of input"),
+        }
+    }
+
+    /// Consumes the symbol if it is next.
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), QasmImportError> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => self.syntax_error(format!("expected {symbol:?}")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, QasmImportError> {
+        match self.next()? {
+            Token::Ident(id) => Ok(id),
+            t => {
+                self.pos -= 1;
+                self.syntax_error(format!("expected an identifier, found {t:?}"))
+            }
+        }
+    }
+
+    fn int(&mut self) -> Result<u128, QasmImportError> {
+        match self.next()? {
+            Token::Int(i) => Ok(i),
+            t => {
+                self.pos -= 1;
+                self.syntax_error(format!("expected an integer, found {t:?}"))
+            }
+        }
+    }
+
+    /// Parses a comma-separated list, ending with `end` (which is consumed).
+    fn list<T>(
+        &mut self,
+        end: &str,
+        mut item: impl FnMut(&mut Self) -> Result<T, QasmImportError>,
+    ) -> Result<Vec<T>, QasmImportError> {
+        let mut items = vec![];
+        if self.eat(end) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat(end) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn parse_program(mut self) -> Result<Program, QasmImportError> {
+        let line = self.line();
+        match (self.ident().ok().as_deref(), self.next()?) {
+            (Some("OPENQASM"), Token::Real(2.0)) => {}
+            (Some("OPENQASM"), Token::Real(v)) => {
+                return Err(QasmImportError::Unsupported {
+                    line,
+                    feature: format!("OpenQASM version {v}"),
+                })
+            }
+            _ => return self.syntax_error("expected an OPENQASM 2.0 header"),
+        }
+        self.expect(";")?;
+        while self.peek().is_some() {
+            self.statement()?;
+        }
+        Ok(self.program)
+    }
+
+    fn statement(&mut self) -> Result<(), QasmImportError> {
+        let line = self.line();
+        match self.ident()?.as_str() {
+            "include" => {
+                let Token::Str(file) = self.next()? else {
+                    return self.syntax_error("expected a file name");
+                };
+                if file != "qelib1.inc" {
+                    return Err(QasmImportError::Unsupported {
+                        line,
+                        feature: format!("include file {file}"),
+                    });
+                }
+                self.qelib = true;
+                self.expect(";")
+            }
+            kw @ ("qreg" | "creg") => {
+                let name = self.ident()?;
+                self.expect("[")?;
+                let size = self.int()?;
+                self.expect("]")?;
+                self.expect(";")?;
+                let invalid = |message: &str| QasmImportError::InvalidRegister {
+                    line,
+                    name: name.clone(),
+                    message: message.into(),
+                };
+                if self.registers.contains_key(&name) {
+                    return Err(invalid("already declared"));
+                }
+                if size == 0 {
+                    return Err(invalid("empty register"));
+                }
+                let total = self.program.n_qubits + self.program.n_bits;
+                let size = usize::try_from(size)
+                    .ok()
+                    .filter(|&size| size <= MAX_REGISTERS_SIZE - total)
+                    .ok_or_else(|| {
+                        invalid(&format!(
+                            "registers larger than {MAX_REGISTERS_SIZE} in total"
+                        ))
+                    })?;
+                let quantum = kw == "qreg";
+                let counter = match quantum {
+                    true => &mut self.program.n_qubits,
+                    false => &mut self.program.n_bits,
+                };
+                let register = Register {
+                    offset: *counter,
+                    size,
+                    quantum,
+                };
+                *counter += size;
+                self.registers.insert(name, register);
+                Ok(())
+            }
+            kw @ ("gate" | "opaque") => self.gate_definition(kw == "opaque"),
+            "if" => {
+                self.expect("(")?;
+                let name = self.ident()?;
+                self.expect("==")?;
+                let value = self.int()?;
+                self.expect(")")?;
+                let register = self.register(&name, false, line)?;
+                let bits = (register.offset..register.offset + register.size).collect();
+                let mut ops = vec![];
+                let keyword = self.ident()?;
+                self.operation(keyword, line, &mut ops)?;
+                self.program.items.push(Item::If { bits, value, ops });
+                Ok(())
+            }
+            keyword => {
+                let mut ops = vec![];
+                self.operation(keyword.to_string(), line, &mut ops)?;
+                self.program.items.extend(ops.into_iter().map(Item::Op));
+                Ok(())
+            }
+        }
+    }
+
+    fn gate_definition(&mut self, opaque: bool) -> Result<(), QasmImportError> {
+        let name = self.ident()?;
+        let params = match self.eat("(") {
+            true => self.list(")", Self::ident)?,
+            false => vec![],
+        };
+        let mut qubits = vec![self.ident()?];
+        while self.eat(",") {
+            qubits.push(self.ident()?);
+        }
+        let body = if opaque {
+            self.expect(";")?;
+            None
+        } else {
+            self.expect("{")?;
+            let mut body = vec![];
+            while !self.eat("}") {
+                let line = self.line();
+                let name = self.ident()?;
+                let call = self.gate_call(name)?;
+                if call.name == "barrier" {
+                    continue;
+                }
+                for arg in &call.args {
+                    if arg.index.is_some() || !qubits.contains(&arg.name) {
+                        return Err(QasmImportError::UnknownRegister {
+                            line,
+                            name: arg.name.clone(),
+                        });
+                    }
+                }
+                // Only previously defined gates can be used, so definitions
+                // cannot be recursive.
+                self.check_gate(&call, line)?;
+                let env = params.iter().map(|p| (p.clone(), 0.0)).collect();
+                if call.params.iter().any(|p| p.eval(&env).is_none()) {
+                    return Err(QasmImportError::InvalidArguments {
+                        line,
+                        name: call.name,
+                        message: "unknown parameter".into(),
+                    });
+                }
+                body.push(call);
+            }
+            Some(body)
+        };
+        self.gates.insert(
+            name,
+            GateDef {
+                params,
+                qubits,
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    /// Parses the parameters and arguments of a gate application.
+    fn gate_call(&mut self, name: String) -> Result<GateCall, QasmImportError> {
+        let params = match self.eat("(") {
+            true => self.list(")", Self::expr)?,
+            false => vec![],
+        };
+        let args = self.list(";", Self::arg)?;
+        Ok(GateCall { name, params, args })
+    }
+
+    fn arg(&mut self) -> Result<Arg, QasmImportError> {
+        let name = self.ident()?;
+        let index = match self.eat("[") {
+            true => {
+                let i = self.int()? as usize;
+                self.expect("]")?;
+                Some(i)
+            }
+            false => None,
+        };
+        Ok(Arg { name, index })
+    }
+
+    fn expr(&mut self) -> Result<Expr, QasmImportError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ("+" | "-"))) => *op,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, QasmImportError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ("*" | "/"))) => *op,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, QasmImportError> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.primary()?;
+        match self.eat("^") {
+            true => Ok(Expr::Binary("^", Box::new(base), Box::new(self.unary()?))),
+            false => Ok(base),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, QasmImportError> {
+        match self.next()? {
+            Token::Int(i) => Ok(Expr::Number(i as f64)),
+            Token::Real(x) => Ok(Expr::Number(x)),
+            Token::Symbol("(") => {
+                let e = self.expr()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Token::Ident(id) if id == "pi" => Ok(Expr::Number(PI)),
+            Token::Ident(f)
+                if matches!(f.as_str(), "sin" | "cos" | "tan" | "exp" | "ln" | "sqrt") =>
+            {
+                self.expect("(")?;
+                let e = self.expr()?;
+                self.expect(")")?;
+                Ok(Expr::Call(f, Box::new(e)))
+            }
+            Token::Ident(p) => Ok(Expr::Param(p)),
+            t => {
+                self.pos -= 1;
+                self.syntax_error(format!("expected an expression, found {t:?}"))
+            }
+        }
+    }
+
+    fn register(
+        &self,
+        name: &str,
+        quantum: bool,
+        line: usize,
+    ) -> Result<Register, QasmImportError> {
+        self.registers
+            .get(name)
+            .filter(|r| r.quantum == quantum)
+            .copied()
+            .ok_or_else(|| QasmImportError::UnknownRegister {
+                line,
+                name: name.to_string(),
+            })
+    }
+
+    /// The indices of the qubits or bits of an argument.
+    fn resolve(
+        &self,
+        arg: &Arg,
+        quantum: bool,
+        line: usize,
+    ) -> Result<Vec<usize>, QasmImportError> {
+        let register = self.register(&arg.name, quantum, line)?;
+        match arg.index {
+            None => Ok((register.offset..register.offset + register.size).collect()),
+            Some(i) if i < register.size => Ok(vec![register.offset + i]),
+            Some(i) => Err(QasmImportError::InvalidArguments {
+                line,
+                name: arg.name.clone(),
+                message: format!("index {i} out of range"),
+            }),
+        }
+    }
+
+    /// Broadcasts arguments over registers, as in `cx q, r;`.
+    fn broadcast(
+        &self,
+        name: &str,
+        args: &[Vec<usize>],
+        line: usize,
+    ) -> Result<Vec<Vec<usize>>, QasmImportError> {
+        let size = args.iter().map(Vec::len).max().unwrap_or(1);
+        if args.iter().any(|a| a.len() != 1 && a.len() != size) {
+            return Err(QasmImportError::InvalidArguments {
+                line,
+                name: name.to_string(),
+                message: "registers of different sizes".into(),
+            });
+        }
+        Ok((0..size)
+            .map(|i| {
+                args.iter()
+                    .map(|a| a[if a.len() == 1 { 0 } else { i }])
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Checks that a gate is defined and has the right number of parameters
+    /// and qubits.
+    fn check_gate(&self, call: &GateCall, line: usize) -> Result<(), QasmImportError> {
+        let (n_params, n_qubits) = match self.gates.get(&call.name) {
+            Some(def) => (def.params.len(), def.qubits.len()),
+            None => standard_gate(&call.name, self.qelib).ok_or_else(|| {
+                QasmImportError::UnknownGate {
+                    line,
+                    name: call.name.clone(),
+                }
+            })?,
+        };
+        if call.params.len() != n_params || call.args.len() != n_qubits {
+            return Err(QasmImportError::InvalidArguments {
+                line,
+                name: call.name.clone(),
+                message: format!(
+                    "expected {n_params} parameters and {n_qubits} qubits, found {} and {}",
+                    call.params.len(),
+                    call.args.len()
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Parses a quantum operation, appending its expansion to `ops`.
+    fn operation(
+        &mut self,
+        keyword: String,
+        line: usize,
+        ops: &mut Vec<Op>,
+    ) -> Result<(), QasmImportError> {
+        match keyword.as_str() {
+            "measure" => {
+                let qubits = self.arg()?;
+                self.expect("->")?;
+                let bits = self.arg()?;
+                self.expect(";")?;
+                let qubits = self.resolve(&qubits, true, line)?;
+                let bits = self.resolve(&bits, false, line)?;
+                if qubits.len() != bits.len() {
+                    return Err(QasmImportError::InvalidArguments {
+                        line,
+                        name: keyword,
+                        message: "registers of different sizes".into(),
+                    });
+                }
+                ops.extend(
+                    qubits
+                        .into_iter()
+                        .zip(bits)
+                        .map(|(qubit, bit)| Op::Measure { qubit, bit }),
+                );
+            }
+            "reset" => {
+                let qubits = self.arg()?;
+                self.expect(";")?;
+                let qubits = self.resolve(&qubits, true, line)?;
+                ops.extend(qubits.into_iter().map(|qubit| Op::Reset { qubit }));
+            }
+            "barrier" => {
+                let args = self.list(";", Self::arg)?;
+                for arg in &args {
+                    self.resolve(arg, true, line)?;
+                }
+            }
+            _ => {
+                let call = self.gate_call(keyword)?;
+                self.check_gate(&call, line)?;
+                let params = call
+                    .params
+                    .iter()
+                    .map(|p| p.eval(&HashMap::new()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| QasmImportError::InvalidArguments {
+                        line,
+                        name: call.name.clone(),
+                        message: "unknown parameter".into(),
+                    })?;
+                let args = call
+                    .args
+                    .iter()
+                    .map(|a| self.resolve(a, true, line))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for qubits in self.broadcast(&call.name, &args, line)? {
+                    if qubits.iter().collect::<BTreeSet<_>>().len() != qubits.len() {
+                        return Err(QasmImportError::InvalidArguments {
+                            line,
+                            name: call.name.clone(),
+                            message: "repeated qubit".into(),
+                        });
+                    }
+                    let start = ops.len();
+                    self.expand(&call.name, params.clone(), qubits, ops);
+                    let finite = ops[start..].iter().all(|op| match op {
+                        Op::Gate { params, .. } => params.iter().all(|p| p.is_finite()),
+                        _ => true,
+                    });
+                    if !finite {
+                        return Err(QasmImportError::InvalidArguments {
+                            line,
+                            name: call.name.clone(),
+                            message: "non-finite parameter".into(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a checked gate application, inlining defined gates.
+    fn expand(&self, name: &str, params: Vec<f64>, qubits: Vec<usize>, ops: &mut Vec<Op>) {
+        let Some(GateDef {
+            params: param_names,
+            qubits: qubit_names,
+            body: Some(body),
+        }) = self.gates.get(name)
+        else {
+            ops.push(Op::Gate {
+                name: name.to_string(),
+                params,
+                qubits,
+            });
+            return;
+        };
+        let env: HashMap<String, f64> = param_names.iter().cloned().zip(params).collect();
+        let qubit_env: HashMap<&String, usize> = qubit_names.iter().zip(qubits).collect();
+        for call in body {
+            // Parameters and arguments were checked with the definition.
+            let params = call.params.iter().map(|p| p.eval(&env).unwrap()).collect();
+            let qubits = call.args.iter().map(|a| qubit_env[&a.name]).collect();
+            self.expand(&call.name, params, qubits, ops);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cool_asserts::assert_matches;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::algorithm::interpret::{EvaluatorRegistry, Value};
+    use crate::algorithm::simulate::{sample, statevector, StateVector};
+    use crate::hugr::HugrView;
+    use crate::ops::{OpName, OpType};
+    use crate::values::HashableValue;
+
+    fn leaf_names(h: &Hugr) -> Vec<String> {
+        h.nodes()
+            .filter_map(|n| match h.get_optype(n) {
+                OpType::LeafOp(op) => Some(op.name().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bell() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[2];
+            h q[0];
+            cx q[0], q[1];
+            barrier q;
+            measure q -> c;
+            "#,
+        )?;
+        assert_eq!(leaf_names(&h), ["H", "CX", "Measure", "Measure"]);
+        let OpType::DFG(dfg) = h.get_optype(h.root()) else {
+            panic!("Expected a DFG root")
+        };
+        assert_eq!(dfg.signature.input.len(), 2);
+        assert_eq!(dfg.signature.output.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn gate_definitions() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            // A flip of both qubits, with a phase.
+            gate flip(theta) a, b {
+                x a;
+                rz(-theta / 2 + pi) b;
+                cx a, b;
+            }
+            qreg q[2];
+            flip(2 * pi) q[0], q[1];
+            "#,
+        )?;
+        assert_eq!(leaf_names(&h), ["X", "RzF64", "CX"]);
+        let angle = h
+            .nodes()
+            .find_map(|n| match h.get_optype(n) {
+                OpType::Const(c) => Some(c.value().clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(angle, ConstValue::F64(0.0));
+        let state = statevector(&h, h.root(), StateVector::new(2))?;
+        assert!(state.equal_up_to_phase(&StateVector::basis(2, 0b11), 1e-10));
+        Ok(())
+    }
+
+    #[test]
+    fn conditional() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[2];
+            creg c[1];
+            creg d[1];
+            h q[0];
+            measure q[0] -> c[0];
+            if (c == 1) x q[1];
+            measure q[1] -> d[0];
+            "#,
+        )?;
+        assert_eq!(
+            h.nodes()
+                .filter(|&n| matches!(h.get_optype(n), OpType::Conditional(_)))
+                .count(),
+            1
+        );
+
+        let mut registry = EvaluatorRegistry::new();
+        registry.register_op(&resource_id(), CREG_EQ, |args, bits| {
+            let [TypeArg::Int(value)] = args else {
+                return Err("expected an integer argument".into());
+            };
+            let mut reg = 0;
+            for (i, b) in bits.iter().enumerate() {
+                let ConstValue::Hashable(HashableValue::Int(b)) = b else {
+                    return Err("expected a bit".into());
+                };
+                reg |= b << i;
+            }
+            Ok(vec![ConstValue::simple_predicate((reg == *value) as usize)])
+        });
+        let mut rng = StdRng::seed_from_u64(0);
+        let shots = sample(&h, h.root(), [], 50, &mut rng, Some(&registry))?;
+        assert!(shots.iter().all(|s| s[0] == s[1]));
+        assert!(shots
+            .iter()
+            .any(|s| s[0] == Value::Const(ConstValue::from(HashableValue::Int(1)))));
+        Ok(())
+    }
+
+    #[test]
+    fn opaque_gates() -> Result<(), Box<dyn std::error::Error>> {
+        let h = import_qasm_function(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            opaque magic(a, b) q;
+            qreg q[1];
+            u3(pi, 0, pi) q[0];
+            magic(1, 2) q[0];
+            id q[0];
+            "#,
+            "main",
+        )?;
+        assert_eq!(leaf_names(&h), ["qasm.u3", "qasm.magic", "Noop"]);
+        let func = h.children(h.root()).next().unwrap();
+        assert_matches!(h.get_optype(func), OpType::FuncDefn(f) => assert_eq!(f.name, "main"));
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[1];\nh q[0];"),
+            Err(QasmImportError::UnknownGate { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 3.0;"),
+            Err(QasmImportError::Unsupported { line: 1, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[1];\nCX q[0], q[1];"),
+            Err(QasmImportError::InvalidArguments { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[2];\nCX q[0], q[0];"),
+            Err(QasmImportError::InvalidArguments { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[1];\nmeasure q[0] -> c[0];"),
+            Err(QasmImportError::UnknownRegister { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[1]\nU(0, 0, 0) q[0];"),
+            Err(QasmImportError::Syntax { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\ngate g a { U(theta, 0, 0) a; }"),
+            Err(QasmImportError::InvalidArguments { line: 2, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[99999999999999999999];"),
+            Err(QasmImportError::InvalidRegister { line: 2, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[20000];\ncreg c[20000];"),
+            Err(QasmImportError::InvalidRegister { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[0];"),
+            Err(QasmImportError::InvalidRegister { line: 2, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[1];\ncreg q[1];"),
+            Err(QasmImportError::InvalidRegister { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\nqreg q[1];\nU(1/0, 0, 0) q[0];"),
+            Err(QasmImportError::InvalidArguments { line: 3, .. })
+        );
+        assert_matches!(
+            import_qasm("OPENQASM 2.0;\ngate g(a) b { U(1/a, 0, 0) b; }\nqreg q[1];\ng(0) q[0];"),
+            Err(QasmImportError::InvalidArguments { line: 4, .. })
+        );
+    }
+}
@@ -7,6 +7,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 
+use itertools::Itertools;
 use smol_str::SmolStr;
 use thiserror::Error;
 
@@ -200,8 +201,8 @@ pub enum ResourceBuildError {
 }
 
 /// A set of resources identified by their unique [`ResourceId`].
-#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-pub struct ResourceSet(HashSet<ResourceId>);
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, schemars::JsonSchema)]
+pub struct ResourceSet(#[schemars(with = "HashSet<String>")] HashSet<ResourceId>);
 
 impl ResourceSet {
     /// Creates a new empty resource set.
@@ -253,6 +254,13 @@ impl ResourceSet {
     }
 }
 
+impl serde::Serialize for ResourceSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Sorted, so that equal sets always have the same serialization.
+        serializer.collect_seq(self.0.iter().sorted())
+    }
+}
+
 impl Display for ResourceSet {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         f.debug_list().entries(self.0.iter()).finish()
@@ -264,3 +272,28 @@ impl FromIterator<ResourceId> for ResourceSet {
         Self(HashSet::from_iter(iter))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resource_set_serialization_is_sorted() {
+        let names = ["quantum", "arithmetic", "logic", "collections", "angle"];
+        let set: ResourceSet = names.into_iter().map(ResourceId::from).collect();
+
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(
+            json,
+            r#"["angle","arithmetic","collections","logic","quantum"]"#
+        );
+        let decoded: ResourceSet = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, set);
+
+        let reversed: ResourceSet = names.into_iter().rev().map(ResourceId::from).collect();
+        assert_eq!(
+            rmp_serde::to_vec(&reversed).unwrap(),
+            rmp_serde::to_vec(&set).unwrap()
+        );
+    }
+}
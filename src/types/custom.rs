@@ -9,13 +9,17 @@ use crate::resource::ResourceId;
 use super::{type_param::TypeArg, ClassicType, Container, HashableType, SimpleType, TypeTag};
 
 /// An opaque type element. Contains the unique identifier of its definition.
-#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub struct CustomType {
+    #[schemars(with = "String")]
     resource: ResourceId,
     /// Unique identifier of the opaque type.
     /// Same as the corresponding [`TypeDef`]
     ///
     /// [`TypeDef`]: crate::resource::TypeDef
+    #[schemars(with = "String")]
     id: SmolStr,
     /// Arguments that fit the [`TypeParam`]s declared by the typedef
     ///
@@ -57,6 +61,11 @@ impl CustomType {
     pub fn resource(&self) -> &ResourceId {
         &self.resource
     }
+
+    /// The [TypeTag] describing what can be done to instances of this type.
+    pub fn tag(&self) -> TypeTag {
+        self.tag
+    }
 }
 
 impl Display for CustomType {
@@ -40,7 +40,16 @@ impl Display for SimpleType {
 
 /// Categorizes types into three classes according to basic operations supported.
 #[derive(
-    Copy, Clone, PartialEq, Eq, Hash, Debug, derive_more::Display, Serialize_repr, Deserialize_repr,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    derive_more::Display,
+    Serialize_repr,
+    Deserialize_repr,
+    schemars::JsonSchema_repr,
 )]
 #[repr(u8)]
 pub enum TypeTag {
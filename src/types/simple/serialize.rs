@@ -1,3 +1,7 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+
 use super::ClassicType;
 
 use super::Container;
@@ -18,8 +22,9 @@ use super::super::AbstractSignature;
 
 use crate::ops::constant::HugrIntWidthStore;
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, Debug)]
 #[serde(tag = "t")]
+#[schemars(rename = "SimpleType")]
 pub(crate) enum SerSimpleType {
     Q,
     I {
@@ -57,10 +62,12 @@ pub(crate) enum SerSimpleType {
         c: TypeTag,
     },
     Alias {
+        #[schemars(with = "String")]
         name: SmolStr,
         c: TypeTag,
     },
     Var {
+        #[schemars(with = "String")]
         name: SmolStr,
     },
 }
@@ -234,6 +241,29 @@ impl TryFrom<SerSimpleType> for HashableType {
     }
 }
 
+/// Implements [`JsonSchema`] for a type serialized as a [`SerSimpleType`].
+macro_rules! impl_schema_as_ser_simple_type {
+    ($t:ty) => {
+        impl JsonSchema for $t {
+            fn is_referenceable() -> bool {
+                false
+            }
+
+            fn schema_name() -> String {
+                stringify!($t).into()
+            }
+
+            fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+                gen.subschema_for::<SerSimpleType>()
+            }
+        }
+    };
+}
+
+impl_schema_as_ser_simple_type!(SimpleType);
+impl_schema_as_ser_simple_type!(ClassicType);
+impl_schema_as_ser_simple_type!(HashableType);
+
 #[cfg(test)]
 mod test {
     use crate::hugr::serialize::test::ser_roundtrip;
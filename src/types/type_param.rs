@@ -34,7 +34,9 @@ pub enum TypeParam {
 }
 
 /// A statically-known argument value to an operation.
-#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize, schemars::JsonSchema,
+)]
 #[non_exhaustive]
 pub enum TypeArg {
     /// Where the (Type/Op)Def declares that an argument is a [TypeParam::Type]
@@ -46,6 +48,7 @@ pub enum TypeArg {
     /// this is the value.
     HashableType(HashableType),
     /// Where the (Type/Op)Def declares a [TypeParam::Value] of type [HashableType::Int], a constant value thereof
+    #[serde(deserialize_with = "crate::ops::constant::deserialize_int")]
     Int(HugrIntValueStore),
     /// Where the (Type/Op)Def declares a [TypeParam::Value] of type [HashableType::String], here it is
     String(String),
@@ -53,7 +56,7 @@ pub enum TypeArg {
     /// be of the same variety of TypeArg, i.e. `T`s.
     List(Vec<TypeArg>),
     /// Where the TypeDef declares a [TypeParam::Value] of [Container::Opaque]
-    CustomValue(serde_yaml::Value),
+    CustomValue(#[schemars(with = "serde_json::Value")] serde_yaml::Value),
 }
 
 impl TypeArg {
@@ -96,7 +99,9 @@ pub fn check_type_arg(arg: &TypeArg, param: &TypeParam) -> Result<(), TypeArgErr
             Container::Tuple(_) => unimplemented!(),
             Container::Sum(_) => unimplemented!(),
             Container::Array(elem, sz) => {
-                let TypeArg::List(items) = arg else {return Err(TypeArgError::TypeMismatch(arg.clone(), param.clone()))};
+                let TypeArg::List(items) = arg else {
+                    return Err(TypeArgError::TypeMismatch(arg.clone(), param.clone()));
+                };
                 if items.len() != *sz {
                     return Err(TypeArgError::WrongNumber(items.len(), *sz));
                 }
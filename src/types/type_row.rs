@@ -15,7 +15,9 @@ pub trait TypeRowElem: Clone + 'static {}
 impl<T: Clone + 'static> TypeRowElem for T {}
 
 /// List of types, used for function signatures.
-#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 //#[cfg_attr(feature = "pyo3", pyclass)] // TODO: expose unparameterized versions
 #[non_exhaustive]
 #[serde(transparent)]
@@ -16,11 +16,14 @@ use crate::{
 
 /// A constant value/instance of a [HashableType]. Note there is no
 /// equivalent of [HashableType::Variable]; we can't have instances of that.
-#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub enum HashableValue {
     /// A string, i.e. corresponding to [HashableType::String]
     String(String),
     /// An integer, i.e. an instance of all [HashableType::Int]s of sufficient width
+    #[serde(deserialize_with = "crate::ops::constant::deserialize_int")]
     Int(HugrIntValueStore),
     /// A container of other hashable values
     Container(ContainerValue<HashableValue>),
@@ -97,7 +100,9 @@ impl ValueOfType for HashableValue {
 /// resolved to concrete types in order to create instances (values),
 /// nor to [Container::Opaque], which is left to classes for broader
 /// sets of values (see e.g. [ConstValue::Opaque])
-#[derive(Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, schemars::JsonSchema,
+)]
 pub enum ContainerValue<T> {
     /// A [Container::Array] or [Container::Tuple] or [Container::List]
     Sequence(Vec<T>),